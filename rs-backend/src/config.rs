@@ -0,0 +1,475 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::db::blocklist::{BlocklistError, QueryBlocklist};
+use crate::db::init_sql;
+use crate::db::query::InvalidTextRendering;
+use crate::models::query_history::HistoryRetention;
+use crate::utils::json_limits::JsonLimits;
+
+const DEFAULT_PORT: u16 = 3001;
+const DEFAULT_STORAGE_PATH: &str = "storage";
+const DEFAULT_MAX_FILE_SIZE: usize = 1024 * 1024 * 100; // 100MB
+const DEFAULT_MIN_FILE_SIZE: usize = 1024; // 1KB
+const DEFAULT_POOL_SIZE: u32 = 10;
+const DEFAULT_POOL_CACHE_SIZE: usize = 64;
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_PARALLEL_ROW_THRESHOLD: usize = 1000;
+const DEFAULT_ERROR_LOG_SIZE: usize = 200;
+const DEFAULT_MAX_STATEMENT_CHANGES: u64 = 1_000_000;
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_RESULT_COLUMNS: usize = 500;
+const DEFAULT_MAX_QUERY_PAGE_SIZE: usize = 1000;
+const DEFAULT_MAX_JSON_DEPTH: usize = 32;
+const DEFAULT_MAX_JSON_ELEMENTS: usize = 100_000;
+const DEFAULT_MAX_CONCURRENT_UPLOADS: usize = 4;
+const DEFAULT_UPLOAD_PERMIT_WAIT: Duration = Duration::from_secs(5);
+const DEFAULT_DOWNLOAD_LINK_TTL: Duration = Duration::from_secs(300);
+const DEFAULT_UPLOAD_QUOTA_WINDOW: Duration = Duration::from_secs(3600);
+const DEFAULT_SNAPSHOT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_ROW_COUNT_SAMPLE_INTERVAL: Duration = Duration::from_secs(3600);
+const DEFAULT_UPLOAD_EXTENSIONS: &[&str] = &["db", "sqlite", "sqlite3"];
+
+// Shown in place of secrets when the configuration is reported
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Invalid {name}: {value:?} ({expected})")]
+    InvalidValue { name: String, value: String, expected: &'static str },
+    #[error("MIN_FILE_SIZE ({min}) must not exceed MAX_FILE_SIZE ({max})")]
+    FileSizeRange { min: usize, max: usize },
+    #[error("Unsupported storage backend: {0}")]
+    UnsupportedStorageBackend(String),
+    #[error("CONNECTION_INIT_SQL may only read or create temporary objects: {0}")]
+    ConnectionInitSql(String),
+    #[error("Invalid QUERY_BLOCKLIST: {0}")]
+    QueryBlocklist(#[from] BlocklistError),
+}
+
+// Where database files are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackendKind {
+    Local,
+}
+
+impl StorageBackendKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Local => "local",
+        }
+    }
+}
+
+// Normalize ".DB" / " sqlite " style entries to bare lowercase extensions
+pub fn parse_extensions<'a>(extensions: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    extensions.into_iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+// `role:token` pairs separated by commas. The token is everything after the
+// first colon; neither half may be empty.
+pub fn parse_role_tokens(value: &str) -> Option<Vec<(String, String)>> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (role, token) = entry.split_once(':')?;
+            let (role, token) = (role.trim(), token.trim());
+            (!role.is_empty() && !token.is_empty()).then(|| (role.to_string(), token.to_string()))
+        })
+        .collect()
+}
+
+// Every setting resolved from the environment, read once and shared by the app
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub port: u16,
+    pub storage_path: PathBuf,
+    pub storage_backend: StorageBackendKind,
+    // Connections kept per pool, for the metadata database and each user database
+    pub metadata_pool_size: u32,
+    pub database_pool_size: u32,
+    // Stored databases whose connection pools are kept open at once
+    pub database_pool_cache_size: usize,
+    // How long a connection waits for another's lock before failing with "database is locked"
+    pub busy_timeout: Duration,
+    // Enforce foreign key constraints on every connection
+    pub foreign_keys: bool,
+    // Switch stored databases to WAL mode whenever a writable connection opens them.
+    // Off by default, leaving the journal mode to the opt-in upload conversion.
+    pub wal_on_open: bool,
+    // Result sets with at least this many rows are converted on the row conversion pool
+    pub parallel_row_threshold: usize,
+    // Threads in the row conversion pool
+    pub row_conversion_threads: usize,
+    // Recent error responses kept for /admin/errors
+    pub error_log_size: usize,
+    // Bounds on an uploaded or imported database file, in bytes
+    pub max_file_size: usize,
+    pub min_file_size: usize,
+    // Cap on the number of stored databases
+    pub max_databases: Option<usize>,
+    // Base directories local-path imports may read from
+    pub import_allowed_dirs: Vec<PathBuf>,
+    // Bearer token required by the /admin routes (admin API disabled when None)
+    pub admin_token: Option<String>,
+    // Bearer tokens that authenticate a caller as a role, as (role, token) pairs;
+    // a database's column policy is applied by role
+    pub role_tokens: Vec<(String, String)>,
+    // Filename extensions that mark a generically-typed upload as a SQLite candidate
+    pub upload_extensions: Vec<String>,
+    pub history_retention: HistoryRetention,
+    // Setup SQL run on every new user-database connection (temp views and the like)
+    pub connection_init_sql: Option<String>,
+    // Rows a single statement (including its triggers) may change before it is aborted
+    pub max_statement_changes: Option<u64>,
+    // How long a query may run before it is aborted
+    pub query_timeout: Option<Duration>,
+    // Columns a query result may have; wider statements are rejected before they run
+    pub max_result_columns: Option<usize>,
+    // Largest `page_size` a paginated query may ask for; bigger requests are clamped
+    pub max_query_page_size: usize,
+    // How query results show TEXT that isn't valid UTF-8
+    pub invalid_text: InvalidTextRendering,
+    // Nesting depth and element count allowed in a query request body
+    pub json_limits: JsonLimits,
+    // Uploads allowed to run at once, and how long an extra one waits for a slot
+    pub max_concurrent_uploads: Option<usize>,
+    pub upload_permit_wait: Duration,
+    // Upload bytes each client IP may send within a rolling window
+    pub upload_quota_bytes: Option<u64>,
+    pub upload_quota_window: Duration,
+    // Switch every uploaded or imported database to WAL mode, as if each request asked for it
+    pub upload_convert_to_wal: bool,
+    pub query_blocklist: QueryBlocklist,
+    // Key for signing temporary download links; a random per-process key when None,
+    // so links stop working on restart
+    pub download_link_secret: Option<String>,
+    pub download_link_ttl: Duration,
+    // Read snapshots unused for this long are closed
+    pub snapshot_idle_timeout: Duration,
+    // How often every table's row count is recorded for growth trends; None turns sampling off
+    pub row_count_sample_interval: Option<Duration>,
+    // Refuse to start when a critical startup self-test check fails
+    pub self_test_strict: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            storage_path: PathBuf::from(DEFAULT_STORAGE_PATH),
+            storage_backend: StorageBackendKind::Local,
+            metadata_pool_size: DEFAULT_POOL_SIZE,
+            database_pool_size: DEFAULT_POOL_SIZE,
+            database_pool_cache_size: DEFAULT_POOL_CACHE_SIZE,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            foreign_keys: true,
+            wal_on_open: false,
+            parallel_row_threshold: DEFAULT_PARALLEL_ROW_THRESHOLD,
+            row_conversion_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            error_log_size: DEFAULT_ERROR_LOG_SIZE,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            min_file_size: DEFAULT_MIN_FILE_SIZE,
+            max_databases: None,
+            import_allowed_dirs: Vec::new(),
+            admin_token: None,
+            role_tokens: Vec::new(),
+            upload_extensions: parse_extensions(DEFAULT_UPLOAD_EXTENSIONS.iter().copied()),
+            history_retention: HistoryRetention::default(),
+            connection_init_sql: None,
+            max_statement_changes: Some(DEFAULT_MAX_STATEMENT_CHANGES),
+            query_timeout: Some(DEFAULT_QUERY_TIMEOUT),
+            max_result_columns: Some(DEFAULT_MAX_RESULT_COLUMNS),
+            max_query_page_size: DEFAULT_MAX_QUERY_PAGE_SIZE,
+            invalid_text: InvalidTextRendering::default(),
+            json_limits: JsonLimits {
+                max_depth: DEFAULT_MAX_JSON_DEPTH,
+                max_elements: Some(DEFAULT_MAX_JSON_ELEMENTS),
+            },
+            max_concurrent_uploads: Some(DEFAULT_MAX_CONCURRENT_UPLOADS),
+            upload_permit_wait: DEFAULT_UPLOAD_PERMIT_WAIT,
+            upload_quota_bytes: None,
+            upload_quota_window: DEFAULT_UPLOAD_QUOTA_WINDOW,
+            upload_convert_to_wal: false,
+            query_blocklist: QueryBlocklist::default(),
+            download_link_secret: None,
+            download_link_ttl: DEFAULT_DOWNLOAD_LINK_TTL,
+            snapshot_idle_timeout: DEFAULT_SNAPSHOT_IDLE_TIMEOUT,
+            row_count_sample_interval: Some(DEFAULT_ROW_COUNT_SAMPLE_INTERVAL),
+            self_test_strict: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    // Resolve settings through `lookup` (the environment, or a fixed map in tests),
+    // falling back to the defaults for anything unset. Values that are set but
+    // don't parse are errors rather than silently ignored.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        let parsed = |name: &str, expected: &'static str| -> Result<Option<u64>, ConfigError> {
+            lookup(name)
+                .map(|value| value.trim().parse::<u64>().map_err(|_| ConfigError::InvalidValue {
+                    name: name.to_string(),
+                    value,
+                    expected,
+                }))
+                .transpose()
+        };
+        // A numeric limit: None when unset, Some(None) when 0 (unlimited)
+        let limit = |name: &str| -> Result<Option<Option<u64>>, ConfigError> {
+            Ok(parsed(name, "a non-negative integer, 0 for unlimited")?.map(|n| (n != 0).then_some(n)))
+        };
+        let positive = |name: &str| -> Result<Option<u64>, ConfigError> {
+            match parsed(name, "a positive integer")? {
+                Some(0) => Err(ConfigError::InvalidValue {
+                    name: name.to_string(),
+                    value: "0".to_string(),
+                    expected: "a positive integer",
+                }),
+                other => Ok(other),
+            }
+        };
+        let flag = |name: &str| -> Result<Option<bool>, ConfigError> {
+            lookup(name)
+                .map(|value| match value.trim().to_ascii_lowercase().as_str() {
+                    "1" | "true" | "yes" => Ok(true),
+                    "0" | "false" | "no" => Ok(false),
+                    _ => Err(ConfigError::InvalidValue { name: name.to_string(), value, expected: "true or false" }),
+                })
+                .transpose()
+        };
+        let bounded = |name: &str, max: u64| -> Result<Option<u64>, ConfigError> {
+            match positive(name)? {
+                Some(n) if n > max => Err(ConfigError::InvalidValue {
+                    name: name.to_string(),
+                    value: n.to_string(),
+                    expected: "a value in range",
+                }),
+                other => Ok(other),
+            }
+        };
+
+        let storage_backend = match lookup("STORAGE_BACKEND").as_deref() {
+            None | Some("local") => StorageBackendKind::Local,
+            Some(other) => return Err(ConfigError::UnsupportedStorageBackend(other.to_string())),
+        };
+
+        let config = Self {
+            port: bounded("PORT", u16::MAX as u64)?.map(|n| n as u16).unwrap_or(defaults.port),
+            storage_path: lookup("SQLITE_STORAGE_PATH").map(PathBuf::from).unwrap_or(defaults.storage_path),
+            storage_backend,
+            metadata_pool_size: bounded("METADATA_POOL_SIZE", u32::MAX as u64)?
+                .map(|n| n as u32)
+                .unwrap_or(defaults.metadata_pool_size),
+            database_pool_size: bounded("DATABASE_POOL_SIZE", u32::MAX as u64)?
+                .map(|n| n as u32)
+                .unwrap_or(defaults.database_pool_size),
+            database_pool_cache_size: positive("DATABASE_POOL_CACHE_SIZE")?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.database_pool_cache_size),
+            busy_timeout: parsed("SQLITE_BUSY_TIMEOUT_MS", "a number of milliseconds")?
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.busy_timeout),
+            foreign_keys: flag("SQLITE_FOREIGN_KEYS")?.unwrap_or(defaults.foreign_keys),
+            wal_on_open: flag("SQLITE_WAL_ON_OPEN")?.unwrap_or(defaults.wal_on_open),
+            parallel_row_threshold: parsed("PARALLEL_ROW_THRESHOLD", "a non-negative integer")?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.parallel_row_threshold),
+            row_conversion_threads: bounded("ROW_CONVERSION_THREADS", 1024)?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.row_conversion_threads),
+            error_log_size: positive("ERROR_LOG_SIZE")?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.error_log_size),
+            max_file_size: positive("MAX_FILE_SIZE")?.map(|n| n as usize).unwrap_or(defaults.max_file_size),
+            min_file_size: parsed("MIN_FILE_SIZE", "a non-negative integer")?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.min_file_size),
+            max_databases: limit("MAX_DATABASES")?.map(|n| n.map(|n| n as usize)).unwrap_or(defaults.max_databases),
+            import_allowed_dirs: lookup("IMPORT_ALLOWED_DIRS")
+                .map(|v| v.split(',').map(str::trim).filter(|d| !d.is_empty()).map(PathBuf::from).collect())
+                .unwrap_or(defaults.import_allowed_dirs),
+            admin_token: lookup("ADMIN_TOKEN").filter(|t| !t.is_empty()),
+            role_tokens: match lookup("ROLE_TOKENS") {
+                Some(value) => parse_role_tokens(&value).ok_or_else(|| ConfigError::InvalidValue {
+                    name: "ROLE_TOKENS".to_string(),
+                    value: REDACTED.to_string(),
+                    expected: "comma-separated role:token pairs",
+                })?,
+                None => defaults.role_tokens,
+            },
+            upload_extensions: lookup("UPLOAD_SQLITE_EXTENSIONS")
+                .map(|v| parse_extensions(v.split(',')))
+                .unwrap_or(defaults.upload_extensions),
+            history_retention: HistoryRetention {
+                max_entries: limit("QUERY_HISTORY_MAX_ENTRIES")?
+                    .map(|n| n.map(|n| n as usize))
+                    .unwrap_or(defaults.history_retention.max_entries),
+                max_age_days: limit("QUERY_HISTORY_RETENTION_DAYS")?
+                    .map(|n| n.map(|n| n as i64))
+                    .unwrap_or(defaults.history_retention.max_age_days),
+            },
+            connection_init_sql: lookup("CONNECTION_INIT_SQL").filter(|sql| !sql.trim().is_empty()),
+            max_statement_changes: limit("MAX_STATEMENT_CHANGES")?.unwrap_or(defaults.max_statement_changes),
+            query_timeout: limit("QUERY_TIMEOUT_MS")?
+                .map(|n| n.map(Duration::from_millis))
+                .unwrap_or(defaults.query_timeout),
+            max_result_columns: limit("MAX_RESULT_COLUMNS")?
+                .map(|n| n.map(|n| n as usize))
+                .unwrap_or(defaults.max_result_columns),
+            max_query_page_size: positive("MAX_QUERY_PAGE_SIZE")?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.max_query_page_size),
+            invalid_text: match lookup("INVALID_TEXT") {
+                None => defaults.invalid_text,
+                Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+                    "base64" => InvalidTextRendering::Base64,
+                    "lossy" => InvalidTextRendering::Lossy,
+                    _ => return Err(ConfigError::InvalidValue {
+                        name: "INVALID_TEXT".to_string(),
+                        value,
+                        expected: "base64 or lossy",
+                    }),
+                },
+            },
+            // serde_json refuses anything nested deeper than 128 regardless
+            json_limits: JsonLimits {
+                max_depth: bounded("MAX_JSON_DEPTH", 128)?
+                    .map(|n| n as usize)
+                    .unwrap_or(defaults.json_limits.max_depth),
+                max_elements: limit("MAX_JSON_ELEMENTS")?
+                    .map(|n| n.map(|n| n as usize))
+                    .unwrap_or(defaults.json_limits.max_elements),
+            },
+            max_concurrent_uploads: limit("MAX_CONCURRENT_UPLOADS")?
+                .map(|n| n.map(|n| n as usize))
+                .unwrap_or(defaults.max_concurrent_uploads),
+            upload_permit_wait: parsed("UPLOAD_PERMIT_WAIT_MS", "a number of milliseconds")?
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.upload_permit_wait),
+            upload_quota_bytes: limit("UPLOAD_QUOTA_BYTES")?.unwrap_or(defaults.upload_quota_bytes),
+            upload_quota_window: positive("UPLOAD_QUOTA_WINDOW_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.upload_quota_window),
+            upload_convert_to_wal: flag("UPLOAD_CONVERT_TO_WAL")?.unwrap_or(defaults.upload_convert_to_wal),
+            query_blocklist: match lookup("QUERY_BLOCKLIST") {
+                Some(spec) => QueryBlocklist::parse(&spec)?,
+                None => defaults.query_blocklist,
+            },
+            download_link_secret: lookup("DOWNLOAD_LINK_SECRET").filter(|s| !s.is_empty()),
+            download_link_ttl: positive("DOWNLOAD_LINK_TTL_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.download_link_ttl),
+            snapshot_idle_timeout: positive("SNAPSHOT_IDLE_TIMEOUT_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.snapshot_idle_timeout),
+            row_count_sample_interval: limit("ROW_COUNT_SAMPLE_INTERVAL_SECS")?
+                .map(|n| n.map(Duration::from_secs))
+                .unwrap_or(defaults.row_count_sample_interval),
+            self_test_strict: flag("SELF_TEST_STRICT")?.unwrap_or(defaults.self_test_strict),
+        };
+
+        if let Some(sql) = &config.connection_init_sql {
+            init_sql::validate(sql).map_err(ConfigError::ConnectionInitSql)?;
+        }
+        if config.min_file_size > config.max_file_size {
+            return Err(ConfigError::FileSizeRange { min: config.min_file_size, max: config.max_file_size });
+        }
+        Ok(config)
+    }
+
+    // Optional capabilities and whether this configuration turns them on, so
+    // clients can adapt without reading (or being allowed to read) the settings
+    pub fn features(&self) -> Value {
+        let features = [
+            ("admin_api", self.admin_token.is_some(), "Admin routes, enabled by ADMIN_TOKEN"),
+            ("role_tokens", !self.role_tokens.is_empty(), "Bearer tokens that authenticate a role for column policies"),
+            ("path_import", !self.import_allowed_dirs.is_empty(), "Importing database files from allowed local directories"),
+            ("database_limit", self.max_databases.is_some(), "A cap on the number of stored databases"),
+            ("upload_quota", self.upload_quota_bytes.is_some(), "Per-client upload byte quota"),
+            ("upload_concurrency_limit", self.max_concurrent_uploads.is_some(), "A limit on uploads running at once"),
+            ("convert_to_wal_on_upload", self.upload_convert_to_wal, "Every upload switched to WAL mode"),
+            ("wal_on_open", self.wal_on_open, "Stored databases switched to WAL mode when opened for writing"),
+            ("query_blocklist", !self.query_blocklist.is_empty(), "Rejecting blocklisted statements"),
+            ("statement_change_limit", self.max_statement_changes.is_some(), "Aborting statements that change too many rows"),
+            ("query_timeout", self.query_timeout.is_some(), "Aborting queries that run longer than QUERY_TIMEOUT_MS"),
+            ("result_column_limit", self.max_result_columns.is_some(), "Rejecting results wider than MAX_RESULT_COLUMNS"),
+            ("connection_init_sql", self.connection_init_sql.is_some(), "Setup SQL run on each database connection"),
+            ("table_growth_sampling", self.row_count_sample_interval.is_some(), "Periodic per-table row counts for growth trends"),
+            ("persistent_download_links", self.download_link_secret.is_some(), "Signed download links that survive restarts"),
+            (
+                "query_history_retention",
+                self.history_retention.max_entries.is_some() || self.history_retention.max_age_days.is_some(),
+                "Pruning old query history",
+            ),
+        ];
+        Value::Object(features.into_iter()
+            .map(|(name, enabled, description)| {
+                (name.to_string(), json!({ "enabled": enabled, "description": description }))
+            })
+            .collect())
+    }
+
+    // The effective settings as JSON, with secrets replaced by a placeholder
+    pub fn redacted(&self) -> Value {
+        json!({
+            "port": self.port,
+            "storage_path": self.storage_path,
+            "storage_backend": self.storage_backend.as_str(),
+            "metadata_pool_size": self.metadata_pool_size,
+            "database_pool_size": self.database_pool_size,
+            "database_pool_cache_size": self.database_pool_cache_size,
+            "busy_timeout_ms": self.busy_timeout.as_millis() as u64,
+            "foreign_keys": self.foreign_keys,
+            "wal_on_open": self.wal_on_open,
+            "parallel_row_threshold": self.parallel_row_threshold,
+            "row_conversion_threads": self.row_conversion_threads,
+            "error_log_size": self.error_log_size,
+            "max_file_size": self.max_file_size,
+            "min_file_size": self.min_file_size,
+            "max_databases": self.max_databases,
+            "import_allowed_dirs": self.import_allowed_dirs,
+            "admin_token": self.admin_token.as_ref().map(|_| REDACTED),
+            "role_tokens": self.role_tokens.iter().map(|(role, _)| role).collect::<Vec<_>>(),
+            "upload_extensions": self.upload_extensions,
+            "query_history": {
+                "max_entries": self.history_retention.max_entries,
+                "max_age_days": self.history_retention.max_age_days
+            },
+            "connection_init_sql": self.connection_init_sql,
+            "max_statement_changes": self.max_statement_changes,
+            "query_timeout_ms": self.query_timeout.map(|t| t.as_millis() as u64),
+            "max_result_columns": self.max_result_columns,
+            "max_query_page_size": self.max_query_page_size,
+            "invalid_text": match self.invalid_text {
+                InvalidTextRendering::Base64 => "base64",
+                InvalidTextRendering::Lossy => "lossy",
+            },
+            "max_json_depth": self.json_limits.max_depth,
+            "max_json_elements": self.json_limits.max_elements,
+            "max_concurrent_uploads": self.max_concurrent_uploads,
+            "upload_permit_wait_ms": self.upload_permit_wait.as_millis() as u64,
+            "upload_quota_bytes": self.upload_quota_bytes,
+            "upload_quota_window_secs": self.upload_quota_window.as_secs(),
+            "upload_convert_to_wal": self.upload_convert_to_wal,
+            "query_blocklist": self.query_blocklist.rule_names().collect::<Vec<_>>(),
+            "download_link_secret": self.download_link_secret.as_ref().map(|_| REDACTED),
+            "download_link_ttl_secs": self.download_link_ttl.as_secs(),
+            "snapshot_idle_timeout_secs": self.snapshot_idle_timeout.as_secs(),
+            "row_count_sample_interval_secs": self.row_count_sample_interval.map(|i| i.as_secs()),
+            "self_test_strict": self.self_test_strict
+        })
+    }
+}
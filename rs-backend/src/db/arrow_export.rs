@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BinaryBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use rusqlite::types::Value as SqlValue;
+
+pub const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+// Arrow type for a result column: the declared SQLite type when every value
+// fits it, otherwise whatever the values themselves support
+pub fn infer_column_type(decl_type: Option<&str>, values: &[&SqlValue]) -> DataType {
+    if let Some(declared) = decl_type.and_then(declared_type) {
+        if values.iter().all(|v| fits(&declared, v)) {
+            return declared;
+        }
+    }
+
+    let non_null = || values.iter().filter(|v| !matches!(v, SqlValue::Null));
+    if non_null().any(|v| matches!(v, SqlValue::Blob(_))) {
+        DataType::Binary
+    } else if non_null().all(|v| matches!(v, SqlValue::Integer(_))) && non_null().next().is_some() {
+        DataType::Int64
+    } else if non_null().all(|v| matches!(v, SqlValue::Integer(_) | SqlValue::Real(_))) && non_null().next().is_some() {
+        DataType::Float64
+    } else {
+        DataType::Utf8
+    }
+}
+
+// SQLite type affinity rules applied to a declared column type
+fn declared_type(decl: &str) -> Option<DataType> {
+    let decl = decl.to_ascii_uppercase();
+    if decl.contains("INT") {
+        Some(DataType::Int64)
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        Some(DataType::Utf8)
+    } else if decl.contains("BLOB") {
+        Some(DataType::Binary)
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+        Some(DataType::Float64)
+    } else {
+        None
+    }
+}
+
+fn fits(data_type: &DataType, value: &SqlValue) -> bool {
+    matches!(
+        (data_type, value),
+        (_, SqlValue::Null)
+            | (DataType::Int64, SqlValue::Integer(_))
+            | (DataType::Float64, SqlValue::Integer(_) | SqlValue::Real(_))
+            | (DataType::Utf8, SqlValue::Text(_))
+            | (DataType::Binary, SqlValue::Blob(_))
+    )
+}
+
+fn text_of(value: &SqlValue) -> Option<String> {
+    match value {
+        SqlValue::Null => None,
+        SqlValue::Integer(i) => Some(i.to_string()),
+        SqlValue::Real(f) => Some(f.to_string()),
+        SqlValue::Text(s) => Some(s.clone()),
+        SqlValue::Blob(b) => Some(String::from_utf8_lossy(b).into_owned()),
+    }
+}
+
+fn build_array(data_type: &DataType, values: &[&SqlValue]) -> ArrayRef {
+    match data_type {
+        DataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(values.len());
+            for value in values {
+                match value {
+                    SqlValue::Integer(i) => builder.append_value(*i),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::with_capacity(values.len());
+            for value in values {
+                match value {
+                    SqlValue::Integer(i) => builder.append_value(*i as f64),
+                    SqlValue::Real(f) => builder.append_value(*f),
+                    _ => builder.append_null(),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Binary => {
+            let mut builder = BinaryBuilder::new();
+            for value in values {
+                match value {
+                    SqlValue::Null => builder.append_null(),
+                    SqlValue::Blob(b) => builder.append_value(b),
+                    other => builder.append_option(text_of(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        _ => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                builder.append_option(text_of(value));
+            }
+            Arc::new(builder.finish())
+        }
+    }
+}
+
+// Encode a materialized result set as a single-batch Arrow IPC stream
+pub fn rows_to_ipc(
+    columns: &[String],
+    decl_types: &[Option<String>],
+    rows: &[Vec<SqlValue>],
+) -> Result<Vec<u8>, ArrowError> {
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut arrays = Vec::with_capacity(columns.len());
+
+    for (i, column) in columns.iter().enumerate() {
+        let values: Vec<&SqlValue> = rows.iter().map(|row| &row[i]).collect();
+        let data_type = infer_column_type(decl_types.get(i).and_then(|d| d.as_deref()), &values);
+        arrays.push(build_array(&data_type, &values));
+        fields.push(Field::new(column, data_type, true));
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = if columns.is_empty() {
+        RecordBatch::new_empty(schema.clone())
+    } else {
+        RecordBatch::try_new(schema.clone(), arrays)?
+    };
+
+    let mut buffer = Vec::new();
+    {
+        let mut writer = StreamWriter::try_new(&mut buffer, &schema)?;
+        writer.write(&batch)?;
+        writer.finish()?;
+    }
+
+    Ok(buffer)
+}
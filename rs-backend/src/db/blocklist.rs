@@ -0,0 +1,66 @@
+use regex::Regex;
+
+// A named pattern that rejects any query it matches
+#[derive(Debug, Clone)]
+pub struct BlockRule {
+    pub name: String,
+    regex: Regex,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlocklistError {
+    #[error("Blocklist entry '{0}' must be written name=pattern")]
+    MissingName(String),
+    #[error("Invalid pattern for blocklist rule '{name}': {source}")]
+    InvalidPattern { name: String, source: regex::Error },
+}
+
+// Operator-configured patterns checked against incoming SQL. Matching is
+// case-insensitive and anchored to word boundaries, so a rule for `ATTACH`
+// rejects `attach database ...` but not a column named `attachments`.
+#[derive(Debug, Clone, Default)]
+pub struct QueryBlocklist {
+    rules: Vec<BlockRule>,
+}
+
+impl QueryBlocklist {
+    pub fn new<'a>(rules: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self, BlocklistError> {
+        let rules = rules.into_iter()
+            .map(|(name, pattern)| {
+                Regex::new(&format!(r"(?i)\b(?:{})\b", pattern))
+                    .map(|regex| BlockRule { name: name.to_string(), regex })
+                    .map_err(|source| BlocklistError::InvalidPattern { name: name.to_string(), source })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    // Parse `name=pattern` entries separated by `;`, e.g.
+    // "attach=ATTACH;writable_schema=pragma\s+writable_schema"
+    pub fn parse(spec: &str) -> Result<Self, BlocklistError> {
+        let entries = spec.split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.split_once('=') {
+                Some((name, pattern)) if !name.trim().is_empty() => Ok((name.trim(), pattern.trim())),
+                _ => Err(BlocklistError::MissingName(entry.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(entries)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rule_names(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.name.as_str())
+    }
+
+    // Name of the first rule the SQL matches
+    pub fn matched(&self, sql: &str) -> Option<&str> {
+        self.rules.iter()
+            .find(|rule| rule.regex.is_match(sql))
+            .map(|rule| rule.name.as_str())
+    }
+}
@@ -0,0 +1,80 @@
+use regex::Regex;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::utils::quote_identifier;
+
+// Display hints for a single column, for schema browsers
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnMeta {
+    pub name: String,
+    pub declared_type: String,
+    pub nullable: bool,
+    pub primary_key: bool,
+    pub default_value: Option<String>,
+    pub auto_increment: bool,
+    // Distinct non-NULL values currently in the column
+    pub distinct_count: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ColumnMetaError {
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+    #[error("Column not found: {0}")]
+    ColumnNotFound(String),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+// AUTOINCREMENT is only allowed on a table's sole INTEGER PRIMARY KEY, so the
+// keyword anywhere in the DDL identifies that column
+fn declares_autoincrement(sql: &str) -> bool {
+    Regex::new(r"(?i)\bAUTOINCREMENT\b").map(|re| re.is_match(sql)).unwrap_or(false)
+}
+
+pub fn column_meta(conn: &Connection, table: &str, column: &str) -> Result<ColumnMeta, ColumnMetaError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))?;
+    let info: Vec<(String, String, bool, Option<String>, i64)> = stmt
+        .query_map([], |row| Ok((row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    if info.is_empty() {
+        return Err(ColumnMetaError::TableNotFound(table.to_string()));
+    }
+
+    let key_columns = info.iter().filter(|(_, _, _, _, pk)| *pk > 0).count();
+    let Some((name, declared_type, not_null, default_value, pk)) =
+        info.into_iter().find(|(name, ..)| name.eq_ignore_ascii_case(column))
+    else {
+        return Err(ColumnMetaError::ColumnNotFound(column.to_string()));
+    };
+
+    let ddl: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let auto_increment = pk > 0
+        && key_columns == 1
+        && declared_type.eq_ignore_ascii_case("INTEGER")
+        && ddl.as_deref().is_some_and(declares_autoincrement);
+
+    let distinct_count = conn.query_row(
+        &format!("SELECT COUNT(DISTINCT {}) FROM {}", quote_identifier(&name), quote_identifier(table)),
+        [],
+        |row| row.get(0),
+    )?;
+
+    Ok(ColumnMeta {
+        name,
+        declared_type,
+        nullable: !not_null && pk == 0,
+        primary_key: pk > 0,
+        default_value,
+        auto_increment,
+        distinct_count,
+    })
+}
@@ -0,0 +1,269 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Deref;
+
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::{Connection, Statement};
+
+use crate::utils::quote_identifier;
+
+// Columns hidden from each role, as `{"role": {"table": ["column", ...]}}`
+pub type HiddenColumns = BTreeMap<String, Vec<String>>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct ColumnPolicy(BTreeMap<String, HiddenColumns>);
+
+impl ColumnPolicy {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!(
+            "column_policy must map roles to {{\"table\": [\"column\", ...]}} objects: {}", e
+        ))
+    }
+
+    // Columns hidden from `role`; roles without an entry see everything. A caller
+    // with no authenticated role is held to every role's restrictions at once.
+    pub fn hidden_for(&self, role: Option<&str>) -> Option<HiddenColumns> {
+        let mut hidden = HiddenColumns::new();
+        let restrictions: Vec<&HiddenColumns> = match role {
+            Some(role) => self.0.get(role).into_iter().collect(),
+            None => self.0.values().collect(),
+        };
+        for (table, columns) in restrictions.into_iter().flatten() {
+            let merged = hidden.entry(table.clone()).or_default();
+            for column in columns {
+                if !merged.iter().any(|c| c.eq_ignore_ascii_case(column)) {
+                    merged.push(column.clone());
+                }
+            }
+        }
+        hidden.retain(|_, columns| !columns.is_empty());
+        (!hidden.is_empty()).then_some(hidden)
+    }
+}
+
+fn is_hidden(hidden: &HiddenColumns, table: &str, column: &str) -> bool {
+    hidden.iter().any(|(t, columns)| {
+        t.eq_ignore_ascii_case(table) && columns.iter().any(|c| c.eq_ignore_ascii_case(column))
+    })
+}
+
+// Schema changes and attached databases would let a restricted caller rename a
+// hidden column or reach it through another name, so neither is allowed. Temp
+// views stay available for the shadowing below.
+fn is_schema_change(action: &AuthAction<'_>) -> bool {
+    match action {
+        AuthAction::Attach { .. }
+        | AuthAction::Detach { .. }
+        | AuthAction::AlterTable { .. }
+        | AuthAction::CreateIndex { .. }
+        | AuthAction::CreateTable { .. }
+        | AuthAction::CreateTrigger { .. }
+        | AuthAction::CreateView { .. }
+        | AuthAction::CreateVtable { .. }
+        | AuthAction::CreateTempIndex { .. }
+        | AuthAction::CreateTempTable { .. }
+        | AuthAction::CreateTempTrigger { .. }
+        | AuthAction::DropIndex { .. }
+        | AuthAction::DropTable { .. }
+        | AuthAction::DropTrigger { .. }
+        | AuthAction::DropView { .. }
+        | AuthAction::DropVtable { .. }
+        | AuthAction::DropTempIndex { .. }
+        | AuthAction::DropTempTable { .. }
+        | AuthAction::DropTempTrigger { .. } => true,
+        AuthAction::Pragma { pragma_name, .. } => pragma_name.eq_ignore_ascii_case("writable_schema"),
+        _ => false,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PolicyError {
+    #[error("Query references hidden columns")]
+    Forbidden,
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+// Whether SQLite refused a statement because the authorizer denied it
+pub fn is_denied(error: &rusqlite::Error) -> bool {
+    error.sqlite_error_code() == Some(rusqlite::ErrorCode::AuthorizationForStatementDenied)
+}
+
+// A connection with a set of hidden columns enforced until it's dropped, so
+// everything run on it is covered, not just statements prepared through it.
+// Reads of a restricted table resolve to a temp view of its permitted columns, so
+// `SELECT *` leaves hidden ones out and naming them fails to prepare. The
+// authorizer stops qualified names from reaching around the views, writes from
+// reading or updating hidden columns, and schema changes altogether.
+pub struct PolicyConnection<C: Deref<Target = Connection>> {
+    conn: C,
+    hidden: Option<HiddenColumns>,
+    // When set, every table is shadowed by a view of at most this many rows
+    sample_rows: Option<usize>,
+    // Views currently shadowing tables; None while they're out of the way
+    views: RefCell<Option<Vec<String>>>,
+    // Every view created so far. A rolled-back transaction can bring back one that
+    // was dropped inside it, so all of them are dropped again at the end.
+    created: RefCell<BTreeSet<String>>,
+}
+
+impl<C: Deref<Target = Connection>> PolicyConnection<C> {
+    pub fn install(conn: C, hidden: Option<HiddenColumns>) -> rusqlite::Result<Self> {
+        Self::shadowed(conn, hidden, None)
+    }
+
+    // Like install, but unqualified table names only see each table's first
+    // `sample_rows` rows
+    pub fn sampled(conn: C, hidden: Option<HiddenColumns>, sample_rows: usize) -> rusqlite::Result<Self> {
+        Self::shadowed(conn, hidden, Some(sample_rows))
+    }
+
+    fn shadowed(conn: C, hidden: Option<HiddenColumns>, sample_rows: Option<usize>) -> rusqlite::Result<Self> {
+        let policy = Self { conn, hidden, sample_rows, views: RefCell::new(None), created: RefCell::default() };
+        if let Some(hidden) = policy.hidden.clone() {
+            // Hidden columns are matched in any schema, so an attached copy or a
+            // temp table of the same name is no way around them
+            policy.conn.authorizer(Some(move |ctx: AuthContext<'_>| match ctx.action {
+                AuthAction::Read { table_name, column_name }
+                | AuthAction::Update { table_name, column_name }
+                    if is_hidden(&hidden, table_name, column_name) =>
+                {
+                    Authorization::Deny
+                }
+                ref action if is_schema_change(action) => Authorization::Deny,
+                _ => Authorization::Allow,
+            }));
+        }
+        policy.shadow_tables()?;
+        Ok(policy)
+    }
+
+    fn is_shadowing(&self) -> bool {
+        self.hidden.is_some() || self.sample_rows.is_some()
+    }
+
+    // Prepare `sql`, telling a statement refused for touching a hidden column
+    // apart from one that is simply invalid. A view can't be written through, so
+    // a write to a shadowed table is prepared with the views out of the way; the
+    // authorizer still applies, and the views return on the next prepare.
+    pub fn prepare_checked(&self, sql: &str) -> Result<Statement<'_>, PolicyError> {
+        self.shadow_tables()?;
+        let error = match self.conn.prepare(sql) {
+            Ok(stmt) => return Ok(stmt),
+            Err(e) if !self.is_shadowing() => return Err(e.into()),
+            Err(e) => e,
+        };
+
+        self.unshadow_tables();
+        let retried = match self.conn.prepare(sql) {
+            Ok(stmt) if !stmt.readonly() => return Ok(stmt),
+            Err(e) if is_denied(&e) => Err(PolicyError::Forbidden),
+            _ => Err(error.into()),
+        };
+        self.shadow_tables()?;
+        retried
+    }
+
+    // Run `f` with the views out of the way, for statements that write to tables
+    // by name, as migrations do. The authorizer still applies throughout.
+    pub fn without_views<T>(&self, f: impl FnOnce(&Connection) -> T) -> rusqlite::Result<T> {
+        self.unshadow_tables();
+        let result = f(&self.conn);
+        self.shadow_tables()?;
+        Ok(result)
+    }
+
+    fn shadow_tables(&self) -> rusqlite::Result<()> {
+        if !self.is_shadowing() || self.views.borrow().is_some() {
+            return Ok(());
+        }
+        let tables: Vec<String> = match (&self.hidden, self.sample_rows) {
+            (_, Some(_)) => self.conn
+                .prepare("SELECT name FROM main.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?,
+            (Some(hidden), None) => hidden.keys().cloned().collect(),
+            (None, None) => Vec::new(),
+        };
+
+        let mut views = Vec::new();
+        let result = tables.iter().try_for_each(|table| {
+            let Some(select) = self.view_select(table)? else {
+                return Ok(());
+            };
+            let view = quote_identifier(table);
+            self.created.borrow_mut().insert(view.clone());
+            self.conn.execute_batch(&format!(
+                "DROP VIEW IF EXISTS temp.{0}; CREATE TEMP VIEW {0} AS {1}",
+                view, select
+            ))?;
+            views.push(view);
+            Ok(())
+        });
+        *self.views.borrow_mut() = Some(views);
+        if result.is_err() {
+            self.unshadow_tables();
+        }
+        result
+    }
+
+    // The SELECT a table's view is defined as, or None when there's no such table
+    fn view_select(&self, table: &str) -> rusqlite::Result<Option<String>> {
+        let all: Vec<String> = self.conn
+            .prepare(&format!("PRAGMA main.table_info({})", quote_identifier(table)))?
+            .query_map([], |row| row.get(1))?
+            .collect::<rusqlite::Result<_>>()?;
+        if all.is_empty() {
+            return Ok(None);
+        }
+        let hidden = self.hidden.iter()
+            .flatten()
+            .filter(|(t, _)| t.eq_ignore_ascii_case(table))
+            .flat_map(|(_, columns)| columns)
+            .collect::<Vec<_>>();
+        let visible: Vec<String> = all.iter()
+            .filter(|c| !hidden.iter().any(|h| h.eq_ignore_ascii_case(c)))
+            .map(|c| quote_identifier(c.as_str()))
+            .collect();
+
+        let quoted = quote_identifier(table);
+        // A table with nothing left to show still shadows, as an empty row source
+        let mut select = if visible.is_empty() {
+            format!("SELECT NULL FROM main.{} WHERE 0", quoted)
+        } else {
+            format!("SELECT {} FROM main.{}", visible.join(", "), quoted)
+        };
+        if let Some(rows) = self.sample_rows {
+            select.push_str(&format!(" LIMIT {}", rows));
+        }
+        Ok(Some(select))
+    }
+
+    fn unshadow_tables(&self) {
+        for view in self.views.borrow_mut().take().unwrap_or_default() {
+            if let Err(e) = self.conn.execute_batch(&format!("DROP VIEW IF EXISTS temp.{}", view)) {
+                tracing::error!("Failed to drop view {}: {}", view, e);
+            }
+        }
+    }
+}
+
+impl<C: Deref<Target = Connection>> Deref for PolicyConnection<C> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+// The views and authorizer go before the connection returns to its pool
+impl<C: Deref<Target = Connection>> Drop for PolicyConnection<C> {
+    fn drop(&mut self) {
+        if self.hidden.is_some() {
+            self.conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        }
+        self.views.borrow_mut().replace(self.created.take().into_iter().collect());
+        self.unshadow_tables();
+    }
+}
@@ -1,52 +1,418 @@
 use std::path::{Path, PathBuf};
-use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 use r2d2_sqlite::SqliteConnectionManager;
 use r2d2::Pool;
+use rusqlite::OpenFlags;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::{parse_extensions, Config, ConfigError, StorageBackendKind};
+use crate::db::blocklist::QueryBlocklist;
+use crate::db::error_log::ErrorLog;
+use crate::db::fingerprint::FingerprintCache;
+use crate::db::init_sql;
+use crate::db::pool_cache::{DatabaseConnectionManager, DatabasePool, PoolCache, PoolMode};
+use crate::db::query::InvalidTextRendering;
+use crate::db::registry::QueryRegistry;
+use crate::db::row_conversion::RowConverter;
+use crate::db::snapshot::SnapshotRegistry;
+use crate::db::upload_quota::UploadQuota;
+use crate::models::query_history::HistoryRetention;
+use crate::storage::{LocalStorage, StorageBackend};
+use crate::utils::json_limits::JsonLimits;
+use crate::utils::secrets_match;
+
+// Startup failures opening the storage directory or metadata database
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectionError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("Failed to create storage directory {}: {source}", path.display())]
+    StorageDirectory { path: PathBuf, source: std::io::Error },
+    #[error("Failed to open metadata database {}: {source}", path.display())]
+    MetadataPool { path: PathBuf, source: r2d2::Error },
+    #[error("Failed to initialize metadata database {}: {source}", path.display())]
+    MetadataSchema { path: PathBuf, source: rusqlite::Error },
+}
+
+// Settings every new connection gets, to the metadata database and stored databases alike
+fn apply_connection_pragmas(conn: &rusqlite::Connection, busy_timeout: Duration, foreign_keys: bool) -> rusqlite::Result<()> {
+    conn.busy_timeout(busy_timeout)?;
+    conn.pragma_update(None, "foreign_keys", foreign_keys)
+}
+
+// Create (or migrate) every metadata table
+fn init_metadata_schema(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS database_metadata (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            table_count INTEGER NOT NULL,
+            is_favorite BOOLEAN NOT NULL DEFAULT 0,
+            notes TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            properties TEXT,
+            audit_enabled BOOLEAN NOT NULL DEFAULT 0,
+            tags TEXT,
+            checksum TEXT,
+            original_copy TEXT,
+            original_journal_mode TEXT,
+            integrity TEXT
+        )",
+        [],
+    )?;
+    crate::models::database_metadata::migrate_schema(conn)?;
+    crate::models::audit_log::AuditEntry::create_table(conn)?;
+    crate::models::import_job::ImportJob::create_table(conn)?;
+    crate::models::import_job::ImportJob::fail_interrupted(conn)?;
+    crate::models::export_job::ExportJob::create_table(conn)?;
+    crate::models::export_job::ExportJob::fail_interrupted(conn)?;
+    crate::models::query_history::QueryHistoryEntry::create_table(conn)?;
+    crate::models::saved_query::SavedQuery::create_table(conn)?;
+    crate::models::table_growth::TableGrowth::create_table(conn)?;
+    Ok(())
+}
+
+// None means unlimited, which is a semaphore with as many permits as it can hold
+fn upload_semaphore(max_uploads: Option<usize>) -> Arc<Semaphore> {
+    Arc::new(Semaphore::new(max_uploads.unwrap_or(Semaphore::MAX_PERMITS)))
+}
+
+fn upload_quota(limit: Option<u64>, window: Duration) -> Option<Arc<UploadQuota>> {
+    limit.map(|limit| Arc::new(UploadQuota::new(limit, window)))
+}
+
+// The configured signing secret, or 32 random bytes that live as long as the process
+fn download_key(secret: Option<&str>) -> Arc<[u8]> {
+    match secret {
+        Some(secret) => Arc::from(secret.as_bytes()),
+        None => {
+            let mut key = [0u8; 32];
+            getrandom::getrandom(&mut key).expect("Failed to generate download link key");
+            Arc::from(&key[..])
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct DbConnection {
-    storage_path: PathBuf,
+    config: Arc<Config>,
+    storage: Arc<dyn StorageBackend>,
     metadata_pool: Pool<SqliteConnectionManager>,
+    upload_slots: Arc<Semaphore>,
+    upload_quota: Option<Arc<UploadQuota>>,
+    query_registry: Arc<QueryRegistry>,
+    snapshots: Arc<SnapshotRegistry>,
+    fingerprints: Arc<FingerprintCache>,
+    database_pools: Arc<PoolCache>,
+    row_converter: Arc<RowConverter>,
+    error_log: Arc<ErrorLog>,
+    download_key: Arc<[u8]>,
 }
 
 impl DbConnection {
+    // Open storage as configured by the environment, panicking if it can't be initialized
     pub fn new() -> Self {
-        let storage_path = env::var("SQLITE_STORAGE_PATH")
-            .unwrap_or_else(|_| "storage".to_string());
-        
+        Self::try_new().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // Open storage as configured by the environment (SQLITE_STORAGE_PATH, default: storage)
+    pub fn try_new() -> Result<Self, ConnectionError> {
+        Self::from_config(Config::from_env()?)
+    }
+
+    // Like try_new, but with storage at `storage_path`
+    pub fn open(storage_path: impl Into<PathBuf>) -> Result<Self, ConnectionError> {
+        Self::from_config(Config {
+            storage_path: storage_path.into(),
+            ..Config::from_env()?
+        })
+    }
+
+    // Create the storage directory and metadata database described by `config`
+    pub fn from_config(config: Config) -> Result<Self, ConnectionError> {
+        let storage_path = config.storage_path.clone();
+
         // Create storage directory if it doesn't exist
-        std::fs::create_dir_all(&storage_path).expect("Failed to create storage directory");
+        std::fs::create_dir_all(&storage_path).map_err(|source| ConnectionError::StorageDirectory {
+            path: storage_path.clone(),
+            source,
+        })?;
 
         // Initialize metadata database pool
-        let metadata_db_path = PathBuf::from(&storage_path).join("metadata.db");
-        let manager = SqliteConnectionManager::file(&metadata_db_path);
-        let metadata_pool = Pool::new(manager).expect("Failed to create connection pool");
+        let metadata_db_path = storage_path.join("metadata.db");
+        // The metadata database is the server's own, so it's kept in WAL mode and
+        // readers don't wait on writers. Stored databases keep the journal mode they
+        // were uploaded with unless conversion is asked for (UPLOAD_CONVERT_TO_WAL).
+        let (busy_timeout, foreign_keys) = (config.busy_timeout, config.foreign_keys);
+        let manager = SqliteConnectionManager::file(&metadata_db_path).with_init(move |conn| {
+            apply_connection_pragmas(conn, busy_timeout, foreign_keys)?;
+            conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+        });
+        let pool_error = |source| ConnectionError::MetadataPool {
+            path: metadata_db_path.clone(),
+            source,
+        };
+        let metadata_pool = Pool::builder()
+            .max_size(config.metadata_pool_size)
+            .build(manager)
+            .map_err(pool_error)?;
 
         // Initialize metadata database schema
-        let conn = metadata_pool.get().expect("Failed to get connection from pool");
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS database_metadata (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL,
-                size INTEGER NOT NULL,
-                table_count INTEGER NOT NULL,
-                is_favorite BOOLEAN NOT NULL DEFAULT 0,
-                notes TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )",
-            [],
-        ).expect("Failed to create metadata table");
-
-        Self {
-            storage_path: PathBuf::from(storage_path),
+        let conn = metadata_pool.get().map_err(pool_error)?;
+        init_metadata_schema(&conn).map_err(|source| ConnectionError::MetadataSchema {
+            path: metadata_db_path.clone(),
+            source,
+        })?;
+
+        let storage: Arc<dyn StorageBackend> = match config.storage_backend {
+            StorageBackendKind::Local => Arc::new(LocalStorage::new(&storage_path)),
+        };
+
+        Ok(Self {
+            upload_slots: upload_semaphore(config.max_concurrent_uploads),
+            database_pools: Arc::new(PoolCache::new(config.database_pool_cache_size)),
+            row_converter: Arc::new(RowConverter::new(config.parallel_row_threshold, config.row_conversion_threads)),
+            error_log: Arc::new(ErrorLog::new(config.error_log_size)),
+            upload_quota: upload_quota(config.upload_quota_bytes, config.upload_quota_window),
+            download_key: download_key(config.download_link_secret.as_deref()),
+            config: Arc::new(config),
+            storage,
             metadata_pool,
-        }
+            query_registry: Arc::new(QueryRegistry::default()),
+            snapshots: Arc::new(SnapshotRegistry::default()),
+            fingerprints: Arc::new(FingerprintCache::default()),
+        })
+    }
+
+    // The settings in effect, including any builder overrides
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn config_mut(&mut self) -> &mut Config {
+        Arc::make_mut(&mut self.config)
+    }
+
+    pub fn with_storage(mut self, storage: impl StorageBackend + 'static) -> Self {
+        self.storage = Arc::new(storage);
+        self
+    }
+
+    pub fn storage(&self) -> &Arc<dyn StorageBackend> {
+        &self.storage
+    }
+
+    pub fn with_max_databases(mut self, max_databases: Option<usize>) -> Self {
+        self.config_mut().max_databases = max_databases;
+        self
+    }
+
+    pub fn max_databases(&self) -> Option<usize> {
+        self.config.max_databases
+    }
+
+    pub fn with_import_allowed_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.config_mut().import_allowed_dirs = dirs;
+        self
+    }
+
+    pub fn with_wal_on_open(mut self, wal_on_open: bool) -> Self {
+        self.config_mut().wal_on_open = wal_on_open;
+        self
+    }
+
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.config_mut().admin_token = token;
+        self
+    }
+
+    pub fn admin_token(&self) -> Option<&str> {
+        self.config.admin_token.as_deref()
+    }
+
+    pub fn with_role_token(mut self, role: &str, token: &str) -> Self {
+        self.config_mut().role_tokens.push((role.to_string(), token.to_string()));
+        self
+    }
+
+    // The role a bearer token authenticates. Every configured token is compared,
+    // in constant time, so the match position doesn't leak either.
+    pub fn role_for_token(&self, token: &str) -> Option<&str> {
+        self.config.role_tokens.iter()
+            .fold(None, |found, (role, expected)| {
+                let matched = secrets_match(token.as_bytes(), expected.as_bytes());
+                found.or(matched.then_some(role.as_str()))
+            })
+    }
+
+    pub fn with_download_link_ttl(mut self, ttl: Duration) -> Self {
+        self.config_mut().download_link_ttl = ttl;
+        self
+    }
+
+    // Key that signs temporary download links
+    pub fn download_key(&self) -> &[u8] {
+        &self.download_key
+    }
+
+    pub fn with_upload_extensions<'a>(mut self, extensions: impl IntoIterator<Item = &'a str>) -> Self {
+        self.config_mut().upload_extensions = parse_extensions(extensions);
+        self
+    }
+
+    // Whether the filename ends in one of the configured SQLite extensions
+    pub fn has_sqlite_extension(&self, filename: &str) -> bool {
+        Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.config.upload_extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext)))
+    }
+
+    pub fn with_history_retention(mut self, retention: HistoryRetention) -> Self {
+        self.config_mut().history_retention = retention;
+        self
+    }
+
+    pub fn history_retention(&self) -> &HistoryRetention {
+        &self.config.history_retention
+    }
+
+    pub fn with_max_statement_changes(mut self, max_changes: Option<u64>) -> Self {
+        self.config_mut().max_statement_changes = max_changes;
+        self
+    }
+
+    pub fn max_statement_changes(&self) -> Option<u64> {
+        self.config.max_statement_changes
+    }
+
+    pub fn with_query_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config_mut().query_timeout = timeout;
+        self
+    }
+
+    // Not validated up front like CONNECTION_INIT_SQL, but persistent writes are still denied
+    pub fn with_connection_init_sql(mut self, sql: Option<String>) -> Self {
+        self.config_mut().connection_init_sql = sql;
+        self
+    }
+
+    pub fn with_max_result_columns(mut self, max_columns: Option<usize>) -> Self {
+        self.config_mut().max_result_columns = max_columns;
+        self
+    }
+
+    pub fn max_result_columns(&self) -> Option<usize> {
+        self.config.max_result_columns
+    }
+
+    pub fn with_max_query_page_size(mut self, max_page_size: usize) -> Self {
+        self.config_mut().max_query_page_size = max_page_size;
+        self
+    }
+
+    pub fn with_invalid_text(mut self, mode: InvalidTextRendering) -> Self {
+        self.config_mut().invalid_text = mode;
+        self
+    }
+
+    pub fn with_json_limits(mut self, limits: JsonLimits) -> Self {
+        self.config_mut().json_limits = limits;
+        self
+    }
+
+    // Replaces the upload semaphore, so clones made before this call keep the old limit
+    pub fn with_max_concurrent_uploads(mut self, max_uploads: Option<usize>) -> Self {
+        self.config_mut().max_concurrent_uploads = max_uploads;
+        self.upload_slots = upload_semaphore(max_uploads);
+        self
+    }
+
+    pub fn with_upload_quota(mut self, limit: Option<u64>, window: Duration) -> Self {
+        let config = self.config_mut();
+        config.upload_quota_bytes = limit;
+        config.upload_quota_window = window;
+        self.upload_quota = upload_quota(limit, window);
+        self
+    }
+
+    // Per-client upload accounting, when a quota is configured
+    pub fn upload_quota(&self) -> Option<&Arc<UploadQuota>> {
+        self.upload_quota.as_ref()
+    }
+
+    pub fn with_upload_permit_wait(mut self, wait: Duration) -> Self {
+        self.config_mut().upload_permit_wait = wait;
+        self
+    }
+
+    // Wait up to the configured time for an upload slot; None if every slot stayed busy.
+    // The slot is released when the permit is dropped.
+    pub async fn acquire_upload_permit(&self) -> Option<OwnedSemaphorePermit> {
+        tokio::time::timeout(self.config.upload_permit_wait, self.upload_slots.clone().acquire_owned())
+            .await
+            .ok()?
+            .ok()
+    }
+
+    pub fn with_query_blocklist(mut self, blocklist: QueryBlocklist) -> Self {
+        self.config_mut().query_blocklist = blocklist;
+        self
+    }
+
+    pub fn query_blocklist(&self) -> &QueryBlocklist {
+        &self.config.query_blocklist
+    }
+
+    pub fn query_registry(&self) -> &Arc<QueryRegistry> {
+        &self.query_registry
+    }
+
+    pub fn snapshots(&self) -> &SnapshotRegistry {
+        &self.snapshots
+    }
+
+    pub fn row_converter(&self) -> &RowConverter {
+        &self.row_converter
+    }
+
+    pub fn error_log(&self) -> &ErrorLog {
+        &self.error_log
+    }
+
+    pub fn fingerprints(&self) -> &FingerprintCache {
+        &self.fingerprints
+    }
+
+    pub fn with_upload_convert_to_wal(mut self, convert: bool) -> Self {
+        self.config_mut().upload_convert_to_wal = convert;
+        self
+    }
+
+    pub fn with_snapshot_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config_mut().snapshot_idle_timeout = timeout;
+        self
+    }
+
+    pub fn metadata_db_path(&self) -> PathBuf {
+        self.config.storage_path.join("metadata.db")
+    }
+
+    // Canonicalize a local import path and confirm it lies within an allowed base directory
+    pub fn resolve_import_path(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        let resolved = std::fs::canonicalize(path.as_ref()).ok()?;
+        self.config.import_allowed_dirs.iter()
+            .filter_map(|base| std::fs::canonicalize(base).ok())
+            .any(|base| resolved.starts_with(&base))
+            .then_some(resolved)
     }
 
     pub fn get_storage_path(&self, path: impl AsRef<Path>) -> PathBuf {
-        let full_path = self.storage_path.join(path);
+        let full_path = self.config.storage_path.join(path);
         if let Some(parent) = full_path.parent() {
             if !parent.exists() {
                 std::fs::create_dir_all(parent).expect("Failed to create storage directory");
@@ -55,17 +421,92 @@ impl DbConnection {
         full_path
     }
 
+    // The storage key of a file under the storage root, or None for databases
+    // registered from elsewhere on disk
+    pub fn storage_key_for(&self, path: impl AsRef<Path>) -> Option<String> {
+        let relative = path.as_ref().strip_prefix(&self.config.storage_path).ok()?;
+        let key = relative.components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        (!key.is_empty()).then_some(key)
+    }
+
     pub fn get_metadata_pool(&self) -> &Pool<SqliteConnectionManager> {
         &self.metadata_pool
     }
 
-    pub fn get_database_pool(&self, path: impl AsRef<Path>) -> Pool<SqliteConnectionManager> {
-        let manager = SqliteConnectionManager::file(path.as_ref());
-        Pool::new(manager).expect("Failed to create database pool")
+    // The pool for a stored database, opened on first use and then shared
+    pub fn get_database_pool(&self, path: impl AsRef<Path>) -> DatabasePool {
+        let path = path.as_ref();
+        self.database_pools.get_or_build(path, PoolMode::ReadWrite, || {
+            self.build_database_pool(SqliteConnectionManager::file(path), self.config.wal_on_open)
+        })
+    }
+
+    // Connections opened with SQLITE_OPEN_READ_ONLY, so any write fails inside SQLite
+    // (temp objects, which don't touch the file, are still allowed)
+    pub fn get_read_only_database_pool(&self, path: impl AsRef<Path>) -> DatabasePool {
+        let path = path.as_ref();
+        self.database_pools.get_or_build(path, PoolMode::ReadOnly, || {
+            self.build_database_pool(SqliteConnectionManager::file(path).with_flags(
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            ), false)
+        })
+    }
+
+    // Close the cached pools for a database file that was deleted or replaced
+    pub fn evict_database_pool(&self, path: impl AsRef<Path>) {
+        self.database_pools.evict(path.as_ref());
+    }
+
+    pub fn database_pools(&self) -> &PoolCache {
+        &self.database_pools
+    }
+
+    pub fn with_database_pool_cache_size(mut self, size: usize) -> Self {
+        self.config_mut().database_pool_cache_size = size;
+        self.database_pools = Arc::new(PoolCache::new(size));
+        self
+    }
+
+    pub fn with_row_conversion(mut self, parallel_threshold: usize, threads: usize) -> Self {
+        let config = self.config_mut();
+        config.parallel_row_threshold = parallel_threshold;
+        config.row_conversion_threads = threads;
+        self.row_converter = Arc::new(RowConverter::new(parallel_threshold, threads));
+        self
+    }
+
+    // Read-only connections can't change the journal mode, so `wal` is only
+    // passed for writable pools
+    fn build_database_pool(&self, manager: SqliteConnectionManager, wal: bool) -> DatabasePool {
+        let (busy_timeout, foreign_keys) = (self.config.busy_timeout, self.config.foreign_keys);
+        let connection_init_sql = self.config.connection_init_sql.clone();
+        let manager = manager.with_init(move |conn| {
+            apply_connection_pragmas(conn, busy_timeout, foreign_keys)?;
+            if wal {
+                conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+            }
+            match &connection_init_sql {
+                Some(sql) => init_sql::apply(conn, sql),
+                None => Ok(()),
+            }
+        });
+        Pool::builder()
+            .max_size(self.config.database_pool_size)
+            .build(DatabaseConnectionManager::new(manager))
+            .expect("Failed to create database pool")
     }
 
     #[allow(dead_code)]
     pub fn open_database(&self, path: impl AsRef<Path>) -> rusqlite::Result<rusqlite::Connection> {
         rusqlite::Connection::open(path.as_ref())
     }
-} 
\ No newline at end of file
+}
+
+impl Default for DbConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,171 @@
+use std::io::{self, Write};
+
+use axum::body::Bytes;
+use futures::Stream;
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::db::describe;
+use crate::db::export::{self, ExportError, ExportFormat};
+use crate::utils::quote_identifier;
+
+pub const TAR_CONTENT_TYPE: &str = "application/x-tar";
+
+// Archive bytes sent to the client per chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+const CHANNEL_CAPACITY: usize = 16;
+
+// io::Write over a bounded channel, so the blocking writer only runs as far ahead
+// of the client as the channel allows
+struct ChannelWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send(&mut self, chunk: io::Result<Bytes>) -> io::Result<()> {
+        self.sender.blocking_send(chunk)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::take(&mut self.buffer));
+        self.send(Ok(chunk))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum BundleError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    #[error("table {0} changed while it was being exported")]
+    Changed(String),
+}
+
+// Archive entry name for a table: path separators and control characters can't
+// appear in it, and names that collide once cleaned get a numeric suffix
+fn entry_name(table: &str, taken: &[String]) -> String {
+    let base: String = table.chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    let mut name = format!("{}.csv", base);
+    let mut n = 1;
+    while taken.contains(&name) {
+        n += 1;
+        name = format!("{}-{}.csv", base, n);
+    }
+    name
+}
+
+fn write_table_csv(conn: &Connection, table: &str, out: impl Write) -> Result<(u64, u64), BundleError> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(table)))?;
+    Ok(export::write_export(&mut stmt, Vec::new(), ExportFormat::Csv, out, |_, _| Ok(true))?)
+}
+
+fn append_entry(out: &mut ChannelWriter, path: &str, size: u64) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_path(path)?;
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    out.write_all(header.as_bytes())
+}
+
+// Entries are padded to the 512-byte tar block size
+fn pad_entry(out: &mut ChannelWriter, size: u64) -> io::Result<()> {
+    let remainder = (size % 512) as usize;
+    if remainder > 0 {
+        out.write_all(&[0; 512][remainder..])?;
+    }
+    Ok(())
+}
+
+// Tar entries need their size up front, so each table is written twice inside one
+// read transaction: once to measure it and once into the archive. Nothing is
+// buffered beyond a chunk.
+fn write_bundle(conn: &Connection, out: &mut ChannelWriter) -> Result<(), BundleError> {
+    conn.execute_batch("BEGIN DEFERRED")?;
+    let result = (|| {
+        let schema = describe::describe_schema(conn)?;
+
+        let mut files: Vec<String> = Vec::with_capacity(schema.tables.len());
+        let mut sizes = Vec::with_capacity(schema.tables.len());
+        let mut manifest_tables = Vec::with_capacity(schema.tables.len());
+        for table in &schema.tables {
+            let (rows, bytes) = write_table_csv(conn, &table.name, io::sink())?;
+            let file = entry_name(&table.name, &files);
+
+            let mut entry = serde_json::to_value(table).unwrap_or(Value::Null);
+            entry["file"] = json!(file);
+            entry["rows"] = json!(rows);
+            manifest_tables.push(entry);
+
+            files.push(file);
+            sizes.push((rows, bytes));
+        }
+
+        let manifest = serde_json::to_vec_pretty(&json!({
+            "schema_hash": schema.schema_hash,
+            "tables": manifest_tables
+        }))
+        .unwrap_or_default();
+        append_entry(out, "manifest.json", manifest.len() as u64)?;
+        out.write_all(&manifest)?;
+        pad_entry(out, manifest.len() as u64)?;
+
+        for ((table, file), (rows, bytes)) in schema.tables.iter().zip(&files).zip(sizes) {
+            append_entry(out, file, bytes)?;
+            if write_table_csv(conn, &table.name, &mut *out)? != (rows, bytes) {
+                return Err(BundleError::Changed(table.name.clone()));
+            }
+            pad_entry(out, bytes)?;
+        }
+
+        // End-of-archive marker: two empty blocks
+        out.write_all(&[0; 1024])?;
+        out.flush()?;
+        Ok(())
+    })();
+    conn.execute_batch("COMMIT").ok();
+    result
+}
+
+// Stream a tar of manifest.json plus one CSV per table. A failure part-way ends
+// the body with an error rather than a truncated archive that looks complete.
+pub fn stream(conn: impl std::ops::Deref<Target = Connection> + Send + 'static)
+    -> impl Stream<Item = io::Result<Bytes>> + Send
+{
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+    tokio::task::spawn_blocking(move || {
+        let mut out = ChannelWriter { sender, buffer: Vec::with_capacity(CHUNK_SIZE) };
+        if let Err(e) = write_bundle(&conn, &mut out) {
+            tracing::error!("CSV bundle export failed: {}", e);
+            out.buffer.clear();
+            out.send(Err(io::Error::other(e.to_string()))).ok();
+        }
+    });
+
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+}
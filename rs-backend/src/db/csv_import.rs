@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{params_from_iter, types::Value as SqlValue, Connection};
+use serde::Serialize;
+
+use crate::utils::quote_identifier;
+
+// Column layout inferred from a CSV header and a full pass over its records
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvSchema {
+    pub columns: Vec<CsvColumn>,
+    pub row_count: usize,
+    // Headers that had to be renamed and columns typed by default rather than by data
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub sql_type: &'static str,
+}
+
+// Infer column names and SQLite types (INTEGER, REAL or TEXT) from CSV data
+pub fn infer_schema(data: &[u8]) -> Result<CsvSchema> {
+    let mut reader = csv::Reader::from_reader(data);
+    let (names, mut warnings) = column_names(reader.headers()?);
+    let mut integer = vec![true; names.len()];
+    let mut real = vec![true; names.len()];
+    let mut seen = vec![false; names.len()];
+    let mut row_count = 0;
+
+    for record in reader.records() {
+        let record = record?;
+        for (i, field) in record.iter().enumerate() {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            seen[i] = true;
+            integer[i] = integer[i] && field.parse::<i64>().is_ok();
+            real[i] = real[i] && field.parse::<f64>().is_ok();
+        }
+        row_count += 1;
+    }
+
+    let columns = names.into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            if !seen[i] {
+                warnings.push(format!("Column '{}' has no values, so it will be TEXT", name));
+            }
+            CsvColumn {
+                name,
+                sql_type: match (seen[i], integer[i], real[i]) {
+                    (true, true, _) => "INTEGER",
+                    (true, false, true) => "REAL",
+                    _ => "TEXT",
+                },
+            }
+        })
+        .collect();
+
+    Ok(CsvSchema { columns, row_count, warnings })
+}
+
+// Write CSV records into a new table, committing every `batch_size` rows
+pub fn import_csv(
+    data: &[u8],
+    schema: &CsvSchema,
+    db_path: &Path,
+    table: &str,
+    batch_size: usize,
+    mut on_progress: impl FnMut(usize) -> Result<()>,
+) -> Result<()> {
+    let mut conn = Connection::open(db_path)?;
+    let quoted_table = quote_identifier(table);
+    let column_defs: Vec<String> = schema.columns.iter()
+        .map(|c| format!("{} {}", quote_identifier(&c.name), c.sql_type))
+        .collect();
+    conn.execute_batch(&format!("CREATE TABLE {} ({})", quoted_table, column_defs.join(", ")))?;
+
+    let placeholders = vec!["?"; schema.columns.len()].join(", ");
+    let insert = format!("INSERT INTO {} VALUES ({})", quoted_table, placeholders);
+
+    let mut reader = csv::Reader::from_reader(data);
+    let mut records = reader.records();
+    let mut processed = 0;
+
+    loop {
+        let tx = conn.transaction()?;
+        let mut batch = 0;
+        {
+            let mut stmt = tx.prepare_cached(&insert)?;
+            while batch < batch_size {
+                let Some(record) = records.next() else { break };
+                let record = record?;
+                let values = record.iter()
+                    .zip(&schema.columns)
+                    .map(|(field, column)| to_sql_value(field, column.sql_type));
+                stmt.execute(params_from_iter(values))?;
+                batch += 1;
+            }
+        }
+        tx.commit()?;
+
+        processed += batch;
+        on_progress(processed)?;
+        if batch < batch_size {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Unique column names for the headers, with a warning for each one renamed
+fn column_names(headers: &csv::StringRecord) -> (Vec<String>, Vec<String>) {
+    let mut used = HashSet::new();
+    let mut warnings = Vec::new();
+    let names = headers.iter()
+        .enumerate()
+        .map(|(i, header)| {
+            let base = match header.trim() {
+                "" => {
+                    warnings.push(format!("Column {} has no header, so it will be named column_{}", i + 1, i + 1));
+                    format!("column_{}", i + 1)
+                }
+                name => name.to_string(),
+            };
+            let mut name = base.clone();
+            let mut suffix = 2;
+            while !used.insert(name.to_lowercase()) {
+                name = format!("{}_{}", base, suffix);
+                suffix += 1;
+            }
+            if name != base {
+                warnings.push(format!("Duplicate column '{}' will be named '{}'", base, name));
+            }
+            name
+        })
+        .collect();
+    (names, warnings)
+}
+
+fn to_sql_value(field: &str, sql_type: &str) -> SqlValue {
+    let trimmed = field.trim();
+    if trimmed.is_empty() {
+        return SqlValue::Null;
+    }
+    match sql_type {
+        "INTEGER" => trimmed.parse().map(SqlValue::Integer).unwrap_or_else(|_| SqlValue::Text(field.to_string())),
+        "REAL" => trimmed.parse().map(SqlValue::Real).unwrap_or_else(|_| SqlValue::Text(field.to_string())),
+        _ => SqlValue::Text(field.to_string()),
+    }
+}
+
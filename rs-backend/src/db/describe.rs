@@ -0,0 +1,118 @@
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::utils::sha256_hex;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnDescription {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub decl_type: String,
+    pub not_null: bool,
+    pub default: Option<String>,
+    // Position within the primary key (1-based), or 0 when not part of it
+    pub primary_key: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForeignKeyDescription {
+    // The constraint's id within its table, as PRAGMA foreign_key_list numbers it
+    #[serde(skip)]
+    pub id: i64,
+    // Columns in key order (the pragma's `seq`)
+    pub columns: Vec<String>,
+    pub references_table: String,
+    // None where the key references the parent's primary key implicitly
+    pub references_columns: Vec<Option<String>>,
+    pub on_update: String,
+    pub on_delete: String,
+    #[serde(rename = "match")]
+    pub match_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableDescription {
+    pub name: String,
+    pub columns: Vec<ColumnDescription>,
+    pub primary_key: Vec<String>,
+    pub foreign_keys: Vec<ForeignKeyDescription>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaDescription {
+    pub tables: Vec<TableDescription>,
+    // SHA-256 of the serialized tables, so structural changes are detectable
+    // without comparing the whole description
+    pub schema_hash: String,
+}
+
+fn describe_columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<ColumnDescription>> {
+    let mut stmt = conn.prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid")?;
+    let columns = stmt.query_map([table], |row| {
+        Ok(ColumnDescription {
+            name: row.get(0)?,
+            decl_type: row.get(1)?,
+            not_null: row.get(2)?,
+            default: row.get(3)?,
+            primary_key: row.get(4)?,
+        })
+    })?
+    .collect();
+    columns
+}
+
+// A table's foreign keys, one per constraint. The pragma returns a row per column
+// pair, so composite keys are grouped by id.
+pub fn describe_foreign_keys(conn: &Connection, table: &str) -> rusqlite::Result<Vec<ForeignKeyDescription>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete, \"match\"
+         FROM pragma_foreign_key_list(?1) ORDER BY id, seq"
+    )?;
+    let rows: Vec<ForeignKeyDescription> = stmt
+        .query_map([table], |row| {
+            Ok(ForeignKeyDescription {
+                id: row.get(0)?,
+                references_table: row.get(1)?,
+                columns: vec![row.get(2)?],
+                references_columns: vec![row.get(3)?],
+                on_update: row.get(4)?,
+                on_delete: row.get(5)?,
+                match_type: row.get(6)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut keys: Vec<ForeignKeyDescription> = Vec::new();
+    for row in rows {
+        match keys.last_mut() {
+            Some(key) if key.id == row.id => {
+                key.columns.extend(row.columns);
+                key.references_columns.extend(row.references_columns);
+            }
+            _ => keys.push(row),
+        }
+    }
+    Ok(keys)
+}
+
+// Structure of every ordinary table, read from the schema and table PRAGMAs only;
+// no table is scanned
+pub fn describe_schema(conn: &Connection) -> rusqlite::Result<SchemaDescription> {
+    let names: Vec<String> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut tables = Vec::with_capacity(names.len());
+    for name in names {
+        let columns = describe_columns(conn, &name)?;
+        let mut key: Vec<&ColumnDescription> = columns.iter().filter(|c| c.primary_key > 0).collect();
+        key.sort_by_key(|c| c.primary_key);
+        let primary_key = key.into_iter().map(|c| c.name.clone()).collect();
+        let foreign_keys = describe_foreign_keys(conn, &name)?;
+        tables.push(TableDescription { name, columns, primary_key, foreign_keys });
+    }
+
+    let schema_hash = sha256_hex(&serde_json::to_vec(&tables).unwrap_or_default());
+    Ok(SchemaDescription { tables, schema_hash })
+}
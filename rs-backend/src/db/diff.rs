@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::db::query;
+use crate::utils::quote_identifier;
+
+// Column layout needed to key supplied rows against a table
+#[derive(Debug, Clone)]
+pub struct TableColumns {
+    pub columns: Vec<String>,
+    pub primary_key: Vec<String>,
+}
+
+// Read column names and the primary key (in key order) for a table; None if it doesn't exist
+pub fn table_columns(conn: &Connection, table: &str) -> rusqlite::Result<Option<TableColumns>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))?;
+    let info: Vec<(String, i64)> = stmt.query_map([], |row| Ok((row.get(1)?, row.get(5)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    if info.is_empty() {
+        return Ok(None);
+    }
+
+    let mut key: Vec<&(String, i64)> = info.iter().filter(|(_, pk)| *pk > 0).collect();
+    key.sort_by_key(|(_, pk)| *pk);
+
+    Ok(Some(TableColumns {
+        primary_key: key.into_iter().map(|(name, _)| name.clone()).collect(),
+        columns: info.into_iter().map(|(name, _)| name).collect(),
+    }))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub from: Value,
+    pub to: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowUpdate {
+    pub key: Map<String, Value>,
+    pub changes: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffPreview {
+    pub insert: Vec<Map<String, Value>>,
+    pub update: Vec<RowUpdate>,
+    pub unchanged: Vec<Map<String, Value>>,
+}
+
+// Problems with the supplied rows themselves, reported before anything is compared
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowError {
+    UnknownColumns(Vec<String>),
+    MissingKey { row: usize, columns: Vec<String> },
+    DuplicateKey { row: usize },
+    InvalidValue { row: usize, column: String, message: String },
+}
+
+// Check every supplied row against the table's columns and primary key
+pub fn validate_rows(table: &TableColumns, rows: &[Map<String, Value>]) -> Result<(), RowError> {
+    let mut unknown: Vec<String> = rows.iter()
+        .flat_map(|row| row.keys())
+        .filter(|column| !table.columns.contains(column))
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(RowError::UnknownColumns(unknown));
+    }
+
+    let mut seen = HashSet::new();
+    for (i, row) in rows.iter().enumerate() {
+        let missing: Vec<String> = table.primary_key.iter()
+            .filter(|column| row.get(*column).is_none_or(Value::is_null))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(RowError::MissingKey { row: i, columns: missing });
+        }
+
+        for (column, value) in row {
+            if let Err(message) = query::json_to_sql(value) {
+                return Err(RowError::InvalidValue { row: i, column: column.clone(), message });
+            }
+        }
+
+        let key: Vec<String> = table.primary_key.iter().map(|column| row[column].to_string()).collect();
+        if !seen.insert(key) {
+            return Err(RowError::DuplicateKey { row: i });
+        }
+    }
+
+    Ok(())
+}
+
+// SQLite compares integers and reals numerically, so 1 and 1.0 are the same value
+fn same_value(current: &SqlValue, supplied: &SqlValue) -> bool {
+    match (current, supplied) {
+        (SqlValue::Integer(a), SqlValue::Real(b)) | (SqlValue::Real(b), SqlValue::Integer(a)) => *a as f64 == *b,
+        _ => current == supplied,
+    }
+}
+
+// Categorize validated rows as inserts, updates (with per-field changes) or unchanged,
+// looking each one up by primary key without modifying the table
+pub fn preview(
+    conn: &Connection,
+    table_name: &str,
+    table: &TableColumns,
+    rows: &[Map<String, Value>],
+) -> rusqlite::Result<DiffPreview> {
+    let predicate = table.primary_key.iter()
+        .map(|column| format!("{} = ?", quote_identifier(column)))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM {} WHERE {}",
+        quote_identifier(table_name),
+        predicate
+    ))?;
+    let columns = query::column_names(&stmt);
+
+    let mut diff = DiffPreview::default();
+    for row in rows {
+        let key: Map<String, Value> = table.primary_key.iter()
+            .map(|column| (column.clone(), row[column].clone()))
+            .collect();
+        let key_params = table.primary_key.iter()
+            .map(|column| query::json_to_sql(&row[column]).unwrap_or(SqlValue::Null));
+
+        let mut existing = stmt.query(params_from_iter(key_params))?;
+        let Some(current) = existing.next()? else {
+            diff.insert.push(row.clone());
+            continue;
+        };
+
+        let mut changes = Map::new();
+        for (column, supplied) in row {
+            let index = columns.iter().position(|c| c == column).unwrap_or_default();
+            let current_value: SqlValue = current.get(index)?;
+            let supplied_value = query::json_to_sql(supplied).unwrap_or(SqlValue::Null);
+            if !same_value(&current_value, &supplied_value) {
+                let change = FieldChange {
+                    from: query::value_to_json(ValueRef::from(&current_value)),
+                    to: supplied.clone(),
+                };
+                changes.insert(column.clone(), serde_json::to_value(change).unwrap_or_default());
+            }
+        }
+
+        if changes.is_empty() {
+            diff.unchanged.push(key);
+        } else {
+            diff.update.push(RowUpdate { key, changes });
+        }
+    }
+
+    Ok(diff)
+}
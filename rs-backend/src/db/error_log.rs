@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::utils::pagination::Pagination;
+
+// One error response as reported by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEntry {
+    pub at: DateTime<Utc>,
+    pub method: String,
+    // The route pattern, e.g. /databases/:id/query, when the request matched one
+    pub route: Option<String>,
+    pub path: String,
+    pub database_id: Option<i64>,
+    pub status: u16,
+    // The response's `error` field, or its whole body when it has none
+    pub error: Value,
+}
+
+// The most recent error responses, oldest dropped first once `capacity` is reached
+pub struct ErrorLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<ErrorEntry>>,
+}
+
+impl ErrorLog {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self { capacity, entries: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    pub fn record(&self, entry: ErrorEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    // Newest first
+    pub fn recent(&self, page: Pagination) -> Vec<ErrorEntry> {
+        self.entries.lock().unwrap()
+            .iter()
+            .rev()
+            .skip(page.offset as usize)
+            .take(page.limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
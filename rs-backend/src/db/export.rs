@@ -0,0 +1,153 @@
+use std::io::{self, Write};
+
+use rusqlite::{params_from_iter, types::Value as SqlValue, types::ValueRef, Statement};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::db::query;
+
+// Rows written between progress reports (and cancellation checks)
+pub const EXPORT_PROGRESS_INTERVAL: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => crate::db::stream::NDJSON_CONTENT_TYPE,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Export cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Progress(String),
+}
+
+// Counts bytes passed through to the inner writer
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// CSV cells are text; NULL becomes an empty field and blobs a size placeholder
+pub fn csv_cell(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(s) => String::from_utf8_lossy(s).into_owned(),
+        ValueRef::Blob(b) => format!("<BLOB: {} bytes>", b.len()),
+    }
+}
+
+enum Sink<W: Write> {
+    Csv(Box<csv::Writer<CountingWriter<W>>>),
+    Ndjson(CountingWriter<W>),
+}
+
+impl<W: Write> Sink<W> {
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Csv(writer) => writer.flush(),
+            Self::Ndjson(writer) => writer.flush(),
+        }
+    }
+
+    // Bytes handed to the underlying writer so far (after a flush, everything written)
+    fn written(&self) -> u64 {
+        match self {
+            Self::Csv(writer) => writer.get_ref().written,
+            Self::Ndjson(writer) => writer.written,
+        }
+    }
+}
+
+// Write every row of `stmt` to `out`, calling `progress(rows, bytes)` every
+// EXPORT_PROGRESS_INTERVAL rows; the export stops with `Cancelled` as soon as
+// it returns Ok(false). Returns the final row and byte counts.
+pub fn write_export<W: Write>(
+    stmt: &mut Statement<'_>,
+    params: Vec<SqlValue>,
+    format: ExportFormat,
+    out: W,
+    mut progress: impl FnMut(u64, u64) -> Result<bool, String>,
+) -> Result<(u64, u64), ExportError> {
+    let columns = query::column_names(stmt);
+    let out = CountingWriter { inner: out, written: 0 };
+    let mut sink = match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(&columns)?;
+            Sink::Csv(Box::new(writer))
+        }
+        ExportFormat::Ndjson => Sink::Ndjson(out),
+    };
+
+    let mut rows_written = 0u64;
+    let mut rows = stmt.query(params_from_iter(params))?;
+    while let Some(row) = rows.next()? {
+        match &mut sink {
+            Sink::Csv(writer) => {
+                let record = (0..columns.len())
+                    .map(|i| row.get_ref(i).map(csv_cell))
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                writer.write_record(&record)?;
+            }
+            Sink::Ndjson(writer) => {
+                let mut obj = Map::new();
+                for (i, column) in columns.iter().enumerate() {
+                    obj.insert(column.clone(), query::value_to_json(row.get_ref(i)?));
+                }
+                let mut line = Value::Object(obj).to_string().into_bytes();
+                line.push(b'\n');
+                writer.write_all(&line)?;
+            }
+        }
+        rows_written += 1;
+
+        if rows_written.is_multiple_of(EXPORT_PROGRESS_INTERVAL as u64) {
+            sink.flush()?;
+            if !progress(rows_written, sink.written()).map_err(ExportError::Progress)? {
+                return Err(ExportError::Cancelled);
+            }
+        }
+    }
+
+    sink.flush()?;
+    Ok((rows_written, sink.written()))
+}
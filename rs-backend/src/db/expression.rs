@@ -0,0 +1,88 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::quote_identifier;
+
+// Where a fragment will be used: as a result column or as a filter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpressionKind {
+    #[default]
+    Select,
+    Where,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpressionCheck {
+    pub valid: bool,
+    pub kind: ExpressionKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Declared type when a select expression is a plain column reference
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declared_type: Option<String>,
+    // Storage class the expression yields for the table's first row, when it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inferred_type: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpressionError {
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+fn invalid(kind: ExpressionKind, error: String) -> ExpressionCheck {
+    ExpressionCheck { valid: false, kind, error: Some(error), declared_type: None, inferred_type: None }
+}
+
+// Check that `expression` compiles against `table` by preparing (never running) a
+// query around it. Only a single read-only statement counts as valid.
+pub fn check_expression(
+    conn: &Connection,
+    table: &str,
+    expression: &str,
+    kind: ExpressionKind,
+) -> Result<ExpressionCheck, ExpressionError> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?",
+            [table],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        return Err(ExpressionError::TableNotFound(table.to_string()));
+    }
+
+    let table = quote_identifier(table);
+    let sql = match kind {
+        ExpressionKind::Select => format!("SELECT {} FROM {} LIMIT 0", expression, table),
+        ExpressionKind::Where => format!("SELECT 1 FROM {} WHERE {} LIMIT 0", table, expression),
+    };
+    let stmt = match conn.prepare(&sql) {
+        Ok(stmt) => stmt,
+        Err(e) => return Ok(invalid(kind, e.to_string())),
+    };
+    if !stmt.readonly() {
+        return Ok(invalid(kind, "Expression must not modify the database".to_string()));
+    }
+    if kind == ExpressionKind::Select && stmt.column_count() != 1 {
+        return Ok(invalid(kind, "Expression must produce exactly one column".to_string()));
+    }
+    if kind == ExpressionKind::Where {
+        return Ok(ExpressionCheck { valid: true, kind, error: None, declared_type: None, inferred_type: None });
+    }
+
+    let declared_type = stmt.columns()[0].decl_type().map(String::from);
+    drop(stmt);
+    let inferred_type = conn
+        .query_row(&format!("SELECT typeof({}) FROM {} LIMIT 1", expression, table), [], |row| row.get(0))
+        .optional()
+        .unwrap_or(None);
+
+    Ok(ExpressionCheck { valid: true, kind, error: None, declared_type, inferred_type })
+}
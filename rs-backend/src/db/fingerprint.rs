@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::utils::files_sha256;
+
+pub const ALGORITHM: &str = "sha256";
+
+// Modification time and length of a file, or None when it doesn't exist
+type FileStamp = Option<(SystemTime, u64)>;
+
+// What a cached fingerprint was computed from. In WAL mode committed writes land
+// in the -wal file and leave the main file untouched until a checkpoint, so both
+// files are stamped and hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    main: FileStamp,
+    wal: FileStamp,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Fingerprint {
+    pub fingerprint: String,
+    pub algorithm: &'static str,
+    // Whether the value came from the cache rather than reading the file
+    pub cached: bool,
+}
+
+// Fingerprints by database path, reused until the file's mtime or size changes
+#[derive(Default)]
+pub struct FingerprintCache {
+    entries: Mutex<HashMap<PathBuf, (Stamp, String)>>,
+}
+
+fn wal_path(path: &Path) -> PathBuf {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    PathBuf::from(wal)
+}
+
+fn file_stamp(path: &Path) -> io::Result<FileStamp> {
+    match std::fs::metadata(path) {
+        Ok(meta) => Ok(Some((meta.modified()?, meta.len()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl FingerprintCache {
+    // SHA-256 of the database file's bytes, followed by its -wal file's when one
+    // exists. Byte-level, so VACUUM or a checkpoint changes it even though the
+    // contents read back the same.
+    pub fn fingerprint(&self, path: impl AsRef<Path>) -> io::Result<Fingerprint> {
+        let path = path.as_ref();
+        let wal = wal_path(path);
+        let stamp = Stamp { main: file_stamp(path)?, wal: file_stamp(&wal)? };
+        if stamp.main.is_none() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Database file not found"));
+        }
+
+        if let Some((cached_stamp, hash)) = self.entries.lock().unwrap().get(path) {
+            if *cached_stamp == stamp {
+                return Ok(Fingerprint { fingerprint: hash.clone(), algorithm: ALGORITHM, cached: true });
+            }
+        }
+
+        let files = if stamp.wal.is_some() { vec![path, wal.as_path()] } else { vec![path] };
+        let hash = files_sha256(files)?;
+
+        self.entries.lock().unwrap().insert(path.to_path_buf(), (stamp, hash.clone()));
+        Ok(Fingerprint { fingerprint: hash, algorithm: ALGORITHM, cached: false })
+    }
+}
@@ -0,0 +1,133 @@
+use rusqlite::Connection;
+
+use crate::db::describe::describe_foreign_keys;
+use crate::utils::quote_identifier;
+
+struct Column {
+    name: String,
+    decl_type: String,
+    not_null: bool,
+    primary_key: bool,
+}
+
+struct ForeignKey {
+    from: String,
+    table: String,
+}
+
+// GraphQL names must match /[_A-Za-z][_0-9A-Za-z]*/
+fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !sanitized.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+// `order_items` -> `OrderItems`
+fn type_name(table: &str) -> String {
+    let pascal: String = table.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+    sanitize_name(&pascal)
+}
+
+// GraphQL scalar for a declared SQLite column type, following SQLite's affinity rules
+fn scalar_type(decl_type: &str) -> &'static str {
+    let decl = decl_type.to_ascii_uppercase();
+    if decl.contains("BOOL") {
+        "Boolean"
+    } else if decl.contains("INT") {
+        "Int"
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        "String"
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB")
+        || decl.contains("NUM") || decl.contains("DEC")
+    {
+        "Float"
+    } else {
+        "String"
+    }
+}
+
+fn read_columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<Column>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))?;
+    let columns = stmt.query_map([], |row| {
+        Ok(Column {
+            name: row.get(1)?,
+            decl_type: row.get(2)?,
+            not_null: row.get(3)?,
+            primary_key: row.get::<_, i64>(5)? > 0,
+        })
+    })?
+    .collect();
+    columns
+}
+
+fn read_foreign_keys(conn: &Connection, table: &str) -> rusqlite::Result<Vec<ForeignKey>> {
+    // Composite keys have no single column to hang a reference off, so skip them
+    Ok(describe_foreign_keys(conn, table)?
+        .into_iter()
+        .filter(|fk| fk.columns.len() == 1)
+        .map(|fk| ForeignKey { from: fk.columns[0].clone(), table: fk.references_table })
+        .collect())
+}
+
+// Field name for a reference: `parent_id` -> `parent`, otherwise the referenced table
+fn reference_field(fk: &ForeignKey, taken: &[String]) -> String {
+    let lower = fk.from.to_ascii_lowercase();
+    let base = ["_id", "id"].iter()
+        .find_map(|suffix| lower.strip_suffix(suffix).filter(|b| !b.is_empty()).map(|b| fk.from[..b.len()].to_string()))
+        .unwrap_or_else(|| fk.table.clone());
+    let mut name = sanitize_name(base.trim_end_matches('_'));
+    while taken.contains(&name) {
+        name.push_str("Ref");
+    }
+    name
+}
+
+// Render one GraphQL object type per table; foreign keys become object references
+pub fn generate_sdl(conn: &Connection) -> rusqlite::Result<String> {
+    let tables: Vec<String> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut types = Vec::with_capacity(tables.len());
+    for table in &tables {
+        let columns = read_columns(conn, table)?;
+        let single_key = columns.iter().filter(|c| c.primary_key).count() == 1;
+
+        let mut fields = Vec::new();
+        let mut taken = Vec::new();
+        for column in &columns {
+            let name = sanitize_name(&column.name);
+            let scalar = if column.primary_key && single_key { "ID" } else { scalar_type(&column.decl_type) };
+            let required = if column.not_null || (column.primary_key && single_key) { "!" } else { "" };
+            fields.push(format!("  {}: {}{}", name, scalar, required));
+            taken.push(name);
+        }
+
+        for fk in read_foreign_keys(conn, table)? {
+            if !tables.contains(&fk.table) {
+                continue;
+            }
+            let required = columns.iter().any(|c| c.name == fk.from && c.not_null);
+            let name = reference_field(&fk, &taken);
+            fields.push(format!("  {}: {}{}", name, type_name(&fk.table), if required { "!" } else { "" }));
+            taken.push(name);
+        }
+
+        types.push(format!("type {} {{\n{}\n}}", type_name(table), fields.join("\n")));
+    }
+
+    Ok(types.join("\n\n") + "\n")
+}
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rusqlite::hooks::Action;
+use rusqlite::Connection;
+
+// How many VM instructions run between checks of the change counter and deadline
+const PROGRESS_INTERVAL: i32 = 1000;
+
+const NOT_TRIPPED: u8 = 0;
+const TRIPPED_CHANGES: u8 = 1;
+const TRIPPED_TIMEOUT: u8 = 2;
+
+// Which limit stopped a statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tripped {
+    MaxChanges(u64),
+    Timeout(Duration),
+}
+
+// Aborts statements on a connection once they (and any triggers they fire) have
+// changed more than `max_changes` rows, or once `timeout` has passed since the
+// guard was installed. A runaway change count almost always means a trigger loop.
+// Both share SQLite's single progress handler, which is removed on drop so the
+// limits don't follow the connection back into the pool.
+pub struct StatementGuard<'c> {
+    conn: &'c Connection,
+    max_changes: Option<u64>,
+    timeout: Option<Duration>,
+    tripped: Arc<AtomicU8>,
+}
+
+impl<'c> StatementGuard<'c> {
+    pub fn install(conn: &'c Connection, max_changes: Option<u64>, timeout: Option<Duration>) -> rusqlite::Result<Self> {
+        let tripped = Arc::new(AtomicU8::new(NOT_TRIPPED));
+        let guard = Self { conn, max_changes, timeout, tripped: tripped.clone() };
+        if max_changes.is_none() && timeout.is_none() {
+            return Ok(guard);
+        }
+
+        let changes = Arc::new(AtomicU64::new(0));
+        if max_changes.is_some() {
+            // Never let a trigger re-fire itself, whatever an earlier statement set
+            conn.execute_batch("PRAGMA recursive_triggers = OFF")?;
+
+            let counter = changes.clone();
+            conn.update_hook(Some(move |_: Action, _: &str, _: &str, _: i64| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        conn.progress_handler(PROGRESS_INTERVAL, Some(move || {
+            let reason = if max_changes.is_some_and(|max| changes.load(Ordering::Relaxed) > max) {
+                TRIPPED_CHANGES
+            } else if deadline.is_some_and(|d| Instant::now() >= d) {
+                TRIPPED_TIMEOUT
+            } else {
+                return false;
+            };
+            tripped.store(reason, Ordering::Relaxed);
+            true
+        }));
+
+        Ok(guard)
+    }
+
+    // The limit that aborted a statement, if one did
+    pub fn tripped(&self) -> Option<Tripped> {
+        match self.tripped.load(Ordering::Relaxed) {
+            TRIPPED_CHANGES => self.max_changes.map(Tripped::MaxChanges),
+            TRIPPED_TIMEOUT => self.timeout.map(Tripped::Timeout),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for StatementGuard<'_> {
+    fn drop(&mut self) {
+        if self.max_changes.is_some() {
+            self.conn.update_hook(None::<fn(Action, &str, &str, i64)>);
+        }
+        if self.max_changes.is_some() || self.timeout.is_some() {
+            self.conn.progress_handler(0, None::<fn() -> bool>);
+        }
+    }
+}
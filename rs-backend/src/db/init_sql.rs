@@ -0,0 +1,42 @@
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::{Connection, ErrorCode};
+
+// Reads and changes to the connection's temp schema are allowed; anything that
+// would persist to the database file (or change connection-wide settings) is denied
+fn authorize(ctx: AuthContext<'_>) -> Authorization {
+    let temp = ctx.database_name == Some("temp");
+    match ctx.action {
+        AuthAction::Read { .. } | AuthAction::Select | AuthAction::Function { .. } => Authorization::Allow,
+        AuthAction::CreateTempTable { .. }
+        | AuthAction::CreateTempView { .. }
+        | AuthAction::CreateTempIndex { .. }
+        | AuthAction::CreateTempTrigger { .. }
+        | AuthAction::DropTempTable { .. }
+        | AuthAction::DropTempView { .. }
+        | AuthAction::DropTempIndex { .. }
+        | AuthAction::DropTempTrigger { .. } => Authorization::Allow,
+        AuthAction::Insert { .. } | AuthAction::Update { .. } | AuthAction::Delete { .. } if temp => Authorization::Allow,
+        _ => Authorization::Deny,
+    }
+}
+
+// Run operator-supplied setup SQL on a freshly opened connection
+pub fn apply(conn: &Connection, sql: &str) -> rusqlite::Result<()> {
+    conn.authorizer(Some(authorize));
+    let result = conn.execute_batch(sql);
+    conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+    result
+}
+
+// Reject init SQL that would write to a database file. Runs against an empty
+// in-memory database, so statements naming user tables can't be fully checked
+// here; apply() still refuses them on real connections.
+pub fn validate(sql: &str) -> Result<(), String> {
+    let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+    match apply(&conn, sql) {
+        Err(rusqlite::Error::SqliteFailure(e, message)) if e.code == ErrorCode::AuthorizationForStatementDenied => {
+            Err(message.unwrap_or_else(|| e.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,44 @@
+use rusqlite::Connection;
+use serde::Serialize;
+
+// Problems kept per check; a badly damaged file can report one per row
+pub const MAX_REPORTED_PROBLEMS: usize = 100;
+
+// What one of SQLite's consistency pragmas found
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckOutcome {
+    pub ok: bool,
+    pub problems: Vec<String>,
+    // More problems were found than are listed
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub ok: bool,
+    pub quick_check: CheckOutcome,
+    pub integrity_check: CheckOutcome,
+}
+
+// Run `PRAGMA quick_check` and `PRAGMA integrity_check`. quick_check skips index
+// contents, so a file can pass it and still fail the full check.
+pub fn check(conn: &Connection) -> rusqlite::Result<IntegrityReport> {
+    let quick_check = run(conn, "quick_check")?;
+    let integrity_check = run(conn, "integrity_check")?;
+    Ok(IntegrityReport { ok: quick_check.ok && integrity_check.ok, quick_check, integrity_check })
+}
+
+fn run(conn: &Connection, pragma: &str) -> rusqlite::Result<CheckOutcome> {
+    // Ask for one row past the cap so a truncated list can be told apart
+    let mut stmt = conn.prepare(&format!("PRAGMA {}({})", pragma, MAX_REPORTED_PROBLEMS + 1))?;
+    let mut problems: Vec<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    if problems.len() == 1 && problems[0] == "ok" {
+        return Ok(CheckOutcome { ok: true, problems: Vec::new(), truncated: false });
+    }
+    let truncated = problems.len() > MAX_REPORTED_PROBLEMS;
+    problems.truncate(MAX_REPORTED_PROBLEMS);
+    Ok(CheckOutcome { ok: false, problems, truncated })
+}
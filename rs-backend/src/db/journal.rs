@@ -0,0 +1,22 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+
+// Switch a database file to WAL mode, returning the journal mode it had before.
+// The change is persistent: it's recorded in the file header, so every later
+// connection opens the database in WAL mode.
+pub fn convert_to_wal(path: impl AsRef<Path>) -> rusqlite::Result<String> {
+    let conn = Connection::open(path)?;
+    let original: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    if !original.eq_ignore_ascii_case("wal") {
+        // Needs an exclusive lock on the file, which a freshly stored upload has no contention for
+        let mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+                Some(format!("journal mode stayed {} after switching to WAL", mode)),
+            ));
+        }
+    }
+    Ok(original.to_ascii_lowercase())
+}
@@ -0,0 +1,73 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+// One schema step, applied when the database's user_version is below `version`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Migration {
+    pub version: i64,
+    pub up_sql: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    pub user_version_before: i64,
+    pub user_version: i64,
+    pub applied: Vec<i64>,
+    pub skipped: Vec<i64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("Migration versions must be positive and strictly increasing (got {version} after {previous})")]
+    OutOfOrder { version: i64, previous: i64 },
+    #[error("Migration {version} failed: {source}")]
+    Step { version: i64, source: rusqlite::Error },
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+// Versions must be positive and listed in ascending order
+pub fn validate(migrations: &[Migration]) -> Result<(), MigrationError> {
+    let mut previous = 0;
+    for migration in migrations {
+        if migration.version <= previous {
+            return Err(MigrationError::OutOfOrder { version: migration.version, previous });
+        }
+        previous = migration.version;
+    }
+    Ok(())
+}
+
+// Apply every step newer than the current user_version in a single transaction,
+// bumping user_version after each; any failure rolls the whole run back
+pub fn apply(conn: &Connection, migrations: &[Migration]) -> Result<MigrationReport, MigrationError> {
+    validate(migrations)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let user_version_before: i64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    let mut report = MigrationReport {
+        user_version_before,
+        user_version: user_version_before,
+        applied: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    for migration in migrations {
+        if migration.version <= report.user_version {
+            report.skipped.push(migration.version);
+            continue;
+        }
+
+        let step = |source| MigrationError::Step { version: migration.version, source };
+        tx.execute_batch(&migration.up_sql).map_err(step)?;
+        // PRAGMA values can't be bound as parameters; version is an integer
+        tx.execute_batch(&format!("PRAGMA user_version = {}", migration.version)).map_err(step)?;
+
+        report.user_version = migration.version;
+        report.applied.push(migration.version);
+    }
+
+    tx.commit()?;
+    Ok(report)
+}
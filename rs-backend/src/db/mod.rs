@@ -1,2 +1,35 @@
+pub mod arrow_export;
+pub mod blocklist;
+pub mod column_meta;
+pub mod column_policy;
 pub mod connection;
-pub mod models; 
\ No newline at end of file
+pub mod csv_bundle;
+pub mod csv_import;
+pub mod describe;
+pub mod diff;
+pub mod error_log;
+pub mod export;
+pub mod expression;
+pub mod fingerprint;
+pub mod graphql;
+pub mod guard;
+pub mod init_sql;
+pub mod integrity;
+pub mod journal;
+pub mod migrations;
+pub mod models;
+pub mod pivot;
+pub mod pool_cache;
+pub mod pragmas;
+pub mod quality;
+pub mod query;
+pub mod registry;
+pub mod result_diff;
+pub mod row_conversion;
+pub mod sample_insert;
+pub mod self_test;
+pub mod snapshot;
+pub mod stream;
+pub mod template;
+pub mod upload_quota;
+pub mod xlsx_export;
@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// Distinct col_key values a pivot may spread across before it's refused
+pub const MAX_PIVOT_COLUMNS: usize = 200;
+
+// How the values landing in one cell are combined; NULLs are ignored, as in SQL
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Count,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PivotError {
+    UnknownColumn(String),
+    TooManyColumns { limit: usize },
+    NonNumericValue(Value),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PivotRow {
+    pub key: Value,
+    pub values: Map<String, Value>,
+}
+
+// Row and column keys are listed in the order they first appear in the result
+#[derive(Debug, Clone, Serialize)]
+pub struct Pivot {
+    pub columns: Vec<String>,
+    pub rows: Vec<PivotRow>,
+}
+
+// Object keys for column headers: strings as-is, anything else as its JSON text
+fn column_label(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// SQLite's ordering across types: numbers before text before anything else
+fn compare(a: &Value, b: &Value) -> Ordering {
+    let rank = |v: &Value| match v {
+        Value::Number(_) => 0,
+        Value::String(_) => 1,
+        _ => 2,
+    };
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            x.as_f64().unwrap_or_default().total_cmp(&y.as_f64().unwrap_or_default())
+        }
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn sum(values: &[&Value]) -> Result<Value, PivotError> {
+    if let Some(bad) = values.iter().find(|v| !v.is_number()) {
+        return Err(PivotError::NonNumericValue((*bad).clone()));
+    }
+    // Stay integral while every value is an integer and the total fits
+    let integral = values.iter()
+        .try_fold(0i64, |total, v| v.as_i64().and_then(|n| total.checked_add(n)));
+    Ok(match integral {
+        Some(total) => Value::from(total),
+        None => Value::from(values.iter().filter_map(|v| v.as_f64()).sum::<f64>()),
+    })
+}
+
+fn aggregate(values: &[&Value], aggregate: Aggregate) -> Result<Value, PivotError> {
+    if values.is_empty() {
+        return Ok(if aggregate == Aggregate::Count { Value::from(0) } else { Value::Null });
+    }
+    Ok(match aggregate {
+        Aggregate::Count => Value::from(values.len()),
+        Aggregate::Sum => sum(values)?,
+        Aggregate::Avg => {
+            let total = sum(values)?.as_f64().unwrap_or_default();
+            Value::from(total / values.len() as f64)
+        }
+        Aggregate::Min => values.iter().copied().min_by(|a, b| compare(a, b)).cloned().unwrap_or_default(),
+        Aggregate::Max => values.iter().copied().max_by(|a, b| compare(a, b)).cloned().unwrap_or_default(),
+    })
+}
+
+// Cross-tabulate a materialized result: one row per distinct `row_key`, one column
+// per distinct `col_key`, each cell aggregating the `value` column. Combinations
+// that never occur are left out of the row's `values`.
+pub fn pivot(
+    columns: &[String],
+    rows: &[Vec<Value>],
+    row_key: &str,
+    col_key: &str,
+    value: &str,
+    how: Aggregate,
+    max_columns: usize,
+) -> Result<Pivot, PivotError> {
+    let index = |name: &str| columns.iter()
+        .position(|c| c == name)
+        .ok_or_else(|| PivotError::UnknownColumn(name.to_string()));
+    let (row_index, col_index, value_index) = (index(row_key)?, index(col_key)?, index(value)?);
+
+    let mut column_labels: Vec<String> = Vec::new();
+    let mut column_positions: HashMap<String, usize> = HashMap::new();
+    let mut row_keys: Vec<&Value> = Vec::new();
+    let mut row_positions: HashMap<String, usize> = HashMap::new();
+    // cells[row][column] holds the non-NULL values of that cell; None if it never occurs
+    let mut cells: Vec<Vec<Option<Vec<&Value>>>> = Vec::new();
+
+    for row in rows {
+        let label = column_label(&row[col_index]);
+        let column = match column_positions.get(&label) {
+            Some(&position) => position,
+            None => {
+                if column_labels.len() >= max_columns {
+                    return Err(PivotError::TooManyColumns { limit: max_columns });
+                }
+                column_positions.insert(label.clone(), column_labels.len());
+                column_labels.push(label);
+                column_labels.len() - 1
+            }
+        };
+
+        let key = &row[row_index];
+        let position = *row_positions.entry(key.to_string()).or_insert_with(|| {
+            row_keys.push(key);
+            cells.push(Vec::new());
+            row_keys.len() - 1
+        });
+
+        let row_cells = &mut cells[position];
+        if row_cells.len() <= column {
+            row_cells.resize_with(column + 1, || None);
+        }
+        let cell = row_cells[column].get_or_insert_with(Vec::new);
+        if !row[value_index].is_null() {
+            cell.push(&row[value_index]);
+        }
+    }
+
+    let mut pivoted = Vec::with_capacity(row_keys.len());
+    for (key, row_cells) in row_keys.into_iter().zip(&cells) {
+        let mut values = Map::new();
+        for (column, cell) in row_cells.iter().enumerate() {
+            if let Some(cell) = cell {
+                values.insert(column_labels[column].clone(), aggregate(cell, how)?);
+            }
+        }
+        pivoted.push(PivotRow { key: key.clone(), values });
+    }
+
+    Ok(Pivot { columns: column_labels, rows: pivoted })
+}
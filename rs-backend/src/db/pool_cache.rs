@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use r2d2::{ManageConnection, Pool};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+// SqliteConnectionManager for long-lived pools. A connection keeps the schema it
+// last loaded, so after another connection alters a table it would prepare
+// statements (and report `SELECT *` columns) against the old one. Reading
+// sqlite_schema on checkout compares the schema cookie and reloads if it moved.
+pub struct DatabaseConnectionManager(SqliteConnectionManager);
+
+impl DatabaseConnectionManager {
+    pub fn new(manager: SqliteConnectionManager) -> Self {
+        Self(manager)
+    }
+}
+
+impl ManageConnection for DatabaseConnectionManager {
+    type Connection = Connection;
+    type Error = rusqlite::Error;
+
+    fn connect(&self) -> Result<Connection, rusqlite::Error> {
+        self.0.connect()
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> Result<(), rusqlite::Error> {
+        conn.execute_batch("SELECT 1 FROM sqlite_schema LIMIT 0")
+    }
+
+    fn has_broken(&self, conn: &mut Connection) -> bool {
+        self.0.has_broken(conn)
+    }
+}
+
+pub type DatabasePool = Pool<DatabaseConnectionManager>;
+
+// How a cached pool's connections were opened; the same file can have one of each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoolMode {
+    ReadWrite,
+    ReadOnly,
+}
+
+struct Entry {
+    pool: DatabasePool,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    pools: HashMap<(PathBuf, PoolMode), Entry>,
+    clock: u64,
+}
+
+// Connection pools by canonical database path, so requests for one database
+// share its open connections. Holds at most `capacity` pools, dropping the least
+// recently used to make room.
+pub struct PoolCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+// The canonical form of `path`. A file that's already gone is resolved through its
+// parent directory, so it can still be evicted after deletion.
+fn cache_key(path: &Path) -> PathBuf {
+    if let Ok(path) = std::fs::canonicalize(path) {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            std::fs::canonicalize(parent).map_or_else(|_| path.to_path_buf(), |parent| parent.join(name))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+impl PoolCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), entries: Mutex::default() }
+    }
+
+    // The cached pool for `path`, or the one `build` makes, which is cached. The
+    // lock is held while building so concurrent requests don't each open a pool.
+    pub fn get_or_build(
+        &self,
+        path: &Path,
+        mode: PoolMode,
+        build: impl FnOnce() -> DatabasePool,
+    ) -> DatabasePool {
+        let key = (cache_key(path), mode);
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let now = entries.clock;
+
+        if let Some(entry) = entries.pools.get_mut(&key) {
+            entry.last_used = now;
+            return entry.pool.clone();
+        }
+
+        let pool = build();
+        if entries.pools.len() >= self.capacity {
+            let oldest = entries.pools.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.pools.remove(&oldest);
+            }
+        }
+        entries.pools.insert(key, Entry { pool: pool.clone(), last_used: now });
+        pool
+    }
+
+    // Forget every pool for `path`, e.g. once the file is deleted or replaced.
+    // Connections already checked out stay open until they're returned.
+    pub fn evict(&self, path: &Path) -> usize {
+        let path = cache_key(path);
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.pools.len();
+        entries.pools.retain(|(cached, _), _| cached != &path);
+        before - entries.pools.len()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        let path = cache_key(path);
+        self.entries.lock().unwrap().pools.keys().any(|(cached, _)| cached == &path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
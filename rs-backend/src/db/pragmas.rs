@@ -0,0 +1,102 @@
+use rusqlite::Connection;
+use serde_json::Value;
+
+// Pragmas a request may override for itself, none of which can corrupt data or
+// outlast the request
+pub const ALLOWED_PRAGMAS: &[&str] = &["cache_size", "synchronous", "temp_store"];
+
+// Bounds on cache_size: pages when positive, KiB when negative (1 GiB either way at 1 KiB pages)
+const MAX_CACHE_SIZE: i64 = 1 << 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PragmaError {
+    #[error("pragmas must be an object")]
+    NotAnObject,
+    #[error("Pragma '{0}' can't be set per request")]
+    NotAllowed(String),
+    #[error("Invalid value for pragma '{name}': expected {expected}")]
+    InvalidValue { name: String, expected: &'static str },
+}
+
+// Validated overrides, held as the numeric values SQLite reports them as
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PragmaOverrides {
+    values: Vec<(&'static str, i64)>,
+}
+
+fn keyword_or_number(value: &Value, keywords: &[&str]) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64().filter(|n| (0..keywords.len() as i64).contains(n)),
+        Value::String(s) => keywords.iter().position(|k| k.eq_ignore_ascii_case(s.trim())).map(|i| i as i64),
+        _ => None,
+    }
+}
+
+impl PragmaOverrides {
+    // Parse the `pragmas` object of a request body; None or null means no overrides
+    pub fn parse(pragmas: Option<&Value>) -> Result<Self, PragmaError> {
+        let pragmas = match pragmas {
+            None | Some(Value::Null) => return Ok(Self::default()),
+            Some(Value::Object(pragmas)) => pragmas,
+            Some(_) => return Err(PragmaError::NotAnObject),
+        };
+
+        let mut values = Vec::with_capacity(pragmas.len());
+        for (name, value) in pragmas {
+            let invalid = |expected| PragmaError::InvalidValue { name: name.clone(), expected };
+            let parsed = match name.to_ascii_lowercase().as_str() {
+                "cache_size" => (
+                    "cache_size",
+                    value.as_i64()
+                        .filter(|n| n.abs() <= MAX_CACHE_SIZE)
+                        .ok_or_else(|| invalid("an integer between -1048576 and 1048576"))?,
+                ),
+                "synchronous" => (
+                    "synchronous",
+                    keyword_or_number(value, &["off", "normal", "full", "extra"])
+                        .ok_or_else(|| invalid("off, normal, full, extra or 0 to 3"))?,
+                ),
+                "temp_store" => (
+                    "temp_store",
+                    keyword_or_number(value, &["default", "file", "memory"])
+                        .ok_or_else(|| invalid("default, file, memory or 0 to 2"))?,
+                ),
+                _ => return Err(PragmaError::NotAllowed(name.clone())),
+            };
+            values.push(parsed);
+        }
+
+        Ok(Self { values })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+// Overrides in effect on a pooled connection; the previous values are put back on drop
+pub struct PragmaGuard<'c> {
+    conn: &'c Connection,
+    previous: Vec<(&'static str, i64)>,
+}
+
+impl<'c> PragmaGuard<'c> {
+    pub fn apply(conn: &'c Connection, overrides: &PragmaOverrides) -> rusqlite::Result<Self> {
+        let mut guard = Self { conn, previous: Vec::with_capacity(overrides.values.len()) };
+        for &(name, value) in &overrides.values {
+            let previous: i64 = conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))?;
+            guard.previous.push((name, previous));
+            // Names come from the allowlist and values are integers, so this is safe to format
+            conn.execute_batch(&format!("PRAGMA {} = {}", name, value))?;
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for PragmaGuard<'_> {
+    fn drop(&mut self) {
+        for &(name, value) in self.previous.iter().rev() {
+            let _ = self.conn.execute_batch(&format!("PRAGMA {} = {}", name, value));
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::utils::quote_identifier;
+
+// Ordinary tables with no declared primary key, so rows can only be addressed by
+// rowid. Views, virtual tables and SQLite's internal tables are skipped.
+pub fn tables_without_primary_key(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.name FROM sqlite_master m
+         WHERE m.type = 'table'
+           AND m.name NOT LIKE 'sqlite_%'
+           AND m.sql NOT LIKE 'CREATE VIRTUAL TABLE%'
+           AND NOT EXISTS (SELECT 1 FROM pragma_table_info(m.name) WHERE pk > 0)
+         ORDER BY m.name",
+    )?;
+    let tables = stmt.query_map([], |row| row.get(0))?.collect();
+    tables
+}
+
+// Rows read per column when judging an index's selectivity
+const INDEX_SAMPLE_ROWS: i64 = 10_000;
+// A single-column index is flagged when a sample this large holds this few distinct values
+const LOW_CARDINALITY_MIN_ROWS: i64 = 100;
+const LOW_CARDINALITY_MAX_DISTINCT: i64 = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexSummary {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    // Declared with CREATE INDEX (as opposed to backing a UNIQUE or PRIMARY KEY constraint)
+    #[serde(skip)]
+    explicit: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexFinding {
+    pub index: IndexSummary,
+    // The index that makes this one redundant, for duplicates and covered indexes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redundant_with: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distinct_values: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampled_rows: Option<i64>,
+    pub recommendation: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexReport {
+    pub duplicates: Vec<IndexFinding>,
+    pub covered: Vec<IndexFinding>,
+    pub low_cardinality: Vec<IndexFinding>,
+}
+
+// Plain column indexes on one table; expression and partial indexes are left out
+// since their coverage can't be compared by column list
+fn table_indexes(conn: &Connection, table: &str) -> rusqlite::Result<Vec<IndexSummary>> {
+    let mut list = conn.prepare("SELECT name, \"unique\", origin, partial FROM pragma_index_list(?1) ORDER BY name")?;
+    let entries: Vec<(String, bool, String, bool)> = list
+        .query_map([table], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut info = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+    let mut indexes = Vec::new();
+    for (name, unique, origin, partial) in entries {
+        if partial {
+            continue;
+        }
+        let columns: Vec<Option<String>> = info.query_map([&name], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let Some(columns) = columns.into_iter().collect::<Option<Vec<String>>>() else {
+            continue;
+        };
+        indexes.push(IndexSummary { name, table: table.to_string(), columns, unique, explicit: origin == "c" });
+    }
+    Ok(indexes)
+}
+
+// Whether `index` can go in favour of `other`: only explicit indexes can be dropped,
+// and a UNIQUE index still enforces something unless `other` is unique too
+fn droppable_for(index: &IndexSummary, other: &IndexSummary) -> bool {
+    index.explicit && (!index.unique || other.unique)
+}
+
+// Flag indexes that repeat another's columns, that are a leading prefix of a wider
+// index, or that index a single column with almost no distinct values. Only
+// recommendations are returned; nothing is dropped.
+pub fn index_report(conn: &Connection) -> rusqlite::Result<IndexReport> {
+    let tables: Vec<String> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut report = IndexReport::default();
+    for table in &tables {
+        let indexes = table_indexes(conn, table)?;
+
+        for (i, index) in indexes.iter().enumerate() {
+            // Of two identical indexes, report the one that can be dropped (the later one if both can)
+            let duplicate = indexes.iter().enumerate().find(|(j, other)| {
+                *j != i && other.columns == index.columns && droppable_for(index, other)
+                    && (!droppable_for(other, index) || *j < i)
+            });
+            if let Some((_, other)) = duplicate {
+                report.duplicates.push(IndexFinding {
+                    index: index.clone(),
+                    redundant_with: Some(other.name.clone()),
+                    distinct_values: None,
+                    sampled_rows: None,
+                    recommendation: format!("Drop {}; {} indexes the same columns", index.name, other.name),
+                });
+                continue;
+            }
+
+            let covering = indexes.iter().find(|other| {
+                other.columns.len() > index.columns.len()
+                    && other.columns.starts_with(&index.columns)
+                    && index.explicit
+                    && !index.unique
+            });
+            if let Some(other) = covering {
+                report.covered.push(IndexFinding {
+                    index: index.clone(),
+                    redundant_with: Some(other.name.clone()),
+                    distinct_values: None,
+                    sampled_rows: None,
+                    recommendation: format!(
+                        "Consider dropping {}; its columns are a leading prefix of {}",
+                        index.name, other.name
+                    ),
+                });
+                continue;
+            }
+
+            if index.explicit && !index.unique && index.columns.len() == 1 {
+                let (sampled, distinct): (i64, i64) = conn.query_row(
+                    &format!(
+                        "SELECT COUNT(*), COUNT(DISTINCT v) FROM (SELECT {} AS v FROM {} LIMIT ?1)",
+                        quote_identifier(&index.columns[0]),
+                        quote_identifier(table)
+                    ),
+                    [INDEX_SAMPLE_ROWS],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?;
+                if sampled >= LOW_CARDINALITY_MIN_ROWS && distinct <= LOW_CARDINALITY_MAX_DISTINCT {
+                    report.low_cardinality.push(IndexFinding {
+                        index: index.clone(),
+                        redundant_with: None,
+                        distinct_values: Some(distinct),
+                        sampled_rows: Some(sampled),
+                        recommendation: format!(
+                            "Consider dropping {}; {} has only {} distinct values in {} sampled rows, so the index rarely narrows a search",
+                            index.name, index.columns[0], distinct, sampled
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,467 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::Statement;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::db::row_conversion::RowConverter;
+
+// Convert a single SQLite cell into its JSON representation
+pub fn value_to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => json!(i),
+        ValueRef::Real(f) => json!(f),
+        ValueRef::Text(s) => match std::str::from_utf8(s) {
+            Ok(text) => json!(text),
+            Err(_) => invalid_text_json(s),
+        },
+        ValueRef::Blob(b) => json!(format!("<BLOB: {} bytes>", b.len())),
+    }
+}
+
+// Key marking a TEXT cell whose bytes aren't valid UTF-8; cells are otherwise
+// never objects, so the marker can't be mistaken for data
+pub const ENCODING_MARKER: &str = "_encoding";
+
+// Invalid UTF-8 is passed through intact as base64 rather than silently repaired
+fn invalid_text_json(bytes: &[u8]) -> Value {
+    json!({ ENCODING_MARKER: "base64", "data": BASE64.encode(bytes) })
+}
+
+// How TEXT cells holding invalid UTF-8 appear in row objects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidTextRendering {
+    // {"_encoding": "base64", "data": ...} with the original bytes
+    #[default]
+    Base64,
+    // A string with invalid sequences replaced by U+FFFD; responses count the cells affected
+    Lossy,
+}
+
+// Rewrite invalid-text markers according to `mode`, returning how many cells were
+// lossily converted
+pub fn render_invalid_text(converter: &RowConverter, rows: &mut [Value], mode: InvalidTextRendering) -> usize {
+    if mode == InvalidTextRendering::Base64 {
+        return 0;
+    }
+    converter
+        .map_mut(rows, |row| {
+            let Value::Object(obj) = row else { return 0 };
+            obj.values_mut()
+                .filter(|v| v.get(ENCODING_MARKER).is_some())
+                .map(|v| {
+                    let bytes = v["data"].as_str()
+                        .and_then(|data| BASE64.decode(data).ok())
+                        .unwrap_or_default();
+                    *v = Value::String(String::from_utf8_lossy(&bytes).into_owned());
+                })
+                .count()
+        })
+        .into_iter()
+        .sum()
+}
+
+// Column names reported by a prepared statement
+pub fn column_names(stmt: &Statement<'_>) -> Vec<String> {
+    stmt.column_names().into_iter().map(String::from).collect()
+}
+
+// Run a prepared statement and read up to `limit` rows as JSON cells
+pub fn read_rows<P: rusqlite::Params>(
+    stmt: &mut Statement<'_>,
+    params: P,
+    limit: Option<usize>,
+) -> rusqlite::Result<Vec<Vec<Value>>> {
+    let column_count = stmt.column_count();
+    let mut rows = stmt.query(params)?;
+    let mut raw_rows = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        if limit.is_some_and(|limit| raw_rows.len() >= limit) {
+            break;
+        }
+        let mut row_data = Vec::with_capacity(column_count);
+        for i in 0..column_count {
+            row_data.push(value_to_json(row.get_ref(i)?));
+        }
+        raw_rows.push(row_data);
+    }
+
+    Ok(raw_rows)
+}
+
+// Rows read before a statement failed partway through, with the failure
+pub struct PartialRows {
+    pub rows: Vec<Vec<Value>>,
+    pub error: Option<rusqlite::Error>,
+}
+
+// Like read_rows, but a step that fails after some rows have been read keeps
+// those rows instead of discarding them. Errors before the first row are returned
+// as usual.
+pub fn read_rows_partial<P: rusqlite::Params>(
+    stmt: &mut Statement<'_>,
+    params: P,
+) -> rusqlite::Result<PartialRows> {
+    let column_count = stmt.column_count();
+    let mut rows = stmt.query(params)?;
+    let mut raw_rows = Vec::new();
+
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(e) if raw_rows.is_empty() => return Err(e),
+            Err(e) => return Ok(PartialRows { rows: raw_rows, error: Some(e) }),
+        };
+        let mut row_data = Vec::with_capacity(column_count);
+        for i in 0..column_count {
+            row_data.push(value_to_json(row.get_ref(i)?));
+        }
+        raw_rows.push(row_data);
+    }
+
+    Ok(PartialRows { rows: raw_rows, error: None })
+}
+
+// Declared types of the result columns, where they map straight to a table column
+pub fn column_decl_types(stmt: &Statement<'_>) -> Vec<Option<String>> {
+    stmt.columns().iter().map(|c| c.decl_type().map(String::from)).collect()
+}
+
+// Run a prepared statement and read up to `limit` rows as owned SQLite values
+pub fn read_sql_rows<P: rusqlite::Params>(
+    stmt: &mut Statement<'_>,
+    params: P,
+    limit: Option<usize>,
+) -> rusqlite::Result<Vec<Vec<SqlValue>>> {
+    let column_count = stmt.column_count();
+    let mut rows = stmt.query(params)?;
+    let mut raw_rows = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        if limit.is_some_and(|limit| raw_rows.len() >= limit) {
+            break;
+        }
+        let mut row_data = Vec::with_capacity(column_count);
+        for i in 0..column_count {
+            row_data.push(row.get::<_, SqlValue>(i)?);
+        }
+        raw_rows.push(row_data);
+    }
+
+    Ok(raw_rows)
+}
+
+// Turn positional rows into column-keyed objects, in parallel for large results
+pub fn rows_to_objects(converter: &RowConverter, columns: &[String], raw_rows: &[Vec<Value>]) -> Vec<Value> {
+    converter.map(raw_rows, |row_data| {
+        let mut obj = serde_json::Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            obj.insert(column.clone(), row_data[i].clone());
+        }
+        Value::Object(obj)
+    })
+}
+
+// Most decimal places `round_reals` accepts; f64 holds at most 17 significant digits
+pub const MAX_FLOAT_PRECISION: u32 = 17;
+
+// Round every REAL cell in row objects to `precision` decimal places.
+// Rounding goes through the decimal string, so the result is the f64 closest to the
+// rounded decimal and serializes without trailing noise; integers are untouched.
+pub fn round_reals(converter: &RowConverter, rows: &mut [Value], precision: u32) {
+    let precision = precision.min(MAX_FLOAT_PRECISION) as usize;
+    converter.map_mut(rows, |row| {
+        let Value::Object(obj) = row else { return };
+        for cell in obj.values_mut() {
+            let Some(f) = cell.as_f64().filter(|_| cell.is_f64()) else { continue };
+            if let Ok(rounded) = format!("{:.*}", precision, f).parse::<f64>() {
+                *cell = json!(rounded);
+            }
+        }
+    });
+}
+
+// Marker emitted for NULL cells with `NullRendering::String`
+pub const NULL_SENTINEL: &str = "\u{0}NULL";
+
+// How NULL cells appear in row objects
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NullRendering {
+    // JSON null (indistinguishable from a missing value to some consumers)
+    #[default]
+    Null,
+    // The NULL_SENTINEL string (collides only with text that starts with a NUL byte)
+    String,
+    // Leave the key out of the row entirely
+    Omit,
+}
+
+// Rewrite NULL cells in row objects according to `mode`
+pub fn render_nulls(converter: &RowConverter, rows: &mut [Value], mode: NullRendering) {
+    if mode == NullRendering::Null {
+        return;
+    }
+    converter.map_mut(rows, |row| {
+        if let Value::Object(obj) = row {
+            match mode {
+                NullRendering::Omit => obj.retain(|_, v| !v.is_null()),
+                _ => obj.values_mut()
+                    .filter(|v| v.is_null())
+                    .for_each(|v| *v = Value::String(NULL_SENTINEL.to_string())),
+            }
+        }
+    });
+}
+
+// Per-column count, null count and numeric range over materialized rows
+pub fn describe_columns(columns: &[String], raw_rows: &[Vec<Value>]) -> Value {
+    let mut described = serde_json::Map::new();
+
+    for (i, column) in columns.iter().enumerate() {
+        let mut null_count = 0usize;
+        let mut numbers = Vec::new();
+        let mut all_numeric = true;
+
+        for row in raw_rows {
+            match &row[i] {
+                Value::Null => null_count += 1,
+                Value::Number(n) => numbers.extend(n.as_f64()),
+                _ => all_numeric = false,
+            }
+        }
+
+        let mut stats = serde_json::Map::new();
+        stats.insert("count".to_string(), json!(raw_rows.len()));
+        stats.insert("null_count".to_string(), json!(null_count));
+
+        if all_numeric && !numbers.is_empty() {
+            let min = numbers.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = numbers.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let mean = numbers.iter().sum::<f64>() / numbers.len() as f64;
+            stats.insert("min".to_string(), json!(min));
+            stats.insert("max".to_string(), json!(max));
+            stats.insert("mean".to_string(), json!(mean));
+        }
+
+        described.insert(column.clone(), Value::Object(stats));
+    }
+
+    Value::Object(described)
+}
+
+// Convert a JSON scalar into a SQLite value using its natural affinity. Blobs,
+// which JSON has no scalar for, are written as {"blob": "<base64>"}.
+pub fn json_to_sql(value: &Value) -> Result<SqlValue, String> {
+    match value {
+        Value::Object(map) if map.len() == 1 && map.contains_key("blob") => match &map["blob"] {
+            Value::String(data) => BASE64.decode(data)
+                .map(SqlValue::Blob)
+                .map_err(|e| format!("blob is not valid base64: {}", e)),
+            _ => Err("blob must be a base64 string".to_string()),
+        },
+        Value::Null => Ok(SqlValue::Null),
+        Value::Bool(b) => Ok(SqlValue::Integer(*b as i64)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(SqlValue::Integer(i)),
+            None => n.as_f64().map(SqlValue::Real).ok_or_else(|| format!("unsupported number {}", n)),
+        },
+        Value::String(s) => Ok(SqlValue::Text(s.clone())),
+        other => Err(format!("unsupported parameter value {}", other)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindingError {
+    // Placeholders with no matching key (anonymous `?` placeholders are reported as `?N`)
+    Unbound(Vec<String>),
+    Invalid { name: String, message: String },
+}
+
+// Map each `:name`, `@name` or `$name` placeholder to the same-named key in
+// `bindings`, producing values in parameter-index order
+pub fn bind_named(stmt: &Statement<'_>, bindings: &serde_json::Map<String, Value>) -> Result<Vec<SqlValue>, BindingError> {
+    let mut values = Vec::with_capacity(stmt.parameter_count());
+    let mut unbound = Vec::new();
+
+    for index in 1..=stmt.parameter_count() {
+        let placeholder = stmt.parameter_name(index);
+        let key = placeholder.and_then(|p| p.strip_prefix([':', '@', '$']));
+        match key.and_then(|key| bindings.get(key).map(|value| (key, value))) {
+            Some((key, value)) => values.push(json_to_sql(value).map_err(|message| BindingError::Invalid {
+                name: key.to_string(),
+                message,
+            })?),
+            None => {
+                unbound.push(placeholder.map(String::from).unwrap_or_else(|| format!("?{}", index)));
+                values.push(SqlValue::Null);
+            }
+        }
+    }
+
+    if unbound.is_empty() {
+        Ok(values)
+    } else {
+        Err(BindingError::Unbound(unbound))
+    }
+}
+
+// Coerce a JSON value to a declared SQLite type ("integer", "real" or "text")
+pub fn coerce_param(value: &Value, declared: &str) -> Result<SqlValue, String> {
+    let impossible = || format!("cannot coerce {} to {}", value, declared);
+
+    if value.is_null() {
+        return Ok(SqlValue::Null);
+    }
+
+    match declared.to_ascii_lowercase().as_str() {
+        "integer" => match value {
+            Value::Bool(b) => Ok(SqlValue::Integer(*b as i64)),
+            Value::Number(n) => n.as_i64()
+                .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+                .map(SqlValue::Integer)
+                .ok_or_else(impossible),
+            Value::String(s) => s.trim().parse().map(SqlValue::Integer).map_err(|_| impossible()),
+            _ => Err(impossible()),
+        },
+        "real" => match value {
+            Value::Number(n) => n.as_f64().map(SqlValue::Real).ok_or_else(impossible),
+            Value::String(s) => s.trim().parse().map(SqlValue::Real).map_err(|_| impossible()),
+            _ => Err(impossible()),
+        },
+        "text" => match value {
+            Value::String(s) => Ok(SqlValue::Text(s.clone())),
+            Value::Number(n) => Ok(SqlValue::Text(n.to_string())),
+            Value::Bool(b) => Ok(SqlValue::Text(b.to_string())),
+            _ => Err(impossible()),
+        },
+        other => Err(format!("unknown parameter type \"{}\"", other)),
+    }
+}
+
+// Statement kinds a client may declare through the query payload's `expect` field
+pub const STATEMENT_KINDS: &[&str] = &["select", "insert", "update", "delete", "ddl"];
+
+// Keywords and identifiers outside any parentheses, skipping comments and
+// quoted text
+fn top_level_words(sql: &str) -> impl Iterator<Item = String> + '_ {
+    top_level_word_spans(sql).map(|(_, word)| word)
+}
+
+// Like top_level_words, with the byte offset each word starts at
+fn top_level_word_spans(sql: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut chars = sql.char_indices().peekable();
+    let mut depth = 0usize;
+
+    std::iter::from_fn(move || {
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '-' if chars.peek().map(|&(_, c)| c) == Some('-') => {
+                    chars.by_ref().find(|&(_, c)| c == '\n');
+                }
+                '/' if chars.peek().map(|&(_, c)| c) == Some('*') => {
+                    chars.next();
+                    let mut prev = '\0';
+                    for (_, c) in chars.by_ref() {
+                        if prev == '*' && c == '/' {
+                            break;
+                        }
+                        prev = c;
+                    }
+                }
+                '\'' | '"' | '`' => {
+                    chars.by_ref().find(|&(_, q)| q == c);
+                }
+                '[' => {
+                    chars.by_ref().find(|&(_, q)| q == ']');
+                }
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let mut word = String::from(c);
+                    while let Some(&(_, next)) = chars.peek() {
+                        if next.is_ascii_alphanumeric() || next == '_' {
+                            word.push(next);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if depth == 0 {
+                        return Some((start, word));
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    })
+}
+
+// Classify a statement by its leading keyword, looking past comments and any
+// WITH clause to the statement it prefixes. Returns None for anything else
+// (PRAGMA, transaction control, ...)
+pub fn statement_kind(sql: &str) -> Option<&'static str> {
+    let mut in_with = false;
+
+    for word in top_level_words(sql) {
+        let kind = match word.to_ascii_lowercase().as_str() {
+            "with" if !in_with => {
+                in_with = true;
+                continue;
+            }
+            "select" | "values" => Some("select"),
+            "insert" | "replace" => Some("insert"),
+            "update" => Some("update"),
+            "delete" => Some("delete"),
+            "create" | "drop" | "alter" if !in_with => Some("ddl"),
+            // CTE names, AS, RECURSIVE and column lists inside a WITH clause
+            _ if in_with => continue,
+            _ => None,
+        };
+        return kind;
+    }
+
+    None
+}
+
+// Aggregates that fold many rows into one, so a result row no longer stands for a table row
+const AGGREGATE_FUNCTIONS: &[&str] = &[
+    "avg", "count", "group_concat", "json_group_array", "json_group_object", "max", "min", "string_agg", "sum",
+    "total",
+];
+
+// Top-level words that combine or collapse the rows of the FROM clause
+const ROW_MERGING_KEYWORDS: &[&str] = &["distinct", "except", "group", "intersect", "join", "union", "values", "with"];
+
+// `sql` with the rowid of the table it reads prepended as a `_rowid` column, when
+// it's a plain SELECT whose rows each come from a single table row as far as the
+// text shows. Views, subqueries, several tables and WITHOUT ROWID tables get past
+// this check, but the rewritten statement fails to prepare for them, so callers
+// must fall back to `sql` when it does.
+pub fn with_rowid_column(sql: &str) -> Option<String> {
+    let mut words = top_level_word_spans(sql);
+    let (start, first) = words.next()?;
+    if !first.eq_ignore_ascii_case("select") {
+        return None;
+    }
+    for (_, word) in words {
+        let word = word.to_ascii_lowercase();
+        if ROW_MERGING_KEYWORDS.contains(&word.as_str()) || AGGREGATE_FUNCTIONS.contains(&word.as_str()) {
+            return None;
+        }
+    }
+
+    let after_select = start + first.len();
+    Some(format!("{} rowid AS _rowid,{}", &sql[..after_select], &sql[after_select..]))
+}
+
+// Whether the statement has its own LIMIT clause (one inside a subquery doesn't count)
+pub fn has_top_level_limit(sql: &str) -> bool {
+    top_level_words(sql).any(|word| word.eq_ignore_ascii_case("limit"))
+}
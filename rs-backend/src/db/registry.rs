@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use rusqlite::InterruptHandle;
+use serde::Serialize;
+
+// Longest SQL prefix reported when listing running queries
+const SQL_PREVIEW_LENGTH: usize = 200;
+
+struct RunningQuery {
+    database_id: i64,
+    sql: String,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    interrupt: InterruptHandle,
+}
+
+// Snapshot of a running query as reported by the admin API
+#[derive(Debug, Serialize)]
+pub struct RunningQueryInfo {
+    pub id: u64,
+    pub database_id: i64,
+    pub sql: String,
+    pub started_at: DateTime<Utc>,
+    pub elapsed_ms: u128,
+}
+
+// Registry of in-flight queries and the handles needed to interrupt them
+#[derive(Default)]
+pub struct QueryRegistry {
+    next_id: AtomicU64,
+    queries: Mutex<HashMap<u64, RunningQuery>>,
+}
+
+impl QueryRegistry {
+    // Track a query until the returned guard is dropped
+    pub fn register(
+        self: &Arc<Self>,
+        database_id: i64,
+        sql: &str,
+        interrupt: InterruptHandle,
+    ) -> RegisteredQuery {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let query = RunningQuery {
+            database_id,
+            sql: sql.to_string(),
+            started_at: Utc::now(),
+            started: Instant::now(),
+            interrupt,
+        };
+        self.queries.lock().unwrap().insert(id, query);
+        RegisteredQuery { id, registry: Arc::clone(self) }
+    }
+
+    pub fn list(&self) -> Vec<RunningQueryInfo> {
+        let queries = self.queries.lock().unwrap();
+        let mut running: Vec<RunningQueryInfo> = queries.iter()
+            .map(|(id, query)| RunningQueryInfo {
+                id: *id,
+                database_id: query.database_id,
+                sql: query.sql.chars().take(SQL_PREVIEW_LENGTH).collect(),
+                started_at: query.started_at,
+                elapsed_ms: query.started.elapsed().as_millis(),
+            })
+            .collect();
+        running.sort_by_key(|q| q.id);
+        running
+    }
+
+    // Interrupt a running query; returns false if it is no longer registered
+    pub fn interrupt(&self, id: u64) -> bool {
+        match self.queries.lock().unwrap().get(&id) {
+            Some(query) => {
+                query.interrupt.interrupt();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Guard that removes a query from the registry when it finishes
+pub struct RegisteredQuery {
+    id: u64,
+    registry: Arc<QueryRegistry>,
+}
+
+impl RegisteredQuery {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for RegisteredQuery {
+    fn drop(&mut self) {
+        self.registry.queries.lock().unwrap().remove(&self.id);
+    }
+}
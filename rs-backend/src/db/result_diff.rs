@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+// Which of the two compared result sets a problem was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultDiffError {
+    MissingKeyColumn(Side),
+    DuplicateKey { side: Side, key: Value },
+}
+
+// Columns present in only one of the result sets; they're left out of row comparisons
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ColumnDiff {
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedRow {
+    pub key: Value,
+    // Column -> { "from": left value, "to": right value }
+    pub changes: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResultDiff {
+    pub columns: ColumnDiff,
+    pub added: Vec<Map<String, Value>>,
+    pub removed: Vec<Map<String, Value>>,
+    pub changed: Vec<ChangedRow>,
+    pub unchanged: usize,
+}
+
+// Integers and reals that compare equal in SQLite (1 and 1.0) aren't a change
+fn same_value(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a == b || a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+// Rows by the serialized value of their key column, rejecting repeated keys
+fn index_rows(side: Side, rows: &[Vec<Value>], key: usize) -> Result<HashMap<String, &Vec<Value>>, ResultDiffError> {
+    let mut by_key = HashMap::with_capacity(rows.len());
+    for row in rows {
+        if by_key.insert(row[key].to_string(), row).is_some() {
+            return Err(ResultDiffError::DuplicateKey { side, key: row[key].clone() });
+        }
+    }
+    Ok(by_key)
+}
+
+fn to_object(columns: &[String], row: &[Value]) -> Map<String, Value> {
+    columns.iter().cloned().zip(row.iter().cloned()).collect()
+}
+
+// Compare two result sets row by row, matching rows on the `key` column.
+// Rows only on the right are added, only on the left removed; matched rows are
+// compared on the columns both sides share.
+pub fn diff_results(
+    left_columns: &[String],
+    left_rows: &[Vec<Value>],
+    right_columns: &[String],
+    right_rows: &[Vec<Value>],
+    key: &str,
+) -> Result<ResultDiff, ResultDiffError> {
+    let left_key = left_columns.iter().position(|c| c == key)
+        .ok_or(ResultDiffError::MissingKeyColumn(Side::Left))?;
+    let right_key = right_columns.iter().position(|c| c == key)
+        .ok_or(ResultDiffError::MissingKeyColumn(Side::Right))?;
+
+    let left_index = index_rows(Side::Left, left_rows, left_key)?;
+    let right_index = index_rows(Side::Right, right_rows, right_key)?;
+
+    let mut diff = ResultDiff {
+        columns: ColumnDiff {
+            only_left: left_columns.iter().filter(|c| !right_columns.contains(c)).cloned().collect(),
+            only_right: right_columns.iter().filter(|c| !left_columns.contains(c)).cloned().collect(),
+        },
+        ..ResultDiff::default()
+    };
+    // (column, left index, right index) for every column both sides share
+    let shared: Vec<(&String, usize, usize)> = left_columns.iter().enumerate()
+        .filter_map(|(l, column)| right_columns.iter().position(|c| c == column).map(|r| (column, l, r)))
+        .collect();
+
+    for left in left_rows {
+        let Some(right) = right_index.get(&left[left_key].to_string()) else {
+            diff.removed.push(to_object(left_columns, left));
+            continue;
+        };
+
+        let mut changes = Map::new();
+        for (column, l, r) in &shared {
+            if !same_value(&left[*l], &right[*r]) {
+                let mut change = Map::new();
+                change.insert("from".to_string(), left[*l].clone());
+                change.insert("to".to_string(), right[*r].clone());
+                changes.insert((*column).clone(), Value::Object(change));
+            }
+        }
+        if changes.is_empty() {
+            diff.unchanged += 1;
+        } else {
+            diff.changed.push(ChangedRow { key: left[left_key].clone(), changes });
+        }
+    }
+
+    for right in right_rows {
+        if !left_index.contains_key(&right[right_key].to_string()) {
+            diff.added.push(to_object(right_columns, right));
+        }
+    }
+
+    Ok(diff)
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::Serialize;
+use tracing::warn;
+
+// How many conversion passes took each path since the converter was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ConversionStats {
+    // Batches under the parallel threshold
+    pub serial: u64,
+    pub parallel: u64,
+    // Batches large enough for the pool that ran serially because it was saturated
+    pub degraded: u64,
+}
+
+// Runs per-row work over a result set, serially for small batches and on a
+// dedicated rayon pool for large ones. Handing a few rows to the pool costs more
+// than converting them in place, and sharing rayon's global pool lets many
+// concurrent large queries oversubscribe it. Once every pool thread already has a
+// batch, further batches convert on the calling thread instead of queueing.
+pub struct RowConverter {
+    parallel_threshold: usize,
+    threads: usize,
+    // Built on first use, so servers that only see small results never start it
+    pool: OnceLock<Option<ThreadPool>>,
+    in_flight: AtomicUsize,
+    serial: AtomicU64,
+    parallel: AtomicU64,
+    degraded: AtomicU64,
+}
+
+// A claimed share of the pool, released when the batch finishes
+struct Slot<'a>(&'a AtomicUsize);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RowConverter {
+    pub fn new(parallel_threshold: usize, threads: usize) -> Self {
+        Self {
+            parallel_threshold,
+            threads: threads.max(1),
+            pool: OnceLock::new(),
+            in_flight: AtomicUsize::new(0),
+            serial: AtomicU64::new(0),
+            parallel: AtomicU64::new(0),
+            degraded: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> ConversionStats {
+        ConversionStats {
+            serial: self.serial.load(Ordering::Relaxed),
+            parallel: self.parallel.load(Ordering::Relaxed),
+            degraded: self.degraded.load(Ordering::Relaxed),
+        }
+    }
+
+    // Apply `f` to each item, keeping their order
+    pub fn map<T: Sync, R: Send>(&self, items: &[T], f: impl Fn(&T) -> R + Sync + Send) -> Vec<R> {
+        match self.claim(items.len()) {
+            Some((pool, _slot)) => pool.install(|| items.par_iter().map(&f).collect()),
+            None => items.iter().map(f).collect(),
+        }
+    }
+
+    // Apply `f` to each item in place, keeping the results in order
+    pub fn map_mut<T: Send, R: Send>(&self, items: &mut [T], f: impl Fn(&mut T) -> R + Sync + Send) -> Vec<R> {
+        match self.claim(items.len()) {
+            Some((pool, _slot)) => pool.install(|| items.par_iter_mut().map(&f).collect()),
+            None => items.iter_mut().map(f).collect(),
+        }
+    }
+
+    // The pool to run a batch of `len` items on, or None to run it serially
+    fn claim(&self, len: usize) -> Option<(&ThreadPool, Slot<'_>)> {
+        if len < self.parallel_threshold {
+            self.serial.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        let claimed = self.pool().and_then(|pool| {
+            let slot = Slot(&self.in_flight);
+            (self.in_flight.fetch_add(1, Ordering::SeqCst) < pool.current_num_threads()).then_some((pool, slot))
+        });
+        let counter = if claimed.is_some() { &self.parallel } else { &self.degraded };
+        counter.fetch_add(1, Ordering::Relaxed);
+        claimed
+    }
+
+    fn pool(&self) -> Option<&ThreadPool> {
+        self.pool
+            .get_or_init(|| {
+                ThreadPoolBuilder::new()
+                    .num_threads(self.threads)
+                    .thread_name(|i| format!("row-conversion-{}", i))
+                    .build()
+                    .map_err(|e| warn!("Failed to start row conversion pool, converting serially: {}", e))
+                    .ok()
+            })
+            .as_ref()
+    }
+}
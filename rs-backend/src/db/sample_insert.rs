@@ -0,0 +1,106 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::utils::quote_identifier;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InsertColumn {
+    pub name: String,
+    pub declared_type: String,
+    // SQLite affinity derived from the declared type
+    pub affinity: &'static str,
+    // Literal standing in for this column's value in the template
+    pub placeholder: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleInsert {
+    pub columns: Vec<InsertColumn>,
+    pub template: String,
+    // The template filled with the table's first row; None for an empty table
+    pub example: Option<String>,
+}
+
+// SQLite type affinity rules applied to a declared column type
+fn affinity(decl: &str) -> &'static str {
+    let decl = decl.to_ascii_uppercase();
+    if decl.contains("INT") {
+        "INTEGER"
+    } else if decl.contains("CHAR") || decl.contains("CLOB") || decl.contains("TEXT") {
+        "TEXT"
+    } else if decl.contains("BLOB") || decl.is_empty() {
+        "BLOB"
+    } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+        "REAL"
+    } else {
+        "NUMERIC"
+    }
+}
+
+fn placeholder(affinity: &str) -> &'static str {
+    match affinity {
+        "INTEGER" | "NUMERIC" => "0",
+        "REAL" => "0.0",
+        "TEXT" => "''",
+        _ => "X''",
+    }
+}
+
+// A value written as a SQL literal that reads back as the same value
+fn literal(value: &SqlValue) -> String {
+    match value {
+        SqlValue::Null => "NULL".to_string(),
+        SqlValue::Integer(i) => i.to_string(),
+        // Debug formatting always keeps a decimal point or exponent, so the value stays REAL
+        SqlValue::Real(f) if f.is_finite() => format!("{:?}", f),
+        SqlValue::Real(f) if f.is_nan() => "NULL".to_string(),
+        SqlValue::Real(f) => if *f > 0.0 { "9e999".to_string() } else { "-9e999".to_string() },
+        SqlValue::Text(s) => format!("'{}'", s.replace('\'', "''")),
+        SqlValue::Blob(b) => format!("X'{}'", b.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()),
+    }
+}
+
+fn statement(table: &str, columns: &[InsertColumn], values: &[String]) -> String {
+    let names: Vec<String> = columns.iter().map(|c| quote_identifier(&c.name)).collect();
+    format!(
+        "INSERT INTO {} ({}) VALUES ({});",
+        quote_identifier(table),
+        names.join(", "),
+        values.join(", ")
+    )
+}
+
+// Scaffold an INSERT for `table` listing its columns in declaration order. None if
+// the table doesn't exist.
+pub fn sample_insert(conn: &Connection, table: &str) -> rusqlite::Result<Option<SampleInsert>> {
+    let columns: Vec<InsertColumn> = conn
+        .prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))?
+        .query_map([], |row| {
+            let declared_type: String = row.get(2)?;
+            let affinity = affinity(&declared_type);
+            Ok(InsertColumn { name: row.get(1)?, declared_type, affinity, placeholder: placeholder(affinity) })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    if columns.is_empty() {
+        return Ok(None);
+    }
+
+    let placeholders: Vec<String> = columns.iter().map(|c| c.placeholder.to_string()).collect();
+    let template = statement(table, &columns, &placeholders);
+
+    let names: Vec<String> = columns.iter().map(|c| quote_identifier(&c.name)).collect();
+    let sampled = conn
+        .query_row(
+            &format!("SELECT {} FROM {} LIMIT 1", names.join(", "), quote_identifier(table)),
+            [],
+            |row| (0..columns.len()).map(|i| row.get::<_, SqlValue>(i)).collect::<rusqlite::Result<Vec<_>>>(),
+        )
+        .optional()?;
+    let example = sampled.map(|values| {
+        let values: Vec<String> = values.iter().map(literal).collect();
+        statement(table, &columns, &values)
+    });
+
+    Ok(Some(SampleInsert { columns, template, example }))
+}
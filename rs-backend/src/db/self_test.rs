@@ -0,0 +1,119 @@
+use serde::Serialize;
+
+use crate::db::connection::DbConnection;
+
+// Compile options the server can't work without, as reported by `PRAGMA compile_options`
+const REQUIRED_COMPILE_OPTIONS: &[&str] = &["THREADSAFE"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    // A failed critical check means the server shouldn't start
+    pub critical: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    pub fn critical_failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|c| c.critical && !c.passed)
+    }
+
+    pub fn check(&self, name: &str) -> Option<&CheckResult> {
+        self.checks.iter().find(|c| c.name == name)
+    }
+
+    // One line per check, e.g. "PASS storage_writable: ..."
+    pub fn summary(&self) -> String {
+        self.checks.iter()
+            .map(|c| format!("{} {}: {}", if c.passed { "PASS" } else { "FAIL" }, c.name, c.detail))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn check(name: &'static str, critical: bool, result: Result<String, String>) -> CheckResult {
+    let (passed, detail) = match result {
+        Ok(detail) => (true, detail),
+        Err(detail) => (false, detail),
+    };
+    CheckResult { name, passed, critical, detail }
+}
+
+// Write and remove a probe file, as an upload would
+fn storage_writable(db_connection: &DbConnection) -> Result<String, String> {
+    let dir = &db_connection.config().storage_path;
+    let probe = dir.join(format!(".self-test-{}", std::process::id()));
+    std::fs::write(&probe, b"self-test")
+        .map_err(|e| format!("can't write to {}: {}", dir.display(), e))?;
+    std::fs::remove_file(&probe)
+        .map_err(|e| format!("can't remove {}: {}", probe.display(), e))?;
+    Ok(format!("{} is writable", dir.display()))
+}
+
+fn metadata_pool(db_connection: &DbConnection) -> Result<String, String> {
+    let conn = db_connection.get_metadata_pool().get()
+        .map_err(|e| format!("no metadata connection available: {}", e))?;
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM database_metadata", [], |row| row.get(0))
+        .map_err(|e| format!("metadata database unreadable: {}", e))?;
+    Ok(format!("{} database record(s)", count))
+}
+
+// A throwaway in-memory database exercising what query execution relies on
+fn trivial_query() -> Result<String, String> {
+    let conn = rusqlite::Connection::open_in_memory().map_err(|e| e.to_string())?;
+    let (one, json): (i64, i64) = conn
+        .query_row("SELECT 1, (SELECT COUNT(*) FROM json_each('[1, 2]'))", [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("SELECT failed: {}", e))?;
+    if one != 1 || json != 2 {
+        return Err(format!("unexpected result ({}, {})", one, json));
+    }
+    Ok(format!("SQLite {}", rusqlite::version()))
+}
+
+fn compile_options() -> Result<String, String> {
+    let conn = rusqlite::Connection::open_in_memory().map_err(|e| e.to_string())?;
+    let options: Vec<String> = conn
+        .prepare("PRAGMA compile_options")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| format!("can't read compile options: {}", e))?;
+
+    let mut missing = Vec::new();
+    for &required in REQUIRED_COMPILE_OPTIONS {
+        // THREADSAFE is reported as THREADSAFE=<n>, where 0 means disabled
+        let enabled = options.iter().any(|o| {
+            o == required || o.strip_prefix(required).and_then(|v| v.strip_prefix('=')).is_some_and(|v| v != "0")
+        });
+        if !enabled {
+            missing.push(required);
+        }
+    }
+    if missing.is_empty() {
+        Ok(format!("{} present", REQUIRED_COMPILE_OPTIONS.join(", ")))
+    } else {
+        Err(format!("missing required compile option(s): {}", missing.join(", ")))
+    }
+}
+
+// Checks run once at startup, so a broken environment fails fast instead of on
+// the first request that needs it
+pub fn self_test(db_connection: &DbConnection) -> SelfTestReport {
+    SelfTestReport {
+        checks: vec![
+            check("storage_writable", true, storage_writable(db_connection)),
+            check("metadata_pool", true, metadata_pool(db_connection)),
+            check("trivial_query", true, trivial_query()),
+            check("sqlite_compile_options", true, compile_options()),
+        ],
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::column_policy::{HiddenColumns, PolicyConnection};
+
+// A read transaction held open on a dedicated connection. In WAL mode it keeps
+// seeing the database as it was when opened while other connections write; in
+// rollback-journal mode it blocks writers until closed. The column policy of
+// whoever opened it stays in force for every query run on it.
+pub struct Snapshot {
+    database_id: i64,
+    conn: Mutex<PolicyConnection<Box<Connection>>>,
+    last_used: Mutex<Instant>,
+}
+
+impl Snapshot {
+    pub fn database_id(&self) -> i64 {
+        self.database_id
+    }
+
+    // Run `f` against the snapshot's connection; callers are serialized
+    pub fn with_connection<T>(&self, f: impl FnOnce(&PolicyConnection<Box<Connection>>) -> T) -> T {
+        *self.last_used.lock().unwrap() = Instant::now();
+        f(&self.conn.lock().unwrap())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotInfo {
+    pub token: String,
+    pub database_id: i64,
+    pub journal_mode: String,
+    pub opened_at: DateTime<Utc>,
+}
+
+// Open snapshots by token
+#[derive(Default)]
+pub struct SnapshotRegistry {
+    snapshots: Mutex<HashMap<String, Arc<Snapshot>>>,
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("Failed to generate snapshot token");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl SnapshotRegistry {
+    // Begin a read transaction on a new connection to `path` and pin its view by
+    // reading from it (a deferred transaction only takes its snapshot on first read)
+    pub fn open(
+        &self,
+        database_id: i64,
+        path: impl AsRef<Path>,
+        hidden: Option<HiddenColumns>,
+    ) -> rusqlite::Result<SnapshotInfo> {
+        let conn = PolicyConnection::install(Box::new(Connection::open(path)?), hidden)?;
+        conn.execute_batch("PRAGMA query_only = ON; BEGIN DEFERRED;")?;
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+
+        let token = new_token();
+        let snapshot = Snapshot {
+            database_id,
+            conn: Mutex::new(conn),
+            last_used: Mutex::new(Instant::now()),
+        };
+        self.snapshots.lock().unwrap().insert(token.clone(), Arc::new(snapshot));
+
+        Ok(SnapshotInfo { token, database_id, journal_mode, opened_at: Utc::now() })
+    }
+
+    pub fn get(&self, token: &str) -> Option<Arc<Snapshot>> {
+        self.snapshots.lock().unwrap().get(token).cloned()
+    }
+
+    // End the read transaction and release the connection; false if the token is unknown
+    pub fn close(&self, token: &str) -> bool {
+        let Some(snapshot) = self.snapshots.lock().unwrap().remove(token) else {
+            return false;
+        };
+        snapshot.with_connection(|conn| conn.execute_batch("ROLLBACK").ok());
+        true
+    }
+
+    // Close snapshots unused for longer than `max_idle`; returns how many were closed
+    pub fn reap_idle(&self, max_idle: Duration) -> usize {
+        let idle: Vec<String> = self.snapshots.lock().unwrap().iter()
+            .filter(|(_, snapshot)| snapshot.last_used.lock().unwrap().elapsed() > max_idle)
+            .map(|(token, _)| token.clone())
+            .collect();
+        idle.iter().filter(|token| self.close(token)).count()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,175 @@
+use std::convert::Infallible;
+use std::io;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use futures::Stream;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection, Statement};
+use serde_json::{json, Map, Value};
+use tokio::sync::{mpsc, oneshot};
+use tracing::error;
+
+use crate::db::query;
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+// Rows the reader may get ahead of the client before it blocks
+pub const STREAM_CHANNEL_CAPACITY: usize = 64;
+
+// NDJSON lines fed by a blocking reader through a bounded channel, so rows are
+// only fetched from SQLite as fast as the consumer takes them
+pub struct NdjsonStream {
+    receiver: mpsc::Receiver<Bytes>,
+    produced: Arc<AtomicUsize>,
+}
+
+impl NdjsonStream {
+    // Rows read from SQLite so far (sent or waiting to be sent)
+    pub fn produced(&self) -> usize {
+        self.produced.load(Ordering::Relaxed)
+    }
+
+    pub async fn next_line(&mut self) -> Option<Bytes> {
+        self.receiver.recv().await
+    }
+
+    pub fn into_body_stream(self) -> impl Stream<Item = Result<Bytes, Infallible>> + Send {
+        futures::stream::unfold(self.receiver, |mut receiver| async move {
+            receiver.recv().await.map(|line| (Ok(line), receiver))
+        })
+    }
+}
+
+// A blocking `Write` feeding a response body through a bounded channel, for
+// encoders (e.g. export::write_export) that run on a blocking thread. Writes block
+// while the channel is full and fail once the client has gone away.
+#[derive(Clone)]
+pub struct ChannelWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+}
+
+impl ChannelWriter {
+    // Abort the body, so the client sees a failed transfer instead of a
+    // complete-looking but truncated file
+    pub fn fail(&self, message: &str) {
+        self.sender.blocking_send(Err(io::Error::other(message.to_string()))).ok();
+    }
+}
+
+impl io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender.blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// A writer and the body stream it feeds
+pub fn body_channel(capacity: usize) -> (ChannelWriter, impl Stream<Item = io::Result<Bytes>> + Send) {
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    let body = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    (ChannelWriter { sender }, body)
+}
+
+// The line that ends a stream when a row fails to read. Its `error` is an object,
+// which no row cell can be, so clients can tell it apart from a row with an
+// `error` column.
+pub fn error_line(message: &str, rows_sent: usize) -> Bytes {
+    let mut line = json!({ "error": { "message": message, "rows_sent": rows_sent } }).to_string().into_bytes();
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+// Prepare `sql` on a blocking thread and stream its rows as NDJSON lines.
+// Preparation and binding errors are returned before any output; an error while
+// iterating ends the stream with an `error_line`. `keep_alive` (e.g. a query
+// registration) is held until the reader finishes.
+pub async fn start<C, E, B, K>(
+    conn: C,
+    sql: String,
+    capacity: usize,
+    bind: B,
+    keep_alive: K,
+) -> Result<NdjsonStream, E>
+where
+    C: Deref<Target = Connection> + Send + 'static,
+    E: From<rusqlite::Error> + Send + 'static,
+    B: FnOnce(&Statement<'_>) -> Result<Vec<SqlValue>, E> + Send + 'static,
+    K: Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    let (ready, prepared) = oneshot::channel::<Result<(), E>>();
+    let produced = Arc::new(AtomicUsize::new(0));
+    let counter = produced.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let _keep_alive = keep_alive;
+        let prepared = conn.prepare(&sql)
+            .map_err(E::from)
+            .and_then(|stmt| bind(&stmt).map(|params| (stmt, params)));
+        let (mut stmt, params) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                ready.send(Err(e)).ok();
+                return;
+            }
+        };
+
+        let columns = query::column_names(&stmt);
+        let mut rows = match stmt.query(params_from_iter(params)) {
+            Ok(rows) => rows,
+            Err(e) => {
+                ready.send(Err(E::from(e))).ok();
+                return;
+            }
+        };
+        if ready.send(Ok(())).is_err() {
+            return;
+        }
+
+        loop {
+            let row = match rows.next() {
+                Ok(Some(row)) => row,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Streaming query failed: {}", e);
+                    sender.blocking_send(error_line(&e.to_string(), counter.load(Ordering::Relaxed))).ok();
+                    break;
+                }
+            };
+
+            let mut obj = Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                let cell = row.get_ref(i).map(query::value_to_json).unwrap_or(Value::Null);
+                obj.insert(column.clone(), cell);
+            }
+            let mut line = Value::Object(obj).to_string().into_bytes();
+            line.push(b'\n');
+
+            counter.fetch_add(1, Ordering::Relaxed);
+            // Blocks while the channel is full; fails once the client has gone away
+            if sender.blocking_send(Bytes::from(line)).is_err() {
+                break;
+            }
+        }
+    });
+
+    match prepared.await {
+        Ok(Ok(())) => Ok(NdjsonStream { receiver, produced }),
+        Ok(Err(e)) => Err(e),
+        // The reader only drops `ready` without sending if it panicked
+        Err(_) => Err(E::from(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ABORT),
+            Some("streaming reader stopped unexpectedly".to_string()),
+        ))),
+    }
+}
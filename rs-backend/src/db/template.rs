@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::utils::quote_identifier;
+
+// What a `{{placeholder}}` may be replaced with. Unlike bound parameters these
+// are spliced into the SQL text, so identifiers are checked against the schema
+// and quoted, and integers are the only literals allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaceholderKind {
+    Table,
+    Column,
+    Integer,
+}
+
+impl PlaceholderKind {
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "table" => Some(Self::Table),
+            "column" => Some(Self::Column),
+            "integer" => Some(Self::Integer),
+            _ => None,
+        }
+    }
+
+    // Kind implied by a bare `{{name}}`
+    fn implied_by(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(Self::Table),
+            "column" => Some(Self::Column),
+            "limit" | "offset" => Some(Self::Integer),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Placeholder {
+    pub name: String,
+    pub kind: PlaceholderKind,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Malformed placeholder: {0}")]
+    Malformed(String),
+    #[error("Placeholder '{0}' needs a kind, e.g. {{{{{0}:table}}}}, {{{{{0}:column}}}} or {{{{{0}:integer}}}}")]
+    UnknownKind(String),
+    #[error("Placeholder '{name}' is used as both {first:?} and {second:?}")]
+    ConflictingKinds { name: String, first: PlaceholderKind, second: PlaceholderKind },
+    #[error("Missing template argument '{0}'")]
+    MissingArgument(String),
+    #[error("Template argument '{name}' {message}")]
+    InvalidArgument { name: String, message: String },
+    #[error("Table '{0}' does not exist")]
+    UnknownTable(String),
+    #[error("Column '{0}' does not exist")]
+    UnknownColumn(String),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+// A run of template text or a placeholder, in order
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder),
+}
+
+fn parse(template: &str) -> Result<Vec<Segment<'_>>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        segments.push(Segment::Text(&rest[..start]));
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| TemplateError::Malformed(rest[start..].to_string()))?;
+        let body = after[..end].trim();
+
+        let (name, kind) = match body.split_once(':') {
+            Some((name, kind)) => {
+                let name = name.trim();
+                let kind = PlaceholderKind::parse(kind.trim())
+                    .ok_or_else(|| TemplateError::Malformed(format!("{{{{{}}}}}", body)))?;
+                (name, kind)
+            }
+            None => (body, PlaceholderKind::implied_by(body).ok_or_else(|| TemplateError::UnknownKind(body.to_string()))?),
+        };
+        let valid_name = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(TemplateError::Malformed(format!("{{{{{}}}}}", body)));
+        }
+
+        segments.push(Segment::Placeholder(Placeholder { name: name.to_string(), kind }));
+        rest = &after[end + 2..];
+    }
+    segments.push(Segment::Text(rest));
+    Ok(segments)
+}
+
+// The distinct placeholders in a template, in first-use order
+pub fn placeholders(template: &str) -> Result<Vec<Placeholder>, TemplateError> {
+    let mut found: Vec<Placeholder> = Vec::new();
+    for segment in parse(template)? {
+        let Segment::Placeholder(placeholder) = segment else { continue };
+        match found.iter().find(|p| p.name == placeholder.name) {
+            Some(existing) if existing.kind != placeholder.kind => {
+                return Err(TemplateError::ConflictingKinds {
+                    name: placeholder.name,
+                    first: existing.kind,
+                    second: placeholder.kind,
+                });
+            }
+            Some(_) => {}
+            None => found.push(placeholder),
+        }
+    }
+    Ok(found)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?",
+        [table],
+        |_| Ok(()),
+    ).optional().map(|found| found.is_some())
+}
+
+// Whether `column` belongs to one of `tables`, or to any table when none are given
+fn column_exists(conn: &Connection, tables: &[String], column: &str) -> rusqlite::Result<bool> {
+    let candidates: Vec<String> = if tables.is_empty() {
+        conn.prepare("SELECT name FROM sqlite_master WHERE type IN ('table', 'view')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?
+    } else {
+        tables.to_vec()
+    };
+
+    for table in candidates {
+        let found = conn.query_row(
+            "SELECT 1 FROM pragma_table_info(?) WHERE name = ?",
+            [table.as_str(), column],
+            |_| Ok(()),
+        ).optional()?;
+        if found.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Substitute `args` into the template. Tables must exist; columns must exist in
+// one of the substituted tables (or any table if the template names none).
+pub fn render(conn: &Connection, template: &str, args: &Map<String, Value>) -> Result<String, TemplateError> {
+    let declared = placeholders(template)?;
+    let arg = |name: &str| args.get(name).ok_or_else(|| TemplateError::MissingArgument(name.to_string()));
+    let identifier = |name: &str| -> Result<String, TemplateError> {
+        arg(name)?.as_str().map(String::from).ok_or_else(|| TemplateError::InvalidArgument {
+            name: name.to_string(),
+            message: "must be a string".to_string(),
+        })
+    };
+
+    let mut tables = Vec::new();
+    for placeholder in declared.iter().filter(|p| p.kind == PlaceholderKind::Table) {
+        let table = identifier(&placeholder.name)?;
+        if !table_exists(conn, &table)? {
+            return Err(TemplateError::UnknownTable(table));
+        }
+        tables.push(table);
+    }
+
+    let mut values = HashMap::new();
+    for placeholder in &declared {
+        let rendered = match placeholder.kind {
+            PlaceholderKind::Table => quote_identifier(&identifier(&placeholder.name)?),
+            PlaceholderKind::Column => {
+                let column = identifier(&placeholder.name)?;
+                if !column_exists(conn, &tables, &column)? {
+                    return Err(TemplateError::UnknownColumn(column));
+                }
+                quote_identifier(&column)
+            }
+            PlaceholderKind::Integer => arg(&placeholder.name)?
+                .as_i64()
+                .ok_or_else(|| TemplateError::InvalidArgument {
+                    name: placeholder.name.clone(),
+                    message: "must be an integer".to_string(),
+                })?
+                .to_string(),
+        };
+        values.insert(placeholder.name.as_str(), rendered);
+    }
+
+    Ok(parse(template)?
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.to_string(),
+            Segment::Placeholder(p) => values[p.name.as_str()].clone(),
+        })
+        .collect())
+}
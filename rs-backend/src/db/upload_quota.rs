@@ -0,0 +1,104 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    // Bytes the client has uploaded within the window
+    pub used: u64,
+    pub limit: u64,
+    // When enough earlier uploads age out of the window for this one to fit
+    pub retry_after: Duration,
+}
+
+// Upload bytes per client over a rolling window, kept in memory
+pub struct UploadQuota {
+    limit: u64,
+    window: Duration,
+    uploads: Mutex<HashMap<String, VecDeque<(Instant, u64)>>>,
+}
+
+impl UploadQuota {
+    pub fn new(limit: u64, window: Duration) -> Self {
+        Self { limit, window, uploads: Mutex::new(HashMap::new()) }
+    }
+
+    // Count `bytes` against `client` if they fit within its quota. The returned
+    // reservation is refunded when dropped unless committed, so failed uploads
+    // don't use up the quota.
+    pub fn reserve(self: &Arc<Self>, client: &str, bytes: u64) -> Result<QuotaReservation, QuotaExceeded> {
+        let now = Instant::now();
+        let mut uploads = self.uploads.lock().unwrap();
+        let entries = uploads.entry(client.to_string()).or_default();
+        while entries.front().is_some_and(|(at, _)| now.duration_since(*at) >= self.window) {
+            entries.pop_front();
+        }
+
+        let used: u64 = entries.iter().map(|(_, size)| size).sum();
+        if used.saturating_add(bytes) > self.limit {
+            // Drop the oldest uploads until this one would fit; the last one dropped
+            // says when that happens (never within the window if it's over the limit alone)
+            let mut remaining = used;
+            let mut retry_after = self.window;
+            for (at, size) in entries.iter() {
+                remaining -= size;
+                if remaining.saturating_add(bytes) <= self.limit {
+                    retry_after = self.window.saturating_sub(now.duration_since(*at));
+                    break;
+                }
+            }
+            return Err(QuotaExceeded { used, limit: self.limit, retry_after });
+        }
+
+        entries.push_back((now, bytes));
+        Ok(QuotaReservation {
+            quota: Arc::clone(self),
+            client: client.to_string(),
+            at: now,
+            bytes,
+            committed: false,
+        })
+    }
+
+    // Bytes counted against `client` within the current window
+    pub fn used(&self, client: &str) -> u64 {
+        let now = Instant::now();
+        self.uploads.lock().unwrap().get(client)
+            .map(|entries| entries.iter()
+                .filter(|(at, _)| now.duration_since(*at) < self.window)
+                .map(|(_, size)| size)
+                .sum())
+            .unwrap_or(0)
+    }
+}
+
+pub struct QuotaReservation {
+    quota: Arc<UploadQuota>,
+    client: String,
+    at: Instant,
+    bytes: u64,
+    committed: bool,
+}
+
+impl QuotaReservation {
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        let mut uploads = self.quota.uploads.lock().unwrap();
+        if let Some(entries) = uploads.get_mut(&self.client) {
+            if let Some(index) = entries.iter().position(|(at, size)| *at == self.at && *size == self.bytes) {
+                entries.remove(index);
+            }
+            if entries.is_empty() {
+                uploads.remove(&self.client);
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use rusqlite::types::Value as SqlValue;
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, XlsxError};
+
+pub const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+// Excel's sheet limits: 1,048,576 rows (one taken by the header) and 16,384 columns
+pub const MAX_XLSX_ROWS: usize = 1_048_575;
+pub const MAX_XLSX_COLUMNS: usize = 16_384;
+
+// Integers beyond this lose precision as Excel doubles, so they're written as text
+const MAX_EXACT_INTEGER: i64 = 1 << 53;
+
+// Write a result set as a single-sheet workbook with a styled header row.
+// Numbers become numeric cells and text stays text; NULLs are left blank.
+pub fn rows_to_xlsx(columns: &[String], rows: &[Vec<SqlValue>]) -> Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Results")?;
+
+    let header = Format::new()
+        .set_bold()
+        .set_background_color(Color::RGB(0xD9E1F2))
+        .set_border_bottom(FormatBorder::Thin);
+
+    for (col, name) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, name, &header)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+
+    for (i, row) in rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        for (col, value) in row.iter().enumerate() {
+            let c = col as u16;
+            match value {
+                SqlValue::Null => {}
+                SqlValue::Integer(n) if n.abs() <= MAX_EXACT_INTEGER => {
+                    sheet.write_number(r, c, *n as f64)?;
+                }
+                SqlValue::Integer(n) => {
+                    sheet.write_string(r, c, n.to_string())?;
+                }
+                SqlValue::Real(f) => {
+                    sheet.write_number(r, c, *f)?;
+                }
+                SqlValue::Text(s) => {
+                    sheet.write_string(r, c, s)?;
+                }
+                SqlValue::Blob(b) => {
+                    sheet.write_string(r, c, format!("<BLOB: {} bytes>", b.len()))?;
+                }
+            }
+        }
+    }
+
+    workbook.save_to_buffer()
+}
@@ -1,24 +1,115 @@
+pub mod config;
 pub mod db;
 pub mod models;
+pub mod storage;
 pub mod utils;
 
 use axum::{
     Router,
+    body::Bytes,
+    middleware::{self, Next},
+    extract::Request,
     routing::{get, post, delete, put},
-    extract::{Path, State, Multipart},
-    response::Json,
-    http::StatusCode,
+    extract::{ConnectInfo, FromRequest, FromRequestParts, Query, State, Multipart, rejection::PathRejection},
+    response::{IntoResponse, Json, Response},
+    http::{header, HeaderMap, StatusCode},
 };
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use rusqlite::params_from_iter;
 use tracing::error;
+use std::collections::BTreeSet;
 use std::fmt::Display;
+use std::net::SocketAddr;
 
 use db::connection::DbConnection;
-use models::database_metadata::DatabaseMetadata;
+use db::error_log::ErrorEntry;
+use utils::{file_sha256, is_valid_identifier, quote_identifier, secrets_match, sha256_hex};
+use utils::json_limits;
+use utils::pagination::{self, Pagination, QueryPage};
+use utils::signed_link::{self, LinkError};
+use db::query;
+use db::arrow_export;
+use db::xlsx_export;
+use db::stream;
+use db::template::{self, TemplateError};
+use db::csv_bundle;
+use db::csv_import;
+use db::diff;
+use db::describe;
+use db::export::{self, ExportError, ExportFormat};
+use db::expression::{self, ExpressionError, ExpressionKind};
+use db::column_meta::{self, ColumnMetaError};
+use db::column_policy::{self, ColumnPolicy, HiddenColumns, PolicyConnection, PolicyError};
+use db::pivot;
+use db::pool_cache::DatabaseConnectionManager;
+use db::pragmas::{self, PragmaError, PragmaGuard, PragmaOverrides};
+use db::sample_insert;
+use db::quality;
+use db::result_diff::{self, ResultDiffError, Side};
+use db::graphql;
+use db::guard::{StatementGuard, Tripped};
+use db::integrity::{self, IntegrityReport};
+use db::journal;
+use db::migrations::{self, Migration, MigrationError};
+use models::audit_log::AuditEntry;
+use models::import_job::ImportJob;
+use models::export_job::{self, ExportJob};
+use models::query_history::QueryHistoryEntry;
+use models::saved_query::SavedQuery;
+use models::table_growth::TableGrowth;
+use models::database_metadata::{self, DatabaseMetadata, ListFilter, SortColumn, SortOrder};
 
-// Constants for file upload limits
-const MAX_FILE_SIZE: usize = 1024 * 1024 * 100; // 100MB
-const MIN_FILE_SIZE: usize = 1024; // 1KB
+// Accepted upload content types and the magic bytes that identify a file
+const SQLITE_CONTENT_TYPES: &[&str] = &["application/x-sqlite3", "application/vnd.sqlite3"];
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const GZIP_CONTENT_TYPES: &[&str] = &["application/gzip", "application/x-gzip"];
+
+// Bounds SQLite places on a database's page size
+const MIN_PAGE_SIZE: i64 = 512;
+const MAX_PAGE_SIZE: i64 = 65536;
+
+// Rows serialized to extrapolate a query's result size
+const SIZE_ESTIMATE_SAMPLE_ROWS: usize = 50;
+
+// Default and maximum number of rows per table visible to a sampled query
+const DEFAULT_SAMPLE_SIZE: usize = 100;
+const MAX_SAMPLE_SIZE: usize = 10_000;
+
+// Rows committed per transaction by background CSV imports
+const CSV_IMPORT_BATCH_SIZE: usize = 1000;
+
+// Header identifying the calling client for audit attribution
+const CLIENT_ID_HEADER: &str = "x-client-id";
+
+// Default and maximum page size for every `limit` / `offset` listing
+const DEFAULT_PAGE_LIMIT: i64 = 100;
+const MAX_PAGE_LIMIT: i64 = 1000;
+
+// Rows per page when a query asks for a `page` without a `page_size`
+const DEFAULT_QUERY_PAGE_SIZE: i64 = 100;
+
+// Upload header that rejects the upload when a database with the same name exists
+const IF_NONE_NAME_HEADER: &str = "x-if-none-name";
+
+// Upload header asking for the stored database to be switched to WAL mode
+const CONVERT_TO_WAL_HEADER: &str = "x-convert-to-wal";
+
+// Keys the server once kept in a database's properties before they moved to
+// their own columns. Older records may still carry them; bundles neither export
+// nor import them.
+const SERVER_OWNED_PROPERTIES: &[&str] = &["original_copy", "original_journal_mode", "integrity"];
+
+// Export formats clients ask for that background exports don't produce
+const UNSUPPORTED_EXPORT_FORMATS: &[&str] = &["sql", "parquet"];
+
+// Property holding the database's column policy: JSON mapping each role to the
+// columns, by table, it may not read
+const COLUMN_POLICY_PROPERTY: &str = "column_policy";
+
+// Export response header set to "true" when rows were dropped to fit the format
+const TRUNCATED_HEADER: &str = "x-truncated";
 
 // Define our own error type that wraps the StatusCode and Json response
 #[derive(Debug)]
@@ -38,9 +129,70 @@ impl From<(StatusCode, Json<Value>)> for ApiError {
     }
 }
 
+// Path extractor that reports unparseable segments (e.g. a non-numeric id) as a
+// JSON 400 with a fixed message, instead of axum's plain-text rejection
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        match rejection {
+            PathRejection::FailedToDeserializePathParams(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Invalid path parameter" }))
+            ).into(),
+            other => handle_error(other, "Failed to read path parameters"),
+        }
+    }
+}
+
+// JSON body extractor for query payloads: checks nesting depth and element count
+// against the configured limits on the raw bytes, so a pathological body is
+// refused with a 400 before serde builds anything from it
+pub struct GuardedJson(pub Value);
+
+#[axum::async_trait]
+impl FromRequest<DbConnection> for GuardedJson {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &DbConnection) -> Result<Self, Self::Rejection> {
+        let is_json = req.headers().get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json") || v.contains("+json"));
+        if !is_json {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Json(json!({ "error": "Expected request with `Content-Type: application/json`" }))
+            ).into());
+        }
+
+        let bytes = Bytes::from_request(req, state).await
+            .map_err(|e| (e.status(), Json(json!({ "error": e.body_text() }))))?;
+        json_limits::check(&bytes, state.config().json_limits).map_err(|e| (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() }))
+        ))?;
+        let Json(value) = Json::<Value>::from_bytes(&bytes).map_err(|e| (
+            e.status(),
+            Json(json!({ "error": e.body_text() }))
+        ))?;
+        Ok(GuardedJson(value))
+    }
+}
+
 // Now we can implement From for rusqlite::Error
 impl From<rusqlite::Error> for ApiError {
     fn from(err: rusqlite::Error) -> Self {
+        if let Some(table) = missing_table(&err) {
+            return table_not_found(&table);
+        }
+        if is_busy(&err) {
+            return database_busy();
+        }
+        if is_policy_denied(&err) {
+            return column_forbidden(None);
+        }
         match err {
             rusqlite::Error::SqliteFailure(_, Some(msg)) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -53,6 +205,41 @@ impl From<rusqlite::Error> for ApiError {
 
 type ApiResult = Result<Json<Value>, ApiError>;
 
+// Extract the table name from SQLite's "no such table: X" error
+fn missing_table(err: &rusqlite::Error) -> Option<String> {
+    let msg = match err {
+        rusqlite::Error::SqliteFailure(_, Some(msg)) => msg.as_str(),
+        rusqlite::Error::SqlInputError { msg, .. } => msg.as_str(),
+        _ => return None,
+    };
+    let table = msg.strip_prefix("no such table: ")?.trim();
+    Some(table.strip_prefix("main.").unwrap_or(table).to_string())
+}
+
+fn table_not_found(table: &str) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": { "code": "TABLE_NOT_FOUND", "table": table } }))
+    ).into()
+}
+
+// Map a query preparation error, surfacing missing tables as a 404
+fn map_prepare_error(e: rusqlite::Error, status: StatusCode) -> ApiError {
+    if let Some(table) = missing_table(&e) {
+        return table_not_found(&table);
+    }
+    if is_busy(&e) {
+        return database_busy();
+    }
+    if is_policy_denied(&e) {
+        return column_forbidden(None);
+    }
+    (
+        status,
+        Json(json!({ "error": format!("Failed to prepare query: {}", e) }))
+    ).into()
+}
+
 fn handle_error<E: Display>(e: E, msg: impl Into<String>) -> ApiError {
     let msg = msg.into();
     error!("{}: {}", msg, e);
@@ -62,151 +249,1261 @@ fn handle_error<E: Display>(e: E, msg: impl Into<String>) -> ApiError {
     ).into()
 }
 
-fn map_db_error<E: Display>(e: E, msg: impl Into<String>) -> ApiError {
+fn map_db_error<E: Display + 'static>(e: E, msg: impl Into<String>) -> ApiError {
+    if is_busy(&e) {
+        return database_busy();
+    }
+    if is_policy_denied(&e) {
+        return column_forbidden(None);
+    }
     handle_error(e, msg)
 }
 
+// The SQLite error behind `e`, directly or wrapped in anyhow
+fn as_sqlite_error(e: &dyn std::any::Any) -> Option<&rusqlite::Error> {
+    e.downcast_ref::<rusqlite::Error>()
+        .or_else(|| e.downcast_ref::<anyhow::Error>().and_then(|e| e.downcast_ref::<rusqlite::Error>()))
+}
+
+// Whether an error is SQLite giving up on a lock another connection held for
+// longer than the busy timeout
+fn is_busy(e: &dyn std::any::Any) -> bool {
+    matches!(
+        as_sqlite_error(e),
+        Some(rusqlite::Error::SqliteFailure(err, _))
+            if matches!(err.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+// Whether a metadata save collided with a record already holding its checksum
+fn is_duplicate_checksum(e: &dyn std::any::Any) -> bool {
+    matches!(
+        as_sqlite_error(e),
+        Some(rusqlite::Error::SqliteFailure(err, Some(msg)))
+            if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE && msg.contains(".checksum")
+    )
+}
+
+// Whether a statement was refused by a column policy's authorizer, the only one
+// installed on database connections
+fn is_policy_denied(e: &dyn std::any::Any) -> bool {
+    as_sqlite_error(e).is_some_and(column_policy::is_denied)
+}
+
+fn column_forbidden(role: Option<&str>) -> ApiError {
+    let error = match role {
+        Some(role) => format!("Query references columns hidden from role '{}'", role),
+        None => "Query references hidden columns".to_string(),
+    };
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": error, "code": "COLUMN_FORBIDDEN", "role": role }))
+    ).into()
+}
+
+// Lock contention clears on its own, so clients are told to back off and retry
+fn database_busy() -> ApiError {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "error": "Database is busy, try again shortly",
+            "code": "DATABASE_BUSY",
+            "retryable": true
+        }))
+    ).into()
+}
+
+// Reject new databases once the configured MAX_DATABASES cap is reached
+fn ensure_database_capacity(db_connection: &DbConnection) -> Result<(), ApiError> {
+    let Some(max_databases) = db_connection.max_databases() else {
+        return Ok(());
+    };
+
+    let count = DatabaseMetadata::count(db_connection)
+        .map_err(|e| map_db_error(e, "Failed to count databases"))?;
+
+    if count as usize >= max_databases {
+        return Err((
+            StatusCode::INSUFFICIENT_STORAGE,
+            Json(json!({
+                "error": format!(
+                    "Database limit reached ({} of {}). Delete unused databases before uploading more.",
+                    count, max_databases
+                )
+            }))
+        ).into());
+    }
+
+    Ok(())
+}
+
+// Map an error raised while stepping a statement, distinguishing operator interrupts
+fn map_execution_error(e: rusqlite::Error, msg: &str) -> ApiError {
+    match &e {
+        rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::OperationInterrupted => (
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "Query was interrupted" }))
+        ).into(),
+        rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::ReadOnly => read_only_violation(),
+        _ => map_db_error(e, msg),
+    }
+}
+
+fn read_only_violation() -> ApiError {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({
+            "error": "Database is read-only; the statement would have written to it",
+            "code": "READ_ONLY"
+        }))
+    ).into()
+}
+
+// Install the configured change limit and query timeout on a connection
+fn install_statement_guard<'c>(
+    db_connection: &DbConnection,
+    conn: &'c rusqlite::Connection,
+) -> Result<StatementGuard<'c>, ApiError> {
+    StatementGuard::install(conn, db_connection.max_statement_changes(), db_connection.config().query_timeout)
+        .map_err(|e| map_db_error(e, "Failed to prepare connection"))
+}
+
+// Like map_execution_error, but reports statements aborted by the statement guard
+fn map_guarded_error(e: rusqlite::Error, guard: &StatementGuard<'_>, msg: &str) -> ApiError {
+    match guard.tripped() {
+        Some(Tripped::MaxChanges(max_changes)) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+                "error": format!(
+                    "Statement aborted after more than {} row changes; suspected trigger loop",
+                    max_changes
+                ),
+                "code": "MAX_CHANGES_EXCEEDED",
+                "max_changes": max_changes
+            }))
+        ).into(),
+        Some(Tripped::Timeout(timeout)) => (
+            StatusCode::REQUEST_TIMEOUT,
+            Json(json!({
+                "error": format!("Query aborted after exceeding the {} ms timeout", timeout.as_millis()),
+                "code": "QUERY_TIMEOUT",
+                "timeout_ms": timeout.as_millis() as u64
+            }))
+        ).into(),
+        None => map_execution_error(e, msg),
+    }
+}
+
+// Reject client-supplied table names that can't be used even when quoted
+fn validate_table_name(table: &str) -> Result<(), ApiError> {
+    if is_valid_identifier(table) {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid table name" }))
+        ).into())
+    }
+}
+
+// Look up database metadata, mapping a missing record to a 404
+fn find_database(db_connection: &DbConnection, id: i64) -> Result<DatabaseMetadata, ApiError> {
+    match DatabaseMetadata::find_by_id(db_connection, id) {
+        Ok(Some(m)) => Ok(m),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Database not found" }))
+        ).into()),
+        Err(e) => Err(map_db_error(e, "Failed to find database")),
+    }
+}
+
 pub fn create_app(db_connection: DbConnection) -> Router {
+    let admin = Router::new()
+        .route("/admin/metadata/vacuum", post(vacuum_metadata))
+        .route("/admin/metadata/export", get(export_metadata))
+        .route("/admin/metadata/import", post(import_metadata))
+        .route("/admin/config", get(get_config))
+        .route("/admin/queries", get(list_running_queries))
+        .route("/admin/recent-queries", get(list_recent_queries))
+        .route("/admin/errors", get(list_recent_errors))
+        .route("/admin/files", get(list_storage_files))
+        .route("/admin/queries/:query_id", delete(kill_query))
+        .route_layer(middleware::from_fn_with_state(db_connection.clone(), require_admin));
+
     Router::new()
+        .merge(admin)
         .route("/health", get(health_check))
+        .route("/features", get(get_features))
         .route("/databases", get(list_databases))
+        .route("/databases/search", get(search_databases))
         .route("/databases/upload", post(upload_database))
+        .route("/databases/import/path", post(import_database_from_path))
+        .route("/databases/import/csv", post(import_csv))
+        .route("/databases/query-diff", post(execute_query_diff))
+        .route("/databases/bulk-tag", post(bulk_tag_databases))
+        .route("/imports/:id/status", get(get_import_status))
+        .route("/databases/:id/exports", post(start_export))
+        .route("/exports/:id", get(get_export_status))
+        .route("/exports/:id/cancel", post(cancel_export))
+        .route("/exports/:id/download", get(download_export))
         .route("/databases/:id/tables", get(get_tables))
+        .route("/databases/:id/views", get(get_views))
+        .route("/databases/:id/integrity", post(check_integrity))
+        .route("/databases/:id/graphql-sdl", get(get_graphql_sdl))
+        .route("/databases/:id/describe", get(describe_database))
+        .route("/databases/:id/fingerprint", get(get_fingerprint))
+        .route("/databases/:id/export/csv-bundle", get(export_csv_bundle))
+        .route("/databases/:id/quality/no-pk", get(get_tables_without_primary_key))
+        .route("/databases/:id/quality/indexes", get(get_index_report))
+        .route("/databases/:id/validate-expression", post(validate_expression))
+        .route("/databases/:id/download", get(download_database))
+        .route("/databases/:id/download-link", get(create_download_link))
+        .route("/download/:token", get(download_with_token))
         .route("/databases/:id/tables/:table/schema", get(get_table_schema))
+        .route("/databases/:id/tables/:table/foreign-keys", get(get_table_foreign_keys))
+        .route("/databases/:id/tables/:table/columns/:column/meta", get(get_column_meta))
+        .route("/databases/:id/tables/:table/columns/:column/rename", post(rename_column))
+        .route("/databases/:id/tables/:table/sample-insert", get(get_sample_insert))
+        .route("/databases/:id/tables/:table/diff-preview", post(preview_table_diff))
+        .route("/databases/:id/tables/:table/growth", get(get_table_growth))
         .route("/databases/:id/query", post(execute_query))
+        .route("/databases/:id/query/sample", post(execute_sample_query))
+        .route("/databases/:id/query/arrow", post(execute_arrow_query))
+        .route("/databases/:id/query/xlsx", post(execute_xlsx_query))
+        .route("/databases/:id/query/stream", post(execute_stream_query))
+        .route("/databases/:id/query/export", post(execute_csv_export_query))
+        .route("/databases/:id/query/size-estimate", post(estimate_query_size))
+        .route("/databases/:id/query/affected-preview", post(preview_affected_rows))
+        .route("/databases/:id/query/pivot", post(execute_pivot_query))
+        .route("/databases/:id/audit", get(get_audit_log))
+        .route("/databases/:id/history", get(get_query_history))
+        .route("/databases/:id/migrate", post(migrate_database))
+        .route("/databases/:id/page-size", get(get_page_size).put(set_page_size))
+        .route("/databases/:id/reindex", post(reindex_database))
+        .route("/databases/:id/snapshots", post(open_snapshot))
+        .route("/snapshots/:token/query", post(query_snapshot))
+        .route("/snapshots/:token/close", post(close_snapshot))
+        .route("/databases/:id/saved-queries", get(list_saved_queries).post(create_saved_query))
+        .route("/databases/:id/saved-queries/:query_id", delete(delete_saved_query))
+        .route("/databases/:id/saved-queries/:query_id/run", post(run_saved_query))
+        .route("/databases/:id/bundle/export", get(export_bundle))
+        .route("/databases/:id/bundle/import", post(import_bundle))
         .route("/databases/:id", get(get_database))
         .route("/databases/:id", delete(delete_database))
         .route("/databases/:id", put(update_database))
+        .route("/databases/:id/reset", post(reset_database))
+        .layer(middleware::from_fn_with_state(db_connection.clone(), record_errors))
         .with_state(db_connection)
 }
 
+// Keep every error response in the connection's error log for /admin/errors
+async fn record_errors(
+    State(db_connection): State<DbConnection>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let route = request.extensions().get::<axum::extract::MatchedPath>().map(|p| p.as_str().to_string());
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    // Error bodies are small JSON documents, so buffering them costs little
+    let (parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let error = serde_json::from_slice::<Value>(&bytes)
+        .map(|body| body.get("error").cloned().unwrap_or(body))
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    let database_id = path.strip_prefix("/databases/")
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|id| id.parse().ok());
+
+    db_connection.error_log().record(ErrorEntry {
+        at: chrono::Utc::now(),
+        method,
+        route,
+        path,
+        database_id,
+        status: status.as_u16(),
+        error,
+    });
+    Response::from_parts(parts, axum::body::Body::from(bytes))
+}
+
+// Guard for /admin routes: requires `Authorization: Bearer <ADMIN_TOKEN>`
+async fn require_admin(
+    State(db_connection): State<DbConnection>,
+    request: Request,
+    next: Next,
+) -> Result<axum::response::Response, ApiError> {
+    authorize_admin(&db_connection, request.headers())?;
+    Ok(next.run(request).await)
+}
+
+// Check for the admin token outside /admin, for settings ordinary callers may not change
+fn authorize_admin(db_connection: &DbConnection, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(expected) = db_connection.admin_token() else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Admin API is disabled. Set ADMIN_TOKEN to enable it." }))
+        ).into());
+    };
+
+    let provided = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if !provided.is_some_and(|token| secrets_match(token.as_bytes(), expected.as_bytes())) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "Invalid or missing admin token" }))
+        ).into());
+    }
+    Ok(())
+}
+
 // Route handlers
-pub async fn health_check() -> Json<Value> {
-    Json(json!({
-        "status": "ok",
-        "timestamp": chrono::Utc::now().to_rfc3339()
-    }))
+pub async fn health_check(
+    State(db_connection): State<DbConnection>,
+) -> (StatusCode, Json<Value>) {
+    let timestamp = chrono::Utc::now().to_rfc3339();
+
+    let drift = db_connection.get_metadata_pool().get()
+        .map_err(|e| e.to_string())
+        .and_then(|conn| database_metadata::check_schema(&conn).map_err(|e| e.to_string()));
+
+    match drift {
+        Ok(drift) if drift.is_empty() => (StatusCode::OK, Json(json!({
+            "status": "ok",
+            "timestamp": timestamp
+        }))),
+        Ok(drift) => (StatusCode::SERVICE_UNAVAILABLE, Json(json!({
+            "status": "degraded",
+            "schema_drift": drift,
+            "timestamp": timestamp
+        }))),
+        Err(e) => {
+            error!("Health check failed to inspect metadata schema: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, Json(json!({
+                "status": "degraded",
+                "error": "Failed to inspect metadata schema",
+                "timestamp": timestamp
+            })))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListParams {
+    pub name: Option<String>,
+    pub property: Option<String>,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub favorites_only: bool,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub limit: Option<String>,
+    pub offset: Option<String>,
+}
+
+// Parse `limit` / `offset` query inputs the same way for every paginated endpoint
+fn parse_pagination(
+    limit: Option<&str>,
+    offset: Option<&str>,
+    default_limit: i64,
+    max_limit: i64,
+) -> Result<Pagination, ApiError> {
+    pagination::parse(limit, offset, default_limit, max_limit).map_err(invalid_pagination)
+}
+
+fn invalid_pagination(e: pagination::PaginationError) -> ApiError {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "error": e.to_string(),
+            "code": "INVALID_PAGINATION",
+            "field": e.field
+        }))
+    ).into()
+}
+
+// The search filters shared by listing and bulk tagging; `property` is `key:value`
+fn list_filter(name: Option<String>, property: Option<&str>, tag: Option<String>) -> Result<ListFilter, ApiError> {
+    let property = match property.map(|p| p.split_once(':')) {
+        None => None,
+        Some(Some((key, value))) if !key.is_empty() => Some((key.to_string(), value.to_string())),
+        Some(_) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Property filter must be in the form key:value" }))
+        ).into()),
+    };
+
+    Ok(ListFilter {
+        name: name.filter(|n| !n.is_empty()),
+        property,
+        tag: tag.filter(|t| !t.is_empty()),
+        ..ListFilter::default()
+    })
 }
 
 pub async fn list_databases(
-    State(db_connection): State<DbConnection>
+    State(db_connection): State<DbConnection>,
+    Query(params): Query<ListParams>,
 ) -> ApiResult {
-    DatabaseMetadata::list(&db_connection)
+    let mut filter = list_filter(params.name, params.property.as_deref(), params.tag)?;
+    filter.favorites_only = params.favorites_only;
+    if let Some(sort) = params.sort.as_deref() {
+        filter.sort = SortColumn::parse(sort).ok_or_else(|| ApiError(
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Unknown sort column '{}'", sort), "allowed": SortColumn::NAMES }))
+        ))?;
+    }
+    if let Some(order) = params.order.as_deref() {
+        filter.order = SortOrder::parse(order).ok_or_else(|| ApiError(
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "order must be asc or desc" }))
+        ))?;
+    }
+
+    // Listing stays unpaginated unless the caller asks for a page
+    if params.limit.is_some() || params.offset.is_some() {
+        filter.page = Some(parse_pagination(
+            params.limit.as_deref(),
+            params.offset.as_deref(),
+            DEFAULT_PAGE_LIMIT,
+            MAX_PAGE_LIMIT,
+        )?);
+    }
+
+    DatabaseMetadata::list_filtered(&db_connection, &filter)
         .map(|databases| Json(json!({ "databases": databases })))
         .map_err(|e| map_db_error(e, "Failed to list databases"))
 }
 
-#[axum::debug_handler]
-pub async fn upload_database(
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub q: Option<String>,
+}
+
+// Find databases by text in their name or notes
+pub async fn search_databases(
     State(db_connection): State<DbConnection>,
-    mut multipart: Multipart,
+    Query(params): Query<SearchParams>,
 ) -> ApiResult {
-    // Process multipart form data
-    let (filename, content_type, file_data) = match process_multipart(&mut multipart).await {
-        Ok(data) => data,
-        Err(e) => return Err(e),
+    let query = params.q.as_deref().unwrap_or("").trim();
+    DatabaseMetadata::search(&db_connection, query)
+        .map(|databases| Json(json!({ "databases": databases })))
+        .map_err(|e| map_db_error(e, "Failed to search databases"))
+}
+
+// Tags are trimmed; blank ones and non-strings are rejected rather than stored
+fn parse_tags(payload: &Value, field: &str) -> Result<BTreeSet<String>, ApiError> {
+    let bad_request = |msg: String| -> ApiError {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": msg }))).into()
     };
-    
-    // Validate file type
-    if !content_type.starts_with("application/x-sqlite3") && !content_type.starts_with("application/octet-stream") {
+    let tags = match payload.get(field) {
+        None | Some(Value::Null) => return Ok(BTreeSet::new()),
+        Some(Value::Array(tags)) => tags,
+        Some(_) => return Err(bad_request(format!("{} must be an array of tags", field))),
+    };
+    tags.iter()
+        .map(|tag| match tag.as_str().map(str::trim) {
+            Some("") | None => Err(bad_request(format!("{} must contain only non-empty strings", field))),
+            Some(tag) => Ok(tag.to_string()),
+        })
+        .collect()
+}
+
+// Add and remove tags on every database matching a list filter, all or nothing
+pub async fn bulk_tag_databases(
+    State(db_connection): State<DbConnection>,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let add = parse_tags(&payload, "add")?;
+    let remove = parse_tags(&payload, "remove")?;
+    if add.is_empty() && remove.is_empty() {
         return Err((
             StatusCode::BAD_REQUEST,
-            Json(json!({ "error": "Invalid file type. Only SQLite databases are allowed." }))
+            Json(json!({ "error": "Supply tags to add or remove" }))
         ).into());
     }
-
-    // Check file size
-    let total_size = file_data.len();
-    if total_size > MAX_FILE_SIZE {
+    if let Some(tag) = add.intersection(&remove).next() {
         return Err((
             StatusCode::BAD_REQUEST,
-            Json(json!({ "error": format!("File too large. Maximum size is {}MB", MAX_FILE_SIZE / 1024 / 1024) }))
+            Json(json!({ "error": format!("Tag '{}' is both added and removed", tag) }))
         ).into());
     }
-    if total_size < MIN_FILE_SIZE {
-        return Err((
+
+    let filter = match payload.get("filter") {
+        None | Some(Value::Null) => ListFilter::default(),
+        // A misspelled or mistyped field must not widen the filter to every database
+        Some(Value::Object(filter)) => {
+            let mut fields = [("name", None), ("property", None), ("tag", None)];
+            for (key, value) in filter {
+                let slot = fields.iter_mut().find(|(name, _)| name == key).ok_or_else(|| ApiError(
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": format!("Unknown filter field '{}'", key), "allowed": ["name", "property", "tag"] }))
+                ))?;
+                match value {
+                    Value::String(s) => slot.1 = Some(s.clone()),
+                    Value::Null => {}
+                    _ => return Err((
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "error": format!("Filter field '{}' must be a string", key) }))
+                    ).into()),
+                }
+            }
+            let [(_, name), (_, property), (_, tag)] = fields;
+            list_filter(name, property.as_deref(), tag)?
+        }
+        Some(_) => return Err((
             StatusCode::BAD_REQUEST,
-            Json(json!({ "error": format!("File too small. Minimum size is {}KB", MIN_FILE_SIZE / 1024) }))
+            Json(json!({ "error": "filter must be an object" }))
+        ).into()),
+    };
+
+    let matched = DatabaseMetadata::bulk_tag(&db_connection, &filter, &add, &remove)
+        .map_err(|e| map_db_error(e, "Failed to tag databases"))?;
+
+    Ok(Json(json!({
+        "matched": matched,
+        "added": add,
+        "removed": remove
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn upload_database(
+    State(db_connection): State<DbConnection>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> ApiResult {
+    // Bound how many uploads buffer and write files at once; held until the handler returns
+    let Some(_upload_permit) = db_connection.acquire_upload_permit().await else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Too many uploads in progress, try again shortly" }))
         ).into());
-    }
+    };
 
-    // Generate unique filename and path
-    let timestamp = chrono::Utc::now().timestamp();
-    let unique_filename = format!("{}-{}", timestamp, filename);
-    let storage_path = db_connection.get_storage_path("databases").join(&unique_filename);
+    // Process multipart form data
+    let (mut filename, mut content_type, mut file_data, gzip_encoded) = match process_multipart(&mut multipart).await {
+        Ok(data) => data,
+        Err(e) => return Err(e),
+    };
 
-    // Ensure parent directory exists
-    if let Some(parent) = storage_path.parent() {
-        if let Err(e) = tokio::fs::create_dir_all(parent).await {
-            error!("Failed to create directory: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Failed to create directory" }))
-            ).into());
+    // Compressed uploads are checked and stored as the database they contain
+    if gzip_encoded || file_data.starts_with(GZIP_MAGIC) {
+        file_data = gunzip_upload(&db_connection, file_data).await?;
+        if let Some(stem) = filename.strip_suffix(".gz").or_else(|| filename.strip_suffix(".GZ")) {
+            filename = stem.to_string();
+        }
+        if GZIP_CONTENT_TYPES.iter().any(|t| content_type.starts_with(t)) {
+            content_type = "application/octet-stream".to_string();
         }
     }
 
-    // Write file
-    if let Err(e) = tokio::fs::write(&storage_path, &file_data).await {
-        error!("Failed to write file: {}", e);
+    ensure_database_capacity(&db_connection)?;
+
+    // Conditional upload: refuse to create a second database with the same name
+    if is_header_flag_set(&headers, IF_NONE_NAME_HEADER) {
+        match DatabaseMetadata::find_by_name(&db_connection, &filename) {
+            Ok(Some(existing)) => return Err((
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "A database with this name already exists",
+                    "existing_id": existing.id
+                }))
+            ).into()),
+            Ok(None) => {}
+            Err(e) => return Err(map_db_error(e, "Failed to check for existing database")),
+        }
+    }
+    
+    // Validate file type: a SQLite content type, or a generic one with a SQLite
+    // extension, and in either case the SQLite header bytes
+    if !is_sqlite_candidate(&db_connection, &filename, &content_type) {
         return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": "Failed to save file" }))
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid file type. Only SQLite databases are allowed." }))
         ).into());
     }
+    check_sqlite_magic(&file_data)?;
 
-    // Validate SQLite database and count tables
-    let table_count = match validate_sqlite_db(&storage_path) {
-        Ok(count) => count,
-        Err(e) => {
-            tokio::fs::remove_file(&storage_path).await.ok();
-            return Err(e);
+    // Count the upload against the client's quota; refunded if storing it fails
+    let reservation = match db_connection.upload_quota() {
+        Some(quota) => {
+            let client = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            let reservation = quota.reserve(&client, file_data.len() as u64).map_err(|e| ApiError(
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json!({
+                    "error": "Upload quota exceeded, try again later",
+                    "used_bytes": e.used,
+                    "quota_bytes": e.limit,
+                    "retry_after_secs": e.retry_after.as_secs()
+                }))
+            ))?;
+            Some(reservation)
         }
+        None => None,
     };
 
-    // Create metadata
-    let metadata = DatabaseMetadata::new(
-        filename,
-        storage_path.to_string_lossy().into_owned(),
-        total_size as i64,
-        table_count,
-        false,
-        Some(format!("Uploaded on {}", chrono::Local::now().to_rfc2822())),
-    );
+    let convert_to_wal = db_connection.config().upload_convert_to_wal
+        || is_header_flag_set(&headers, CONVERT_TO_WAL_HEADER);
+    let notes = format!("Uploaded on {}", chrono::Local::now().to_rfc2822());
+    let stored = store_database(&db_connection, filename, file_data, notes, convert_to_wal).await?;
+    if let Some(reservation) = reservation {
+        reservation.commit();
+    }
+    Ok(stored)
+}
 
-    metadata.save(&db_connection)
-        .map(|database| Json(json!({ "database": database })))
-        .map_err(|e| map_db_error(e, "Failed to save database metadata"))
+#[derive(Debug, Deserialize)]
+pub struct DryRunParams {
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
-// Helper function to process multipart form data
-async fn process_multipart(multipart: &mut Multipart) -> Result<(String, String, Vec<u8>), ApiError> {
-    let field = match multipart.next_field().await {
-        Ok(Some(field)) => field,
-        Ok(None) => {
-            return Err(ApiError(
-                StatusCode::BAD_REQUEST,
-                Json(json!({ "error": "No file provided" }))
-            ));
-        }
-        Err(e) => {
-            error!("Failed to process multipart form: {}", e);
-            return Err(ApiError(
-                StatusCode::BAD_REQUEST,
-                Json(json!({ "error": "Failed to process upload" }))
-            ));
-        }
+pub async fn import_database_from_path(
+    State(db_connection): State<DbConnection>,
+    Query(params): Query<DryRunParams>,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let path = match payload.get("path").and_then(|v| v.as_str()) {
+        Some(p) => p,
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Path is required" }))
+        ).into()),
     };
 
-    if field.name() != Some("file") {
-        return Err(ApiError(
-            StatusCode::BAD_REQUEST,
-            Json(json!({ "error": "No file provided" }))
-        ));
-    }
+    // Only files inside an allowed base directory may be read; missing files and
+    // disallowed locations get the same response so existence isn't leaked
+    let resolved = db_connection.resolve_import_path(path).ok_or_else(|| ApiError(
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Path is not within an allowed import directory" }))
+    ))?;
 
-    let filename = field.file_name().unwrap_or("unknown.db").to_string();
+    ensure_database_capacity(&db_connection)?;
+
+    let file_data = tokio::fs::read(&resolved).await
+        .map_err(|e| handle_error(e, "Failed to read import file"))?;
+
+    let filename = payload.get("name")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .or_else(|| resolved.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "imported.db".to_string());
+
+    let convert_to_wal = db_connection.config().upload_convert_to_wal
+        || payload.get("convert_to_wal").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    check_sqlite_magic(&file_data)?;
+
+    // Report what would be stored, reading the source file in place
+    if params.dry_run {
+        check_file_size(&db_connection, file_data.len())?;
+        let tables = tokio::task::spawn_blocking(move || describe_import_tables(&resolved))
+            .await
+            .map_err(|e| handle_error(e, "Dry run task failed"))??;
+
+        let mut warnings = Vec::new();
+        match DatabaseMetadata::find_by_name(&db_connection, &filename) {
+            Ok(Some(existing)) => warnings.push(format!("A database named '{}' already exists (id {})", filename, existing.id.unwrap_or_default())),
+            Ok(None) => {}
+            Err(e) => return Err(map_db_error(e, "Failed to check for existing database")),
+        }
+        if tables.is_empty() {
+            warnings.push("The database has no tables".to_string());
+        }
+        return Ok(Json(json!({
+            "dry_run": true,
+            "name": filename,
+            "convert_to_wal": convert_to_wal,
+            "tables": tables,
+            "warnings": warnings
+        })));
+    }
+
+    let notes = format!("Imported from {} on {}", resolved.display(), chrono::Local::now().to_rfc2822());
+    store_database(&db_connection, filename, file_data, notes, convert_to_wal).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvImportParams {
+    pub name: Option<String>,
+    pub table: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+pub async fn import_csv(
+    State(db_connection): State<DbConnection>,
+    Query(params): Query<CsvImportParams>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    if body.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "CSV body is required" }))
+        ).into());
+    }
+
+    ensure_database_capacity(&db_connection)?;
+
+    let name = params.name.unwrap_or_else(|| "import.db".to_string());
+    let table = params.table.unwrap_or_else(|| "data".to_string());
+    validate_table_name(&table)?;
+
+    // Infer everything the job would, but create neither a job nor a database
+    if params.dry_run {
+        let schema = tokio::task::spawn_blocking(move || csv_import::infer_schema(&body))
+            .await
+            .map_err(|e| handle_error(e, "Dry run task failed"))?
+            .map_err(|e| ApiError(
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Invalid CSV: {}", e) }))
+            ))?;
+        return Ok((StatusCode::OK, Json(json!({
+            "dry_run": true,
+            "name": name,
+            "tables": [{ "name": table, "columns": schema.columns, "row_count": schema.row_count }],
+            "warnings": schema.warnings
+        }))));
+    }
+
+    let job = ImportJob::create(&db_connection, "csv", &name, &table)
+        .map_err(|e| map_db_error(e, "Failed to create import job"))?;
+
+    let job_id = job.id;
+    let connection = db_connection.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = run_csv_import(&connection, job_id, &name, &table, &body) {
+            error!("CSV import job {} failed: {}", job_id, e);
+            if let Err(e) = ImportJob::fail(&connection, job_id, &e.to_string()) {
+                error!("Failed to record import job {} failure: {}", job_id, e);
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "job": job }))))
+}
+
+// Background body of a CSV import job: infer, load in batches, then register
+fn run_csv_import(
+    db_connection: &DbConnection,
+    job_id: i64,
+    name: &str,
+    table: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let schema = csv_import::infer_schema(data)?;
+    let total_rows = Some(schema.row_count as i64);
+    ImportJob::update_progress(db_connection, job_id, 0, total_rows)?;
+
+    // SQLite builds the database in a local staging file, which is then handed to
+    // the storage backend like an uploaded one
+    let staging = db_connection.get_storage_path(format!("staging/import-{}.db", job_id));
+    std::fs::remove_file(&staging).ok();
+
+    let imported = csv_import::import_csv(
+        data,
+        &schema,
+        &staging,
+        table,
+        CSV_IMPORT_BATCH_SIZE,
+        |processed| ImportJob::update_progress(db_connection, job_id, processed as i64, total_rows),
+    );
+    let file_data = imported.and_then(|_| Ok(std::fs::read(&staging)?));
+    std::fs::remove_file(&staging).ok();
+    let file_data = file_data?;
+
+    // Held to the same size limits, duplicate check and untouched original copy
+    // as an uploaded database
+    check_file_size(db_connection, file_data.len())
+        .map_err(|ApiError(_, Json(body))| anyhow::anyhow!("{}", body["error"]))?;
+    let checksum = sha256_hex(&file_data);
+    if let Some(existing) = DatabaseMetadata::find_by_checksum(db_connection, &checksum)? {
+        anyhow::bail!("An identical database has already been uploaded (id {})", existing.id.unwrap_or_default());
+    }
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let key = format!("databases/{}-{}", timestamp, name);
+    let original_key = format!("originals/{}-{}", timestamp, name);
+    let discard = || {
+        db_connection.storage().delete(&key).ok();
+        db_connection.storage().delete(&original_key).ok();
+    };
+    let stored = db_connection.storage().put(&key, &file_data)
+        .and_then(|_| db_connection.storage().put(&original_key, &file_data))
+        .and_then(|_| db_connection.storage().local_path(&key));
+    let storage_path = stored.inspect_err(|_| discard())?;
+
+    // A new file may have landed where a cached pool still points at an old one
+    db_connection.evict_database_pool(&storage_path);
+    let mut metadata = DatabaseMetadata::new(
+        name.to_string(),
+        storage_path.to_string_lossy().into_owned(),
+        file_data.len() as i64,
+        1,
+        false,
+        Some(format!("Imported from CSV on {}", chrono::Local::now().to_rfc2822())),
+    );
+    metadata.original_copy = Some(original_key.clone());
+    metadata.checksum = Some(checksum);
+    let metadata = match metadata.save(db_connection) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            discard();
+            // Lost a race with an identical database registered since the check above
+            if is_duplicate_checksum(&e) {
+                anyhow::bail!("An identical database has already been uploaded");
+            }
+            return Err(e);
+        }
+    };
+
+    ImportJob::complete(db_connection, job_id, metadata.id.unwrap_or_default())
+}
+
+pub async fn get_import_status(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    match ImportJob::find_by_id(&db_connection, id) {
+        Ok(Some(job)) => Ok(Json(json!({ "job": job }))),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Import job not found" }))
+        ).into()),
+        Err(e) => Err(map_db_error(e, "Failed to find import job")),
+    }
+}
+
+// Start a background export of a read-only query's results to a CSV or NDJSON file.
+// Progress is reported through the job; the artifact is served once it completes.
+pub async fn start_export(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+    let format: ExportFormat = match payload.get("format") {
+        None | Some(Value::Null) => ExportFormat::Csv,
+        Some(Value::String(name)) if UNSUPPORTED_EXPORT_FORMATS.contains(&name.as_str()) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!("{} exports are not supported; use csv or ndjson", name),
+                "code": "UNSUPPORTED_EXPORT_FORMAT",
+                "format": name
+            }))
+        ).into()),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|_| ApiError::from((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "format must be one of csv, ndjson" }))
+        )))?,
+    };
+    check_blocklist(&db_connection, &sql)?;
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+
+    // Surface SQL errors now rather than as a failed job
+    {
+        let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+        let stmt = prepare_for_caller(&conn, &sql, &caller, StatusCode::BAD_REQUEST)?;
+        if !stmt.readonly() || stmt.column_count() == 0 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Exports require a read-only query that returns rows" }))
+            ).into());
+        }
+    }
+
+    // The job keeps the caller's column policy, as of when it was started
+    let hidden = caller.hidden_columns(&metadata)?;
+    let job = ExportJob::create(&db_connection, id, format.as_str())
+        .map_err(|e| map_db_error(e, "Failed to create export job"))?;
+
+    let job_id = job.id;
+    let connection = db_connection.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = run_export(&connection, job_id, &metadata, hidden, &sql, params, format) {
+            error!("Export job {} failed: {}", job_id, e);
+            if let Err(e) = ExportJob::fail(&connection, job_id, &e.to_string()) {
+                error!("Failed to record export job {} failure: {}", job_id, e);
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "job": job }))))
+}
+
+// Background body of an export job: write to a local staging file, then hand it
+// to storage. Staging is removed if the export fails or is cancelled.
+fn run_export(
+    db_connection: &DbConnection,
+    job_id: i64,
+    metadata: &DatabaseMetadata,
+    hidden: Option<HiddenColumns>,
+    sql: &str,
+    params: QueryParams,
+    format: ExportFormat,
+) -> anyhow::Result<()> {
+    let key = format!("exports/{}.{}", job_id, format.as_str());
+    let staging = db_connection.get_storage_path(format!("staging/export-{}.{}", job_id, format.as_str()));
+
+    let conn = PolicyConnection::install(db_connection.get_database_pool(&metadata.path).get()?, hidden)?;
+    let _registered = db_connection.query_registry().register(metadata.id.unwrap_or_default(), sql, conn.get_interrupt_handle());
+    let mut stmt = conn.prepare_checked(sql)?;
+    let params = params.resolve(&stmt).map_err(|ApiError(_, Json(body))| anyhow::anyhow!("{}", body["error"]))?;
+
+    let file = std::io::BufWriter::new(std::fs::File::create(&staging)?);
+    let written = export::write_export(&mut stmt, params, format, file, |rows, bytes| {
+        ExportJob::update_progress(db_connection, job_id, rows as i64, bytes as i64).map_err(|e| e.to_string())
+    });
+    let (rows, bytes) = match written {
+        Ok(counts) => counts,
+        Err(ExportError::Cancelled) => {
+            std::fs::remove_file(&staging).ok();
+            return Ok(());
+        }
+        Err(e) => {
+            std::fs::remove_file(&staging).ok();
+            return Err(e.into());
+        }
+    };
+
+    let data = std::fs::read(&staging);
+    std::fs::remove_file(&staging).ok();
+    db_connection.storage().put(&key, &data?)?;
+    let artifact = match db_connection.storage().local_path(&key) {
+        Ok(path) => path,
+        Err(e) => {
+            db_connection.storage().delete(&key).ok();
+            return Err(e.into());
+        }
+    };
+    if !ExportJob::complete(db_connection, job_id, rows as i64, bytes as i64, &artifact.to_string_lossy())? {
+        // Cancelled (or its database deleted) after the last progress check
+        db_connection.storage().delete(&key).ok();
+    }
+    Ok(())
+}
+
+// Delete a finished export's artifact from storage
+fn remove_export_artifact(db_connection: &DbConnection, path: &str) {
+    let Some(key) = db_connection.storage_key_for(path) else { return };
+    if let Err(e) = db_connection.storage().delete(&key) {
+        error!("Failed to delete export file: {}", e);
+    }
+}
+
+fn find_export_job(db_connection: &DbConnection, id: i64) -> Result<ExportJob, ApiError> {
+    match ExportJob::find_by_id(db_connection, id) {
+        Ok(Some(job)) => Ok(job),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Export job not found" }))
+        ).into()),
+        Err(e) => Err(map_db_error(e, "Failed to find export job")),
+    }
+}
+
+fn export_job_json(job: &ExportJob) -> Value {
+    let mut body = json!({ "job": job });
+    if job.status == export_job::STATUS_COMPLETED {
+        body["download_url"] = json!(format!("/exports/{}/download", job.id));
+    }
+    body
+}
+
+pub async fn get_export_status(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let job = find_export_job(&db_connection, id)?;
+    Ok(Json(export_job_json(&job)))
+}
+
+pub async fn cancel_export(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    find_export_job(&db_connection, id)?;
+    let cancelled = ExportJob::cancel(&db_connection, id)
+        .map_err(|e| map_db_error(e, "Failed to cancel export job"))?;
+    let job = find_export_job(&db_connection, id)?;
+    if !cancelled {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": format!("Export job already {}", job.status), "job": job }))
+        ).into());
+    }
+    Ok(Json(export_job_json(&job)))
+}
+
+pub async fn download_export(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let job = find_export_job(&db_connection, id)?;
+    let Some(path) = job.file_path.as_ref().filter(|_| job.status == export_job::STATUS_COMPLETED) else {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": format!("Export job is {}", job.status), "job": job }))
+        ).into());
+    };
+    let format: ExportFormat = serde_json::from_value(json!(job.format))
+        .map_err(|e| handle_error(e, "Unknown export format"))?;
+
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| handle_error(e, "Failed to open export file"))?;
+    let body = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"export-{}.{}\"", job.id, job.format)),
+        ],
+        body,
+    ).into_response())
+}
+
+// Helper function to size-check, write, validate and register a new database file
+async fn store_database(
+    db_connection: &DbConnection,
+    filename: String,
+    file_data: Vec<u8>,
+    notes: String,
+    convert_to_wal: bool,
+) -> ApiResult {
+    let total_size = file_data.len();
+    check_file_size(db_connection, total_size)?;
+
+    // The same bytes uploaded twice point at the first copy instead of storing another.
+    // Uploads that race past this check are settled by the unique checksum index.
+    let checksum = sha256_hex(&file_data);
+    match DatabaseMetadata::find_by_checksum(db_connection, &checksum) {
+        Ok(Some(existing)) => return Err(duplicate_upload(existing.id)),
+        Ok(None) => {}
+        Err(e) => return Err(map_db_error(e, "Failed to check for duplicate database")),
+    }
+
+    // Generate unique filename and storage key
+    let timestamp = chrono::Utc::now().timestamp();
+    let key = format!("databases/{}-{}", timestamp, filename);
+    let original_key = format!("originals/{}-{}", timestamp, filename);
+
+    // Write file through the configured storage backend, then resolve the local
+    // file SQLite will open. An untouched copy is kept so the database can be reset.
+    let storage = db_connection.storage().clone();
+    let stored = tokio::task::spawn_blocking({
+        let key = key.clone();
+        let original_key = original_key.clone();
+        move || {
+            storage.put(&key, &file_data)
+                .and_then(|_| storage.put(&original_key, &file_data))
+                .and_then(|_| storage.local_path(&key))
+        }
+    })
+    .await
+    .map_err(|e| handle_error(e, "Failed to save file"))?;
+    let storage_path = match stored {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to write file: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to save file" }))
+            ).into());
+        }
+    };
+
+    // Validate SQLite database and count tables
+    let table_count = match validate_sqlite_db(&storage_path) {
+        Ok(count) => count,
+        Err(e) => {
+            db_connection.storage().delete(&key).ok();
+            db_connection.storage().delete(&original_key).ok();
+            return Err(e);
+        }
+    };
+
+    // Refuse files SQLite can open but reports damage in
+    let integrity = match run_integrity_check(storage_path.clone()).await {
+        Ok(report) if report.ok => report,
+        result => {
+            db_connection.storage().delete(&key).ok();
+            db_connection.storage().delete(&original_key).ok();
+            return Err(match result {
+                Ok(report) => ApiError(
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "Database failed its integrity check", "integrity": report }))
+                ),
+                Err(e) => e,
+            });
+        }
+    };
+
+    // Opt-in: this rewrites the stored file, so the original mode is kept in metadata
+    let original_journal_mode = if convert_to_wal {
+        match journal::convert_to_wal(&storage_path) {
+            Ok(mode) => Some(mode),
+            Err(e) => {
+                db_connection.storage().delete(&key).ok();
+                db_connection.storage().delete(&original_key).ok();
+                return Err(map_db_error(e, "Failed to switch database to WAL mode"));
+            }
+        }
+    } else {
+        None
+    };
+
+    // A new file may have landed where a cached pool still points at an old one
+    db_connection.evict_database_pool(&storage_path);
+
+    // Create metadata
+    let mut metadata = DatabaseMetadata::new(
+        filename,
+        storage_path.to_string_lossy().into_owned(),
+        total_size as i64,
+        table_count,
+        false,
+        Some(notes),
+    );
+    metadata.original_journal_mode = original_journal_mode;
+    metadata.original_copy = Some(original_key.clone());
+    metadata.integrity = Some("ok".to_string());
+    metadata.checksum = Some(checksum.clone());
+
+    match metadata.save(db_connection) {
+        Ok(database) => Ok(Json(json!({ "database": database, "integrity": integrity }))),
+        // Lost a race with an identical upload that passed the check above
+        Err(e) if is_duplicate_checksum(&e) => {
+            db_connection.storage().delete(&key).ok();
+            db_connection.storage().delete(&original_key).ok();
+            let existing = DatabaseMetadata::find_by_checksum(db_connection, &checksum)
+                .map_err(|e| map_db_error(e, "Failed to check for duplicate database"))?;
+            Err(duplicate_upload(existing.and_then(|m| m.id)))
+        }
+        Err(e) => Err(map_db_error(e, "Failed to save database metadata")),
+    }
+}
+
+fn duplicate_upload(existing_id: Option<i64>) -> ApiError {
+    ApiError(
+        StatusCode::CONFLICT,
+        Json(json!({
+            "error": "An identical database has already been uploaded",
+            "existing_id": existing_id
+        }))
+    )
+}
+
+// Run SQLite's consistency checks over a database file, off the async runtime
+// since they read every page
+async fn run_integrity_check(path: std::path::PathBuf) -> Result<IntegrityReport, ApiError> {
+    tokio::task::spawn_blocking(move || {
+        let conn = rusqlite::Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        integrity::check(&conn)
+    })
+    .await
+    .map_err(|e| handle_error(e, "Integrity check task failed"))?
+    .map_err(|e| map_db_error(e, "Failed to run integrity check"))
+}
+
+// Declared content types that say nothing about the payload
+fn is_generic_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    essence.is_empty()
+        || essence.eq_ignore_ascii_case("application/octet-stream")
+        || essence.eq_ignore_ascii_case("binary/octet-stream")
+}
+
+// Whether an upload should go on to the magic-byte check: declared as SQLite,
+// or generically typed but named with one of the configured SQLite extensions
+fn is_sqlite_candidate(db_connection: &DbConnection, filename: &str, content_type: &str) -> bool {
+    if SQLITE_CONTENT_TYPES.iter().any(|t| content_type.starts_with(t)) {
+        return true;
+    }
+    is_generic_content_type(content_type) && db_connection.has_sqlite_extension(filename)
+}
+
+// Helper function to read a boolean-style flag header ("1", "true", "*")
+fn is_header_flag_set(headers: &HeaderMap, name: &str) -> bool {
+    headers.get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "*"))
+        .unwrap_or(false)
+}
+
+// Decompress a gzip upload. Reads at most one byte past the size limit, so an
+// oversized archive is rejected by the size check without inflating all of it.
+async fn gunzip_upload(db_connection: &DbConnection, data: Vec<u8>) -> Result<Vec<u8>, ApiError> {
+    use std::io::Read;
+
+    let limit = db_connection.config().max_file_size as u64 + 1;
+    let inflated = tokio::task::spawn_blocking(move || {
+        let mut out = Vec::new();
+        flate2::read::GzDecoder::new(data.as_slice()).take(limit).read_to_end(&mut out).map(|_| out)
+    })
+    .await
+    .map_err(|e| handle_error(e, "Failed to decompress upload"))?;
+
+    inflated.map_err(|e| ApiError(
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": format!("Failed to decompress gzip upload: {}", e) }))
+    ))
+}
+
+// Helper function to process multipart form data. The flag is set when the part
+// declares `Content-Encoding: gzip`.
+async fn process_multipart(multipart: &mut Multipart) -> Result<(String, String, Vec<u8>, bool), ApiError> {
+    let field = match multipart.next_field().await {
+        Ok(Some(field)) => field,
+        Ok(None) => {
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "No file provided" }))
+            ));
+        }
+        Err(e) => {
+            error!("Failed to process multipart form: {}", e);
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Failed to process upload" }))
+            ));
+        }
+    };
+
+    if field.name() != Some("file") {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "No file provided" }))
+        ));
+    }
+
+    let filename = field.file_name().unwrap_or("unknown.db").to_string();
     let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+    let gzip_encoded = field.headers().get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("gzip"));
     
     let data = match field.bytes().await {
         Ok(data) => data,
@@ -219,7 +1516,66 @@ async fn process_multipart(multipart: &mut Multipart) -> Result<(String, String,
         }
     };
     
-    Ok((filename, content_type, data.to_vec()))
+    Ok((filename, content_type, data.to_vec(), gzip_encoded))
+}
+
+// Reject files that don't start with the SQLite header before anything is written.
+// Some garbage files open cleanly and report no tables, so the later structure
+// check alone doesn't catch them.
+fn check_sqlite_magic(data: &[u8]) -> Result<(), ApiError> {
+    if data.starts_with(SQLITE_MAGIC) {
+        return Ok(());
+    }
+    Err(ApiError(
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": "Not a valid SQLite database file" }))
+    ))
+}
+
+// Reject database files outside the configured size bounds
+fn check_file_size(db_connection: &DbConnection, size: usize) -> Result<(), ApiError> {
+    let config = db_connection.config();
+    if size > config.max_file_size {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("File too large. Maximum size is {}MB", config.max_file_size / 1024 / 1024) }))
+        ).into());
+    }
+    if size < config.min_file_size {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("File too small. Minimum size is {}KB", config.min_file_size / 1024) }))
+        ).into());
+    }
+    Ok(())
+}
+
+// Tables an import would bring in, with column types and row counts, read
+// without modifying the file
+fn describe_import_tables(path: &std::path::Path) -> Result<Vec<Value>, ApiError> {
+    let invalid = |_| ApiError(
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": "Failed to read database structure" }))
+    );
+    let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(invalid)?;
+    let tables: Vec<String> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(invalid)?;
+
+    tables.into_iter()
+        .map(|table| {
+            let columns = read_table_schema(&conn, &table)?
+                .into_iter()
+                .map(|column| json!({ "name": column["name"], "type": column["type"] }))
+                .collect::<Vec<_>>();
+            let row_count: i64 = conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", quote_identifier(&table)), [], |row| row.get(0))
+                .map_err(invalid)?;
+            Ok(json!({ "name": table, "columns": columns, "row_count": row_count }))
+        })
+        .collect()
 }
 
 // Helper function to validate SQLite database and count tables
@@ -246,73 +1602,2289 @@ fn validate_sqlite_db(path: &std::path::Path) -> Result<i32, ApiError> {
     Ok(table_count)
 }
 
-pub async fn get_tables(
+pub async fn get_tables(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = match DatabaseMetadata::find_by_id(&db_connection, id) {
+        Ok(Some(m)) => m,
+        Ok(None) => return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Database not found" }))
+        ).into()),
+        Err(e) => return Err(map_db_error(e, "Failed to find database")),
+    };
+
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table'")
+        .map_err(|e| map_db_error(e, "Failed to read database structure"))?;
+
+    let tables: Result<Vec<String>, _> = stmt.query_map([], |row| row.get(0))
+        .map_err(|e| map_db_error(e, "Failed to read tables"))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| map_db_error(e, "Failed to collect tables"));
+
+    Ok(Json(json!({ "tables": tables? })))
+}
+
+// Re-run the integrity checks on demand and record the outcome as the
+// database's integrity status
+pub async fn check_integrity(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let mut metadata = find_database(&db_connection, id)?;
+    let report = run_integrity_check(metadata.path.clone().into()).await?;
+
+    let status = if report.ok { "ok" } else { "corrupt" };
+    if metadata.integrity.as_deref() != Some(status) {
+        metadata.integrity = Some(status.to_string());
+        metadata.save(&db_connection).map_err(|e| map_db_error(e, "Failed to record integrity status"))?;
+    }
+
+    Ok(Json(json!({ "database_id": id, "integrity": report })))
+}
+
+// Views and their CREATE VIEW statements; get_tables lists only tables
+pub async fn get_views(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master WHERE type = 'view' ORDER BY name")
+        .map_err(|e| map_db_error(e, "Failed to read database structure"))?;
+    let views: Vec<Value> = stmt.query_map([], |row| {
+        Ok(json!({
+            "name": row.get::<_, String>(0)?,
+            "sql": row.get::<_, Option<String>>(1)?,
+        }))
+    })
+    .map_err(|e| map_db_error(e, "Failed to read views"))?
+    .collect::<Result<_, _>>()
+    .map_err(|e| map_db_error(e, "Failed to collect views"))?;
+
+    Ok(Json(json!({ "views": views })))
+}
+
+pub async fn get_table_schema(
+    State(db_connection): State<DbConnection>,
+    Path((id, table)): Path<(i64, String)>,
+) -> ApiResult {
+    validate_table_name(&table)?;
+
+    let metadata = match DatabaseMetadata::find_by_id(&db_connection, id) {
+        Ok(Some(m)) => m,
+        Ok(None) => return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Database not found" }))
+        ).into()),
+        Err(e) => return Err(map_db_error(e, "Failed to find database")),
+    };
+
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let schema = read_table_schema(&conn, &table)?;
+    Ok(Json(json!({ "schema": schema })))
+}
+
+// A table's foreign keys, one object per constraint, with each column pair's
+// position in the key as `seq`
+pub async fn get_table_foreign_keys(
+    State(db_connection): State<DbConnection>,
+    Path((id, table)): Path<(i64, String)>,
+) -> ApiResult {
+    validate_table_name(&table)?;
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    if !table_exists(&conn, &table)? {
+        return Err(table_not_found(&table));
+    }
+
+    let foreign_keys: Vec<Value> = describe::describe_foreign_keys(&conn, &table)
+        .map_err(|e| map_db_error(e, "Failed to read foreign keys"))?
+        .into_iter()
+        .map(|fk| {
+            let columns: Vec<Value> = fk.columns.iter()
+                .zip(&fk.references_columns)
+                .enumerate()
+                .map(|(seq, (from, to))| json!({ "seq": seq, "from": from, "to": to }))
+                .collect();
+            json!({
+                "id": fk.id,
+                "table": fk.references_table,
+                "columns": columns,
+                "on_update": fk.on_update,
+                "on_delete": fk.on_delete,
+                "match": fk.match_type,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "table": table, "foreign_keys": foreign_keys })))
+}
+
+// Whether a table (or view) by exactly this name exists, matched as a value
+// rather than parsed as SQL
+fn table_exists(conn: &rusqlite::Connection, table: &str) -> Result<bool, ApiError> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?)",
+        [table],
+        |row| row.get(0),
+    )
+    .map_err(|e| map_db_error(e, "Failed to look up table"))
+}
+
+// Column rows from PRAGMA table_info as JSON, or TABLE_NOT_FOUND
+fn read_table_schema(conn: &rusqlite::Connection, table: &str) -> Result<Vec<Value>, ApiError> {
+    if !table_exists(conn, table)? {
+        return Err(table_not_found(table));
+    }
+
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote_identifier(table)))
+        .map_err(|e| map_db_error(e, "Failed to read table schema"))?;
+
+    let schema: Vec<Value> = stmt.query_map([], |row| -> rusqlite::Result<Value> {
+        Ok(json!({
+            "cid": row.get::<_, i64>(0)?,
+            "name": row.get::<_, String>(1)?,
+            "type": row.get::<_, String>(2)?,
+            "notnull": row.get::<_, bool>(3)?,
+            "dflt_value": row.get::<_, Option<String>>(4)?,
+            "pk": row.get::<_, bool>(5)?
+        }))
+    })
+    .map_err(|e| map_db_error(e, "Failed to read schema"))?
+    .collect::<Result<_, _>>()
+    .map_err(|e| map_db_error(e, "Failed to collect schema"))?;
+
+    Ok(schema)
+}
+
+// Rename a column in place with ALTER TABLE ... RENAME COLUMN, returning the new schema
+pub async fn rename_column(
+    State(db_connection): State<DbConnection>,
+    Path((id, table, column)): Path<(i64, String, String)>,
+    caller: Caller,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    validate_table_name(&table)?;
+    let new_name = match payload.get("new_name").and_then(|v| v.as_str()) {
+        Some(name) => name.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "new_name is required" }))
+        ).into()),
+    };
+    if !is_valid_identifier(&column) || !is_valid_identifier(&new_name) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Invalid column name" }))
+        ).into());
+    }
+
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+
+    // SQLite column names are case-insensitive, so "Name" collides with "name"
+    let schema = read_table_schema(&conn, &table)?;
+    let existing: Vec<&str> = schema.iter().filter_map(|c| c["name"].as_str()).collect();
+    if !existing.iter().any(|name| name.eq_ignore_ascii_case(&column)) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": { "code": "COLUMN_NOT_FOUND", "table": table, "column": column } }))
+        ).into());
+    }
+    if existing.iter().any(|name| name.eq_ignore_ascii_case(&new_name) && !name.eq_ignore_ascii_case(&column)) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": { "code": "COLUMN_EXISTS", "table": table, "column": new_name } }))
+        ).into());
+    }
+
+    // Fails if a view or trigger can't be rewritten to the new name, and for
+    // callers with hidden columns, who may not change the schema
+    let sql = format!(
+        "ALTER TABLE {} RENAME COLUMN {} TO {}",
+        quote_identifier(&table),
+        quote_identifier(&column),
+        quote_identifier(&new_name)
+    );
+    prepare_for_caller(&conn, &sql, &caller, StatusCode::UNPROCESSABLE_ENTITY)?
+        .execute([])
+        .map_err(|e| map_prepare_error(e, StatusCode::UNPROCESSABLE_ENTITY))?;
+
+    let schema = read_table_schema(&conn, &table)?;
+    Ok(Json(json!({ "schema": schema })))
+}
+
+// Type, key, default and cardinality hints for one column
+pub async fn get_column_meta(
+    State(db_connection): State<DbConnection>,
+    Path((id, table, column)): Path<(i64, String, String)>,
+    caller: Caller,
+) -> ApiResult {
+    validate_table_name(&table)?;
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+
+    match column_meta::column_meta(&conn, &table, &column) {
+        Ok(meta) => Ok(Json(json!({ "column": meta }))),
+        Err(ColumnMetaError::TableNotFound(table)) => Err(table_not_found(&table)),
+        Err(ColumnMetaError::ColumnNotFound(column)) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": { "code": "COLUMN_NOT_FOUND", "table": table, "column": column } }))
+        ).into()),
+        Err(ColumnMetaError::Sqlite(e)) => Err(map_db_error(e, "Failed to read column metadata")),
+    }
+}
+
+// Scaffold an INSERT statement for a table: a typed template and, when the table
+// has rows, the same statement filled with its first one
+pub async fn get_sample_insert(
+    State(db_connection): State<DbConnection>,
+    Path((id, table)): Path<(i64, String)>,
+    caller: Caller,
+) -> ApiResult {
+    validate_table_name(&table)?;
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+
+    match sample_insert::sample_insert(&conn, &table) {
+        Ok(Some(insert)) => Ok(Json(json!(insert))),
+        Ok(None) => Err(table_not_found(&table)),
+        Err(e) => Err(map_db_error(e, "Failed to generate INSERT statement")),
+    }
+}
+
+// Check a column expression or WHERE clause against a table without running it,
+// so query builders can validate fragments before assembling a query
+pub async fn validate_expression(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let bad_request = |error: &str| ApiError::from((StatusCode::BAD_REQUEST, Json(json!({ "error": error }))));
+    let expression = payload.get("expression").and_then(|v| v.as_str())
+        .filter(|e| !e.trim().is_empty())
+        .ok_or_else(|| bad_request("expression is required"))?;
+    let table = payload.get("table").and_then(|v| v.as_str())
+        .ok_or_else(|| bad_request("table is required"))?;
+    validate_table_name(table)?;
+    let kind: ExpressionKind = match payload.get("kind") {
+        None | Some(Value::Null) => ExpressionKind::default(),
+        Some(v) => serde_json::from_value(v.clone())
+            .map_err(|_| bad_request("kind must be one of select, where"))?,
+    };
+    check_blocklist(&db_connection, expression)?;
+
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    match expression::check_expression(&conn, table, expression, kind) {
+        Ok(check) => Ok(Json(json!(check))),
+        Err(ExpressionError::TableNotFound(table)) => Err(table_not_found(&table)),
+        Err(ExpressionError::Sqlite(e)) => Err(map_db_error(e, "Failed to validate expression")),
+    }
+}
+
+// Every table's columns, primary key and foreign keys, plus a hash of the whole
+// schema for spotting structural changes
+pub async fn describe_database(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let description = describe::describe_schema(&conn)
+        .map_err(|e| map_db_error(e, "Failed to read database structure"))?;
+    Ok(Json(json!(description)))
+}
+
+// Every table as CSV in one tar, streamed as it's generated
+pub async fn export_csv_bundle(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+) -> Result<Response, ApiError> {
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+
+    let body = axum::body::Body::from_stream(csv_bundle::stream(conn));
+    Ok((
+        [
+            (header::CONTENT_TYPE, csv_bundle::TAR_CONTENT_TYPE.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"database-{}-csv.tar\"", id)),
+        ],
+        body,
+    ).into_response())
+}
+
+// Tables without a primary key, whose rows can only be edited by rowid
+pub async fn get_tables_without_primary_key(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let tables = quality::tables_without_primary_key(&conn)
+        .map_err(|e| map_db_error(e, "Failed to inspect tables"))?;
+    Ok(Json(json!({ "tables": tables })))
+}
+
+pub async fn get_index_report(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+
+    // Cardinality sampling reads table data, so keep it off the async workers
+    tokio::task::spawn_blocking(move || {
+        let pool = db_connection.get_database_pool(&metadata.path);
+        let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+        let report = quality::index_report(&conn)
+            .map_err(|e| map_db_error(e, "Failed to inspect indexes"))?;
+        Ok(Json(json!(report)))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Index report task failed"))?
+}
+
+pub async fn get_fingerprint(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+
+    // Hashing reads the whole file on a cache miss
+    tokio::task::spawn_blocking(move || {
+        let fingerprint = db_connection.fingerprints().fingerprint(&metadata.path)
+            .map_err(|e| handle_error(e, "Failed to fingerprint database"))?;
+        let mut body = json!(fingerprint);
+        body["database_id"] = json!(id);
+        Ok(Json(body))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Fingerprint task failed"))?
+}
+
+// Generate (not serve) a GraphQL SDL document describing the database's tables
+pub async fn get_graphql_sdl(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let sdl = graphql::generate_sdl(&conn)
+        .map_err(|e| map_db_error(e, "Failed to read database structure"))?;
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], sdl).into_response())
+}
+
+// Issue a short-lived signed link that downloads the database file without
+// any other credentials, e.g. to hand to a browser
+pub async fn create_download_link(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    ensure_whole_file_visible(&caller, &metadata)?;
+
+    let ttl = chrono::Duration::from_std(db_connection.config().download_link_ttl)
+        .map_err(|e| handle_error(e, "Invalid download link lifetime"))?;
+    let expires_at = chrono::Utc::now() + ttl;
+    let token = signed_link::sign(db_connection.download_key(), id, expires_at.timestamp());
+
+    Ok(Json(json!({
+        "url": format!("/download/{}", token),
+        "token": token,
+        "expires_at": expires_at.to_rfc3339()
+    })))
+}
+
+// Serve a database file for a token issued by `create_download_link`
+pub async fn download_with_token(
+    State(db_connection): State<DbConnection>,
+    Path(token): Path<String>,
+) -> Result<Response, ApiError> {
+    let id = signed_link::verify(db_connection.download_key(), &token, chrono::Utc::now().timestamp())
+        .map_err(|e| -> ApiError {
+            let status = match e {
+                LinkError::Expired => StatusCode::GONE,
+                LinkError::Malformed | LinkError::BadSignature => StatusCode::FORBIDDEN,
+            };
+            (status, Json(json!({ "error": e.to_string() }))).into()
+        })?;
+    let metadata = find_database(&db_connection, id)?;
+    serve_database_file(&db_connection, &metadata).await
+}
+
+pub async fn download_database(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+) -> Result<Response, ApiError> {
+    let metadata = find_database(&db_connection, id)?;
+    ensure_whole_file_visible(&caller, &metadata)?;
+    serve_database_file(&db_connection, &metadata).await
+}
+
+// The file holds every column, so it only goes to callers the column policy
+// hides nothing from
+fn ensure_whole_file_visible(caller: &Caller, metadata: &DatabaseMetadata) -> Result<(), ApiError> {
+    match caller.hidden_columns(metadata)? {
+        None => Ok(()),
+        Some(_) => Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Database has columns hidden from this caller, so the file can't be downloaded",
+                "code": "COLUMN_FORBIDDEN",
+                "role": caller.role
+            }))
+        ).into()),
+    }
+}
+
+// Stream a database file as an attachment named after the database, without
+// reading it into memory
+async fn serve_database_file(db_connection: &DbConnection, metadata: &DatabaseMetadata) -> Result<Response, ApiError> {
+    let missing = || ApiError(
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "Database file is missing from storage" }))
+    );
+    let path = match db_connection.storage_key_for(&metadata.path).map(|key| db_connection.storage().local_path(&key)) {
+        Some(Ok(path)) => path,
+        Some(Err(storage::StorageError::NotFound(_))) | None => return Err(missing()),
+        Some(Err(e)) => return Err(handle_error(e, "Failed to open database file")),
+    };
+    let file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(missing()),
+        Err(e) => return Err(handle_error(e, "Failed to open database file")),
+    };
+    let size = file.metadata()
+        .await
+        .map_err(|e| handle_error(e, "Failed to open database file"))?
+        .len();
+    let body = axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, SQLITE_CONTENT_TYPES[0].to_string()),
+            (header::CONTENT_LENGTH, size.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", metadata.name.replace('"', ""))),
+        ],
+        body,
+    ).into_response())
+}
+
+// Compare supplied rows with a table by primary key without writing anything
+pub async fn preview_table_diff(
+    State(db_connection): State<DbConnection>,
+    Path((id, table)): Path<(i64, String)>,
+    caller: Caller,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    validate_table_name(&table)?;
+    let bad_request = |body: Value| -> ApiError { (StatusCode::BAD_REQUEST, Json(body)).into() };
+
+    let rows = match payload.get("rows") {
+        Some(Value::Array(rows)) => rows.iter()
+            .map(|row| row.as_object().cloned())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| bad_request(json!({ "error": "Each row must be an object" })))?,
+        _ => return Err(bad_request(json!({ "error": "rows must be an array" }))),
+    };
+
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+
+    let columns = diff::table_columns(&conn, &table)
+        .map_err(|e| map_db_error(e, "Failed to read table schema"))?
+        .ok_or_else(|| table_not_found(&table))?;
+
+    if columns.primary_key.is_empty() {
+        return Err(bad_request(json!({ "error": "Table has no primary key to match rows on" })));
+    }
+
+    diff::validate_rows(&columns, &rows).map_err(|e| match e {
+        diff::RowError::UnknownColumns(unknown) => bad_request(json!({
+            "error": "Rows contain columns not in the table",
+            "columns": unknown
+        })),
+        diff::RowError::MissingKey { row, columns } => bad_request(json!({
+            "error": format!("Row {} is missing primary key values", row),
+            "columns": columns
+        })),
+        diff::RowError::DuplicateKey { row } => bad_request(json!({
+            "error": format!("Row {} repeats the primary key of an earlier row", row)
+        })),
+        diff::RowError::InvalidValue { row, column, message } => bad_request(json!({
+            "error": format!("Row {} column {}: {}", row, column, message)
+        })),
+    })?;
+
+    let preview = diff::preview(&conn, &table, &columns, &rows)
+        .map_err(|e| map_db_error(e, "Failed to compare rows"))?;
+
+    Ok(Json(json!({
+        "table": table,
+        "primary_key": columns.primary_key,
+        "summary": {
+            "insert": preview.insert.len(),
+            "update": preview.update.len(),
+            "unchanged": preview.unchanged.len()
+        },
+        "insert": preview.insert,
+        "update": preview.update,
+        "unchanged": preview.unchanged
+    })))
+}
+
+// Query-string options accepted by execute_query
+#[derive(Debug, Default, Deserialize)]
+pub struct QueryOptions {
+    #[serde(default)]
+    pub describe: bool,
+    #[serde(default)]
+    pub null_as: query::NullRendering,
+    // Decimal places REAL values are rounded to; full precision when absent
+    pub float_precision: Option<u32>,
+    // Response encoding; defaults to what the Accept header asks for, then JSON
+    pub format: Option<ResponseFormat>,
+    // Overrides the configured INVALID_TEXT handling for this request
+    pub invalid_text: Option<query::InvalidTextRendering>,
+    // Refuse, before running it, a statement that has no result columns
+    #[serde(default)]
+    pub require_rows: bool,
+    // Prepend each row's `_rowid` when the statement reads rows of a single rowid table
+    #[serde(default)]
+    pub with_rowid: bool,
+    // Window of rows to return, from the body's `page` / `page_size`
+    #[serde(skip)]
+    pub page: Option<QueryPage>,
+    // Connection pragmas set for this request only, from the body's `pragmas`
+    #[serde(skip)]
+    pub pragmas: PragmaOverrides,
+    // Run on a writable connection, when the body sets `read_only` to false;
+    // every other query runs read-only
+    #[serde(skip)]
+    pub writable: bool,
+}
+
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+impl ResponseFormat {
+    // An explicit `?format=` wins over Accept
+    fn negotiate(requested: Option<Self>, headers: &HeaderMap) -> Self {
+        requested.unwrap_or_else(|| {
+            let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or("");
+            let wants_msgpack = accept.split(',')
+                .map(|media| media.split(';').next().unwrap_or("").trim())
+                .any(|media| media.eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+                    || media.eq_ignore_ascii_case("application/x-msgpack"));
+            if wants_msgpack { Self::Msgpack } else { Self::Json }
+        })
+    }
+
+    // Encode a response body, keeping the same structure as the JSON form
+    fn respond(self, body: Value) -> Result<Response, ApiError> {
+        match self {
+            Self::Json => Ok(Json(body).into_response()),
+            Self::Msgpack => {
+                let bytes = rmp_serde::to_vec_named(&body)
+                    .map_err(|e| handle_error(e, "Failed to encode MessagePack response"))?;
+                Ok(([(header::CONTENT_TYPE, MSGPACK_CONTENT_TYPE)], bytes).into_response())
+            }
+        }
+    }
+}
+
+// Query parameters as supplied by the client: positional values, or named
+// `bindings` that can only be placed once the statement is prepared
+pub enum QueryParams {
+    Positional(Vec<rusqlite::types::Value>),
+    Named(serde_json::Map<String, Value>),
+}
+
+impl QueryParams {
+    // Positional values for a prepared statement
+    fn resolve(self, stmt: &rusqlite::Statement<'_>) -> Result<Vec<rusqlite::types::Value>, ApiError> {
+        match self {
+            QueryParams::Positional(params) if params.len() != stmt.parameter_count() => Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": format!(
+                        "Query has {} placeholder(s) but {} params were supplied",
+                        stmt.parameter_count(), params.len()
+                    ),
+                    "expected": stmt.parameter_count(),
+                    "supplied": params.len()
+                }))
+            ).into()),
+            QueryParams::Positional(params) => Ok(params),
+            QueryParams::Named(bindings) => query::bind_named(stmt, &bindings).map_err(|e| match e {
+                query::BindingError::Unbound(unbound) => (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "Query has unbound placeholders", "unbound": unbound }))
+                ).into(),
+                query::BindingError::Invalid { name, message } => (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": format!("Binding {}: {}", name, message) }))
+                ).into(),
+            }),
+        }
+    }
+}
+
+// Parse either named `bindings` or the optional positional `params` array,
+// coercing positional values through `param_types` when given
+fn parse_query_params(payload: &Value) -> Result<QueryParams, ApiError> {
+    let bad_request = |msg: String| -> ApiError {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": msg }))).into()
+    };
+
+    match payload.get("bindings") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(_)) if payload.get("params").is_some_and(|p| !p.is_null()) => {
+            return Err(bad_request("Supply either params or bindings, not both".to_string()));
+        }
+        Some(Value::Object(bindings)) => return Ok(QueryParams::Named(bindings.clone())),
+        Some(_) => return Err(bad_request("bindings must be an object".to_string())),
+    }
+
+    positional_query_params(payload, bad_request).map(QueryParams::Positional)
+}
+
+fn positional_query_params(
+    payload: &Value,
+    bad_request: impl Fn(String) -> ApiError,
+) -> Result<Vec<rusqlite::types::Value>, ApiError> {
+    let params = match payload.get("params") {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(Value::Array(params)) => params,
+        Some(_) => return Err(bad_request("params must be an array".to_string())),
+    };
+
+    match payload.get("param_types") {
+        None | Some(Value::Null) => params.iter()
+            .enumerate()
+            .map(|(i, v)| query::json_to_sql(v).map_err(|e| bad_request(format!("Parameter {}: {}", i + 1, e))))
+            .collect(),
+        Some(Value::Array(types)) => {
+            if types.len() != params.len() {
+                return Err(bad_request(format!(
+                    "param_types has {} entries but {} params were supplied",
+                    types.len(), params.len()
+                )));
+            }
+            params.iter()
+                .zip(types)
+                .enumerate()
+                .map(|(i, (v, t))| {
+                    let declared = t.as_str()
+                        .ok_or_else(|| bad_request(format!("Parameter {}: type must be a string", i + 1)))?;
+                    query::coerce_param(v, declared)
+                        .map_err(|e| bad_request(format!("Parameter {}: {}", i + 1, e)))
+                })
+                .collect()
+        }
+        Some(_) => Err(bad_request("param_types must be an array".to_string())),
+    }
+}
+
+// Reject SQL matching one of the operator's QUERY_BLOCKLIST rules
+fn check_blocklist(db_connection: &DbConnection, sql: &str) -> Result<(), ApiError> {
+    match db_connection.query_blocklist().matched(sql) {
+        Some(rule) => Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": format!("Query blocked by rule '{}'", rule),
+                "rule": rule
+            }))
+        ).into()),
+        None => Ok(()),
+    }
+}
+
+// Reject the statement when the payload's optional `expect` names a different kind
+fn check_expected_statement(payload: &Value, sql: &str) -> Result<(), ApiError> {
+    let bad_request = |body: Value| -> ApiError { (StatusCode::BAD_REQUEST, Json(body)).into() };
+
+    let expected = match payload.get("expect") {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::String(expected)) => expected.to_ascii_lowercase(),
+        Some(_) => return Err(bad_request(json!({ "error": "expect must be a string" }))),
+    };
+
+    if !query::STATEMENT_KINDS.contains(&expected.as_str()) {
+        return Err(bad_request(json!({
+            "error": format!("Unknown statement type \"{}\"", expected),
+            "allowed": query::STATEMENT_KINDS
+        })));
+    }
+
+    let actual = query::statement_kind(sql);
+    if actual != Some(expected.as_str()) {
+        return Err(bad_request(json!({
+            "error": "Statement type does not match expect",
+            "expected": expected,
+            "actual": actual
+        })));
+    }
+
+    Ok(())
+}
+
+// `page` / `page_size` from a query body. Paging wraps the statement in an outer
+// LIMIT/OFFSET, so it only applies to SELECTs without a LIMIT of their own.
+fn parse_query_page(db_connection: &DbConnection, payload: &Value, sql: &str) -> Result<Option<QueryPage>, ApiError> {
+    let page = pagination::parse_query_page(
+        payload.get("page"),
+        payload.get("page_size"),
+        DEFAULT_QUERY_PAGE_SIZE.min(db_connection.config().max_query_page_size as i64),
+        db_connection.config().max_query_page_size as i64,
+    )
+    .map_err(invalid_pagination)?;
+
+    if page.is_some() {
+        let bad_request = |error: &str| ApiError::from((StatusCode::BAD_REQUEST, Json(json!({ "error": error }))));
+        if query::statement_kind(sql) != Some("select") {
+            return Err(bad_request("page and page_size only apply to SELECT statements"));
+        }
+        if query::has_top_level_limit(sql) {
+            return Err(bad_request(
+                "Query has its own LIMIT clause, which conflicts with page and page_size; remove one or the other"
+            ));
+        }
+    }
+
+    Ok(page)
+}
+
+// `sql` restricted to one page, and the query counting every row it would return.
+// The page's LIMIT and OFFSET are the last two parameters.
+fn paged_sql(sql: &str) -> (String, String) {
+    // A trailing comment would swallow the closing parenthesis, hence the newline
+    let inner = sql.trim_end().trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    (
+        format!("SELECT * FROM ({}\n) LIMIT ? OFFSET ?", inner),
+        format!("SELECT COUNT(*) FROM ({}\n)", inner),
+    )
+}
+
+// Whether a query body asks to run writable, with `"read_only": false`; writes
+// have to be asked for explicitly
+fn parse_writable(payload: &Value) -> Result<bool, ApiError> {
+    match payload.get("read_only") {
+        None | Some(Value::Null) => Ok(false),
+        Some(Value::Bool(read_only)) => Ok(!*read_only),
+        Some(_) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "read_only must be a boolean" }))
+        ).into()),
+    }
+}
+
+pub async fn execute_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Query(options): Query<QueryOptions>,
+    headers: HeaderMap,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> Result<Response, ApiError> {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s,
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+
+    check_blocklist(&db_connection, sql)?;
+    check_expected_statement(&payload, sql)?;
+    let params = parse_query_params(&payload)?;
+    let mut options = options;
+    options.page = parse_query_page(&db_connection, &payload, sql)?;
+    options.writable = parse_writable(&payload)?;
+    options.pragmas = PragmaOverrides::parse(payload.get("pragmas")).map_err(|e| {
+        let mut body = json!({ "error": e.to_string() });
+        if let PragmaError::NotAllowed(pragma) = &e {
+            body["code"] = json!("PRAGMA_NOT_ALLOWED");
+            body["pragma"] = json!(pragma);
+            body["allowed"] = json!(pragmas::ALLOWED_PRAGMAS);
+        }
+        ApiError(StatusCode::BAD_REQUEST, Json(body))
+    })?;
+    let format = ResponseFormat::negotiate(options.format, &headers);
+    if options.float_precision.is_some_and(|p| p > query::MAX_FLOAT_PRECISION) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("float_precision must be between 0 and {}", query::MAX_FLOAT_PRECISION) }))
+        ).into());
+    }
+    let prev_result_hash = match payload.get("prev_result_hash") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(hash)) => Some(hash.clone()),
+        Some(_) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "prev_result_hash must be a string" }))
+        ).into()),
+    };
+
+    let metadata = match DatabaseMetadata::find_by_id(&db_connection, id) {
+        Ok(Some(m)) => m,
+        Ok(None) => return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Database not found" }))
+        ).into()),
+        Err(e) => return Err(map_db_error(e, "Failed to find database")),
+    };
+
+    let sql = sql.to_string();
+
+    // Run on the blocking pool so long queries neither stall the runtime nor
+    // prevent an operator from interrupting them
+    let Json(body) = tokio::task::spawn_blocking(move || {
+        run_query(&db_connection, &metadata, &sql, params, &options, &caller, prev_result_hash.as_deref())
+    })
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))??;
+
+    // Rows read before a mid-query failure come back as 207 Multi-Status
+    let status = if body["partial"] == true { StatusCode::MULTI_STATUS } else { StatusCode::OK };
+    let mut response = format.respond(body)?;
+    *response.status_mut() = status;
+    Ok(response)
+}
+
+// Who is making a request. The client id only attributes audit entries and is
+// taken from its header as given; the role is the one a bearer token from
+// ROLE_TOKENS authenticates, and decides which columns the caller may read.
+#[derive(Debug, Default, Clone)]
+pub struct Caller {
+    client_id: Option<String>,
+    role: Option<String>,
+}
+
+#[axum::async_trait]
+impl FromRequestParts<DbConnection> for Caller {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        db_connection: &DbConnection,
+    ) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+        let role = header(header::AUTHORIZATION.as_str())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .and_then(|token| db_connection.role_for_token(token))
+            .map(String::from);
+        Ok(Self { client_id: header(CLIENT_ID_HEADER).map(String::from), role })
+    }
+}
+
+impl Caller {
+    // Columns of the database this caller may not read, if any
+    fn hidden_columns(&self, metadata: &DatabaseMetadata) -> Result<Option<HiddenColumns>, ApiError> {
+        Ok(column_policy(metadata)?.and_then(|policy| policy.hidden_for(self.role.as_deref())))
+    }
+}
+
+fn column_policy(metadata: &DatabaseMetadata) -> Result<Option<ColumnPolicy>, ApiError> {
+    metadata.properties.get(COLUMN_POLICY_PROPERTY)
+        .map(|json| ColumnPolicy::parse(json))
+        .transpose()
+        .map_err(|e| handle_error(e, "Invalid column policy"))
+}
+
+type CallerConnection = PolicyConnection<r2d2::PooledConnection<DatabaseConnectionManager>>;
+
+// Check out a connection to a stored database with the caller's column policy in
+// force for as long as it's held. Anything that reads rows for a caller goes
+// through here, so no endpoint can reach columns hidden from them.
+fn open_for_caller(
+    db_connection: &DbConnection,
+    metadata: &DatabaseMetadata,
+    caller: &Caller,
+    read_only: bool,
+) -> Result<CallerConnection, ApiError> {
+    let pool = if read_only {
+        db_connection.get_read_only_database_pool(&metadata.path)
+    } else {
+        db_connection.get_database_pool(&metadata.path)
+    };
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+    PolicyConnection::install(conn, caller.hidden_columns(metadata)?)
+        .map_err(|e| map_db_error(e, "Failed to apply column policy"))
+}
+
+// Prepare `sql` for the caller, reporting a statement that touches a hidden column as a 403
+fn prepare_for_caller<'c>(
+    conn: &'c CallerConnection,
+    sql: &str,
+    caller: &Caller,
+    status: StatusCode,
+) -> Result<rusqlite::Statement<'c>, ApiError> {
+    conn.prepare_checked(sql).map_err(|e| match e {
+        PolicyError::Forbidden => column_forbidden(caller.role.as_deref()),
+        PolicyError::Sqlite(e) => map_prepare_error(e, status),
+    })
+}
+
+fn run_query(
+    db_connection: &DbConnection,
+    metadata: &DatabaseMetadata,
+    sql: &str,
+    params: QueryParams,
+    options: &QueryOptions,
+    caller: &Caller,
+    prev_result_hash: Option<&str>,
+) -> ApiResult {
+    let id = metadata.id.unwrap_or_default();
+    let conn = open_for_caller(db_connection, metadata, caller, !options.writable)?;
+    let _registered = db_connection.query_registry().register(id, sql, conn.get_interrupt_handle());
+    let guard = install_statement_guard(db_connection, &conn)?;
+    let started = std::time::Instant::now();
+
+    let _pragmas = (!options.pragmas.is_empty())
+        .then(|| PragmaGuard::apply(&conn, &options.pragmas))
+        .transpose()
+        .map_err(|e| map_execution_error(e, "Failed to apply pragmas"))?;
+
+    // The `_rowid` variant is used only if it prepares (for this caller's policy
+    // too); anything it can't be added to runs as written
+    let mut rowid_included = false;
+    let mut prepared = None;
+    if let Some(rowid_sql) = options.with_rowid.then(|| query::with_rowid_column(sql)).flatten() {
+        let paged = options.page.map(|page| (page, paged_sql(&rowid_sql)));
+        let prepared_sql = paged.as_ref().map_or(rowid_sql.as_str(), |(_, (rows_sql, _))| rows_sql.as_str());
+        if let Ok(statement) = prepare_for_caller(&conn, prepared_sql, caller, StatusCode::INTERNAL_SERVER_ERROR) {
+            rowid_included = true;
+            prepared = Some((paged, statement));
+        }
+    }
+    let (paged, mut stmt) = match prepared {
+        Some(prepared) => prepared,
+        None => {
+            let paged = options.page.map(|page| (page, paged_sql(sql)));
+            let prepared_sql = paged.as_ref().map_or(sql, |(_, (rows_sql, _))| rows_sql.as_str());
+            let statement = prepare_for_caller(&conn, prepared_sql, caller, StatusCode::INTERNAL_SERVER_ERROR)?;
+            (paged, statement)
+        }
+    };
+
+    if let Some(max_columns) = db_connection.max_result_columns() {
+        if stmt.column_count() > max_columns {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": format!(
+                        "Query returns {} columns, more than the limit of {}; select the columns you need explicitly",
+                        stmt.column_count(),
+                        max_columns
+                    ),
+                    "code": "TOO_MANY_COLUMNS",
+                    "max_columns": max_columns
+                }))
+            ).into());
+        }
+    }
+
+    // DDL, and DML without RETURNING, produce no result set at all, as opposed to
+    // a query that happens to match nothing
+    let returns_rows = stmt.column_count() > 0;
+    if options.require_rows && !returns_rows {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+                "error": "Statement returns no result columns",
+                "code": "NO_RESULT_COLUMNS",
+                "statement": query::statement_kind(sql)
+            }))
+        ).into());
+    }
+
+    // Paged, the caller's parameters are resolved against the count query, which
+    // has exactly the placeholders of their SQL
+    let mut total_count = None;
+    let params = match &paged {
+        Some((page, (_, count_sql))) => {
+            let mut count_stmt = conn.prepare(count_sql)
+                .map_err(|e| map_prepare_error(e, StatusCode::INTERNAL_SERVER_ERROR))?;
+            let mut params = params.resolve(&count_stmt)?;
+            let count: i64 = count_stmt.query_row(params_from_iter(params.iter()), |row| row.get(0))
+                .map_err(|e| map_guarded_error(e, &guard, "Failed to count query rows"))?;
+            total_count = Some(count);
+            params.push(page.page_size.into());
+            params.push(page.offset().into());
+            params
+        }
+        None => params.resolve(&stmt)?,
+    };
+    let columns = query::column_names(&stmt);
+
+    // Collect rows first. A read that fails partway keeps what it already read;
+    // a failed write is rolled back, so its rows would be misleading
+    let (raw_rows, failure) = if stmt.readonly() {
+        let partial = query::read_rows_partial(&mut stmt, params_from_iter(params))
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to execute query"))?;
+        (partial.rows, partial.error)
+    } else {
+        let rows = query::read_rows(&mut stmt, params_from_iter(params), None)
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to execute query"))?;
+        (rows, None)
+    };
+    // Rows read before a timeout aren't a usable partial result
+    let failure = match failure {
+        Some(e) if guard.tripped().is_some() => return Err(map_guarded_error(e, &guard, "Failed to execute query")),
+        failure => failure,
+    };
+
+    // Process rows, in parallel when there are enough of them
+    let mut rows = query::rows_to_objects(db_connection.row_converter(), &columns, &raw_rows);
+    if let Some(precision) = options.float_precision {
+        query::round_reals(db_connection.row_converter(), &mut rows, precision);
+    }
+    let lossy_text_cells = query::render_invalid_text(
+        db_connection.row_converter(),
+        &mut rows,
+        options.invalid_text.unwrap_or(db_connection.config().invalid_text),
+    );
+    query::render_nulls(db_connection.row_converter(), &mut rows, options.null_as);
+
+    if metadata.audit_enabled {
+        AuditEntry::record(db_connection, id, sql, caller.client_id.as_deref(), rows.len() as i64)
+            .map_err(|e| map_db_error(e, "Failed to write audit log"))?;
+    }
+
+    // History is best-effort: a failed write shouldn't fail the query itself
+    let duration_ms = started.elapsed().as_millis() as i64;
+    if let Err(e) = QueryHistoryEntry::record(db_connection, id, sql, rows.len() as i64, duration_ms) {
+        error!("Failed to record query history: {}", e);
+    }
+
+    // An incomplete result has no stable hash, so it's returned as-is
+    if let Some(e) = failure {
+        error!("Query failed after {} rows: {}", rows.len(), e);
+        let mut body = json!({
+            "columns": columns,
+            "rows": rows,
+            "partial": true,
+            "error": format!("Query failed after {} rows", rows.len()),
+            "detail": e.to_string()
+        });
+        if lossy_text_cells > 0 {
+            body["lossy_text_cells"] = json!(lossy_text_cells);
+        }
+        return Ok(Json(body));
+    }
+
+    // A client already holding this exact result only needs to hear that it's current
+    let result_hash = sha256_hex(&serde_json::to_vec(&rows).unwrap_or_default());
+    if prev_result_hash == Some(result_hash.as_str()) {
+        return Ok(Json(json!({ "unchanged": true, "result_hash": result_hash })));
+    }
+
+    let mut body = json!({
+        "columns": columns,
+        "rows": rows,
+        "returns_rows": returns_rows,
+        "result_hash": result_hash
+    });
+    if rows.is_empty() {
+        body["empty_result"] = json!(true);
+    }
+    if options.with_rowid {
+        body["rowid_included"] = json!(rowid_included);
+    }
+    if let (Some((page, _)), Some(total_count)) = (&paged, total_count) {
+        body["page"] = json!(page.page);
+        body["page_size"] = json!(page.page_size);
+        body["total_count"] = json!(total_count);
+    }
+    if options.describe {
+        body["describe"] = query::describe_columns(&columns, &raw_rows);
+    }
+    // Replaced characters are never silent
+    if lossy_text_cells > 0 {
+        body["lossy_text_cells"] = json!(lossy_text_cells);
+    }
+    Ok(Json(body))
+}
+
+// A result set read as SQLite values, for encoders that need cell types
+struct RawQueryResult {
+    columns: Vec<String>,
+    decl_types: Vec<Option<String>>,
+    rows: Vec<Vec<rusqlite::types::Value>>,
+}
+
+// Blocking counterpart of run_query that keeps SQLite values instead of JSON
+fn run_raw_query(
+    db_connection: &DbConnection,
+    metadata: &DatabaseMetadata,
+    sql: &str,
+    params: QueryParams,
+    caller: &Caller,
+    limit: Option<usize>,
+    writable: bool,
+) -> Result<RawQueryResult, ApiError> {
+    let id = metadata.id.unwrap_or_default();
+    let conn = open_for_caller(db_connection, metadata, caller, !writable)?;
+    let _registered = db_connection.query_registry().register(id, sql, conn.get_interrupt_handle());
+    let guard = install_statement_guard(db_connection, &conn)?;
+
+    let mut stmt = prepare_for_caller(&conn, sql, caller, StatusCode::INTERNAL_SERVER_ERROR)?;
+    let params = params.resolve(&stmt)?;
+    let columns = query::column_names(&stmt);
+    let decl_types = query::column_decl_types(&stmt);
+    let rows = query::read_sql_rows(&mut stmt, params_from_iter(params), limit)
+        .map_err(|e| map_guarded_error(e, &guard, "Failed to execute query"))?;
+
+    if metadata.audit_enabled {
+        AuditEntry::record(db_connection, id, sql, caller.client_id.as_deref(), rows.len() as i64)
+            .map_err(|e| map_db_error(e, "Failed to write audit log"))?;
+    }
+
+    Ok(RawQueryResult { columns, decl_types, rows })
+}
+
+// Same as execute_query, but the result set is returned as an Arrow IPC stream
+pub async fn execute_arrow_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> Result<Response, ApiError> {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+
+    check_blocklist(&db_connection, &sql)?;
+    check_expected_statement(&payload, &sql)?;
+    let params = parse_query_params(&payload)?;
+    let writable = parse_writable(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+
+    let body = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, ApiError> {
+        let result = run_raw_query(&db_connection, &metadata, &sql, params, &caller, None, writable)?;
+        arrow_export::rows_to_ipc(&result.columns, &result.decl_types, &result.rows)
+            .map_err(|e| handle_error(e, "Failed to encode Arrow stream"))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))??;
+
+    Ok(([(header::CONTENT_TYPE, arrow_export::ARROW_STREAM_CONTENT_TYPE)], body).into_response())
+}
+
+// `<database stem>-results.<extension>`, reduced to characters that are safe in
+// a Content-Disposition header
+fn results_filename(database_name: &str, extension: &str) -> String {
+    let stem = std::path::Path::new(database_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "query".to_string());
+    format!("{}-results.{}", stem, extension)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+        .collect()
+}
+
+// Same as execute_query, but the result set is returned as an Excel workbook.
+// Results beyond Excel's row limit are dropped and flagged with X-Truncated.
+pub async fn execute_xlsx_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> Result<Response, ApiError> {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+
+    check_blocklist(&db_connection, &sql)?;
+    check_expected_statement(&payload, &sql)?;
+    let params = parse_query_params(&payload)?;
+    let writable = parse_writable(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+
+    let filename = results_filename(&metadata.name, "xlsx");
+
+    let (body, truncated) = tokio::task::spawn_blocking(move || -> Result<(Vec<u8>, bool), ApiError> {
+        // Read one row past the limit to tell whether anything was cut off
+        let mut result = run_raw_query(
+            &db_connection, &metadata, &sql, params, &caller, Some(xlsx_export::MAX_XLSX_ROWS + 1), writable,
+        )?;
+        if result.columns.len() > xlsx_export::MAX_XLSX_COLUMNS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Excel sheets hold at most {} columns", xlsx_export::MAX_XLSX_COLUMNS) }))
+            ).into());
+        }
+        let truncated = result.rows.len() > xlsx_export::MAX_XLSX_ROWS;
+        result.rows.truncate(xlsx_export::MAX_XLSX_ROWS);
+
+        let body = xlsx_export::rows_to_xlsx(&result.columns, &result.rows)
+            .map_err(|e| handle_error(e, "Failed to write workbook"))?;
+        Ok((body, truncated))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))??;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, xlsx_export::XLSX_CONTENT_TYPE.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+            (header::HeaderName::from_static(TRUNCATED_HEADER), truncated.to_string()),
+        ],
+        body,
+    ).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    pub limit: Option<String>,
+    pub offset: Option<String>,
+}
+
+pub async fn get_audit_log(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Query(params): Query<PageParams>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    let page = parse_pagination(
+        params.limit.as_deref(),
+        params.offset.as_deref(),
+        DEFAULT_PAGE_LIMIT,
+        MAX_PAGE_LIMIT,
+    )?;
+
+    AuditEntry::list_for_database(&db_connection, id, page)
+        .map(|entries| Json(json!({
+            "audit_enabled": metadata.audit_enabled,
+            "entries": entries
+        })))
+        .map_err(|e| map_db_error(e, "Failed to read audit log"))
+}
+
+// Apply ordered schema migrations gated on the database's user_version
+pub async fn migrate_database(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let bad_request = |msg: String| -> ApiError {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": msg }))).into()
+    };
+
+    let steps: Vec<Migration> = match payload.get("migrations") {
+        Some(steps @ Value::Array(_)) => serde_json::from_value(steps.clone())
+            .map_err(|e| bad_request(format!("Invalid migrations: {}", e)))?,
+        _ => return Err(bad_request("migrations must be an array".to_string())),
+    };
+
+    let mut metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+
+    let report = conn.without_views(|conn| migrations::apply(conn, &steps))
+        .map_err(|e| map_db_error(e, "Failed to apply column policy"))?
+        .map_err(|e| match e {
+            MigrationError::OutOfOrder { .. } => bad_request(e.to_string()),
+            MigrationError::Step { ref source, .. } if column_policy::is_denied(source) => {
+                column_forbidden(caller.role.as_deref())
+            }
+            MigrationError::Step { version, ref source } => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": format!("Migration {} failed; no migrations were applied", version),
+                    "version": version,
+                    "details": source.to_string()
+                }))
+            ).into(),
+            MigrationError::Sqlite(e) => map_db_error(e, "Failed to run migrations"),
+        })?;
+
+    // Keep the recorded table count and size in step with the new schema
+    if !report.applied.is_empty() {
+        let table_count: i32 = conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE type='table'", [], |row| row.get(0))
+            .map_err(|e| map_db_error(e, "Failed to read database structure"))?;
+        metadata.table_count = table_count;
+        metadata.size = std::fs::metadata(&metadata.path).map(|m| m.len() as i64).unwrap_or(metadata.size);
+        metadata.save(&db_connection)
+            .map_err(|e| map_db_error(e, "Failed to update database metadata"))?;
+    }
+
+    Ok(Json(json!({ "migrations": report })))
+}
+
+fn read_page_layout(conn: &rusqlite::Connection) -> Result<(i64, i64), ApiError> {
+    let page_size = conn.query_row("PRAGMA page_size", [], |row| row.get(0))
+        .map_err(|e| map_db_error(e, "Failed to read page size"))?;
+    let page_count = conn.query_row("PRAGMA page_count", [], |row| row.get(0))
+        .map_err(|e| map_db_error(e, "Failed to read page count"))?;
+    Ok((page_size, page_count))
+}
+
+pub async fn get_page_size(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let (page_size, page_count) = read_page_layout(&conn)?;
+    Ok(Json(json!({ "page_size": page_size, "page_count": page_count })))
+}
+
+// Change the page size, which only takes effect once VACUUM rebuilds the file
+pub async fn set_page_size(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let page_size = match payload.get("page_size").and_then(|v| v.as_i64()) {
+        Some(n) if (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&n) && (n as u64).is_power_of_two() => n,
+        _ => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!(
+                    "page_size must be a power of two between {} and {}",
+                    MIN_PAGE_SIZE, MAX_PAGE_SIZE
+                )
+            }))
+        ).into()),
+    };
+
+    let mut metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    // page_size is validated above and PRAGMA values can't be bound
+    conn.execute_batch(&format!("PRAGMA page_size = {}; VACUUM;", page_size))
+        .map_err(|e| map_execution_error(e, "Failed to change page size"))?;
+
+    let (actual, page_count) = read_page_layout(&conn)?;
+    if actual != page_size {
+        // e.g. databases in WAL mode keep their page size
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "Page size could not be changed for this database",
+                "page_size": actual
+            }))
+        ).into());
+    }
+
+    metadata.size = std::fs::metadata(&metadata.path).map(|m| m.len() as i64).unwrap_or(metadata.size);
+    let metadata = metadata.save(&db_connection)
+        .map_err(|e| map_db_error(e, "Failed to update database metadata"))?;
+
+    Ok(Json(json!({
+        "page_size": actual,
+        "page_count": page_count,
+        "size": metadata.size
+    })))
+}
+
+// Extract the collation name from SQLite's "no such collation sequence: X" error
+fn missing_collation(err: &rusqlite::Error) -> Option<String> {
+    match err {
+        rusqlite::Error::SqliteFailure(_, Some(msg)) => msg
+            .strip_prefix("no such collation sequence: ")
+            .map(|name| name.trim().to_string()),
+        _ => None,
+    }
+}
+
+// Rebuild every index in the database, or only those of one `table` or a single `index`
+pub async fn reindex_database(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    payload: Option<Json<Value>>,
+) -> ApiResult {
+    let target = |key: &str| payload.as_ref().and_then(|Json(p)| p.get(key)).and_then(|v| v.as_str());
+    let scope = match (target("table"), target("index")) {
+        (Some(_), Some(_)) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Specify either table or index, not both" }))
+        ).into()),
+        (Some(table), None) => Some(("table", table)),
+        (None, Some(index)) => Some(("index", index)),
+        (None, None) => None,
+    };
+
+    let metadata = find_database(&db_connection, id)?;
+    let pool = db_connection.get_database_pool(&metadata.path);
+    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+
+    let sql = match scope {
+        Some((kind, name)) => {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = ? AND name = ?)",
+                [kind, name],
+                |row| row.get(0),
+            ).map_err(|e| map_db_error(e, "Failed to read schema"))?;
+            if !exists {
+                if kind == "table" {
+                    return Err(table_not_found(name));
+                }
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    Json(json!({ "error": format!("Index '{}' not found", name) }))
+                ).into());
+            }
+            format!("REINDEX {}", quote_identifier(name))
+        }
+        None => "REINDEX".to_string(),
+    };
+
+    if let Err(e) = conn.execute_batch(&sql) {
+        // An index built with a collation this server doesn't define can't be rebuilt here
+        if let Some(collation) = missing_collation(&e) {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                    "error": format!(
+                        "An index uses collation '{}', which is not available on this server",
+                        collation
+                    ),
+                    "code": "MISSING_COLLATION",
+                    "collation": collation
+                }))
+            ).into());
+        }
+        return Err(map_execution_error(e, "Failed to reindex database"));
+    }
+
+    let scope = scope.map(|(kind, name)| json!({ kind: name })).unwrap_or(json!("database"));
+    Ok(Json(json!({
+        "message": "Reindex complete",
+        "reindexed": scope
+    })))
+}
+
+fn map_template_error(e: TemplateError) -> ApiError {
+    match e {
+        TemplateError::Sqlite(e) => map_db_error(e, "Failed to read database schema"),
+        other => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": other.to_string() }))
+        ).into(),
+    }
+}
+
+fn find_saved_query(db_connection: &DbConnection, id: i64, query_id: i64) -> Result<SavedQuery, ApiError> {
+    match SavedQuery::find(db_connection, id, query_id) {
+        Ok(Some(query)) => Ok(query),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Saved query not found" }))
+        ).into()),
+        Err(e) => Err(map_db_error(e, "Failed to find saved query")),
+    }
+}
+
+pub async fn list_saved_queries(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    find_database(&db_connection, id)?;
+    SavedQuery::list_for_database(&db_connection, id)
+        .map(|queries| Json(json!({ "queries": queries })))
+        .map_err(|e| map_db_error(e, "Failed to list saved queries"))
+}
+
+// Save a named SQL template. `{{table}}`, `{{column}}`, `{{limit}}` and `{{offset}}`
+// placeholders have implied kinds; any other name is written `{{name:kind}}`.
+pub async fn create_saved_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let field = |name: &str| payload.get(name)
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    let (Some(name), Some(sql)) = (field("name"), field("sql")) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "name and sql are required" }))
+        ).into());
+    };
+
+    let placeholders = template::placeholders(sql).map_err(map_template_error)?;
+    find_database(&db_connection, id)?;
+
+    match SavedQuery::create(&db_connection, id, name, sql) {
+        Ok(Some(query)) => Ok(Json(json!({ "query": query, "placeholders": placeholders }))),
+        Ok(None) => Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "A saved query with this name already exists" }))
+        ).into()),
+        Err(e) => Err(map_db_error(e, "Failed to save query")),
+    }
+}
+
+pub async fn delete_saved_query(
+    State(db_connection): State<DbConnection>,
+    Path((id, query_id)): Path<(i64, i64)>,
+) -> ApiResult {
+    match SavedQuery::delete(&db_connection, id, query_id) {
+        Ok(true) => Ok(Json(json!({ "message": "Saved query deleted" }))),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Saved query not found" }))
+        ).into()),
+        Err(e) => Err(map_db_error(e, "Failed to delete saved query")),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleSettings {
+    #[serde(default)]
+    is_favorite: bool,
+    #[serde(default)]
+    audit_enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleQuery {
+    name: String,
+    sql: String,
+}
+
+// A database's metadata layer without its data: what a team carries to another
+// instance holding the same file. `sha256` identifies the file it belongs to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseBundle {
+    sha256: String,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    properties: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    tags: std::collections::BTreeSet<String>,
+    settings: BundleSettings,
+    #[serde(default)]
+    saved_queries: Vec<BundleQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BundleImportParams {
+    #[serde(default)]
+    force: bool,
+}
+
+// Hash the database's file off the async runtime
+async fn database_sha256(db_connection: &DbConnection, metadata: &DatabaseMetadata) -> Result<String, ApiError> {
+    let connection = db_connection.clone();
+    let path = metadata.path.clone();
+    tokio::task::spawn_blocking(move || connection.fingerprints().fingerprint(&path))
+        .await
+        .map_err(|e| handle_error(e, "Fingerprint task failed"))?
+        .map(|fingerprint| fingerprint.fingerprint)
+        .map_err(|e| handle_error(e, "Failed to fingerprint database"))
+}
+
+pub async fn export_bundle(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+) -> ApiResult {
+    let metadata = find_database(&db_connection, id)?;
+    let sha256 = database_sha256(&db_connection, &metadata).await?;
+    let saved_queries = SavedQuery::list_for_database(&db_connection, id)
+        .map_err(|e| map_db_error(e, "Failed to list saved queries"))?;
+
+    let bundle = DatabaseBundle {
+        sha256,
+        notes: metadata.notes,
+        properties: metadata.properties.into_iter()
+            .filter(|(key, _)| !SERVER_OWNED_PROPERTIES.contains(&key.as_str()))
+            .collect(),
+        tags: metadata.tags,
+        settings: BundleSettings { is_favorite: metadata.is_favorite, audit_enabled: metadata.audit_enabled },
+        saved_queries: saved_queries.into_iter()
+            .map(|query| BundleQuery { name: query.name, sql: query.sql })
+            .collect(),
+    };
+    let mut body = json!(bundle);
+    body["exported_at"] = json!(chrono::Utc::now().to_rfc3339());
+    body["source"] = json!({ "id": id, "name": metadata.name });
+    Ok(Json(body))
+}
+
+// Apply a bundle to a database whose file hashes the same as the bundle's source,
+// unless ?force=true. Notes and settings are replaced, properties other than
+// server-owned ones merged, tags added; saved queries whose name is already taken are skipped.
+pub async fn import_bundle(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Query(params): Query<BundleImportParams>,
+    headers: HeaderMap,
+    Json(bundle): Json<DatabaseBundle>,
+) -> ApiResult {
+    let mut metadata = find_database(&db_connection, id)?;
+    let sha256 = database_sha256(&db_connection, &metadata).await?;
+    if sha256 != bundle.sha256 && !params.force {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": {
+                    "code": "BUNDLE_MISMATCH",
+                    "message": "The bundle was exported from a database with different contents",
+                    "expected_sha256": bundle.sha256,
+                    "actual_sha256": sha256
+                }
+            }))
+        ).into());
+    }
+    if bundle.settings.audit_enabled != metadata.audit_enabled {
+        authorize_admin(&db_connection, &headers)?;
+    }
+
+    let policy_before = metadata.properties.get(COLUMN_POLICY_PROPERTY).cloned();
+    for (key, value) in bundle.properties {
+        if !SERVER_OWNED_PROPERTIES.contains(&key.as_str()) {
+            metadata.properties.insert(key, value);
+        }
+    }
+    if metadata.properties.get(COLUMN_POLICY_PROPERTY) != policy_before.as_ref() {
+        authorize_admin(&db_connection, &headers)?;
+    }
+    if let Some(policy) = metadata.properties.get(COLUMN_POLICY_PROPERTY) {
+        ColumnPolicy::parse(policy).map_err(|e| ApiError(
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e }))
+        ))?;
+    }
+
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    for query in bundle.saved_queries {
+        if let Err(e) = template::placeholders(&query.sql) {
+            skipped.push(json!({ "name": query.name, "reason": e.to_string() }));
+            continue;
+        }
+        match SavedQuery::create(&db_connection, id, &query.name, &query.sql) {
+            Ok(Some(_)) => imported.push(query.name),
+            Ok(None) => skipped.push(json!({ "name": query.name, "reason": "A saved query with this name already exists" })),
+            Err(e) => return Err(map_db_error(e, "Failed to save query")),
+        }
+    }
+
+    metadata.notes = bundle.notes;
+    metadata.is_favorite = bundle.settings.is_favorite;
+    metadata.audit_enabled = bundle.settings.audit_enabled;
+    metadata.tags.extend(bundle.tags);
+    metadata.updated_at = Some(chrono::Utc::now());
+    let database = metadata.save(&db_connection)
+        .map_err(|e| map_db_error(e, "Failed to update database"))?;
+
+    Ok(Json(json!({
+        "database": database,
+        "saved_queries": { "imported": imported, "skipped": skipped }
+    })))
+}
+
+// Fill a saved template's placeholders from `args`, then run it like execute_query
+// (bound `params`/`bindings` are still accepted for values)
+pub async fn run_saved_query(
+    State(db_connection): State<DbConnection>,
+    Path((id, query_id)): Path<(i64, i64)>,
+    caller: Caller,
+    payload: Option<Json<Value>>,
+) -> ApiResult {
+    let payload = payload.map(|Json(p)| p).unwrap_or_else(|| json!({}));
+    let args = match payload.get("args") {
+        None | Some(Value::Null) => serde_json::Map::new(),
+        Some(Value::Object(args)) => args.clone(),
+        Some(_) => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "args must be an object" }))
+        ).into()),
+    };
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+    let saved = find_saved_query(&db_connection, id, query_id)?;
+
+    tokio::task::spawn_blocking(move || {
+        let sql = {
+            let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+            template::render(&conn, &saved.sql, &args).map_err(map_template_error)?
+        };
+        check_blocklist(&db_connection, &sql)?;
+
+        let Json(mut result) = run_query(
+            &db_connection, &metadata, &sql, params, &QueryOptions::default(), &caller, None,
+        )?;
+        result["sql"] = json!(sql);
+        Ok(Json(result))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))?
+}
+
+pub async fn get_query_history(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Query(params): Query<PageParams>,
+) -> ApiResult {
+    find_database(&db_connection, id)?;
+    let page = parse_pagination(
+        params.limit.as_deref(),
+        params.offset.as_deref(),
+        DEFAULT_PAGE_LIMIT,
+        MAX_PAGE_LIMIT,
+    )?;
+
+    QueryHistoryEntry::list_for_database(&db_connection, id, page)
+        .map(|entries| Json(json!({ "entries": entries })))
+        .map_err(|e| map_db_error(e, "Failed to read query history"))
+}
+
+// A table's sampled row counts over time, oldest first
+pub async fn get_table_growth(
+    State(db_connection): State<DbConnection>,
+    Path((id, table)): Path<(i64, String)>,
+    Query(params): Query<PageParams>,
+) -> ApiResult {
+    validate_table_name(&table)?;
+    let metadata = find_database(&db_connection, id)?;
+    let page = parse_pagination(
+        params.limit.as_deref(),
+        params.offset.as_deref(),
+        DEFAULT_PAGE_LIMIT,
+        MAX_PAGE_LIMIT,
+    )?;
+
+    let samples = TableGrowth::list_for_table(&db_connection, id, &table, page)
+        .map_err(|e| map_db_error(e, "Failed to read row count samples"))?;
+
+    // No samples yet is only an error if there's no such table to sample
+    if samples.is_empty() {
+        let pool = db_connection.get_read_only_database_pool(&metadata.path);
+        let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+        if !table_exists(&conn, &table)? {
+            return Err(table_not_found(&table));
+        }
+    }
+
+    Ok(Json(json!({ "table": table, "samples": samples })))
+}
+
+pub async fn execute_sample_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+    check_blocklist(&db_connection, &sql)?;
+    let params = parse_query_params(&payload)?;
+
+    let sample_size = match payload.get("sample_size") {
+        None => DEFAULT_SAMPLE_SIZE,
+        Some(v) => match v.as_u64() {
+            Some(n) if n > 0 => (n as usize).min(MAX_SAMPLE_SIZE),
+            _ => return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "sample_size must be a positive integer" }))
+            ).into()),
+        },
+    };
+
+    // Every table is shadowed by a temporary view over its first rows. Unqualified
+    // names resolve to the temp schema first, so the query only sees the sample.
+    let metadata = find_database(&db_connection, id)?;
+    tokio::task::spawn_blocking(move || {
+        let pool = db_connection.get_read_only_database_pool(&metadata.path);
+        let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+        let conn = PolicyConnection::sampled(conn, caller.hidden_columns(&metadata)?, sample_size)
+            .map_err(|e| map_db_error(e, "Failed to prepare sample"))?;
+        let _registered = db_connection.query_registry().register(id, &sql, conn.get_interrupt_handle());
+        let guard = install_statement_guard(&db_connection, &conn)?;
+
+        let mut stmt = prepare_for_caller(&conn, &sql, &caller, StatusCode::BAD_REQUEST)?;
+        if !stmt.readonly() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Sample queries must be read-only" }))
+            ).into());
+        }
+        let params = params.resolve(&stmt)?;
+
+        let columns = query::column_names(&stmt);
+        let raw_rows = query::read_rows(&mut stmt, params_from_iter(params), Some(sample_size))
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to execute query"))?;
+        let rows = query::rows_to_objects(db_connection.row_converter(), &columns, &raw_rows);
+
+        Ok(Json(json!({
+            "rows": rows,
+            "sample": true,
+            "sample_size": sample_size,
+            "note": format!("Query ran against at most the first {} rows of each table", sample_size)
+        })))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))?
+}
+
+// Stream a read-only query's rows as NDJSON, one object per line, reading from
+// SQLite only as fast as the client consumes the response
+pub async fn execute_stream_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> Result<Response, ApiError> {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+
+    check_blocklist(&db_connection, &sql)?;
+    check_expected_statement(&payload, &sql)?;
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+    prepare_for_caller(&conn, &sql, &caller, StatusCode::BAD_REQUEST)?;
+    let registered = db_connection.query_registry().register(id, &sql, conn.get_interrupt_handle());
+
+    let rows = stream::start(conn, sql, stream::STREAM_CHANNEL_CAPACITY, move |stmt| {
+        if !stmt.readonly() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Streamed queries must be read-only" }))
+            ).into());
+        }
+        params.resolve(stmt)
+    }, registered).await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, stream::NDJSON_CONTENT_TYPE)],
+        axum::body::Body::from_stream(rows.into_body_stream()),
+    ).into_response())
+}
+
+// Same as execute_query, but the result set is streamed as a CSV download with a
+// header row. NULL is an empty field and blobs a size placeholder. Errors found
+// before the first byte get a status; a row that fails later aborts the body.
+pub async fn execute_csv_export_query(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> Result<Response, ApiError> {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+
+    check_blocklist(&db_connection, &sql)?;
+    check_expected_statement(&payload, &sql)?;
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+    let filename = results_filename(&metadata.name, "csv");
+
+    let (writer, body) = stream::body_channel(stream::STREAM_CHANNEL_CAPACITY);
+    let (ready, prepared) = tokio::sync::oneshot::channel::<Result<(), ApiError>>();
+    tokio::task::spawn_blocking(move || {
+        let opened = open_for_caller(&db_connection, &metadata, &caller, true);
+        let conn = match opened {
+            Ok(conn) => conn,
+            Err(e) => {
+                ready.send(Err(e)).ok();
+                return;
+            }
+        };
+        let _registered = db_connection.query_registry().register(id, &sql, conn.get_interrupt_handle());
+        let prepared = install_statement_guard(&db_connection, &conn).and_then(|guard| {
+            let stmt = prepare_for_caller(&conn, &sql, &caller, StatusCode::INTERNAL_SERVER_ERROR)?;
+            // Refused before the response starts, rather than failing mid-stream
+            if !stmt.readonly() {
+                return Err(read_only_violation());
+            }
+            let params = params.resolve(&stmt)?;
+            Ok((guard, stmt, params))
+        });
+        let (_guard, mut stmt, params) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                ready.send(Err(e)).ok();
+                return;
+            }
+        };
+        if ready.send(Ok(())).is_err() {
+            return;
+        }
+
+        let failure = writer.clone();
+        match export::write_export(&mut stmt, params, ExportFormat::Csv, writer, |_, _| Ok(true)) {
+            Ok((rows, _)) => {
+                if metadata.audit_enabled {
+                    if let Err(e) = AuditEntry::record(&db_connection, id, &sql, caller.client_id.as_deref(), rows as i64) {
+                        error!("Failed to write audit log: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("CSV export failed: {}", e);
+                failure.fail(&e.to_string());
+            }
+        }
+    });
+
+    match prepared.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Err(e),
+        Err(e) => return Err(handle_error(e, "Query task failed")),
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, ExportFormat::Csv.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        axum::body::Body::from_stream(body),
+    ).into_response())
+}
+
+// Count a query's rows and extrapolate its JSON size from a small sample,
+// without materializing the full result
+pub async fn estimate_query_size(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> ApiResult {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.trim().trim_end_matches(';').to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
+        ).into()),
+    };
+    check_blocklist(&db_connection, &sql)?;
+
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+
+    // Counting can take as long as the query itself, so it runs off the runtime
+    // under the same guard as other queries
+    tokio::task::spawn_blocking(move || {
+        let conn = open_for_caller(&db_connection, &metadata, &caller, true)?;
+        let _registered = db_connection.query_registry().register(id, &sql, conn.get_interrupt_handle());
+        let guard = install_statement_guard(&db_connection, &conn)?;
+
+        let mut stmt = prepare_for_caller(&conn, &sql, &caller, StatusCode::BAD_REQUEST)?;
+        if !stmt.readonly() || stmt.column_count() == 0 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Size estimates require a read-only query that returns rows" }))
+            ).into());
+        }
+        let params = params.resolve(&stmt)?;
+
+        let columns = query::column_names(&stmt);
+        let sample = query::read_rows(&mut stmt, params_from_iter(&params), Some(SIZE_ESTIMATE_SAMPLE_ROWS))
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to execute query"))?;
+        let sample_bytes: usize = query::rows_to_objects(db_connection.row_converter(), &columns, &sample)
+            .iter()
+            .map(|row| row.to_string().len())
+            .sum();
+
+        let (_, count_sql) = paged_sql(&sql);
+        let estimated_rows: i64 = conn
+            .query_row(&count_sql, params_from_iter(&params), |row| row.get(0))
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to count query rows"))?;
+
+        let average_row_bytes = if sample.is_empty() { 0 } else { sample_bytes / sample.len() };
+
+        Ok(Json(json!({
+            "estimated_rows": estimated_rows,
+            "estimated_bytes": average_row_bytes as i64 * estimated_rows,
+            "average_row_bytes": average_row_bytes,
+            "sampled_rows": sample.len(),
+            "estimate": true
+        })))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))?
+}
+
+// Run an INSERT/UPDATE/DELETE inside a transaction that is always rolled back and
+// report how many rows it would have changed
+pub async fn preview_affected_rows(
     State(db_connection): State<DbConnection>,
     Path(id): Path<i64>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
 ) -> ApiResult {
-    let metadata = match DatabaseMetadata::find_by_id(&db_connection, id) {
-        Ok(Some(m)) => m,
-        Ok(None) => return Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({ "error": "Database not found" }))
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
         ).into()),
-        Err(e) => return Err(map_db_error(e, "Failed to find database")),
     };
+    check_blocklist(&db_connection, &sql)?;
 
-    let pool = db_connection.get_database_pool(&metadata.path);
-    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+    // Transaction control or DDL could escape the rollback, so only plain row writes are previewed
+    let kind = query::statement_kind(&sql);
+    if !matches!(kind, Some("insert" | "update" | "delete")) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Affected-row previews require an INSERT, UPDATE or DELETE statement",
+                "actual": kind
+            }))
+        ).into());
+    }
 
-    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table'")
-        .map_err(|e| map_db_error(e, "Failed to read database structure"))?;
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
 
-    let tables: Result<Vec<String>, _> = stmt.query_map([], |row| row.get(0))
-        .map_err(|e| map_db_error(e, "Failed to read tables"))?
-        .collect::<Result<_, _>>()
-        .map_err(|e| map_db_error(e, "Failed to collect tables"));
+    tokio::task::spawn_blocking(move || {
+        let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
+        let _registered = db_connection.query_registry().register(id, &sql, conn.get_interrupt_handle());
+        let guard = install_statement_guard(&db_connection, &conn)?;
 
-    Ok(Json(json!({ "tables": tables? })))
+        // Prepared first, as the column policy may adjust the connection to prepare it
+        let mut stmt = prepare_for_caller(&conn, &sql, &caller, StatusCode::BAD_REQUEST)?;
+        // Dropped without commit, so everything below is rolled back
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| map_db_error(e, "Failed to begin transaction"))?;
+        let params = params.resolve(&stmt)?;
+
+        // Step through any RETURNING rows so the whole statement runs
+        let mut rows = stmt.query(params_from_iter(params))
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to execute statement"))?;
+        while rows.next()
+            .map_err(|e| map_guarded_error(e, &guard, "Failed to execute statement"))?
+            .is_some()
+        {}
+        drop(rows);
+        let affected_rows = tx.changes();
+
+        Ok(Json(json!({
+            "statement": kind,
+            "affected_rows": affected_rows,
+            "rolled_back": true
+        })))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Preview task failed"))?
 }
 
-pub async fn get_table_schema(
+fn snapshot_not_found(token: &str) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": { "code": "SNAPSHOT_NOT_FOUND", "token": token } }))
+    ).into()
+}
+
+// Open a read snapshot so several queries see one consistent view of the database
+pub async fn open_snapshot(
     State(db_connection): State<DbConnection>,
-    Path((id, table)): Path<(i64, String)>,
+    Path(id): Path<i64>,
+    caller: Caller,
 ) -> ApiResult {
-    let metadata = match DatabaseMetadata::find_by_id(&db_connection, id) {
-        Ok(Some(m)) => m,
-        Ok(None) => return Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({ "error": "Database not found" }))
+    let metadata = find_database(&db_connection, id)?;
+    let hidden = caller.hidden_columns(&metadata)?;
+    db_connection.snapshots().reap_idle(db_connection.config().snapshot_idle_timeout);
+
+    tokio::task::spawn_blocking(move || {
+        db_connection.snapshots().open(id, &metadata.path, hidden)
+            .map(|snapshot| Json(json!({ "snapshot": snapshot })))
+            .map_err(|e| map_db_error(e, "Failed to open snapshot"))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Snapshot task failed"))?
+}
+
+// Run a read-only query against an open snapshot
+pub async fn query_snapshot(
+    State(db_connection): State<DbConnection>,
+    Path(token): Path<String>,
+    GuardedJson(payload): GuardedJson,
+) -> ApiResult {
+    let sql = match payload.get("sql").and_then(|v| v.as_str()) {
+        Some(s) => s.to_string(),
+        None => return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "SQL query is required" }))
         ).into()),
-        Err(e) => return Err(map_db_error(e, "Failed to find database")),
     };
+    check_blocklist(&db_connection, &sql)?;
+    let params = parse_query_params(&payload)?;
 
-    let pool = db_connection.get_database_pool(&metadata.path);
-    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+    db_connection.snapshots().reap_idle(db_connection.config().snapshot_idle_timeout);
+    let snapshot = db_connection.snapshots().get(&token).ok_or_else(|| snapshot_not_found(&token))?;
 
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))
-        .map_err(|e| map_db_error(e, "Failed to read table schema"))?;
+    tokio::task::spawn_blocking(move || {
+        snapshot.with_connection(|conn| -> ApiResult {
+            let mut stmt = conn.prepare_checked(&sql).map_err(|e| match e {
+                PolicyError::Forbidden => column_forbidden(None),
+                PolicyError::Sqlite(e) => map_prepare_error(e, StatusCode::BAD_REQUEST),
+            })?;
+            if !stmt.readonly() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "Snapshots only run read-only queries" }))
+                ).into());
+            }
+            let params = params.resolve(&stmt)?;
+            let columns = query::column_names(&stmt);
+            let rows = query::read_rows(&mut stmt, params_from_iter(params), None)
+                .map_err(|e| map_execution_error(e, "Failed to execute query"))?;
 
-    let schema: Vec<Value> = stmt.query_map([], |row| -> rusqlite::Result<Value> {
-        Ok(json!({
-            "cid": row.get::<_, i64>(0)?,
-            "name": row.get::<_, String>(1)?,
-            "type": row.get::<_, String>(2)?,
-            "notnull": row.get::<_, bool>(3)?,
-            "dflt_value": row.get::<_, Option<String>>(4)?,
-            "pk": row.get::<_, bool>(5)?
-        }))
+            Ok(Json(json!({
+                "database_id": snapshot.database_id(),
+                "rows": query::rows_to_objects(db_connection.row_converter(), &columns, &rows)
+            })))
+        })
     })
-    .map_err(|e| map_db_error(e, "Failed to read schema"))?
-    .collect::<Result<_, _>>()
-    .map_err(|e| map_db_error(e, "Failed to collect schema"))?;
+    .await
+    .map_err(|e| handle_error(e, "Query task failed"))?
+}
 
-    Ok(Json(json!({ "schema": schema })))
+pub async fn close_snapshot(
+    State(db_connection): State<DbConnection>,
+    Path(token): Path<String>,
+) -> ApiResult {
+    if db_connection.snapshots().close(&token) {
+        Ok(Json(json!({ "message": "Snapshot closed", "token": token })))
+    } else {
+        Err(snapshot_not_found(&token))
+    }
 }
 
-pub async fn execute_query(
+// Run a read-only query on one database for comparison with another
+fn read_comparison_rows(
+    db_connection: &DbConnection,
+    caller: &Caller,
+    id: i64,
+    sql: &str,
+    params: QueryParams,
+) -> Result<(Vec<String>, Vec<Vec<Value>>), ApiError> {
+    let metadata = find_database(db_connection, id)?;
+    let conn = open_for_caller(db_connection, &metadata, caller, false)?;
+
+    let mut stmt = prepare_for_caller(&conn, sql, caller, StatusCode::BAD_REQUEST)?;
+    if !stmt.readonly() || stmt.column_count() == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Query diffs require a read-only query that returns rows" }))
+        ).into());
+    }
+    let params = params.resolve(&stmt)?;
+    let columns = query::column_names(&stmt);
+    let rows = query::read_rows(&mut stmt, params_from_iter(params), None)
+        .map_err(|e| map_execution_error(e, "Failed to execute query"))?;
+    Ok((columns, rows))
+}
+
+// Run the same query against two databases and report rows added, removed or
+// changed on the right relative to the left, matched by a key column
+pub async fn execute_query_diff(
+    State(db_connection): State<DbConnection>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
+) -> ApiResult {
+    let bad_request = |error: String| ApiError::from((StatusCode::BAD_REQUEST, Json(json!({ "error": error }))));
+    let sql = payload.get("sql").and_then(|v| v.as_str())
+        .ok_or_else(|| bad_request("SQL query is required".to_string()))?;
+    let key = payload.get("key").and_then(|v| v.as_str())
+        .ok_or_else(|| bad_request("key is required".to_string()))?;
+    let database_id = |name: &str| payload.get(name).and_then(|v| v.as_i64())
+        .ok_or_else(|| bad_request(format!("{} is required", name)));
+    let (left_id, right_id) = (database_id("left_id")?, database_id("right_id")?);
+    check_blocklist(&db_connection, sql)?;
+
+    let (left_columns, left_rows) =
+        read_comparison_rows(&db_connection, &caller, left_id, sql, parse_query_params(&payload)?)?;
+    let (right_columns, right_rows) =
+        read_comparison_rows(&db_connection, &caller, right_id, sql, parse_query_params(&payload)?)?;
+
+    let diff = result_diff::diff_results(&left_columns, &left_rows, &right_columns, &right_rows, key)
+        .map_err(|e| match e {
+            ResultDiffError::MissingKeyColumn(side) => bad_request(format!(
+                "Key column '{}' is not in the {} result", key, if side == Side::Left { "left" } else { "right" }
+            )),
+            ResultDiffError::DuplicateKey { side, key: value } => ApiError::from((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                    "error": format!("Key column '{}' is not unique in the result", key),
+                    "side": side,
+                    "key": value
+                }))
+            )),
+        })?;
+
+    Ok(Json(json!({
+        "left_id": left_id,
+        "right_id": right_id,
+        "key": key,
+        "diff": diff
+    })))
+}
+
+// Run a read-only query and cross-tabulate its rows by `row_key` and `col_key`,
+// aggregating the `value` column (sum by default)
+pub async fn execute_pivot_query(
     State(db_connection): State<DbConnection>,
     Path(id): Path<i64>,
-    Json(payload): Json<Value>,
+    caller: Caller,
+    GuardedJson(payload): GuardedJson,
 ) -> ApiResult {
     let sql = match payload.get("sql").and_then(|v| v.as_str()) {
         Some(s) => s,
@@ -321,61 +3893,64 @@ pub async fn execute_query(
             Json(json!({ "error": "SQL query is required" }))
         ).into()),
     };
+    check_blocklist(&db_connection, sql)?;
 
-    let metadata = match DatabaseMetadata::find_by_id(&db_connection, id) {
-        Ok(Some(m)) => m,
-        Ok(None) => return Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({ "error": "Database not found" }))
-        ).into()),
-        Err(e) => return Err(map_db_error(e, "Failed to find database")),
+    let key = |name: &str| match payload.get(name).and_then(|v| v.as_str()) {
+        Some(column) => Ok(column.to_string()),
+        None => Err(ApiError::from((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("{} is required", name) }))
+        ))),
+    };
+    let (row_key, col_key, value) = (key("row_key")?, key("col_key")?, key("value")?);
+    let aggregate: pivot::Aggregate = match payload.get("aggregate") {
+        None | Some(Value::Null) => pivot::Aggregate::default(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|_| ApiError::from((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "aggregate must be one of sum, count, avg, min, max" }))
+        )))?,
     };
 
-    let pool = db_connection.get_database_pool(&metadata.path);
-    let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
+    let params = parse_query_params(&payload)?;
+    let metadata = find_database(&db_connection, id)?;
+    let conn = open_for_caller(&db_connection, &metadata, &caller, false)?;
 
-    let mut stmt = match conn.prepare(sql) {
-        Ok(stmt) => stmt,
-        Err(e) => return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": format!("Failed to prepare query: {}", e) }))
-        ).into()),
-    };
+    let mut stmt = prepare_for_caller(&conn, sql, &caller, StatusCode::BAD_REQUEST)?;
+    if !stmt.readonly() || stmt.column_count() == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Pivots require a read-only query that returns rows" }))
+        ).into());
+    }
+    let params = params.resolve(&stmt)?;
+    let columns = query::column_names(&stmt);
+    let rows = query::read_rows(&mut stmt, params_from_iter(params), None)
+        .map_err(|e| map_execution_error(e, "Failed to execute query"))?;
 
-    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
-    
-    // Collect rows first
-    let raw_rows: Vec<Vec<Value>> = stmt.query_map([], |row| -> rusqlite::Result<Vec<Value>> {
-        let mut row_data = Vec::with_capacity(columns.len());
-        for i in 0..columns.len() {
-            let value = match row.get_ref(i)? {
-                rusqlite::types::ValueRef::Null => Value::Null,
-                rusqlite::types::ValueRef::Integer(i) => json!(i),
-                rusqlite::types::ValueRef::Real(f) => json!(f),
-                rusqlite::types::ValueRef::Text(s) => json!(s),
-                rusqlite::types::ValueRef::Blob(b) => json!(format!("<BLOB: {} bytes>", b.len())),
-            };
-            row_data.push(value);
-        }
-        Ok(row_data)
-    })
-    .map_err(|e| map_db_error(e, "Failed to execute query"))?
-    .collect::<Result<_, _>>()
-    .map_err(|e| map_db_error(e, "Failed to collect results"))?;
-
-    // Process rows in parallel
-    use rayon::prelude::*;
-    let rows: Vec<Value> = raw_rows.par_iter()
-        .map(|row_data| {
-            let mut obj = serde_json::Map::new();
-            for (i, column) in columns.iter().enumerate() {
-                obj.insert(column.clone(), row_data[i].clone());
-            }
-            Value::Object(obj)
-        })
-        .collect();
+    let pivoted = pivot::pivot(
+        &columns, &rows, &row_key, &col_key, &value, aggregate, pivot::MAX_PIVOT_COLUMNS,
+    ).map_err(|e| {
+        let error = match e {
+            pivot::PivotError::UnknownColumn(column) => format!("Column '{}' is not in the query result", column),
+            pivot::PivotError::TooManyColumns { limit } => format!(
+                "{} has more than {} distinct values; narrow the query before pivoting",
+                col_key, limit
+            ),
+            pivot::PivotError::NonNumericValue(found) => format!(
+                "Cannot aggregate non-numeric value {} in {}; use count, min or max",
+                found, value
+            ),
+        };
+        ApiError::from((StatusCode::BAD_REQUEST, Json(json!({ "error": error }))))
+    })?;
 
-    Ok(Json(json!({ "rows": rows })))
+    Ok(Json(json!({
+        "row_key": row_key,
+        "col_key": col_key,
+        "value": value,
+        "columns": pivoted.columns,
+        "rows": pivoted.rows
+    })))
 }
 
 pub async fn get_database(
@@ -407,10 +3982,40 @@ pub async fn delete_database(
         Err(e) => return Err(map_db_error(e, "Failed to find database")),
     };
 
-    // Delete the database file
-    if let Err(e) = tokio::fs::remove_file(&metadata.path).await {
-        error!("Failed to delete database file: {}", e);
-        // Continue with metadata deletion even if file deletion fails
+    // Delete the database file, closing its pooled connections first. Files
+    // registered from outside storage aren't the server's to delete.
+    db_connection.evict_database_pool(&metadata.path);
+    if let Some(key) = db_connection.storage_key_for(&metadata.path) {
+        if let Err(e) = db_connection.storage().delete(&key) {
+            error!("Failed to delete database file: {}", e);
+            // Continue with metadata deletion even if file deletion fails
+        }
+    }
+    if let Some(original_key) = &metadata.original_copy {
+        if let Err(e) = db_connection.storage().delete(original_key) {
+            error!("Failed to delete original database copy: {}", e);
+        }
+    }
+
+    if let Err(e) = QueryHistoryEntry::delete_for_database(&db_connection, id) {
+        error!("Failed to delete query history: {}", e);
+    }
+
+    if let Err(e) = SavedQuery::delete_for_database(&db_connection, id) {
+        error!("Failed to delete saved queries: {}", e);
+    }
+
+    if let Err(e) = TableGrowth::delete_for_database(&db_connection, id) {
+        error!("Failed to delete row count samples: {}", e);
+    }
+
+    match ExportJob::delete_for_database(&db_connection, id) {
+        Ok(paths) => {
+            for path in &paths {
+                remove_export_artifact(&db_connection, path);
+            }
+        }
+        Err(e) => error!("Failed to delete export jobs: {}", e),
     }
 
     // Delete the metadata
@@ -420,10 +4025,71 @@ pub async fn delete_database(
     }
 }
 
+// Put the database file back to the bytes it was uploaded with, discarding every
+// change since. Requires {"confirm": true}.
+pub async fn reset_database(
+    State(db_connection): State<DbConnection>,
+    Path(id): Path<i64>,
+    Json(payload): Json<Value>,
+) -> ApiResult {
+    if payload.get("confirm") != Some(&Value::Bool(true)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Resetting discards every change since upload; set confirm to true to proceed" }))
+        ).into());
+    }
+
+    let mut metadata = find_database(&db_connection, id)?;
+    let (Some(original_key), Some(key)) = (metadata.original_copy.clone(), db_connection.storage_key_for(&metadata.path)) else {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({ "error": "No original copy is kept for this database" }))
+        ).into());
+    };
+
+    // Close pooled connections before the file is replaced underneath them
+    db_connection.evict_database_pool(&metadata.path);
+
+    let storage = db_connection.storage().clone();
+    let restore_wal = metadata.original_journal_mode.is_some();
+    let (size, table_count) = tokio::task::spawn_blocking(move || -> Result<(usize, i32), ApiError> {
+        let data = storage.get(&original_key)
+            .map_err(|e| handle_error(e, "Failed to read original database"))?;
+
+        // Drop any WAL or shared-memory file left by the mutated database so it
+        // can't be replayed onto the original
+        for suffix in ["-wal", "-shm", "-journal"] {
+            storage.delete(&format!("{}{}", key, suffix)).ok();
+        }
+        storage.put(&key, &data)
+            .map_err(|e| handle_error(e, "Failed to restore original database"))?;
+        let path = storage.local_path(&key)
+            .map_err(|e| handle_error(e, "Failed to restore original database"))?;
+
+        // Uploads converted to WAL are put back the way they were served
+        if restore_wal {
+            journal::convert_to_wal(&path)
+                .map_err(|e| map_db_error(e, "Failed to switch database to WAL mode"))?;
+        }
+        Ok((data.len(), validate_sqlite_db(&path)?))
+    })
+    .await
+    .map_err(|e| handle_error(e, "Reset task failed"))??;
+    db_connection.evict_database_pool(&metadata.path);
+
+    metadata.table_count = table_count;
+    metadata.size = size as i64;
+    metadata.updated_at = Some(chrono::Utc::now());
+    metadata.save(&db_connection)
+        .map(|database| Json(json!({ "database": database })))
+        .map_err(|e| map_db_error(e, "Failed to save database metadata"))
+}
+
 #[axum::debug_handler]
 pub async fn update_database(
     State(db_connection): State<DbConnection>,
     Path(id): Path<i64>,
+    headers: HeaderMap,
     Json(payload): Json<Value>,
 ) -> ApiResult {
     // Find the database metadata
@@ -449,6 +4115,47 @@ pub async fn update_database(
         metadata.is_favorite = is_favorite;
     }
 
+    // Switching auditing off would hide queries as surely as clearing the log
+    if let Some(audit_enabled) = payload.get("audit_enabled").and_then(|v| v.as_bool()) {
+        if audit_enabled != metadata.audit_enabled {
+            authorize_admin(&db_connection, &headers)?;
+        }
+        metadata.audit_enabled = audit_enabled;
+    }
+
+    // Replaces the whole set; an empty list (or null) clears it
+    if payload.get("tags").is_some() {
+        metadata.tags = parse_tags(&payload, "tags")?;
+    }
+
+    // Merge properties into the existing set; keys not present are left untouched
+    if let Some(properties) = payload.get("properties") {
+        let properties = properties.as_object()
+            .filter(|map| map.values().all(Value::is_string))
+            .ok_or_else(|| ApiError(
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Properties must be a flat object of string values" }))
+            ))?;
+
+        let policy_before = metadata.properties.get(COLUMN_POLICY_PROPERTY).cloned();
+        for (key, value) in properties {
+            if let Some(value) = value.as_str() {
+                metadata.properties.insert(key.clone(), value.to_string());
+            }
+        }
+
+        // A caller could otherwise lift their own column restrictions
+        if metadata.properties.get(COLUMN_POLICY_PROPERTY) != policy_before.as_ref() {
+            authorize_admin(&db_connection, &headers)?;
+        }
+        if let Some(policy) = metadata.properties.get(COLUMN_POLICY_PROPERTY) {
+            ColumnPolicy::parse(policy).map_err(|e| ApiError(
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e }))
+            ))?;
+        }
+    }
+
     // Update timestamp
     metadata.updated_at = Some(chrono::Utc::now());
 
@@ -457,4 +4164,297 @@ pub async fn update_database(
         Ok(updated) => Ok(Json(json!({ "database": updated }))),
         Err(e) => Err(map_db_error(e, "Failed to update database")),
     }
-} 
\ No newline at end of file
+}
+
+pub async fn vacuum_metadata(
+    State(db_connection): State<DbConnection>,
+    payload: Option<Json<Value>>,
+) -> ApiResult {
+    let retention_days = match payload.as_ref().and_then(|Json(p)| p.get("retention_days")) {
+        None | Some(Value::Null) => None,
+        Some(v) => match v.as_i64() {
+            Some(days) if days >= 0 => Some(days),
+            _ => return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "retention_days must be a non-negative integer" }))
+            ).into()),
+        },
+    };
+
+    let metadata_path = db_connection.metadata_db_path();
+    let size_before = std::fs::metadata(&metadata_path).map(|m| m.len()).unwrap_or(0);
+
+    let conn = db_connection.get_metadata_pool().get()
+        .map_err(|e| map_db_error(e, "Failed to open metadata database"))?;
+
+    // Optionally purge old audit entries and finished jobs before compacting
+    let mut purged = json!({});
+    if let Some(days) = retention_days {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+        let audit = AuditEntry::purge_older_than(&conn, cutoff)
+            .map_err(|e| map_db_error(e, "Failed to purge audit log"))?;
+        let imports = ImportJob::purge_finished_older_than(&conn, cutoff)
+            .map_err(|e| map_db_error(e, "Failed to purge import jobs"))?;
+        let exports = ExportJob::purge_finished_older_than(&conn, cutoff)
+            .map_err(|e| map_db_error(e, "Failed to purge export jobs"))?;
+        for path in &exports {
+            remove_export_artifact(&db_connection, path);
+        }
+        purged = json!({ "audit_log": audit, "import_jobs": imports, "export_jobs": exports.len() });
+    }
+
+    // The metadata database runs in WAL mode, so the compacted pages only reach
+    // the main file once the log is checkpointed
+    conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+        .map_err(|e| map_db_error(e, "Failed to vacuum metadata database"))?;
+
+    let size_after = std::fs::metadata(&metadata_path).map(|m| m.len()).unwrap_or(0);
+
+    Ok(Json(json!({
+        "size_before": size_before,
+        "size_after": size_after,
+        "reclaimed_bytes": size_before.saturating_sub(size_after),
+        "purged": purged
+    })))
+}
+
+// Snapshot of the whole metadata catalog. Each record carries its file's SHA-256
+// so an import can still find the file if it has moved within storage.
+pub async fn export_metadata(
+    State(db_connection): State<DbConnection>,
+) -> ApiResult {
+    let databases = DatabaseMetadata::list(&db_connection)
+        .map_err(|e| map_db_error(e, "Failed to list databases"))?;
+
+    let records: Vec<Value> = databases.iter()
+        .map(|database| {
+            let mut record = serde_json::to_value(database).unwrap_or_default();
+            record["sha256"] = json!(file_sha256(&database.path).ok());
+            record
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "databases": records
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataSnapshotRecord {
+    #[serde(flatten)]
+    metadata: DatabaseMetadata,
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataSnapshot {
+    databases: Vec<MetadataSnapshotRecord>,
+}
+
+// Checksums of the database files currently in storage, keyed by hash
+fn hash_storage_files(db_connection: &DbConnection) -> std::collections::HashMap<String, String> {
+    let storage_dir = db_connection.get_storage_path("databases");
+    let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(&storage_dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    paths.sort();
+
+    let mut hashes = std::collections::HashMap::new();
+    for path in paths {
+        let name = path.to_string_lossy().into_owned();
+        if !path.is_file() || ["-journal", "-wal", "-shm"].iter().any(|suffix| name.ends_with(suffix)) {
+            continue;
+        }
+        if let Ok(hash) = file_sha256(&path) {
+            hashes.entry(hash).or_insert(name);
+        }
+    }
+    hashes
+}
+
+// Rebuild the catalog from an export. Records are restored under their original ids
+// when their file is still at the recorded path, or can be found in storage by hash;
+// the rest are reported as skipped. Existing records are never overwritten.
+pub async fn import_metadata(
+    State(db_connection): State<DbConnection>,
+    Json(snapshot): Json<MetadataSnapshot>,
+) -> ApiResult {
+    // Hashing every stored file is only worth it if some recorded path has gone
+    let mut stored_hashes = None;
+    let mut restored = Vec::new();
+    let mut skipped = Vec::new();
+
+    for record in snapshot.databases {
+        let mut metadata = record.metadata;
+        let skip = |reason: &str| json!({ "id": metadata.id, "name": metadata.name, "reason": reason });
+
+        if metadata.id.is_none() {
+            skipped.push(skip("Record has no id"));
+            continue;
+        }
+
+        let matched = if std::path::Path::new(&metadata.path).is_file() {
+            Some((metadata.path.clone(), "path"))
+        } else {
+            record.sha256.as_ref().and_then(|hash| {
+                stored_hashes.get_or_insert_with(|| hash_storage_files(&db_connection))
+                    .get(hash)
+                    .map(|path| (path.clone(), "sha256"))
+            })
+        };
+        let Some((path, matched_by)) = matched else {
+            skipped.push(skip("Database file not found"));
+            continue;
+        };
+
+        metadata.size = std::fs::metadata(&path).map(|m| m.len() as i64).unwrap_or(metadata.size);
+        metadata.path = path;
+        let inserted = metadata.restore(&db_connection)
+            .map_err(|e| map_db_error(e, "Failed to restore metadata"))?;
+        if inserted {
+            restored.push(json!({ "id": metadata.id, "path": metadata.path, "matched_by": matched_by }));
+        } else {
+            skipped.push(skip("A record with this id already exists"));
+        }
+    }
+
+    Ok(Json(json!({
+        "restored": restored,
+        "skipped": skipped
+    })))
+}
+
+// Cross-reference files in the databases directory with metadata records:
+// orphaned files, paths shared by several records, and records whose file is gone
+pub async fn list_storage_files(
+    State(db_connection): State<DbConnection>,
+) -> ApiResult {
+    let databases = DatabaseMetadata::list(&db_connection)
+        .map_err(|e| map_db_error(e, "Failed to list databases"))?;
+
+    // Compare canonical paths so relative and absolute spellings of a file match
+    let canonical = |path: &std::path::Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut references: std::collections::BTreeMap<std::path::PathBuf, Vec<i64>> = Default::default();
+    for database in &databases {
+        references.entry(canonical(std::path::Path::new(&database.path)))
+            .or_default()
+            .extend(database.id);
+    }
+    references.values_mut().for_each(|ids| ids.sort_unstable());
+
+    let storage_dir = db_connection.get_storage_path("databases");
+    let entries = std::fs::read_dir(&storage_dir)
+        .map_err(|e| handle_error(e, "Failed to read storage directory"))?;
+
+    let mut files = Vec::new();
+    let mut orphans = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // SQLite's journal and WAL sidecars belong to their main file
+        if !path.is_file() || ["-journal", "-wal", "-shm"].iter().any(|suffix| name.ends_with(suffix)) {
+            continue;
+        }
+
+        let key = canonical(&path);
+        let referenced_by = references.get(&key).cloned().unwrap_or_default();
+        if referenced_by.is_empty() {
+            orphans.push(path.to_string_lossy().into_owned());
+        }
+        files.push(json!({
+            "path": path.to_string_lossy(),
+            "size": entry.metadata().map(|m| m.len()).unwrap_or(0),
+            "referenced_by": referenced_by,
+            "shared": referenced_by.len() > 1
+        }));
+        seen.insert(key);
+    }
+    files.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+    orphans.sort();
+
+    let shared: Vec<Value> = references.iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|(path, ids)| json!({ "path": path.to_string_lossy(), "referenced_by": ids }))
+        .collect();
+
+    let missing: Vec<Value> = databases.iter()
+        .filter(|database| !seen.contains(&canonical(std::path::Path::new(&database.path))))
+        .filter(|database| !std::path::Path::new(&database.path).is_file())
+        .map(|database| json!({ "id": database.id, "path": database.path }))
+        .collect();
+
+    Ok(Json(json!({
+        "files": files,
+        "orphans": orphans,
+        "shared": shared,
+        "missing": missing
+    })))
+}
+
+// The resolved configuration in effect, with secrets redacted
+pub async fn get_config(
+    State(db_connection): State<DbConnection>,
+) -> Json<Value> {
+    Json(json!({ "config": db_connection.config().redacted() }))
+}
+
+pub async fn get_features(
+    State(db_connection): State<DbConnection>,
+) -> Json<Value> {
+    Json(json!({ "features": db_connection.config().features() }))
+}
+
+pub async fn list_running_queries(
+    State(db_connection): State<DbConnection>,
+) -> Json<Value> {
+    Json(json!({ "queries": db_connection.query_registry().list() }))
+}
+
+// Latest query history across every database, newest first
+pub async fn list_recent_queries(
+    State(db_connection): State<DbConnection>,
+    Query(params): Query<PageParams>,
+) -> ApiResult {
+    let page = parse_pagination(
+        params.limit.as_deref(),
+        params.offset.as_deref(),
+        DEFAULT_PAGE_LIMIT,
+        MAX_PAGE_LIMIT,
+    )?;
+
+    QueryHistoryEntry::list_recent(&db_connection, page)
+        .map(|entries| Json(json!({ "entries": entries })))
+        .map_err(|e| map_db_error(e, "Failed to read query history"))
+}
+
+// Recent error responses across every route, newest first
+pub async fn list_recent_errors(
+    State(db_connection): State<DbConnection>,
+    Query(params): Query<PageParams>,
+) -> ApiResult {
+    let page = parse_pagination(
+        params.limit.as_deref(),
+        params.offset.as_deref(),
+        DEFAULT_PAGE_LIMIT,
+        MAX_PAGE_LIMIT,
+    )?;
+
+    Ok(Json(json!({ "errors": db_connection.error_log().recent(page) })))
+}
+
+pub async fn kill_query(
+    State(db_connection): State<DbConnection>,
+    Path(query_id): Path<u64>,
+) -> ApiResult {
+    if db_connection.query_registry().interrupt(query_id) {
+        Ok(Json(json!({ "message": "Query interrupted", "id": query_id })))
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Query not found" }))
+        ).into())
+    }
+}
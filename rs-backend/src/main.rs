@@ -7,16 +7,18 @@ use axum::{
 use tower_http::cors::{CorsLayer, Any};
 use serde_json::{json, Value};
 use dotenv::dotenv;
-use std::env;
-use tracing::{info, error};
+use tracing::{info, warn, error};
 use std::fmt::Display;
 use tokio::net::TcpListener;
 use multer::Multipart;
 use mime::{Mime, APPLICATION_OCTET_STREAM};
 
 use rs_backend::{
+    config::Config,
     db::connection::DbConnection as DbConnectionAlias,
+    db::query,
     models::database_metadata::DatabaseMetadata,
+    models::table_growth::TableGrowth,
 };
 
 #[derive(Debug)]
@@ -66,13 +68,55 @@ async fn main() {
     rs_backend::utils::logger::init_logger();
     info!("Initializing application...");
 
-    // Get port from environment or use default
-    let port = env::var("PORT").unwrap_or_else(|_| "3001".to_string()).parse::<u16>().unwrap();
+    // Resolve configuration once; everything below reads from it
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let port = config.port;
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
 
     // Initialize database connection
     info!("Initializing database connection...");
-    let db_connection = DbConnectionAlias::new();
+    let db_connection = match DbConnectionAlias::from_config(config) {
+        Ok(db_connection) => db_connection,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Warn early if the metadata table no longer matches what the code expects
+    match db_connection.get_metadata_pool().get() {
+        Ok(conn) => match rs_backend::models::database_metadata::check_schema(&conn) {
+            Ok(drift) if !drift.is_empty() => warn!("Metadata schema drift detected: {:?}", drift),
+            Ok(_) => {}
+            Err(e) => error!("Failed to check metadata schema: {}", e),
+        },
+        Err(e) => error!("Failed to check metadata schema: {}", e),
+    }
+
+    // Check the environment before accepting requests
+    let report = rs_backend::db::self_test::self_test(&db_connection);
+    for check in &report.checks {
+        if check.passed {
+            info!("Self-test PASS {}: {}", check.name, check.detail);
+        } else {
+            error!("Self-test FAIL {}: {}", check.name, check.detail);
+        }
+    }
+    let failed: Vec<&str> = report.critical_failures().map(|c| c.name).collect();
+    if failed.is_empty() {
+        info!("Self-test passed ({} checks)", report.checks.len());
+    } else if db_connection.config().self_test_strict {
+        error!("Self-test failed: {}; refusing to start (set SELF_TEST_STRICT=false to override)", failed.join(", "));
+        std::process::exit(1);
+    } else {
+        warn!("Self-test failed: {}; starting anyway because SELF_TEST_STRICT=false", failed.join(", "));
+    }
 
     // CORS configuration
     let cors = CorsLayer::new()
@@ -82,6 +126,37 @@ async fn main() {
         .allow_credentials(true)
         .max_age(std::time::Duration::from_secs(3600));
 
+    // Close read snapshots clients have stopped using
+    let reaper_connection = db_connection.clone();
+    tokio::spawn(async move {
+        let idle_timeout = reaper_connection.config().snapshot_idle_timeout;
+        let mut interval = tokio::time::interval(idle_timeout / 2);
+        loop {
+            interval.tick().await;
+            let closed = reaper_connection.snapshots().reap_idle(idle_timeout);
+            if closed > 0 {
+                info!("Closed {} idle snapshot(s)", closed);
+            }
+        }
+    });
+
+    // Record every table's row count for the growth endpoint
+    if let Some(sample_interval) = db_connection.config().row_count_sample_interval {
+        let sampler_connection = db_connection.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(sample_interval);
+            loop {
+                interval.tick().await;
+                let connection = sampler_connection.clone();
+                match tokio::task::spawn_blocking(move || TableGrowth::sample_all(&connection)).await {
+                    Ok(Ok(tables)) => info!("Sampled row counts for {} table(s)", tables),
+                    Ok(Err(e)) => error!("Failed to sample row counts: {}", e),
+                    Err(e) => error!("Row count sampler task failed: {}", e),
+                }
+            }
+        });
+    }
+
     // Create router with routes
     let app = rs_backend::create_app(db_connection).layer(cors);
 
@@ -92,7 +167,8 @@ async fn main() {
     rs_backend::utils::logger::startup_complete(port);
     
     // Start server
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses identify clients for upload quotas
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
 
 // Route handlers
@@ -132,6 +208,7 @@ pub async fn upload_database(
             
             // Allow both SQLite and generic binary types
             let sqlite_mime: Mime = "application/x-sqlite3".parse().unwrap();
+            #[allow(clippy::unnecessary_map_or)]
             let is_valid_type = content_type.map_or(true, |mime| {
                 mime == &sqlite_mime || mime == &APPLICATION_OCTET_STREAM
             });
@@ -244,7 +321,7 @@ pub async fn get_table_schema(
     let pool = db_connection.get_database_pool(&metadata.path);
     let conn = pool.get().map_err(|e| map_db_error(e, "Failed to open database"))?;
 
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", rs_backend::utils::quote_identifier(&table)))
         .map_err(|e| map_db_error(e, "Failed to read table schema"))?;
 
     let schema: Vec<Value> = stmt.query_map([], |row| {
@@ -292,46 +369,11 @@ pub async fn execute_query(
     let mut stmt = conn.prepare(sql)
         .map_err(|e| map_db_error(e, "Failed to prepare query"))?;
 
-    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
-    
-    // Collect rows with owned values
-    let raw_rows: Vec<Vec<rusqlite::types::Value>> = stmt.query_map([], |row| {
-        let mut row_data = Vec::with_capacity(columns.len());
-        for i in 0..columns.len() {
-            // Convert to owned Value type
-            let value = match row.get_ref(i)? {
-                rusqlite::types::ValueRef::Null => rusqlite::types::Value::Null,
-                rusqlite::types::ValueRef::Integer(i) => rusqlite::types::Value::Integer(i),
-                rusqlite::types::ValueRef::Real(f) => rusqlite::types::Value::Real(f),
-                rusqlite::types::ValueRef::Text(t) => rusqlite::types::Value::Text(String::from_utf8_lossy(t).into_owned()),
-                rusqlite::types::ValueRef::Blob(b) => rusqlite::types::Value::Blob(b.to_vec()),
-            };
-            row_data.push(value);
-        }
-        Ok(row_data)
-    })?.collect::<Result<_, _>>()
-    .map_err(|e| map_db_error(e, "Failed to collect results"))?;
-
-    // Process rows in parallel
-    use rayon::prelude::*;
-    let rows: Vec<Value> = raw_rows.par_iter()
-        .map(|row_data| {
-            let mut obj = serde_json::Map::new();
-            for (i, column) in columns.iter().enumerate() {
-                let value = match &row_data[i] {
-                    rusqlite::types::Value::Null => Value::Null,
-                    rusqlite::types::Value::Integer(i) => Value::Number((*i).into()),
-                    rusqlite::types::Value::Real(f) => Value::Number(
-                        serde_json::Number::from_f64(*f).unwrap_or_else(|| serde_json::Number::from(0))
-                    ),
-                    rusqlite::types::Value::Text(s) => Value::String(s.clone()),
-                    rusqlite::types::Value::Blob(b) => Value::String(format!("<Binary data: {} bytes>", b.len())),
-                };
-                obj.insert(column.clone(), value);
-            }
-            Value::Object(obj)
-        })
-        .collect();
+    // Same cell conversion as the library handlers, including how invalid UTF-8 is signalled
+    let columns = query::column_names(&stmt);
+    let raw_rows = query::read_rows(&mut stmt, [], None)
+        .map_err(|e| map_db_error(e, "Failed to collect results"))?;
+    let rows = query::rows_to_objects(db_connection.row_converter(), &columns, &raw_rows);
 
     Ok(Json(json!({ "rows": rows })))
 } 
\ No newline at end of file
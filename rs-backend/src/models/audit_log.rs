@@ -0,0 +1,83 @@
+use serde::{Serialize, Deserialize};
+use rusqlite::{params, Connection};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::db::connection::DbConnection;
+use crate::utils::pagination::Pagination;
+
+// Per-database audit trail of executed queries, kept apart from general history
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub database_id: i64,
+    pub query: String,
+    pub client_id: Option<String>,
+    pub row_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY,
+                database_id INTEGER NOT NULL,
+                query TEXT NOT NULL,
+                client_id TEXT,
+                row_count INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_audit_log_database_id ON audit_log (database_id, id);"
+        )
+    }
+
+    pub fn record(
+        db_connection: &DbConnection,
+        database_id: i64,
+        query: &str,
+        client_id: Option<&str>,
+        row_count: i64,
+    ) -> Result<()> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        conn.execute(
+            "INSERT INTO audit_log (database_id, query, client_id, row_count, created_at)
+             VALUES (?, ?, ?, ?, ?)",
+            params![database_id, query, client_id, row_count, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_for_database(db_connection: &DbConnection, database_id: i64, page: Pagination) -> Result<Vec<AuditEntry>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, database_id, query, client_id, row_count, created_at
+             FROM audit_log
+             WHERE database_id = ?
+             ORDER BY id DESC
+             LIMIT ? OFFSET ?"
+        )?;
+
+        let entries = stmt.query_map(params![database_id, page.limit, page.offset], |row| {
+            let created_at: String = row.get(5)?;
+            let created_at = DateTime::parse_from_rfc3339(&created_at)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(e)))?;
+
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                database_id: row.get(1)?,
+                query: row.get(2)?,
+                client_id: row.get(3)?,
+                row_count: row.get(4)?,
+                created_at,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    pub fn purge_older_than(conn: &Connection, cutoff: DateTime<Utc>) -> rusqlite::Result<usize> {
+        conn.execute("DELETE FROM audit_log WHERE created_at < ?", params![cutoff.to_rfc3339()])
+    }
+}
@@ -1,10 +1,71 @@
 use serde::{Serialize, Deserialize};
-use rusqlite::{Connection, params, types::{FromSql, ToSql, ValueRef}};
+use rusqlite::{Connection, TransactionBehavior, params, params_from_iter, types::{FromSql, ToSql, ValueRef}};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
 use crate::db::connection::DbConnection;
+use crate::utils::pagination::Pagination;
 use rusqlite::OptionalExtension;
 
+// Columns selected by every query that maps rows through `DatabaseMetadata::from_row`
+const SELECT_COLUMNS: &str =
+    "id, name, path, size, table_count, is_favorite, notes, created_at, updated_at, properties, audit_enabled, tags, checksum,
+     original_copy, original_journal_mode, integrity";
+
+// Columns added after the original schema, applied to existing metadata databases
+const MIGRATED_COLUMNS: &[(&str, &str)] = &[
+    ("properties", "TEXT"),
+    ("audit_enabled", "BOOLEAN NOT NULL DEFAULT 0"),
+    ("tags", "TEXT"),
+    ("checksum", "TEXT"),
+    ("original_copy", "TEXT"),
+    ("original_journal_mode", "TEXT"),
+    ("integrity", "TEXT"),
+];
+
+// Full column set and declared types the code expects the metadata table to have
+const EXPECTED_COLUMNS: &[(&str, &str)] = &[
+    ("id", "INTEGER"),
+    ("name", "TEXT"),
+    ("path", "TEXT"),
+    ("size", "INTEGER"),
+    ("table_count", "INTEGER"),
+    ("is_favorite", "BOOLEAN"),
+    ("notes", "TEXT"),
+    ("created_at", "TEXT"),
+    ("updated_at", "TEXT"),
+    ("properties", "TEXT"),
+    ("audit_enabled", "BOOLEAN"),
+    ("tags", "TEXT"),
+    ("checksum", "TEXT"),
+    ("original_copy", "TEXT"),
+    ("original_journal_mode", "TEXT"),
+    ("integrity", "TEXT"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeMismatch {
+    pub column: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+// Differences between the live metadata table and `EXPECTED_COLUMNS`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SchemaDrift {
+    pub missing_columns: Vec<String>,
+    pub unexpected_columns: Vec<String>,
+    pub type_mismatches: Vec<TypeMismatch>,
+}
+
+impl SchemaDrift {
+    pub fn is_empty(&self) -> bool {
+        self.missing_columns.is_empty()
+            && self.unexpected_columns.is_empty()
+            && self.type_mismatches.is_empty()
+    }
+}
+
 // Wrapper type for DateTime<Utc> to implement rusqlite traits
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DbDateTime(DateTime<Utc>);
@@ -50,6 +111,149 @@ pub struct DatabaseMetadata {
     pub created_at: Option<DateTime<Utc>>,
     #[serde(with = "datetime_serialization")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+    #[serde(default)]
+    pub audit_enabled: bool,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    // SHA-256 of the file as uploaded; None for databases stored before checksums were kept
+    #[serde(default)]
+    pub checksum: Option<String>,
+    // Storage key of the untouched uploaded file, kept so the database can be reset.
+    // Set by the server only, never through properties.
+    #[serde(default)]
+    pub original_copy: Option<String>,
+    // Journal mode the file was uploaded in, when it was converted to WAL on upload
+    #[serde(default)]
+    pub original_journal_mode: Option<String>,
+    // Outcome of the latest integrity check, "ok" or "corrupt"
+    #[serde(default)]
+    pub integrity: Option<String>,
+}
+
+// Columns a listing can be sorted by. Only these reach the ORDER BY clause, so a
+// sort requested by name can't inject SQL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortColumn {
+    Name,
+    Size,
+    #[default]
+    CreatedAt,
+    TableCount,
+}
+
+impl SortColumn {
+    pub const NAMES: &'static [&'static str] = &["name", "size", "created_at", "table_count"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Self::Name),
+            "size" => Some(Self::Size),
+            "created_at" => Some(Self::CreatedAt),
+            "table_count" => Some(Self::TableCount),
+            _ => None,
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::CreatedAt => "created_at",
+            Self::TableCount => "table_count",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "asc" => Some(Self::Asc),
+            "desc" => Some(Self::Desc),
+            _ => None,
+        }
+    }
+
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+// Filters applied by `DatabaseMetadata::list_filtered`
+#[derive(Debug, Default, Clone)]
+pub struct ListFilter {
+    // Case-insensitive substring of the name
+    pub name: Option<String>,
+    pub property: Option<(String, String)>,
+    pub tag: Option<String>,
+    pub favorites_only: bool,
+    // Newest first by default
+    pub sort: SortColumn,
+    pub order: SortOrder,
+    // Unpaginated when absent
+    pub page: Option<Pagination>,
+}
+
+impl ListFilter {
+    // The WHERE clause (empty when nothing is filtered) and its bound values
+    fn where_clause(&self) -> (String, Vec<rusqlite::types::Value>) {
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+
+        if let Some(name) = &self.name {
+            conditions.push("instr(lower(database_metadata.name), lower(?)) > 0");
+            values.push(name.clone().into());
+        }
+
+        if let Some((key, value)) = &self.property {
+            conditions.push(
+                "EXISTS (SELECT 1 FROM json_each(database_metadata.properties) WHERE key = ? AND value = ?)"
+            );
+            values.push(key.clone().into());
+            values.push(value.clone().into());
+        }
+
+        if let Some(tag) = &self.tag {
+            conditions.push("EXISTS (SELECT 1 FROM json_each(database_metadata.tags) WHERE value = ?)");
+            values.push(tag.clone().into());
+        }
+
+        if self.favorites_only {
+            conditions.push("database_metadata.is_favorite = 1");
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        (where_clause, values)
+    }
+
+    // Ties are broken by id so pages don't overlap
+    fn order_clause(&self) -> String {
+        let order = self.order.keyword();
+        format!("ORDER BY {} {}, id {}", self.sort.column(), order, order)
+    }
+}
+
+// Stored as a JSON array, or NULL when there are none
+fn tags_json(tags: &BTreeSet<String>) -> Result<Option<String>> {
+    if tags.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(tags)?))
 }
 
 // Helper module for DateTime serialization
@@ -100,33 +304,87 @@ impl DatabaseMetadata {
             notes,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
+            properties: BTreeMap::new(),
+            audit_enabled: false,
+            tags: BTreeSet::new(),
+            checksum: None,
+            original_copy: None,
+            original_journal_mode: None,
+            integrity: None,
         }
     }
 
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<DatabaseMetadata> {
+        let created_at: DbDateTime = row.get(7)?;
+        let updated_at: DbDateTime = row.get(8)?;
+        let properties: Option<String> = row.get(9)?;
+        let properties = match properties {
+            Some(json) => serde_json::from_str(&json).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            None => BTreeMap::new(),
+        };
+        let tags: Option<String> = row.get(11)?;
+        let tags = match tags {
+            Some(json) => serde_json::from_str(&json).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(11, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            None => BTreeSet::new(),
+        };
+
+        Ok(DatabaseMetadata {
+            id: Some(row.get(0)?),
+            name: row.get(1)?,
+            path: row.get(2)?,
+            size: row.get(3)?,
+            table_count: row.get(4)?,
+            is_favorite: row.get(5)?,
+            notes: row.get(6)?,
+            created_at: Some(created_at.into()),
+            updated_at: Some(updated_at.into()),
+            properties,
+            audit_enabled: row.get(10)?,
+            tags,
+            checksum: row.get(12)?,
+            original_copy: row.get(13)?,
+            original_journal_mode: row.get(14)?,
+            integrity: row.get(15)?,
+        })
+    }
+
+    fn properties_json(&self) -> Result<Option<String>> {
+        if self.properties.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::to_string(&self.properties)?))
+    }
+
+    fn tags_json(&self) -> Result<Option<String>> {
+        tags_json(&self.tags)
+    }
+
     pub fn list(db_connection: &DbConnection) -> Result<Vec<DatabaseMetadata>> {
+        Self::list_filtered(db_connection, &ListFilter::default())
+    }
+
+    pub fn list_filtered(db_connection: &DbConnection, filter: &ListFilter) -> Result<Vec<DatabaseMetadata>> {
         let conn = Self::init_metadata_db(db_connection)?;
-        let mut stmt = conn.prepare(
-            "SELECT id, name, path, size, table_count, is_favorite, notes, created_at, updated_at 
-             FROM database_metadata 
-             ORDER BY created_at DESC"
-        )?;
 
-        let metadata_iter = stmt.query_map([], |row| {
-            let created_at: DbDateTime = row.get(7)?;
-            let updated_at: DbDateTime = row.get(8)?;
-            
-            Ok(DatabaseMetadata {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                path: row.get(2)?,
-                size: row.get(3)?,
-                table_count: row.get(4)?,
-                is_favorite: row.get(5)?,
-                notes: row.get(6)?,
-                created_at: Some(created_at.into()),
-                updated_at: Some(updated_at.into()),
-            })
-        })?;
+        let (where_clause, mut values) = filter.where_clause();
+
+        let mut page_clause = "";
+        if let Some(page) = filter.page {
+            page_clause = "LIMIT ? OFFSET ?";
+            values.push(page.limit.into());
+            values.push(page.offset.into());
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM database_metadata {} {} {}",
+            SELECT_COLUMNS, where_clause, filter.order_clause(), page_clause
+        ))?;
+
+        let metadata_iter = stmt.query_map(params_from_iter(values), Self::from_row)?;
 
         let mut metadata = Vec::new();
         for item in metadata_iter {
@@ -136,6 +394,65 @@ impl DatabaseMetadata {
         Ok(metadata)
     }
 
+    // Records whose name or notes contain `query`, ignoring ASCII case, newest first.
+    // `%` and `_` in the query match themselves; an empty query matches nothing.
+    pub fn search(db_connection: &DbConnection, query: &str) -> Result<Vec<DatabaseMetadata>> {
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pattern = format!(
+            "%{}%",
+            query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+
+        let conn = Self::init_metadata_db(db_connection)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM database_metadata
+             WHERE name LIKE ?1 ESCAPE '\\' OR notes LIKE ?1 ESCAPE '\\'
+             ORDER BY created_at DESC",
+            SELECT_COLUMNS
+        ))?;
+        let metadata = stmt.query_map(params![pattern], Self::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(metadata)
+    }
+
+    // Add and remove tags on every record matching `filter` (its page is ignored)
+    // in one transaction, returning how many records matched
+    pub fn bulk_tag(
+        db_connection: &DbConnection,
+        filter: &ListFilter,
+        add: &BTreeSet<String>,
+        remove: &BTreeSet<String>,
+    ) -> Result<usize> {
+        let mut conn = Self::init_metadata_db(db_connection)?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let (where_clause, values) = filter.where_clause();
+        let matched: Vec<(i64, Option<String>)> = tx
+            .prepare(&format!("SELECT id, tags FROM database_metadata {}", where_clause))?
+            .query_map(params_from_iter(values), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let now = DbDateTime::from(Utc::now());
+        for (id, tags) in &matched {
+            let mut tags: BTreeSet<String> = match tags {
+                Some(json) => serde_json::from_str(json)?,
+                None => BTreeSet::new(),
+            };
+            tags.extend(add.iter().cloned());
+            tags.retain(|tag| !remove.contains(tag));
+            tx.execute(
+                "UPDATE database_metadata SET tags = ?, updated_at = ? WHERE id = ?",
+                params![tags_json(&tags)?, now, id],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(matched.len())
+    }
+
     pub fn save(&self, db_connection: &DbConnection) -> Result<DatabaseMetadata> {
         let conn = Self::init_metadata_db(db_connection)?;
         
@@ -143,7 +460,9 @@ impl DatabaseMetadata {
             // Update existing record
             conn.execute(
                 "UPDATE database_metadata 
-                 SET name = ?, path = ?, size = ?, table_count = ?, is_favorite = ?, notes = ?, updated_at = ?
+                 SET name = ?, path = ?, size = ?, table_count = ?, is_favorite = ?, notes = ?, updated_at = ?,
+                     properties = ?, audit_enabled = ?, tags = ?, checksum = ?, original_copy = ?,
+                     original_journal_mode = ?, integrity = ?
                  WHERE id = ?",
                 params![
                     self.name,
@@ -153,16 +472,36 @@ impl DatabaseMetadata {
                     self.is_favorite,
                     self.notes,
                     DbDateTime::from(self.updated_at.unwrap_or_else(Utc::now)),
+                    self.properties_json()?,
+                    self.audit_enabled,
+                    self.tags_json()?,
+                    self.checksum,
+                    self.original_copy,
+                    self.original_journal_mode,
+                    self.integrity,
                     id,
                 ],
             )?;
             Ok(self.clone())
         } else {
-            // Insert new record
-            conn.execute(
-                "INSERT INTO database_metadata 
-                 (name, path, size, table_count, is_favorite, notes, created_at, updated_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            // Insert new record. A record with the checksum and path of one already
+            // stored updates that one instead, so a save retried after a lost response
+            // lands on the same row; records without a checksum have no natural key.
+            // The same checksum on another file fails like a plain insert would.
+            let id: Option<i64> = conn.query_row(
+                "INSERT INTO database_metadata
+                 (name, path, size, table_count, is_favorite, notes, created_at, updated_at, properties,
+                  audit_enabled, tags, checksum, original_copy, original_journal_mode, integrity)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                 ON CONFLICT(checksum) WHERE checksum IS NOT NULL DO UPDATE
+                 SET name = excluded.name, path = excluded.path, size = excluded.size,
+                     table_count = excluded.table_count, is_favorite = excluded.is_favorite,
+                     notes = excluded.notes, updated_at = excluded.updated_at,
+                     properties = excluded.properties, audit_enabled = excluded.audit_enabled,
+                     tags = excluded.tags, original_copy = excluded.original_copy,
+                     original_journal_mode = excluded.original_journal_mode, integrity = excluded.integrity
+                 WHERE database_metadata.path = excluded.path
+                 RETURNING id",
                 params![
                     self.name,
                     self.path,
@@ -172,40 +511,99 @@ impl DatabaseMetadata {
                     self.notes,
                     DbDateTime::from(self.created_at.unwrap_or_else(Utc::now)),
                     DbDateTime::from(self.updated_at.unwrap_or_else(Utc::now)),
+                    self.properties_json()?,
+                    self.audit_enabled,
+                    self.tags_json()?,
+                    self.checksum,
+                    self.original_copy,
+                    self.original_journal_mode,
+                    self.integrity,
                 ],
-            )?;
-            
-            let id = conn.last_insert_rowid();
-            let mut new_metadata = self.clone();
-            new_metadata.id = Some(id);
-            Ok(new_metadata)
+                |row| row.get(0),
+            ).optional()?;
+            let Some(id) = id else {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE),
+                    Some("UNIQUE constraint failed: database_metadata.checksum".to_string()),
+                ).into());
+            };
+
+            Self::find_by_id(db_connection, id)?
+                .ok_or_else(|| anyhow::anyhow!("Database metadata {} vanished during save", id))
         }
     }
 
-    pub fn find_by_id(db_connection: &DbConnection, id: i64) -> Result<Option<DatabaseMetadata>> {
+    // Re-insert a record under its original id (and timestamps), e.g. from a catalog snapshot.
+    // Returns false without changing anything if the id or checksum is already taken.
+    pub fn restore(&self, db_connection: &DbConnection) -> Result<bool> {
         let conn = Self::init_metadata_db(db_connection)?;
-        let mut stmt = conn.prepare(
-            "SELECT id, name, path, size, table_count, is_favorite, notes, created_at, updated_at 
-             FROM database_metadata 
-             WHERE id = ?"
+        let inserted = conn.execute(
+            "INSERT INTO database_metadata
+             (id, name, path, size, table_count, is_favorite, notes, created_at, updated_at, properties,
+              audit_enabled, tags, checksum, original_copy, original_journal_mode, integrity)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT DO NOTHING",
+            params![
+                self.id,
+                self.name,
+                self.path,
+                self.size,
+                self.table_count,
+                self.is_favorite,
+                self.notes,
+                DbDateTime::from(self.created_at.unwrap_or_else(Utc::now)),
+                DbDateTime::from(self.updated_at.unwrap_or_else(Utc::now)),
+                self.properties_json()?,
+                self.audit_enabled,
+                self.tags_json()?,
+                self.checksum,
+                self.original_copy,
+                self.original_journal_mode,
+                self.integrity,
+            ],
         )?;
+        Ok(inserted > 0)
+    }
+
+    pub fn find_by_id(db_connection: &DbConnection, id: i64) -> Result<Option<DatabaseMetadata>> {
+        let conn = Self::init_metadata_db(db_connection)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM database_metadata WHERE id = ?",
+            SELECT_COLUMNS
+        ))?;
+
+        let metadata = stmt.query_row(params![id], Self::from_row).optional()?;
+
+        Ok(metadata)
+    }
+
+    pub fn count(db_connection: &DbConnection) -> Result<i64> {
+        let conn = Self::init_metadata_db(db_connection)?;
+        let count = conn.query_row("SELECT COUNT(*) FROM database_metadata", [], |row| row.get(0))?;
+        Ok(count)
+    }
 
-        let metadata = stmt.query_row(params![id], |row| {
-            let created_at: DbDateTime = row.get(7)?;
-            let updated_at: DbDateTime = row.get(8)?;
-            
-            Ok(DatabaseMetadata {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                path: row.get(2)?,
-                size: row.get(3)?,
-                table_count: row.get(4)?,
-                is_favorite: row.get(5)?,
-                notes: row.get(6)?,
-                created_at: Some(created_at.into()),
-                updated_at: Some(updated_at.into()),
-            })
-        }).optional()?;
+    pub fn find_by_name(db_connection: &DbConnection, name: &str) -> Result<Option<DatabaseMetadata>> {
+        let conn = Self::init_metadata_db(db_connection)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM database_metadata WHERE name = ? ORDER BY created_at DESC LIMIT 1",
+            SELECT_COLUMNS
+        ))?;
+
+        let metadata = stmt.query_row(params![name], Self::from_row).optional()?;
+
+        Ok(metadata)
+    }
+
+    // The oldest database uploaded with this checksum
+    pub fn find_by_checksum(db_connection: &DbConnection, checksum: &str) -> Result<Option<DatabaseMetadata>> {
+        let conn = Self::init_metadata_db(db_connection)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM database_metadata WHERE checksum = ? ORDER BY id LIMIT 1",
+            SELECT_COLUMNS
+        ))?;
+
+        let metadata = stmt.query_row(params![checksum], Self::from_row).optional()?;
 
         Ok(metadata)
     }
@@ -235,11 +633,82 @@ impl DatabaseMetadata {
                 is_favorite BOOLEAN NOT NULL DEFAULT 0,
                 notes TEXT,
                 created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
+                updated_at TEXT NOT NULL,
+                properties TEXT,
+                audit_enabled BOOLEAN NOT NULL DEFAULT 0,
+                tags TEXT,
+                checksum TEXT,
+                original_copy TEXT,
+                original_journal_mode TEXT,
+                integrity TEXT
             )",
             [],
         )?;
+        migrate_schema(&conn)?;
 
         Ok(conn)
     }
-} 
\ No newline at end of file
+}
+
+// Add any columns introduced since the metadata table was first created
+pub fn migrate_schema(conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("PRAGMA table_info(database_metadata)")?;
+    let existing: Vec<String> = stmt.query_map([], |row| row.get(1))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    for (column, column_type) in MIGRATED_COLUMNS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute(
+                &format!("ALTER TABLE database_metadata ADD COLUMN {} {}", column, column_type),
+                [],
+            )?;
+        }
+    }
+
+    // One record per checksum, which `save` upserts on. Catalogs written before the
+    // index may hold duplicates; all but the oldest lose their checksum.
+    let indexed: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_database_metadata_checksum')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !indexed {
+        conn.execute_batch(
+            "UPDATE database_metadata SET checksum = NULL
+             WHERE checksum IS NOT NULL
+               AND id > (SELECT MIN(id) FROM database_metadata AS first WHERE first.checksum = database_metadata.checksum);
+             CREATE UNIQUE INDEX IF NOT EXISTS idx_database_metadata_checksum
+             ON database_metadata (checksum) WHERE checksum IS NOT NULL;"
+        )?;
+    }
+
+    Ok(())
+}
+
+// Compare the metadata table against the expected columns without altering it
+pub fn check_schema(conn: &Connection) -> rusqlite::Result<SchemaDrift> {
+    let mut stmt = conn.prepare("PRAGMA table_info(database_metadata)")?;
+    let existing: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut drift = SchemaDrift::default();
+    for (column, expected_type) in EXPECTED_COLUMNS {
+        match existing.iter().find(|(name, _)| name == column) {
+            None => drift.missing_columns.push(column.to_string()),
+            Some((_, actual)) if !actual.eq_ignore_ascii_case(expected_type) => {
+                drift.type_mismatches.push(TypeMismatch {
+                    column: column.to_string(),
+                    expected: expected_type.to_string(),
+                    actual: actual.clone(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    drift.unexpected_columns = existing.into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| !EXPECTED_COLUMNS.iter().any(|(column, _)| column == name))
+        .collect();
+
+    Ok(drift)
+}
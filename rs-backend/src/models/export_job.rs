@@ -0,0 +1,169 @@
+use serde::{Serialize, Deserialize};
+use rusqlite::{params, Connection, OptionalExtension};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::db::connection::DbConnection;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+const COLUMNS: &str = "id, database_id, format, status, rows_written, bytes_written, file_path, error, created_at, updated_at";
+
+// Progress record for a background export, persisted in the metadata database.
+// Cancelling is a status change the export notices at its next progress update.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportJob {
+    pub id: i64,
+    pub database_id: i64,
+    pub format: String,
+    pub status: String,
+    pub rows_written: i64,
+    pub bytes_written: i64,
+    // Finished artifact, once completed
+    #[serde(skip)]
+    pub file_path: Option<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl ExportJob {
+    pub fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS export_jobs (
+                id INTEGER PRIMARY KEY,
+                database_id INTEGER NOT NULL,
+                format TEXT NOT NULL,
+                status TEXT NOT NULL,
+                rows_written INTEGER NOT NULL DEFAULT 0,
+                bytes_written INTEGER NOT NULL DEFAULT 0,
+                file_path TEXT,
+                error TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )"
+        )
+    }
+
+    // Jobs still marked active at startup were interrupted by a restart
+    pub fn fail_interrupted(conn: &Connection) -> rusqlite::Result<usize> {
+        conn.execute(
+            "UPDATE export_jobs SET status = ?, error = ?, updated_at = ? WHERE status IN (?, ?)",
+            params![
+                STATUS_FAILED,
+                "Interrupted by server restart",
+                Utc::now().to_rfc3339(),
+                STATUS_PENDING,
+                STATUS_RUNNING,
+            ],
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<ExportJob> {
+        Ok(ExportJob {
+            id: row.get(0)?,
+            database_id: row.get(1)?,
+            format: row.get(2)?,
+            status: row.get(3)?,
+            rows_written: row.get(4)?,
+            bytes_written: row.get(5)?,
+            file_path: row.get(6)?,
+            error: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        })
+    }
+
+    pub fn create(db_connection: &DbConnection, database_id: i64, format: &str) -> Result<ExportJob> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO export_jobs (database_id, format, status, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+            params![database_id, format, STATUS_PENDING, now, now],
+        )?;
+        let id = conn.last_insert_rowid();
+        Self::find_by_id(db_connection, id)?
+            .ok_or_else(|| anyhow::anyhow!("Export job {} vanished after insert", id))
+    }
+
+    pub fn find_by_id(db_connection: &DbConnection, id: i64) -> Result<Option<ExportJob>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let job = conn.query_row(
+            &format!("SELECT {} FROM export_jobs WHERE id = ?", COLUMNS),
+            params![id],
+            Self::from_row,
+        ).optional()?;
+        Ok(job)
+    }
+
+    // Record progress; returns false once the job is no longer active (i.e. cancelled)
+    pub fn update_progress(db_connection: &DbConnection, id: i64, rows_written: i64, bytes_written: i64) -> Result<bool> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let updated = conn.execute(
+            "UPDATE export_jobs SET status = ?, rows_written = ?, bytes_written = ?, updated_at = ?
+             WHERE id = ? AND status IN (?, ?)",
+            params![STATUS_RUNNING, rows_written, bytes_written, Utc::now().to_rfc3339(), id, STATUS_PENDING, STATUS_RUNNING],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Returns false if the job was cancelled before it could be marked complete
+    pub fn complete(db_connection: &DbConnection, id: i64, rows_written: i64, bytes_written: i64, file_path: &str) -> Result<bool> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let updated = conn.execute(
+            "UPDATE export_jobs SET status = ?, rows_written = ?, bytes_written = ?, file_path = ?, updated_at = ?
+             WHERE id = ? AND status IN (?, ?)",
+            params![
+                STATUS_COMPLETED, rows_written, bytes_written, file_path, Utc::now().to_rfc3339(),
+                id, STATUS_PENDING, STATUS_RUNNING,
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn fail(db_connection: &DbConnection, id: i64, error: &str) -> Result<()> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        conn.execute(
+            "UPDATE export_jobs SET status = ?, error = ?, updated_at = ? WHERE id = ? AND status IN (?, ?)",
+            params![STATUS_FAILED, error, Utc::now().to_rfc3339(), id, STATUS_PENDING, STATUS_RUNNING],
+        )?;
+        Ok(())
+    }
+
+    // Returns false if the job had already finished
+    pub fn cancel(db_connection: &DbConnection, id: i64) -> Result<bool> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let updated = conn.execute(
+            "UPDATE export_jobs SET status = ?, updated_at = ? WHERE id = ? AND status IN (?, ?)",
+            params![STATUS_CANCELLED, Utc::now().to_rfc3339(), id, STATUS_PENDING, STATUS_RUNNING],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Remove every job for a database, returning their artifacts' paths so the
+    // caller can delete the files. Jobs still running stop at their next progress update.
+    pub fn delete_for_database(db_connection: &DbConnection, database_id: i64) -> Result<Vec<String>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let paths = conn
+            .prepare("SELECT file_path FROM export_jobs WHERE database_id = ? AND file_path IS NOT NULL")?
+            .query_map(params![database_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        conn.execute("DELETE FROM export_jobs WHERE database_id = ?", params![database_id])?;
+        Ok(paths)
+    }
+
+    // Remove finished jobs last updated before the cutoff, returning their
+    // artifacts' paths so the caller can delete the files
+    pub fn purge_finished_older_than(conn: &Connection, cutoff: DateTime<Utc>) -> rusqlite::Result<Vec<String>> {
+        let finished = params![STATUS_COMPLETED, STATUS_FAILED, STATUS_CANCELLED, cutoff.to_rfc3339()];
+        let paths = conn
+            .prepare("SELECT file_path FROM export_jobs WHERE status IN (?, ?, ?) AND updated_at < ? AND file_path IS NOT NULL")?
+            .query_map(finished, |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        conn.execute("DELETE FROM export_jobs WHERE status IN (?, ?, ?) AND updated_at < ?", finished)?;
+        Ok(paths)
+    }
+}
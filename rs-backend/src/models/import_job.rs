@@ -0,0 +1,137 @@
+use serde::{Serialize, Deserialize};
+use rusqlite::{params, Connection, OptionalExtension};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::db::connection::DbConnection;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+
+// Progress record for a background import, persisted in the metadata database
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportJob {
+    pub id: i64,
+    pub kind: String,
+    pub status: String,
+    pub database_name: String,
+    pub table_name: String,
+    pub rows_processed: i64,
+    pub total_rows: Option<i64>,
+    pub database_id: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl ImportJob {
+    pub fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS import_jobs (
+                id INTEGER PRIMARY KEY,
+                kind TEXT NOT NULL,
+                status TEXT NOT NULL,
+                database_name TEXT NOT NULL,
+                table_name TEXT NOT NULL,
+                rows_processed INTEGER NOT NULL DEFAULT 0,
+                total_rows INTEGER,
+                database_id INTEGER,
+                error TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )"
+        )
+    }
+
+    // Jobs still marked active at startup were interrupted by a restart
+    pub fn fail_interrupted(conn: &Connection) -> rusqlite::Result<usize> {
+        conn.execute(
+            "UPDATE import_jobs SET status = ?, error = ?, updated_at = ? WHERE status IN (?, ?)",
+            params![
+                STATUS_FAILED,
+                "Interrupted by server restart",
+                Utc::now().to_rfc3339(),
+                STATUS_PENDING,
+                STATUS_RUNNING,
+            ],
+        )
+    }
+
+    pub fn create(db_connection: &DbConnection, kind: &str, database_name: &str, table_name: &str) -> Result<ImportJob> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO import_jobs (kind, status, database_name, table_name, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![kind, STATUS_PENDING, database_name, table_name, now, now],
+        )?;
+        let id = conn.last_insert_rowid();
+        Self::find_by_id(db_connection, id)?
+            .ok_or_else(|| anyhow::anyhow!("Import job {} vanished after insert", id))
+    }
+
+    pub fn find_by_id(db_connection: &DbConnection, id: i64) -> Result<Option<ImportJob>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let job = conn.query_row(
+            "SELECT id, kind, status, database_name, table_name, rows_processed, total_rows,
+                    database_id, error, created_at, updated_at
+             FROM import_jobs WHERE id = ?",
+            params![id],
+            |row| Ok(ImportJob {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                status: row.get(2)?,
+                database_name: row.get(3)?,
+                table_name: row.get(4)?,
+                rows_processed: row.get(5)?,
+                total_rows: row.get(6)?,
+                database_id: row.get(7)?,
+                error: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+            }),
+        ).optional()?;
+        Ok(job)
+    }
+
+    pub fn update_progress(
+        db_connection: &DbConnection,
+        id: i64,
+        rows_processed: i64,
+        total_rows: Option<i64>,
+    ) -> Result<()> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        conn.execute(
+            "UPDATE import_jobs SET status = ?, rows_processed = ?, total_rows = ?, updated_at = ? WHERE id = ?",
+            params![STATUS_RUNNING, rows_processed, total_rows, Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    pub fn complete(db_connection: &DbConnection, id: i64, database_id: i64) -> Result<()> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        conn.execute(
+            "UPDATE import_jobs SET status = ?, database_id = ?, updated_at = ? WHERE id = ?",
+            params![STATUS_COMPLETED, database_id, Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    pub fn fail(db_connection: &DbConnection, id: i64, error: &str) -> Result<()> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        conn.execute(
+            "UPDATE import_jobs SET status = ?, error = ?, updated_at = ? WHERE id = ?",
+            params![STATUS_FAILED, error, Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    // Remove finished jobs last updated before the cutoff
+    pub fn purge_finished_older_than(conn: &Connection, cutoff: DateTime<Utc>) -> rusqlite::Result<usize> {
+        conn.execute(
+            "DELETE FROM import_jobs WHERE status IN (?, ?) AND updated_at < ?",
+            params![STATUS_COMPLETED, STATUS_FAILED, cutoff.to_rfc3339()],
+        )
+    }
+}
@@ -1 +1,7 @@
-pub mod database_metadata; 
\ No newline at end of file
+pub mod audit_log;
+pub mod database_metadata;
+pub mod export_job;
+pub mod import_job;
+pub mod query_history;
+pub mod saved_query;
+pub mod table_growth;
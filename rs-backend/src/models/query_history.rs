@@ -0,0 +1,160 @@
+use serde::{Serialize, Deserialize};
+use rusqlite::{params, Connection};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::db::connection::DbConnection;
+use crate::utils::pagination::Pagination;
+
+// Limits applied to query history each time an entry is recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryRetention {
+    // Newest entries kept per database
+    pub max_entries: Option<usize>,
+    // Entries older than this many days are dropped
+    pub max_age_days: Option<i64>,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_entries: Some(1000),
+            max_age_days: Some(30),
+        }
+    }
+}
+
+// Every successful query run against a database, for recall by clients
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryHistoryEntry {
+    pub id: i64,
+    pub database_id: i64,
+    pub query: String,
+    pub row_count: i64,
+    pub duration_ms: i64,
+    pub executed_at: DateTime<Utc>,
+}
+
+// A history entry attributed to its database, for the cross-database activity view
+#[derive(Debug, Serialize, Clone)]
+pub struct RecentQuery {
+    #[serde(flatten)]
+    pub entry: QueryHistoryEntry,
+    // None once the database has been deleted
+    pub database_name: Option<String>,
+}
+
+impl QueryHistoryEntry {
+    pub fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS query_history (
+                id INTEGER PRIMARY KEY,
+                database_id INTEGER NOT NULL,
+                query TEXT NOT NULL,
+                row_count INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                executed_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_query_history_database_id ON query_history (database_id, id);"
+        )
+    }
+
+    // Insert an entry, then trim the database's history to the retention limits
+    pub fn record(
+        db_connection: &DbConnection,
+        database_id: i64,
+        query: &str,
+        row_count: i64,
+        duration_ms: i64,
+    ) -> Result<()> {
+        let mut conn = db_connection.get_metadata_pool().get()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO query_history (database_id, query, row_count, duration_ms, executed_at)
+             VALUES (?, ?, ?, ?, ?)",
+            params![database_id, query, row_count, duration_ms, Utc::now().to_rfc3339()],
+        )?;
+        Self::apply_retention(&tx, database_id, db_connection.history_retention())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn apply_retention(conn: &Connection, database_id: i64, retention: &HistoryRetention) -> rusqlite::Result<usize> {
+        let mut removed = 0;
+
+        if let Some(max_entries) = retention.max_entries {
+            removed += conn.execute(
+                "DELETE FROM query_history
+                 WHERE database_id = ?1 AND id NOT IN (
+                     SELECT id FROM query_history WHERE database_id = ?1 ORDER BY id DESC LIMIT ?2
+                 )",
+                params![database_id, max_entries as i64],
+            )?;
+        }
+
+        if let Some(days) = retention.max_age_days {
+            let cutoff = Utc::now() - chrono::Duration::days(days);
+            removed += conn.execute(
+                "DELETE FROM query_history WHERE database_id = ? AND executed_at < ?",
+                params![database_id, cutoff.to_rfc3339()],
+            )?;
+        }
+
+        Ok(removed)
+    }
+
+    pub fn list_for_database(db_connection: &DbConnection, database_id: i64, page: Pagination) -> Result<Vec<QueryHistoryEntry>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, database_id, query, row_count, duration_ms, executed_at
+             FROM query_history
+             WHERE database_id = ?
+             ORDER BY id DESC
+             LIMIT ? OFFSET ?"
+        )?;
+
+        let entries = stmt.query_map(params![database_id, page.limit, page.offset], Self::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    // Newest entries across every database, with the name of the database each ran against
+    pub fn list_recent(db_connection: &DbConnection, page: Pagination) -> Result<Vec<RecentQuery>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let mut stmt = conn.prepare(
+            "SELECT h.id, h.database_id, h.query, h.row_count, h.duration_ms, h.executed_at, m.name
+             FROM query_history h
+             LEFT JOIN database_metadata m ON m.id = h.database_id
+             ORDER BY h.id DESC
+             LIMIT ? OFFSET ?"
+        )?;
+
+        let entries = stmt.query_map(params![page.limit, page.offset], |row| {
+            Ok(RecentQuery { entry: Self::from_row(row)?, database_name: row.get(6)? })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(entries)
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        let executed_at: String = row.get(5)?;
+        let executed_at = DateTime::parse_from_rfc3339(&executed_at)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(e)))?;
+
+        Ok(QueryHistoryEntry {
+            id: row.get(0)?,
+            database_id: row.get(1)?,
+            query: row.get(2)?,
+            row_count: row.get(3)?,
+            duration_ms: row.get(4)?,
+            executed_at,
+        })
+    }
+
+    pub fn delete_for_database(db_connection: &DbConnection, database_id: i64) -> Result<usize> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        Ok(conn.execute("DELETE FROM query_history WHERE database_id = ?", params![database_id])?)
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+use rusqlite::{params, Connection, OptionalExtension};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::db::connection::DbConnection;
+
+// A named SQL template kept server-side for a database; see `db::template`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedQuery {
+    pub id: i64,
+    pub database_id: i64,
+    pub name: String,
+    pub sql: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SavedQuery {
+    pub fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS saved_queries (
+                id INTEGER PRIMARY KEY,
+                database_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                sql TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE (database_id, name)
+            );"
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SavedQuery> {
+        let created_at: String = row.get(4)?;
+        let created_at = DateTime::parse_from_rfc3339(&created_at)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e)))?;
+
+        Ok(SavedQuery {
+            id: row.get(0)?,
+            database_id: row.get(1)?,
+            name: row.get(2)?,
+            sql: row.get(3)?,
+            created_at,
+        })
+    }
+
+    // Returns None if the database already has a saved query with this name
+    pub fn create(db_connection: &DbConnection, database_id: i64, name: &str, sql: &str) -> Result<Option<SavedQuery>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let created_at = Utc::now();
+        let inserted = conn.execute(
+            "INSERT INTO saved_queries (database_id, name, sql, created_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT (database_id, name) DO NOTHING",
+            params![database_id, name, sql, created_at.to_rfc3339()],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(SavedQuery {
+            id: conn.last_insert_rowid(),
+            database_id,
+            name: name.to_string(),
+            sql: sql.to_string(),
+            created_at,
+        }))
+    }
+
+    pub fn list_for_database(db_connection: &DbConnection, database_id: i64) -> Result<Vec<SavedQuery>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, database_id, name, sql, created_at
+             FROM saved_queries
+             WHERE database_id = ?
+             ORDER BY name"
+        )?;
+
+        let queries = stmt.query_map(params![database_id], Self::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(queries)
+    }
+
+    pub fn find(db_connection: &DbConnection, database_id: i64, id: i64) -> Result<Option<SavedQuery>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let query = conn.query_row(
+            "SELECT id, database_id, name, sql, created_at
+             FROM saved_queries
+             WHERE database_id = ? AND id = ?",
+            params![database_id, id],
+            Self::from_row,
+        ).optional()?;
+
+        Ok(query)
+    }
+
+    pub fn delete(db_connection: &DbConnection, database_id: i64, id: i64) -> Result<bool> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let deleted = conn.execute(
+            "DELETE FROM saved_queries WHERE database_id = ? AND id = ?",
+            params![database_id, id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    pub fn delete_for_database(db_connection: &DbConnection, database_id: i64) -> Result<usize> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        Ok(conn.execute("DELETE FROM saved_queries WHERE database_id = ?", params![database_id])?)
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use rusqlite::{params, Connection, OpenFlags};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+use crate::db::connection::DbConnection;
+use crate::models::database_metadata::DatabaseMetadata;
+use crate::utils::pagination::Pagination;
+use crate::utils::quote_identifier;
+
+// Samples kept per table; older ones are trimmed as new ones are recorded
+const MAX_SAMPLES_PER_TABLE: i64 = 1000;
+
+// A table's row count at one moment, with the change since the sample before it
+#[derive(Debug, Serialize, Clone)]
+pub struct RowCountSample {
+    pub row_count: i64,
+    // None for the oldest sample returned
+    pub change: Option<i64>,
+    pub sampled_at: DateTime<Utc>,
+}
+
+pub struct TableGrowth;
+
+impl TableGrowth {
+    pub fn create_table(conn: &Connection) -> rusqlite::Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS table_row_counts (
+                id INTEGER PRIMARY KEY,
+                database_id INTEGER NOT NULL,
+                table_name TEXT NOT NULL,
+                row_count INTEGER NOT NULL,
+                sampled_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_table_row_counts_table ON table_row_counts (database_id, table_name, id);"
+        )
+    }
+
+    // Record one sample, then trim the table's series to MAX_SAMPLES_PER_TABLE
+    pub fn record(db_connection: &DbConnection, database_id: i64, table: &str, row_count: i64) -> Result<()> {
+        let mut conn = db_connection.get_metadata_pool().get()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO table_row_counts (database_id, table_name, row_count, sampled_at) VALUES (?, ?, ?, ?)",
+            params![database_id, table, row_count, Utc::now().to_rfc3339()],
+        )?;
+        tx.execute(
+            "DELETE FROM table_row_counts
+             WHERE database_id = ?1 AND table_name = ?2 AND id NOT IN (
+                 SELECT id FROM table_row_counts WHERE database_id = ?1 AND table_name = ?2 ORDER BY id DESC LIMIT ?3
+             )",
+            params![database_id, table, MAX_SAMPLES_PER_TABLE],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Count the rows of every table in a database and record them
+    pub fn sample_database(db_connection: &DbConnection, metadata: &DatabaseMetadata) -> Result<usize> {
+        let database_id = metadata.id.ok_or_else(|| anyhow::anyhow!("Database has no id"))?;
+        // Opened directly so a pass over every database doesn't cycle the pool cache
+        let conn = Connection::open_with_flags(&metadata.path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        let tables: Vec<String> = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for table in &tables {
+            let row_count: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM {}", quote_identifier(table)),
+                [],
+                |row| row.get(0),
+            )?;
+            Self::record(db_connection, database_id, table, row_count)?;
+        }
+        Ok(tables.len())
+    }
+
+    // One sampler pass over every stored database. A database that can't be read
+    // is logged and skipped so it doesn't hold up the rest.
+    pub fn sample_all(db_connection: &DbConnection) -> Result<usize> {
+        let mut sampled = 0;
+        for metadata in DatabaseMetadata::list(db_connection)? {
+            match Self::sample_database(db_connection, &metadata) {
+                Ok(tables) => sampled += tables,
+                Err(e) => warn!("Failed to sample row counts for database {:?}: {}", metadata.id, e),
+            }
+        }
+        Ok(sampled)
+    }
+
+    // The newest samples for a table in time order, oldest first
+    pub fn list_for_table(
+        db_connection: &DbConnection,
+        database_id: i64,
+        table: &str,
+        page: Pagination,
+    ) -> Result<Vec<RowCountSample>> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        let mut stmt = conn.prepare(
+            "SELECT row_count, sampled_at FROM table_row_counts
+             WHERE database_id = ? AND table_name = ?
+             ORDER BY id DESC
+             LIMIT ? OFFSET ?"
+        )?;
+        let mut samples = stmt.query_map(params![database_id, table, page.limit, page.offset], |row| {
+            let sampled_at: String = row.get(1)?;
+            let sampled_at = DateTime::parse_from_rfc3339(&sampled_at)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?;
+            Ok(RowCountSample { row_count: row.get(0)?, change: None, sampled_at })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        samples.reverse();
+        for i in 1..samples.len() {
+            samples[i].change = Some(samples[i].row_count - samples[i - 1].row_count);
+        }
+        Ok(samples)
+    }
+
+    pub fn delete_for_database(db_connection: &DbConnection, database_id: i64) -> Result<usize> {
+        let conn = db_connection.get_metadata_pool().get()?;
+        Ok(conn.execute("DELETE FROM table_row_counts WHERE database_id = ?", params![database_id])?)
+    }
+}
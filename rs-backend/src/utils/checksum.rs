@@ -0,0 +1,43 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+// Hex-encoded SHA-256 of a file's contents, read in chunks
+pub fn file_sha256(path: impl AsRef<Path>) -> io::Result<String> {
+    files_sha256([path])
+}
+
+// Like file_sha256, over several files' contents one after another
+pub fn files_sha256<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    for path in paths {
+        let mut file = File::open(path)?;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// Hex-encoded SHA-256 of an in-memory buffer
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+// Compare a presented secret with the expected one in constant time. Both are
+// hashed first, so neither the contents nor the length leak through timing.
+pub fn secrets_match(provided: &[u8], expected: &[u8]) -> bool {
+    Sha256::digest(provided).ct_eq(&Sha256::digest(expected)).into()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,17 @@
+// Longest table or column name accepted from clients
+pub const MAX_IDENTIFIER_LENGTH: usize = 128;
+
+// Quote an identifier for interpolation into SQL: wrap it in double quotes and
+// double any embedded quotes, so it can never end the identifier early
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Whether a client-supplied name is acceptable as a table or column name.
+// Spaces, quotes and punctuation are fine once quoted; empty names, control
+// characters (including NUL, which would truncate the SQL) and overlong names are not
+pub fn is_valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= MAX_IDENTIFIER_LENGTH
+        && !name.chars().any(char::is_control)
+}
@@ -0,0 +1,76 @@
+// Structural limits checked on raw JSON bytes before they're parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonLimits {
+    pub max_depth: usize,
+    // Array items plus object members, across the whole document
+    pub max_elements: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum JsonLimitError {
+    #[error("JSON nesting exceeds the maximum depth of {0}")]
+    TooDeep(usize),
+    #[error("JSON has more than the maximum of {0} elements")]
+    TooManyElements(usize),
+}
+
+// Scan `bytes` for nesting depth and element count without building any values.
+// Malformed input is left for the parser to reject.
+pub fn check(bytes: &[u8], limits: JsonLimits) -> Result<(), JsonLimitError> {
+    // One entry per open container: whether it has had an element yet
+    let mut open: Vec<bool> = Vec::new();
+    let mut elements = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &b in bytes {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        if b.is_ascii_whitespace() {
+            continue;
+        }
+
+        match b {
+            b']' | b'}' => {
+                open.pop();
+                continue;
+            }
+            b',' => {
+                elements += 1;
+            }
+            // Object keys and values are separated by ':', and a member is counted once
+            b':' => continue,
+            _ => {
+                if let Some(started) = open.last_mut() {
+                    if !*started {
+                        *started = true;
+                        elements += 1;
+                    }
+                }
+            }
+        }
+        if let Some(max) = limits.max_elements.filter(|max| elements > *max) {
+            return Err(JsonLimitError::TooManyElements(max));
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                open.push(false);
+                if open.len() > limits.max_depth {
+                    return Err(JsonLimitError::TooDeep(limits.max_depth));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -3,7 +3,7 @@ use tracing::info;
 
 pub fn init_logger() {
     // Initialize the logger with a default configuration
-    let _subscriber = fmt()
+    fmt()
         .with_env_filter(EnvFilter::from_default_env()
             .add_directive("info".parse().unwrap())
             .add_directive("rs_backend=debug".parse().unwrap()))
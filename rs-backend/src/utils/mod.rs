@@ -1 +1,9 @@
-pub mod logger; 
\ No newline at end of file
+pub mod checksum;
+pub mod identifier;
+pub mod json_limits;
+pub mod logger;
+pub mod pagination;
+pub mod signed_link;
+
+pub use checksum::{file_sha256, files_sha256, secrets_match, sha256_hex};
+pub use identifier::{is_valid_identifier, quote_identifier};
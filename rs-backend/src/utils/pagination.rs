@@ -0,0 +1,87 @@
+// Shared parsing of `limit` / `offset` pagination inputs. Values are taken as raw
+// strings so a non-numeric value is reported the same way as a negative one,
+// rather than as a query-string deserialization failure.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+// A 1-based page of a query's rows, as requested in a query body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryPage {
+    pub page: i64,
+    pub page_size: i64,
+}
+
+impl QueryPage {
+    pub fn offset(&self) -> i64 {
+        (self.page - 1).saturating_mul(self.page_size)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{field} must be {expected}, got '{value}'")]
+pub struct PaginationError {
+    pub field: &'static str,
+    pub value: String,
+    pub expected: &'static str,
+}
+
+// Limits above `max_limit` are capped rather than rejected
+pub fn parse(
+    limit: Option<&str>,
+    offset: Option<&str>,
+    default_limit: i64,
+    max_limit: i64,
+) -> Result<Pagination, PaginationError> {
+    let limit = non_negative("limit", limit)?.map_or(default_limit, |n| n.min(max_limit));
+    let offset = non_negative("offset", offset)?.unwrap_or(0);
+    Ok(Pagination { limit, offset })
+}
+
+// `page` and `page_size` from a JSON body, as integers or numeric strings. None
+// when neither is given; `page` defaults to the first and `page_size` to
+// `default_size`, and sizes above `max_size` are capped.
+pub fn parse_query_page(
+    page: Option<&serde_json::Value>,
+    page_size: Option<&serde_json::Value>,
+    default_size: i64,
+    max_size: i64,
+) -> Result<Option<QueryPage>, PaginationError> {
+    let page = page.filter(|v| !v.is_null());
+    let page_size = page_size.filter(|v| !v.is_null());
+    if page.is_none() && page_size.is_none() {
+        return Ok(None);
+    }
+
+    let page = positive("page", page)?.unwrap_or(1);
+    let page_size = positive("page_size", page_size)?.map_or(default_size, |n| n.min(max_size));
+    Ok(Some(QueryPage { page, page_size }))
+}
+
+fn non_negative(field: &'static str, value: Option<&str>) -> Result<Option<i64>, PaginationError> {
+    let Some(value) = value else { return Ok(None) };
+    match value.trim().parse::<i64>() {
+        Ok(n) if n >= 0 => Ok(Some(n)),
+        _ => Err(PaginationError { field, value: value.to_string(), expected: "a non-negative integer" }),
+    }
+}
+
+fn positive(field: &'static str, value: Option<&serde_json::Value>) -> Result<Option<i64>, PaginationError> {
+    let Some(value) = value else { return Ok(None) };
+    let parsed = match value {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => s.trim().parse::<i64>().ok(),
+        _ => None,
+    };
+    match parsed {
+        Some(n) if n > 0 => Ok(Some(n)),
+        _ => Err(PaginationError {
+            field,
+            value: value.as_str().map_or_else(|| value.to_string(), String::from),
+            expected: "a positive integer",
+        }),
+    }
+}
@@ -0,0 +1,58 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum LinkError {
+    #[error("Malformed download token")]
+    Malformed,
+    #[error("Invalid download token signature")]
+    BadSignature,
+    #[error("Download link has expired")]
+    Expired,
+}
+
+fn mac(key: &[u8], database_id: i64, expires_at: i64) -> HmacSha256 {
+    // HMAC accepts keys of any length
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC key of any length");
+    mac.update(format!("{}.{}", database_id, expires_at).as_bytes());
+    mac
+}
+
+// Token of the form `<id>.<expires_at>.<hex hmac>`, with `expires_at` in unix seconds
+pub fn sign(key: &[u8], database_id: i64, expires_at: i64) -> String {
+    let signature = mac(key, database_id, expires_at).finalize().into_bytes();
+    let hex: String = signature.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.{}.{}", database_id, expires_at, hex)
+}
+
+// Check a token's signature, then its expiry against `now`; returns the database id
+pub fn verify(key: &[u8], token: &str, now: i64) -> Result<i64, LinkError> {
+    let mut parts = token.splitn(3, '.');
+    let (Some(id), Some(expires_at), Some(signature)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(LinkError::Malformed);
+    };
+    let database_id: i64 = id.parse().map_err(|_| LinkError::Malformed)?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| LinkError::Malformed)?;
+    let signature = decode_hex(signature).ok_or(LinkError::Malformed)?;
+
+    mac(key, database_id, expires_at)
+        .verify_slice(&signature)
+        .map_err(|_| LinkError::BadSignature)?;
+
+    if now >= expires_at {
+        return Err(LinkError::Expired);
+    }
+    Ok(database_id)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
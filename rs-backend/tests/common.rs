@@ -6,6 +6,15 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use std::fs;
 use std::thread;
 use std::time::Duration;
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+use rs_backend::db::connection::DbConnection;
+use rs_backend::models::database_metadata::DatabaseMetadata;
 
 static INIT: Once = Once::new();
 
@@ -130,6 +139,12 @@ impl TestEnv {
             [42_i32, 84_i32],
         ).expect("Failed to insert test data into table 2");
         
+        // Stamp the file so fixtures from different tests never share a checksum;
+        // identical uploads are refused as duplicates
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos() as i32;
+        conn.pragma_update(None, "application_id", stamp)
+            .expect("Failed to stamp test database");
+        
         // Ensure all changes are written and connection is closed properly
         conn.pragma_update(None, "wal_checkpoint", "TRUNCATE")
             .expect("Failed to checkpoint database");
@@ -206,6 +221,21 @@ impl TestEnv {
         db_path
     }
     
+    // Create the standard test database, register its metadata and return its id and path
+    pub fn register_test_db(&self, db_connection: &DbConnection) -> (i64, PathBuf) {
+        let db_path = self.create_test_db();
+        let metadata = DatabaseMetadata::new(
+            "test.db".to_string(),
+            db_path.to_string_lossy().into_owned(),
+            1000,
+            2,
+            false,
+            None,
+        );
+        let saved = metadata.save(db_connection).expect("Failed to save test metadata");
+        (saved.id.expect("ID should be present"), db_path)
+    }
+
     pub fn cleanup(&self) {
         // Remove the test directory and all its contents
         if self.test_dir.exists() {
@@ -220,8 +250,59 @@ impl TestEnv {
     }
 }
 
+impl Default for TestEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for TestEnv {
     fn drop(&mut self) {
         self.cleanup();
     }
-} 
\ No newline at end of file
+}
+
+// Send a request with an optional JSON body and decode the JSON response
+pub async fn send_json(app: &Router, method: &str, uri: &str, payload: Option<Value>) -> (StatusCode, Value) {
+    send_json_as(app, method, uri, payload, None).await
+}
+
+// Like send_json, with an `Authorization: Bearer` token when one is given
+pub async fn send_json_as(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    payload: Option<Value>,
+    token: Option<&str>,
+) -> (StatusCode, Value) {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        builder = builder.header("authorization", format!("Bearer {}", token));
+    }
+    let request = match payload {
+        Some(payload) => builder
+            .header("content-type", "application/json")
+            .body(Body::from(payload.to_string())),
+        None => builder.body(Body::empty()),
+    }
+    .unwrap();
+
+    send(app, request).await
+}
+
+// Send a prepared request and decode the JSON response
+pub async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, json)
+}
+
+pub async fn post_json(app: &Router, uri: &str, payload: Value) -> (StatusCode, Value) {
+    send_json(app, "POST", uri, Some(payload)).await
+}
+
+pub async fn get_json(app: &Router, uri: &str) -> (StatusCode, Value) {
+    send_json(app, "GET", uri, None).await
+}
@@ -0,0 +1,356 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+
+use crate::common::{post_json, send, TestEnv};
+use rs_backend::db::connection::DbConnection;
+use rs_backend::models::audit_log::AuditEntry;
+use rs_backend::models::database_metadata::DatabaseMetadata;
+use rs_backend::utils::pagination::Pagination;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+// Build an admin request carrying the bearer token
+fn admin_request(method: &str, uri: &str, payload: Option<Value>) -> Request<Body> {
+    let builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", ADMIN_TOKEN));
+    match payload {
+        Some(payload) => builder
+            .header("content-type", "application/json")
+            .body(Body::from(payload.to_string())),
+        None => builder.body(Body::empty()),
+    }
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_token() {
+    let test_env = TestEnv::new();
+
+    let app = rs_backend::create_app(DbConnection::new().with_admin_token(None));
+    let (status, _) = send(&app, admin_request("POST", "/admin/metadata/vacuum", None)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let app = rs_backend::create_app(DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string())));
+    let request = Request::builder()
+        .method("POST")
+        .uri("/admin/metadata/vacuum")
+        .header("authorization", "Bearer wrong-token")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _) = send(&app, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_vacuum_metadata_reclaims_space() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+
+    let query = "SELECT * FROM test1 WHERE name = 'x' ".repeat(20);
+    for _ in 0..2000 {
+        AuditEntry::record(&db_connection, 1, &query, Some("client"), 0).unwrap();
+    }
+    let metadata_path = db_connection.metadata_db_path();
+    let size_with_rows = std::fs::metadata(&metadata_path).unwrap().len();
+
+    let (status, json) = send(
+        &app,
+        admin_request("POST", "/admin/metadata/vacuum", Some(json!({ "retention_days": 0 }))),
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["purged"]["audit_log"], 2000);
+    assert!(json["reclaimed_bytes"].as_u64().unwrap() > 0);
+    assert!(std::fs::metadata(&metadata_path).unwrap().len() < size_with_rows);
+    assert!(AuditEntry::list_for_database(&db_connection, 1, Pagination { limit: 10, offset: 0 }).unwrap().is_empty());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_list_and_kill_running_query() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let slow_sql = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 10000000000) \
+                    SELECT COUNT(*) FROM c";
+    let query_app = app.clone();
+    let uri = format!("/databases/{}/query", id);
+    let running = tokio::spawn(async move {
+        post_json(&query_app, &uri, json!({ "sql": slow_sql })).await
+    });
+
+    let mut query_id = None;
+    for _ in 0..100 {
+        let (status, json) = send(&app, admin_request("GET", "/admin/queries", None)).await;
+        assert_eq!(status, StatusCode::OK);
+        if let Some(query) = json["queries"].as_array().and_then(|q| q.first()) {
+            assert_eq!(query["database_id"], id);
+            assert!(query["sql"].as_str().unwrap().starts_with("WITH RECURSIVE"));
+            assert!(query["elapsed_ms"].is_u64());
+            query_id = query["id"].as_u64();
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let query_id = query_id.expect("slow query was never listed");
+
+    let (status, _) = send(&app, admin_request("DELETE", &format!("/admin/queries/{}", query_id), None)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, json) = tokio::time::timeout(std::time::Duration::from_secs(10), running)
+        .await
+        .expect("query did not terminate after being killed")
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(json["error"], "Query was interrupted");
+
+    let (_, json) = send(&app, admin_request("GET", "/admin/queries", None)).await;
+    assert!(json["queries"].as_array().unwrap().is_empty());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_admin_files_reports_shared_and_orphaned_paths() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (first_id, db_path) = test_env.register_test_db(&db_connection);
+
+    // A second record pointing at the same file, as a filename collision would leave behind
+    let duplicate = DatabaseMetadata::new(
+        "copy.db".to_string(),
+        db_path.to_string_lossy().into_owned(),
+        1000,
+        2,
+        false,
+        None,
+    ).save(&db_connection).unwrap();
+    let second_id = duplicate.id.unwrap();
+
+    let orphan = test_env.test_dir.join("databases").join("orphan.db");
+    std::fs::write(&orphan, b"not referenced").unwrap();
+
+    let gone = DatabaseMetadata::new(
+        "gone.db".to_string(),
+        test_env.test_dir.join("databases").join("gone.db").to_string_lossy().into_owned(),
+        1000,
+        0,
+        false,
+        None,
+    ).save(&db_connection).unwrap();
+
+    let (status, json) = send(&app, admin_request("GET", "/admin/files", None)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let shared = json["shared"].as_array().unwrap();
+    assert_eq!(shared.len(), 1);
+    assert_eq!(shared[0]["referenced_by"], json!([first_id, second_id]));
+
+    let test_file = json["files"].as_array().unwrap()
+        .iter()
+        .find(|file| file["path"].as_str().unwrap().ends_with("test.db"))
+        .unwrap();
+    assert_eq!(test_file["shared"], true);
+    assert_eq!(test_file["referenced_by"], json!([first_id, second_id]));
+
+    let orphans = json["orphans"].as_array().unwrap();
+    assert_eq!(orphans.len(), 1);
+    assert!(orphans[0].as_str().unwrap().ends_with("orphan.db"));
+
+    assert_eq!(json["missing"], json!([{ "id": gone.id, "path": gone.path }]));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_metadata_export_and_import_restores_catalog() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (kept_id, _) = test_env.register_test_db(&db_connection);
+
+    let storage = test_env.test_dir.join("databases");
+    let moved_path = storage.join("moved.db");
+    std::fs::write(&moved_path, b"moved database contents").unwrap();
+    let mut moved = DatabaseMetadata::new(
+        "moved.db".to_string(),
+        moved_path.to_string_lossy().into_owned(),
+        23,
+        0,
+        true,
+        Some("relocated".to_string()),
+    );
+    moved.properties.insert("team".to_string(), "data".to_string());
+    let moved = moved.save(&db_connection).unwrap();
+
+    let gone = DatabaseMetadata::new(
+        "gone.db".to_string(),
+        storage.join("gone.db").to_string_lossy().into_owned(),
+        1000,
+        0,
+        false,
+        None,
+    ).save(&db_connection).unwrap();
+
+    let (status, snapshot) = send(&app, admin_request("GET", "/admin/metadata/export", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(snapshot["databases"].as_array().unwrap().len(), 3);
+
+    // Lose the catalog, and move one file so only its checksum still identifies it
+    let conn = rusqlite::Connection::open(db_connection.metadata_db_path()).unwrap();
+    conn.execute("DELETE FROM database_metadata", []).unwrap();
+    drop(conn);
+    let renamed_path = storage.join("renamed.db");
+    std::fs::rename(&moved_path, &renamed_path).unwrap();
+    assert_eq!(DatabaseMetadata::count(&db_connection).unwrap(), 0);
+
+    let (status, report) = send(&app, admin_request("POST", "/admin/metadata/import", Some(snapshot.clone()))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["restored"].as_array().unwrap().len(), 2);
+    assert_eq!(report["skipped"], json!([{ "id": gone.id, "name": "gone.db", "reason": "Database file not found" }]));
+
+    let kept = DatabaseMetadata::find_by_id(&db_connection, kept_id).unwrap().unwrap();
+    assert!(kept.path.ends_with("test.db"));
+
+    let restored = DatabaseMetadata::find_by_id(&db_connection, moved.id.unwrap()).unwrap().unwrap();
+    assert_eq!(restored.path, renamed_path.to_string_lossy());
+    assert_eq!(restored.notes.as_deref(), Some("relocated"));
+    assert!(restored.is_favorite);
+    assert_eq!(restored.properties.get("team").map(String::as_str), Some("data"));
+    assert!(DatabaseMetadata::find_by_id(&db_connection, gone.id.unwrap()).unwrap().is_none());
+
+    // A second import leaves the restored records alone
+    let (status, report) = send(&app, admin_request("POST", "/admin/metadata/import", Some(snapshot))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["restored"], json!([]));
+    assert_eq!(report["skipped"].as_array().unwrap().len(), 3);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_admin_config_reports_effective_settings() {
+    use rs_backend::config::Config;
+    use std::collections::HashMap;
+
+    let test_env = TestEnv::new();
+    let vars: HashMap<&str, String> = HashMap::from([
+        ("SQLITE_STORAGE_PATH", test_env.test_dir.to_string_lossy().into_owned()),
+        ("ADMIN_TOKEN", ADMIN_TOKEN.to_string()),
+        ("MAX_DATABASES", "7".to_string()),
+        ("QUERY_BLOCKLIST", "attach=ATTACH".to_string()),
+    ]);
+    let config = Config::from_lookup(|name| vars.get(name).cloned()).unwrap();
+    let db_connection = DbConnection::from_config(config).unwrap();
+    let app = rs_backend::create_app(db_connection);
+
+    let (status, json) = send(&app, admin_request("GET", "/admin/config", None)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let config = &json["config"];
+    assert_eq!(config["max_databases"], 7);
+    assert_eq!(config["storage_path"], test_env.test_dir.to_string_lossy().as_ref());
+    assert_eq!(config["query_blocklist"], json!(["attach"]));
+    // Unset values fall back to their defaults
+    assert_eq!(config["max_statement_changes"], 1_000_000);
+    assert_eq!(config["upload_extensions"], json!(["db", "sqlite", "sqlite3"]));
+
+    assert_eq!(config["admin_token"], "[redacted]");
+    assert!(!json.to_string().contains(ADMIN_TOKEN));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_recent_queries_span_databases() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (first_id, db_path) = test_env.register_test_db(&db_connection);
+
+    let copy_path = test_env.test_dir.join("second.db");
+    std::fs::copy(&db_path, &copy_path).unwrap();
+    let second = DatabaseMetadata::new(
+        "second.db".to_string(),
+        copy_path.to_string_lossy().into_owned(),
+        1000,
+        2,
+        false,
+        None,
+    ).save(&db_connection).unwrap();
+    let second_id = second.id.unwrap();
+
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", first_id), json!({ "sql": "SELECT * FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", second_id), json!({ "sql": "SELECT * FROM test2" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, Request::builder().uri("/admin/recent-queries").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, json) = send(&app, admin_request("GET", "/admin/recent-queries", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = json["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["database_id"], second_id);
+    assert_eq!(entries[0]["database_name"], "second.db");
+    assert_eq!(entries[0]["query"], "SELECT * FROM test2");
+    assert_eq!(entries[1]["database_id"], first_id);
+    assert_eq!(entries[1]["database_name"], "test.db");
+
+    let (_, json) = send(&app, admin_request("GET", "/admin/recent-queries?limit=1", None)).await;
+    assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+
+    let (status, _) = send(&app, admin_request("GET", "/admin/recent-queries?limit=-1", None)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_recent_errors_list_failed_requests() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (bad_status, bad_body) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELEC nonsense" })).await;
+    assert!(bad_status.is_client_error() || bad_status.is_server_error());
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT 1" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, Request::builder().uri("/admin/errors").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, json) = send(&app, admin_request("GET", "/admin/errors", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    let errors = json["errors"].as_array().unwrap();
+    // Newest first: the rejected unauthenticated call, then the bad query
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert_eq!(errors[0]["route"], "/admin/errors");
+    assert_eq!(errors[0]["status"], 401);
+    let bad_query = &errors[1];
+    assert_eq!(bad_query["method"], "POST");
+    assert_eq!(bad_query["route"], "/databases/:id/query");
+    assert_eq!(bad_query["database_id"], id);
+    assert_eq!(bad_query["status"], bad_status.as_u16());
+    assert_eq!(bad_query["error"], bad_body["error"]);
+    assert!(bad_query["at"].is_string());
+
+    let (_, json) = send(&app, admin_request("GET", "/admin/errors?limit=1", None)).await;
+    assert_eq!(json["errors"].as_array().unwrap().len(), 1);
+
+    test_env.cleanup();
+}
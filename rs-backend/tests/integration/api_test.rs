@@ -8,8 +8,10 @@ use tower::ServiceExt;
 use serde_json::{Value, json};
 use bytes::Bytes;
 
-use crate::common::TestEnv;
+use crate::common::{get_json, post_json, send_json, send_json_as, TestEnv};
 use rs_backend::db::connection::DbConnection;
+use rs_backend::models::database_metadata::DatabaseMetadata;
+use rs_backend::models::table_growth::TableGrowth;
 
 pub async fn setup_test_app() -> (Router, DbConnection, TestEnv) {
     let test_env = TestEnv::new();
@@ -47,6 +49,29 @@ async fn test_health_check() {
     test_env.cleanup();
 }
 
+#[tokio::test]
+async fn test_health_check_reports_schema_drift() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+
+    let conn = db_connection.get_metadata_pool().get().unwrap();
+    conn.execute("ALTER TABLE database_metadata DROP COLUMN notes", []).unwrap();
+
+    let response = app
+        .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["status"], "degraded");
+    assert_eq!(json["schema_drift"]["missing_columns"], json!(["notes"]));
+    assert_eq!(json["schema_drift"]["unexpected_columns"], json!([]));
+
+    test_env.cleanup();
+}
+
 #[tokio::test]
 async fn test_list_databases_empty() {
     let (app, _, test_env) = setup_test_app().await;
@@ -141,4 +166,1189 @@ async fn test_update_nonexistent_database() {
     assert_eq!(json["error"], "Database not found");
     
     test_env.cleanup();
-} 
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_update_properties_merges_and_filters() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let db_path = test_env.create_test_db();
+
+    let mut metadata = DatabaseMetadata::new(
+        "Props DB".to_string(),
+        db_path.to_string_lossy().into_owned(),
+        1000,
+        2,
+        false,
+        None,
+    );
+    metadata.properties.insert("owner".to_string(), "team-b".to_string());
+    let saved = metadata.save(&db_connection).unwrap();
+    let id = saved.id.unwrap();
+
+    let update = json!({ "properties": { "owner": "team-a", "env": "prod" } });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/databases/{}", id))
+                .header("content-type", "application/json")
+                .body(Body::from(update.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["database"]["properties"], json!({ "owner": "team-a", "env": "prod" }));
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/databases?property=owner:team-a")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let databases = json["databases"].as_array().unwrap();
+    assert_eq!(databases.len(), 1);
+    assert_eq!(databases[0]["id"], id);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/databases/{}", id))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "properties": { "nested": { "a": 1 } } }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_diff_preview_categorizes_rows() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/tables/test1/diff-preview", id);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "rows": [
+            { "id": 1, "name": "Test 1" },
+            { "id": 2, "name": "Renamed" },
+            { "id": 3, "name": "Brand new" }
+        ]
+    })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["primary_key"], json!(["id"]));
+    assert_eq!(json["summary"], json!({ "insert": 1, "update": 1, "unchanged": 1 }));
+    assert_eq!(json["insert"], json!([{ "id": 3, "name": "Brand new" }]));
+    assert_eq!(json["update"], json!([{
+        "key": { "id": 2 },
+        "changes": { "name": { "from": "Test 2", "to": "Renamed" } }
+    }]));
+    assert_eq!(json["unchanged"], json!([{ "id": 1 }]));
+
+    // Nothing was applied
+    let (_, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT name FROM test1 ORDER BY id" }),
+    ).await;
+    assert_eq!(json["rows"], json!([{ "name": "Test 1" }, { "name": "Test 2" }]));
+
+    let (status, json) = post_json(&app, &uri, json!({ "rows": [{ "id": 1, "colour": "red" }] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["columns"], json!(["colour"]));
+
+    let (status, _) = post_json(&app, &uri, json!({ "rows": [{ "name": "no key" }] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/tables/missing/diff-preview", id),
+        json!({ "rows": [] }),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_graphql_sdl_references_parent_type() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT NOT NULL, rating REAL);
+         CREATE TABLE blog_posts (
+             id INTEGER PRIMARY KEY,
+             title TEXT NOT NULL,
+             published BOOLEAN,
+             author_id INTEGER NOT NULL REFERENCES authors(id)
+         );"
+    ).unwrap();
+    drop(conn);
+
+    let response = app
+        .oneshot(Request::builder().uri(format!("/databases/{}/graphql-sdl", id)).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = read_response_body(response).await.unwrap();
+    let sdl = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(sdl.contains("type Authors {\n  id: ID!\n  name: String!\n  rating: Float\n}"), "{}", sdl);
+    assert!(sdl.contains("type BlogPosts {"), "{}", sdl);
+    assert!(sdl.contains("  published: Boolean\n"), "{}", sdl);
+    assert!(sdl.contains("  author_id: Int!\n"), "{}", sdl);
+    assert!(sdl.contains("  author: Authors!\n"), "{}", sdl);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_migrations_apply_once_in_order() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/migrate", id);
+    let payload = json!({
+        "migrations": [
+            { "version": 1, "up_sql": "CREATE TABLE tags (id INTEGER PRIMARY KEY, label TEXT NOT NULL);" },
+            { "version": 2, "up_sql": "ALTER TABLE tags ADD COLUMN colour TEXT; CREATE INDEX idx_tags_label ON tags (label);" }
+        ]
+    });
+
+    let (status, json) = post_json(&app, &uri, payload.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["migrations"]["user_version_before"], 0);
+    assert_eq!(json["migrations"]["user_version"], 2);
+    assert_eq!(json["migrations"]["applied"], json!([1, 2]));
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let user_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+    assert_eq!(user_version, 2);
+    let columns: Vec<String> = conn.prepare("SELECT name FROM pragma_table_info('tags')").unwrap()
+        .query_map([], |row| row.get(0)).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    assert_eq!(columns, vec!["id", "label", "colour"]);
+    drop(conn);
+
+    let metadata = DatabaseMetadata::find_by_id(&db_connection, id).unwrap().unwrap();
+    assert_eq!(metadata.table_count, 3);
+
+    // Re-running the same migrations is a no-op
+    let (status, json) = post_json(&app, &uri, payload).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["migrations"]["applied"], json!([]));
+    assert_eq!(json["migrations"]["skipped"], json!([1, 2]));
+    assert_eq!(json["migrations"]["user_version"], 2);
+
+    // A failing step rolls back the whole run, including earlier steps
+    let (status, json) = post_json(&app, &uri, json!({
+        "migrations": [
+            { "version": 3, "up_sql": "CREATE TABLE notes (body TEXT);" },
+            { "version": 4, "up_sql": "ALTER TABLE missing ADD COLUMN x TEXT;" }
+        ]
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["version"], 4);
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let user_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+    assert_eq!(user_version, 2);
+    let notes: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'notes'", [], |row| row.get(0)).unwrap();
+    assert_eq!(notes, 0);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_page_size_change_takes_effect() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/page-size", id);
+
+    let (status, json) = get_json(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["page_size"], 4096);
+
+    let (status, json) = send_json(&app, "PUT", &uri, Some(json!({ "page_size": 8192 }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["page_size"], 8192);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0)).unwrap();
+    assert_eq!(page_size, 8192);
+    drop(conn);
+
+    // The recorded size follows the rebuilt file
+    let file_size = std::fs::metadata(&db_path).unwrap().len() as i64;
+    assert_eq!(json["size"], file_size);
+    let metadata = DatabaseMetadata::find_by_id(&db_connection, id).unwrap().unwrap();
+    assert_eq!(metadata.size, file_size);
+
+    for invalid in [json!(1000), json!(256), json!(131072), json!("8192")] {
+        let (status, _) = send_json(&app, "PUT", &uri, Some(json!({ "page_size": invalid }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_reindex_rebuilds_indexes() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/reindex", id);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch("CREATE INDEX test1_name ON test1 (name)").unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(&app, &uri, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["reindexed"], "database");
+
+    let (status, json) = post_json(&app, &uri, json!({ "index": "test1_name" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["reindexed"], json!({ "index": "test1_name" }));
+
+    let (status, json) = post_json(&app, &uri, json!({ "table": "test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["reindexed"], json!({ "table": "test1" }));
+
+    // The rebuilt index is still used for lookups
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({
+        "sql": "EXPLAIN QUERY PLAN SELECT id FROM test1 WHERE name = 'Test 1'"
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["rows"].to_string().contains("INDEX test1_name"));
+
+    let (status, _) = post_json(&app, &uri, json!({ "index": "missing_index" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = post_json(&app, &uri, json!({ "index": "test1_name", "table": "test1" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_reindex_reports_missing_collation() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    // Point an index at a collation this server doesn't define, as a database
+    // created by another application might
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE INDEX test1_name ON test1 (name COLLATE NOCASE);
+         PRAGMA writable_schema = ON;
+         UPDATE sqlite_master SET sql = replace(sql, 'NOCASE', 'app_collation') WHERE name = 'test1_name';
+         PRAGMA writable_schema = OFF;"
+    ).unwrap();
+    let schema_version: i64 = conn.query_row("PRAGMA schema_version", [], |row| row.get(0)).unwrap();
+    conn.execute_batch(&format!("PRAGMA schema_version = {}", schema_version + 1)).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/reindex", id), json!({})).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(json["code"], "MISSING_COLLATION");
+    assert_eq!(json["collation"], "app_collation");
+
+    test_env.cleanup();
+}
+
+// Percent-encode a path segment
+fn encode_segment(segment: &str) -> String {
+    segment.bytes()
+        .map(|b| if b.is_ascii_alphanumeric() { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_special_table_names_work_end_to_end() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let spaced = "order \"line\" items";
+    let hostile = "test1); DROP TABLE test1; --";
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    for name in [spaced, hostile] {
+        conn.execute_batch(&format!(
+            "CREATE TABLE {} (id INTEGER PRIMARY KEY, label TEXT); INSERT INTO {} VALUES (1, 'one');",
+            rs_backend::utils::quote_identifier(name),
+            rs_backend::utils::quote_identifier(name),
+        )).unwrap();
+    }
+    drop(conn);
+
+    for name in [spaced, hostile] {
+        let (status, json) = get_json(&app, &format!("/databases/{}/tables/{}/schema", id, encode_segment(name))).await;
+        assert_eq!(status, StatusCode::OK, "{}", name);
+        assert_eq!(json["schema"][1]["name"], "label");
+
+        let (status, json) = post_json(
+            &app,
+            &format!("/databases/{}/tables/{}/diff-preview", id, encode_segment(name)),
+            json!({ "rows": [{ "id": 1, "label": "uno" }] }),
+        ).await;
+        assert_eq!(status, StatusCode::OK, "{}", name);
+        assert_eq!(json["summary"]["update"], 1);
+    }
+
+    // The injection attempt was treated as a name, so test1 is untouched
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT COUNT(*) AS total FROM test1" }),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 2);
+
+    let (status, _) = get_json(&app, &format!("/databases/{}/tables/{}/schema", id, encode_segment("bad\nname"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A quoted name is looked up literally, not resolved to the table it spells
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/{}/schema", id, encode_segment("\"test1\""))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+    assert_eq!(json["error"]["table"], "\"test1\"");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_foreign_keys_group_composite_columns() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE regions (country TEXT, code TEXT, PRIMARY KEY (country, code));
+         CREATE TABLE stores (
+             id INTEGER PRIMARY KEY,
+             owner_id INTEGER REFERENCES test1 ON DELETE CASCADE,
+             country TEXT,
+             region TEXT,
+             FOREIGN KEY (country, region) REFERENCES regions (country, code) ON UPDATE SET NULL
+         );"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/stores/foreign-keys", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let fks = json["foreign_keys"].as_array().unwrap();
+    assert_eq!(fks.len(), 2);
+
+    let composite = fks.iter().find(|fk| fk["table"] == "regions").unwrap();
+    assert_eq!(composite["columns"], json!([
+        { "seq": 0, "from": "country", "to": "country" },
+        { "seq": 1, "from": "region", "to": "code" },
+    ]));
+    assert_eq!(composite["on_update"], "SET NULL");
+    assert_eq!(composite["on_delete"], "NO ACTION");
+
+    let single = fks.iter().find(|fk| fk["table"] == "test1").unwrap();
+    assert_eq!(single["columns"], json!([{ "seq": 0, "from": "owner_id", "to": null }]));
+    assert_eq!(single["on_delete"], "CASCADE");
+    assert_ne!(single["id"], composite["id"]);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/test1/foreign-keys", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["foreign_keys"], json!([]));
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/missing/foreign-keys", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_views_listed_with_definitions() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/views", id);
+
+    let (status, json) = get_json(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["views"], json!([]));
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch("CREATE VIEW big_values AS SELECT * FROM test2 WHERE value > 50").unwrap();
+    drop(conn);
+
+    let (status, json) = get_json(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["views"], json!([{
+        "name": "big_values",
+        "sql": "CREATE VIEW big_values AS SELECT * FROM test2 WHERE value > 50"
+    }]));
+
+    // Tables are still listed on their own
+    let (_, json) = get_json(&app, &format!("/databases/{}/tables", id)).await;
+    assert!(!json["tables"].as_array().unwrap().contains(&json!("big_values")));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_malformed_ids_are_bad_requests() {
+    let (app, _, test_env) = setup_test_app().await;
+
+    for uri in ["/databases/abc", "/databases/1.5/tables", "/databases/99999999999999999999", "/imports/..%2F1/status"] {
+        let (status, json) = get_json(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert_eq!(json, json!({ "error": "Invalid path parameter" }), "{}", uri);
+    }
+    let (status, _) = post_json(&app, "/databases/abc/query", json!({ "sql": "SELECT 1" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Well-formed but unknown ids are simply not found
+    let (status, json) = get_json(&app, "/databases/424242").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json, json!({ "error": "Database not found" }));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_traversal_table_names_are_not_found() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    for table in ["..%2F..%2Fmetadata.db", "%2Fetc%2Fpasswd", "..%5C..%5Cmetadata"] {
+        let (status, json) = get_json(&app, &format!("/databases/{}/tables/{}/schema", id, table)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", table);
+        assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+
+        let (status, json) = post_json(
+            &app,
+            &format!("/databases/{}/tables/{}/diff-preview", id, table),
+            json!({ "rows": [{ "id": 1 }] }),
+        ).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", table);
+        assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+    }
+
+    // Same response whether or not the database exists behind the name
+    let (status, json) = get_json(&app, "/databases/424242/tables/..%2Fsecrets/schema").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json, json!({ "error": "Database not found" }));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_download_streams_database_file() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri(format!("/databases/{}/download", id)).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-sqlite3");
+    assert_eq!(response.headers()["content-disposition"], "attachment; filename=\"test.db\"");
+    let body = read_response_body(response).await.unwrap();
+    assert_eq!(body.to_vec(), std::fs::read(&db_path).unwrap());
+
+    // Metadata that outlived its file
+    std::fs::remove_file(&db_path).unwrap();
+    let (status, json) = get_json(&app, &format!("/databases/{}/download", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"], "Database file is missing from storage");
+
+    let (status, _) = get_json(&app, "/databases/999999/download").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_download_link_serves_file_until_expiry() {
+    use rs_backend::utils::signed_link;
+
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/download-link", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let url = json["url"].as_str().unwrap().to_string();
+    let token = json["token"].as_str().unwrap().to_string();
+    assert_eq!(url, format!("/download/{}", token));
+
+    // No headers needed beyond the token in the URL
+    let response = app.clone()
+        .oneshot(Request::builder().uri(&url).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-disposition"].to_str().unwrap().starts_with("attachment;"));
+    let body = read_response_body(response).await.unwrap();
+    assert_eq!(body.to_vec(), std::fs::read(&db_path).unwrap());
+
+    // Changing the signature, or the id it covers, invalidates the token
+    let mut tampered = token.clone();
+    let last = if tampered.ends_with('0') { "1" } else { "0" };
+    tampered.replace_range(tampered.len() - 1.., last);
+    let (status, _) = get_json(&app, &format!("/download/{}", tampered)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let retargeted = token.replacen(&id.to_string(), &(id + 1).to_string(), 1);
+    let (status, _) = get_json(&app, &format!("/download/{}", retargeted)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = get_json(&app, "/download/not-a-token").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // A correctly signed token past its expiry is rejected
+    let expired_at = chrono::Utc::now().timestamp() - 1;
+    let expired = signed_link::sign(db_connection.download_key(), id, expired_at);
+    let (status, json) = get_json(&app, &format!("/download/{}", expired)).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(json["error"], "Download link has expired");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_column_meta_flags_autoincrement_key() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE events (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             kind TEXT NOT NULL DEFAULT 'click',
+             note TEXT
+         );
+         CREATE TABLE plain (id INTEGER PRIMARY KEY, label TEXT);
+         INSERT INTO events (kind) VALUES ('click'), ('view'), ('click');"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/events/columns/id/meta", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["column"]["declared_type"], "INTEGER");
+    assert_eq!(json["column"]["primary_key"], true);
+    assert_eq!(json["column"]["auto_increment"], true);
+    assert_eq!(json["column"]["nullable"], false);
+    assert_eq!(json["column"]["distinct_count"], 3);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/events/columns/kind/meta", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["column"]["auto_increment"], false);
+    assert_eq!(json["column"]["default_value"], "'click'");
+    assert_eq!(json["column"]["distinct_count"], 2);
+
+    // A rowid alias without the keyword isn't auto-increment
+    let (_, json) = get_json(&app, &format!("/databases/{}/tables/plain/columns/id/meta", id)).await;
+    assert_eq!(json["column"]["primary_key"], true);
+    assert_eq!(json["column"]["auto_increment"], false);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/events/columns/missing/meta", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "COLUMN_NOT_FOUND");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_validate_expression_reports_validity() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/validate-expression", id);
+
+    // test2 is (id INTEGER PRIMARY KEY, value INTEGER)
+    let (status, json) = post_json(&app, &uri, json!({ "table": "test2", "expression": "value * 2 + 1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["valid"], true);
+    assert_eq!(json["inferred_type"], "integer");
+
+    let (_, json) = post_json(&app, &uri, json!({ "table": "test2", "expression": "value" })).await;
+    assert_eq!(json["declared_type"], "INTEGER");
+
+    let (status, json) = post_json(&app, &uri, json!({ "table": "test2", "expression": "missing_column + 1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["valid"], false);
+    assert!(json["error"].as_str().unwrap().contains("no such column: missing_column"), "{}", json);
+
+    let (_, json) = post_json(&app, &uri, json!({ "table": "test2", "kind": "where", "expression": "value > 1 AND id < 10" })).await;
+    assert_eq!(json["valid"], true);
+    assert_eq!(json["kind"], "where");
+
+    let (status, json) = post_json(&app, &uri, json!({ "table": "nope", "expression": "1" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_quality_reports_tables_without_primary_key() {
+    let (app, db_connection, test_env) = setup_test_app().await;
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE keyed (code TEXT PRIMARY KEY, label TEXT);
+         CREATE TABLE keyless (label TEXT, amount REAL);
+         CREATE VIEW keyless_view AS SELECT label FROM keyless;
+         CREATE TABLE counters (id INTEGER PRIMARY KEY AUTOINCREMENT);"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/quality/no-pk", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    // test1/test2 from the fixture have primary keys; sqlite_sequence is internal
+    assert_eq!(json["tables"], json!(["keyless"]));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_describe_returns_structure_and_schema_hash() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer_id INTEGER NOT NULL REFERENCES test1(id), total REAL);"
+    ).unwrap();
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/describe", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let tables = json["tables"].as_array().unwrap();
+    assert_eq!(tables.iter().map(|t| t["name"].as_str().unwrap()).collect::<Vec<_>>(), ["orders", "test1", "test2"]);
+
+    let orders = &tables[0];
+    assert_eq!(orders["primary_key"], json!(["id"]));
+    assert_eq!(orders["columns"][1], json!({
+        "name": "customer_id", "type": "INTEGER", "not_null": true, "default": null, "primary_key": 0
+    }));
+    assert_eq!(orders["foreign_keys"], json!([
+        {
+            "columns": ["customer_id"],
+            "references_table": "test1",
+            "references_columns": ["id"],
+            "on_update": "NO ACTION",
+            "on_delete": "NO ACTION",
+            "match": "NONE"
+        }
+    ]));
+    assert!(json.get("rows").is_none() && orders.get("rows").is_none());
+
+    // Stable across calls, and changes with the structure
+    let hash = json["schema_hash"].as_str().unwrap().to_string();
+    let (_, json) = get_json(&app, &format!("/databases/{}/describe", id)).await;
+    assert_eq!(json["schema_hash"], hash);
+
+    conn.execute_batch("ALTER TABLE orders ADD COLUMN note TEXT").unwrap();
+    drop(conn);
+    let (_, json) = get_json(&app, &format!("/databases/{}/describe", id)).await;
+    assert_ne!(json["schema_hash"], hash);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_index_report_flags_redundant_indexes() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer TEXT, placed_at TEXT, status TEXT);
+         WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 200)
+         INSERT INTO orders SELECT n, 'c' || (n % 50), date('2024-01-01', '+' || n || ' days'),
+             CASE n % 2 WHEN 0 THEN 'open' ELSE 'closed' END FROM seq;
+         CREATE INDEX idx_orders_customer ON orders (customer);
+         CREATE INDEX idx_orders_customer_again ON orders (customer);
+         CREATE INDEX idx_orders_placed ON orders (placed_at);
+         CREATE INDEX idx_orders_placed_customer ON orders (placed_at, customer);
+         CREATE INDEX idx_orders_status ON orders (status);"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/quality/indexes", id)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let duplicates = json["duplicates"].as_array().unwrap();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0]["index"]["name"], "idx_orders_customer_again");
+    assert_eq!(duplicates[0]["redundant_with"], "idx_orders_customer");
+
+    let covered = json["covered"].as_array().unwrap();
+    assert_eq!(covered.len(), 1);
+    assert_eq!(covered[0]["index"]["name"], "idx_orders_placed");
+    assert_eq!(covered[0]["redundant_with"], "idx_orders_placed_customer");
+
+    let low_cardinality = json["low_cardinality"].as_array().unwrap();
+    assert_eq!(low_cardinality.len(), 1);
+    assert_eq!(low_cardinality[0]["index"]["name"], "idx_orders_status");
+    assert_eq!(low_cardinality[0]["distinct_values"], 2);
+
+    // Only recommendations: every index is still there
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'orders'", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 5);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_rename_column() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/tables/test1/columns/name/rename", id),
+        json!({ "new_name": "title" }),
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["schema"][1]["name"], "title");
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/test1/schema", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<&str> = json["schema"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["id", "title"]);
+
+    // Data follows the column
+    let (_, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT title FROM test1 ORDER BY id" })).await;
+    assert_eq!(json["rows"].as_array().unwrap().len(), 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_rename_column_rejects_collisions() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    // Column names compare case-insensitively
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/tables/test1/columns/name/rename", id),
+        json!({ "new_name": "ID" }),
+    ).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(json["error"]["code"], "COLUMN_EXISTS");
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/tables/test1/columns/missing/rename", id),
+        json!({ "new_name": "other" }),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "COLUMN_NOT_FOUND");
+
+    let (status, _) = post_json(
+        &app,
+        &format!("/databases/{}/tables/test1/columns/name/rename", id),
+        json!({ "new_name": "" }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_features_reflect_configuration() {
+    let test_env = TestEnv::new();
+    let app = rs_backend::create_app(DbConnection::new());
+
+    let (status, json) = get_json(&app, "/features").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["features"]["admin_api"]["enabled"], false);
+    assert_eq!(json["features"]["upload_quota"]["enabled"], false);
+    assert!(json["features"]["admin_api"]["description"].is_string());
+
+    let db_connection = DbConnection::new()
+        .with_admin_token(Some("secret".to_string()))
+        .with_upload_quota(Some(1024 * 1024), std::time::Duration::from_secs(60));
+    let app = rs_backend::create_app(db_connection);
+
+    // Public: no admin token needed to discover that the admin API exists
+    let (status, json) = get_json(&app, "/features").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["features"]["admin_api"]["enabled"], true);
+    assert_eq!(json["features"]["upload_quota"]["enabled"], true);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_pagination_inputs_rejected_uniformly() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let (db_id, _) = test_env.register_test_db(&db_connection);
+    let app = rs_backend::create_app(db_connection);
+
+    let endpoints = [
+        "/databases".to_string(),
+        format!("/databases/{}/audit", db_id),
+        format!("/databases/{}/history", db_id),
+    ];
+    for endpoint in &endpoints {
+        for (field, value) in [("limit", "-1"), ("limit", "ten"), ("offset", "-5"), ("offset", "1.5")] {
+            let (status, json) = get_json(&app, &format!("{}?{}={}", endpoint, field, value)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{} {}={}", endpoint, field, value);
+            assert_eq!(json["code"], "INVALID_PAGINATION");
+            assert_eq!(json["field"], field);
+        }
+
+        let (status, _) = get_json(&app, &format!("{}?limit=0&offset=0", endpoint)).await;
+        assert_eq!(status, StatusCode::OK, "{}", endpoint);
+    }
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_list_databases_pages_with_offset() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    for name in ["first.db", "second.db"] {
+        let path = test_env.test_dir.join(name).to_string_lossy().into_owned();
+        DatabaseMetadata::new(name.to_string(), path, 1000, 2, false, None)
+            .save(&db_connection)
+            .unwrap();
+    }
+    let app = rs_backend::create_app(db_connection);
+
+    let (_, all) = get_json(&app, "/databases").await;
+    let all = all["databases"].as_array().unwrap().clone();
+    assert_eq!(all.len(), 2);
+
+    let (status, page) = get_json(&app, "/databases?limit=1&offset=1").await;
+    assert_eq!(status, StatusCode::OK);
+    let page = page["databases"].as_array().unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0]["id"], all[1]["id"]);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_list_databases_sorts_and_filters_favorites() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    for (name, size, favorite) in [("bravo.db", 300, true), ("alpha.db", 100, false), ("charlie.db", 200, true)] {
+        let path = test_env.test_dir.join(name).to_string_lossy().into_owned();
+        DatabaseMetadata::new(name.to_string(), path, size, 2, favorite, None)
+            .save(&db_connection)
+            .unwrap();
+    }
+    let app = rs_backend::create_app(db_connection);
+    let names = |json: &Value| -> Vec<String> {
+        json["databases"].as_array().unwrap().iter().map(|d| d["name"].as_str().unwrap().to_string()).collect()
+    };
+
+    let (_, json) = get_json(&app, "/databases?sort=name&order=asc").await;
+    assert_eq!(names(&json), ["alpha.db", "bravo.db", "charlie.db"]);
+
+    let (_, json) = get_json(&app, "/databases?sort=size").await;
+    assert_eq!(names(&json), ["bravo.db", "charlie.db", "alpha.db"]);
+
+    let (_, json) = get_json(&app, "/databases?favorites_only=true&sort=size&order=asc").await;
+    assert_eq!(names(&json), ["charlie.db", "bravo.db"]);
+
+    for uri in ["/databases?sort=name%3BDROP%20TABLE%20database_metadata", "/databases?sort=path", "/databases?order=up"] {
+        let (status, _) = get_json(&app, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_bulk_tag_only_touches_matches() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    for name in ["sales-2023.db", "Sales-2024.db", "inventory.db"] {
+        let path = test_env.test_dir.join(name).to_string_lossy().into_owned();
+        DatabaseMetadata::new(name.to_string(), path, 1000, 2, false, None)
+            .save(&db_connection)
+            .unwrap();
+    }
+    let app = rs_backend::create_app(db_connection);
+
+    let (status, json) = send_json(&app, "POST", "/databases/bulk-tag", Some(json!({
+        "filter": { "name": "sales" },
+        "add": ["finance", " reporting "]
+    }))).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["matched"], 2);
+
+    let (_, json) = get_json(&app, "/databases").await;
+    for database in json["databases"].as_array().unwrap() {
+        let expected = if database["name"] == "inventory.db" { json!([]) } else { json!(["finance", "reporting"]) };
+        assert_eq!(database["tags"], expected, "{}", database["name"]);
+    }
+
+    // Filtering by tag, and removal
+    let (status, json) = send_json(&app, "POST", "/databases/bulk-tag", Some(json!({
+        "filter": { "tag": "finance", "name": "2024" },
+        "remove": ["reporting"]
+    }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["matched"], 1);
+    let (_, json) = get_json(&app, "/databases?tag=reporting").await;
+    let names: Vec<&str> = json["databases"].as_array().unwrap().iter().map(|d| d["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["sales-2023.db"]);
+
+    // A filter that can't be applied is rejected rather than matching everything
+    for payload in [
+        json!({ "filter": { "nmae": "sales" }, "add": ["x"] }),
+        json!({ "filter": { "name": 5 }, "add": ["x"] }),
+        json!({ "add": [""] }),
+        json!({ "filter": { "name": "sales" } }),
+    ] {
+        let (status, _) = send_json(&app, "POST", "/databases/bulk-tag", Some(payload.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", payload);
+    }
+    let (_, json) = get_json(&app, "/databases?tag=x").await;
+    assert!(json["databases"].as_array().unwrap().is_empty());
+
+    // A single database's tags can be replaced wholesale
+    let (_, json) = get_json(&app, "/databases?name=inventory").await;
+    let id = json["databases"][0]["id"].as_i64().unwrap();
+    let (status, json) = send_json(&app, "PUT", &format!("/databases/{}", id), Some(json!({ "tags": ["ops", "ops", " stock "] }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["database"]["tags"], json!(["ops", "stock"]));
+    let (status, _) = send_json(&app, "PUT", &format!("/databases/{}", id), Some(json!({ "tags": "ops" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, json) = get_json(&app, "/databases?tag=stock").await;
+    assert_eq!(json["databases"].as_array().unwrap().len(), 1);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_delete_database_evicts_cached_pool() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let (db_id, db_path) = test_env.register_test_db(&db_connection);
+    let app = rs_backend::create_app(db_connection.clone());
+
+    let (status, _) = get_json(&app, &format!("/databases/{}/tables", db_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(db_connection.database_pools().contains(&db_path));
+
+    let (status, _) = send_json(&app, "DELETE", &format!("/databases/{}", db_id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!db_path.exists());
+    assert!(!db_connection.database_pools().contains(&db_path));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_table_growth_reflects_writes_between_samples() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let (db_id, db_path) = test_env.register_test_db(&db_connection);
+    let app = rs_backend::create_app(db_connection.clone());
+
+    TableGrowth::sample_all(&db_connection).unwrap();
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute("INSERT INTO test1 (id, name) VALUES (3, 'Test 3')", []).unwrap();
+    drop(conn);
+    TableGrowth::sample_all(&db_connection).unwrap();
+
+    let (status, body) = get_json(&app, &format!("/databases/{}/tables/test1/growth", db_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["table"], "test1");
+    let samples = body["samples"].as_array().unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0]["row_count"], 2);
+    assert_eq!(samples[0]["change"], Value::Null);
+    assert_eq!(samples[1]["row_count"], 3);
+    assert_eq!(samples[1]["change"], 1);
+
+    let (status, body) = get_json(&app, &format!("/databases/{}/tables/missing/growth", db_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "TABLE_NOT_FOUND");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_bundle_carries_metadata_to_a_clone() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some("secret".to_string()));
+    let (source_id, source_path) = test_env.register_test_db(&db_connection);
+    let app = rs_backend::create_app(db_connection.clone());
+
+    let (status, _) = send_json_as(&app, "PUT", &format!("/databases/{}", source_id), Some(json!({
+        "notes": "Quarterly numbers",
+        "audit_enabled": true,
+        "properties": { "owner": "finance" }
+    })), Some("secret")).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut source = DatabaseMetadata::find_by_id(&db_connection, source_id).unwrap().unwrap();
+    source.tags.insert("finance".to_string());
+    source.properties.insert("original_copy".to_string(), "originals/source.db".to_string());
+    source.save(&db_connection).unwrap();
+    let (status, _) = post_json(&app, &format!("/databases/{}/saved-queries", source_id), json!({
+        "name": "by_id",
+        "sql": "SELECT * FROM test1 WHERE id = {{id:integer}}"
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, bundle) = get_json(&app, &format!("/databases/{}/bundle/export", source_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bundle["saved_queries"][0]["name"], "by_id");
+    assert!(bundle.get("rows").is_none());
+    assert!(bundle["properties"].get("original_copy").is_none());
+
+    // A byte-for-byte copy registered as a separate database
+    let clone_path = test_env.test_dir.join("clone.db");
+    std::fs::copy(&source_path, &clone_path).unwrap();
+    let clone = DatabaseMetadata::new(
+        "clone.db".to_string(),
+        clone_path.to_string_lossy().into_owned(),
+        1000,
+        2,
+        false,
+        None,
+    ).save(&db_connection).unwrap();
+    let clone_id = clone.id.unwrap();
+
+    // Server-owned keys in a hand-edited bundle are ignored
+    let mut edited = bundle.clone();
+    edited["properties"]["integrity"] = json!("ok");
+
+    // Turning on auditing through a bundle takes the admin token too
+    let uri = format!("/databases/{}/bundle/import", clone_id);
+    let (status, _) = post_json(&app, &uri, edited.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, json) = send_json_as(&app, "POST", &uri, Some(edited), Some("secret")).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["saved_queries"]["imported"], json!(["by_id"]));
+    assert_eq!(json["database"]["tags"], json!(["finance"]));
+    assert_eq!(json["database"]["notes"], "Quarterly numbers");
+    assert_eq!(json["database"]["audit_enabled"], true);
+    assert_eq!(json["database"]["properties"]["owner"], "finance");
+    assert!(json["database"]["properties"].get("integrity").is_none());
+
+    let (_, json) = get_json(&app, &format!("/databases/{}/saved-queries", clone_id)).await;
+    assert_eq!(json["queries"][0]["sql"], "SELECT * FROM test1 WHERE id = {{id:integer}}");
+
+    // Importing again leaves the existing query alone
+    let (_, json) = post_json(&app, &uri, bundle.clone()).await;
+    assert_eq!(json["saved_queries"]["skipped"][0]["name"], "by_id");
+
+    // A database with different contents is refused unless forced
+    let conn = rusqlite::Connection::open(&clone_path).unwrap();
+    conn.execute("INSERT INTO test1 (id, name) VALUES (3, 'Test 3')", []).unwrap();
+    drop(conn);
+    let (status, json) = post_json(&app, &uri, bundle.clone()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(json["error"]["code"], "BUNDLE_MISMATCH");
+    let (status, _) = post_json(&app, &format!("{}?force=true", uri), bundle).await;
+    assert_eq!(status, StatusCode::OK);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_fingerprint_stable_until_write() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let (db_id, db_path) = test_env.register_test_db(&db_connection);
+    let app = rs_backend::create_app(db_connection);
+    let uri = format!("/databases/{}/fingerprint", db_id);
+
+    let (status, first) = get_json(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["algorithm"], "sha256");
+    assert_eq!(first["fingerprint"].as_str().unwrap().len(), 64);
+    assert_eq!(first["cached"], false);
+
+    let (_, second) = get_json(&app, &uri).await;
+    assert_eq!(second["fingerprint"], first["fingerprint"]);
+    assert_eq!(second["cached"], true);
+
+    // Let the clock move past the mtime granularity before writing
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute("INSERT INTO test1 (id, name) VALUES (3, 'changed')", []).unwrap();
+    drop(conn);
+
+    let (status, third) = get_json(&app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(third["fingerprint"], first["fingerprint"]);
+    assert_eq!(third["cached"], false);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_sample_insert_lists_columns_with_typed_placeholders() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let (db_id, db_path) = test_env.register_test_db(&db_connection);
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE items (id INTEGER PRIMARY KEY, label VARCHAR(20), price REAL, photo BLOB, qty NUMERIC);
+         CREATE TABLE empty_items (id INTEGER, note TEXT);
+         INSERT INTO items VALUES (7, 'it''s', 2.5, x'0aff', 3);"
+    ).unwrap();
+    drop(conn);
+    let app = rs_backend::create_app(db_connection);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/items/sample-insert", db_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        json["template"],
+        r#"INSERT INTO "items" ("id", "label", "price", "photo", "qty") VALUES (0, '', 0.0, X'', 0);"#
+    );
+    let names: Vec<&str> = json["columns"].as_array().unwrap().iter()
+        .map(|c| c["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["id", "label", "price", "photo", "qty"]);
+    assert_eq!(json["columns"][1]["affinity"], "TEXT");
+    assert_eq!(
+        json["example"],
+        r#"INSERT INTO "items" ("id", "label", "price", "photo", "qty") VALUES (7, 'it''s', 2.5, X'0AFF', 3);"#
+    );
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/empty_items/sample-insert", db_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["example"].is_null());
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/missing/sample-insert", db_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+
+    test_env.cleanup();
+}
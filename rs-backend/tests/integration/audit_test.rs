@@ -0,0 +1,65 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::json;
+
+use crate::common::{get_json, send, send_json, send_json_as, TestEnv};
+use rs_backend::db::connection::DbConnection;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+#[tokio::test]
+async fn test_audit_log_records_client_attribution() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_admin_token(Some(ADMIN_TOKEN.to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    // Queries before audit is enabled are not recorded
+    let (status, _) = send_json(
+        &app, "POST", &format!("/databases/{}/query", id),
+        Some(json!({ "sql": "SELECT * FROM test2" })),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Only an admin can switch auditing on or off
+    let (status, _) = send_json(
+        &app, "PUT", &format!("/databases/{}", id),
+        Some(json!({ "audit_enabled": true })),
+    ).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, json) = send_json_as(
+        &app, "PUT", &format!("/databases/{}", id),
+        Some(json!({ "audit_enabled": true })), Some(ADMIN_TOKEN),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["database"]["audit_enabled"], true);
+
+    for (client, sql) in [("analyst-1", "SELECT * FROM test1"), ("analyst-2", "SELECT * FROM test1 LIMIT 1")] {
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/databases/{}/query", id))
+            .header("content-type", "application/json")
+            .header("x-client-id", client)
+            .body(Body::from(json!({ "sql": sql }).to_string()))
+            .unwrap();
+        let (status, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/audit", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["audit_enabled"], true);
+
+    let entries = json["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["client_id"], "analyst-2");
+    assert_eq!(entries[0]["query"], "SELECT * FROM test1 LIMIT 1");
+    assert_eq!(entries[0]["row_count"], 1);
+    assert_eq!(entries[1]["client_id"], "analyst-1");
+    assert_eq!(entries[1]["row_count"], 2);
+
+    test_env.cleanup();
+}
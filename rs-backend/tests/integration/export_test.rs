@@ -0,0 +1,169 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::common::{get_json, post_json, send_json, TestEnv};
+use rs_backend::db::connection::DbConnection;
+use rs_backend::models::export_job::ExportJob;
+
+async fn wait_for_export(app: &axum::Router, job_id: i64) -> Value {
+    let mut job = Value::Null;
+    for _ in 0..100 {
+        let (status, json) = get_json(app, &format!("/exports/{}", job_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        job = json.clone();
+        if json["job"]["status"] != "pending" && json["job"]["status"] != "running" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    job
+}
+
+#[tokio::test]
+async fn test_export_job_reports_progress_and_downloads() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE events (id INTEGER PRIMARY KEY, name TEXT, score REAL);
+         WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 2500)
+         INSERT INTO events SELECT n, 'event, ' || n, n * 0.5 FROM seq;"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/exports", id),
+        json!({ "sql": "SELECT id, name, score FROM events ORDER BY id", "format": "csv" }),
+    ).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let job_id = json["job"]["id"].as_i64().unwrap();
+
+    for format in ["sql", "parquet"] {
+        let (status, json) = post_json(
+            &app,
+            &format!("/databases/{}/exports", id),
+            json!({ "sql": "SELECT id FROM events", "format": format }),
+        ).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "UNSUPPORTED_EXPORT_FORMAT");
+    }
+
+    let json = wait_for_export(&app, job_id).await;
+    assert_eq!(json["job"]["status"], "completed", "export did not complete: {}", json);
+    assert_eq!(json["job"]["rows_written"], 2500);
+    let bytes_written = json["job"]["bytes_written"].as_u64().unwrap();
+    let download_url = json["download_url"].as_str().unwrap().to_string();
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri(&download_url).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/csv"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.len() as u64, bytes_written);
+
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2501);
+    assert_eq!(lines[0], "id,name,score");
+    assert_eq!(lines[1], "1,\"event, 1\",0.5");
+
+    // Only the finished artifact is left behind
+    let exports: Vec<_> = std::fs::read_dir(test_env.test_dir.join("exports")).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(exports, vec![format!("{}.csv", job_id)]);
+
+    // A finished job can't be cancelled
+    let (status, _) = post_json(&app, &format!("/exports/{}/cancel", job_id), json!({})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // The artifact is held by the storage backend, and goes with its database
+    let key = format!("exports/{}.csv", job_id);
+    assert!(db_connection.storage().exists(&key).unwrap());
+    let (status, _) = send_json(&app, "DELETE", &format!("/databases/{}", id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_json(&app, &format!("/exports/{}", job_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(!db_connection.storage().exists(&key).unwrap());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_cancelled_export_stops_at_next_progress_update() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let job = ExportJob::create(&db_connection, id, "csv").unwrap();
+    assert!(ExportJob::update_progress(&db_connection, job.id, 1000, 4096).unwrap());
+
+    let (status, json) = post_json(&app, &format!("/exports/{}/cancel", job.id), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["job"]["status"], "cancelled");
+    assert!(json.get("download_url").is_none());
+
+    // The writer's next progress report tells it to stop, and it can't complete
+    assert!(!ExportJob::update_progress(&db_connection, job.id, 2000, 8192).unwrap());
+    assert!(!ExportJob::complete(&db_connection, job.id, 2500, 10000, "unused").unwrap());
+
+    let (status, _) = get_json(&app, &format!("/exports/{}/download", job.id)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_csv_bundle_contains_every_table() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let response = app.clone()
+        .oneshot(Request::builder().uri(format!("/databases/{}/export/csv-bundle", id)).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-tar");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    let mut entries = std::collections::BTreeMap::new();
+    let mut archive = tar::Archive::new(&body[..]);
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+        entries.insert(path, contents);
+    }
+    assert_eq!(entries.keys().collect::<Vec<_>>(), ["manifest.json", "test1.csv", "test2.csv"]);
+
+    let test1: Vec<&str> = entries["test1.csv"].lines().collect();
+    assert_eq!(test1.len(), 3);
+    assert_eq!(test1[0], "id,name");
+    let test2: Vec<&str> = entries["test2.csv"].lines().collect();
+    assert_eq!(test2.len(), 3);
+    assert_eq!(test2[0], "id,value");
+
+    let manifest: Value = serde_json::from_str(&entries["manifest.json"]).unwrap();
+    assert_eq!(manifest["tables"][0]["name"], "test1");
+    assert_eq!(manifest["tables"][0]["file"], "test1.csv");
+    assert_eq!(manifest["tables"][0]["rows"], 2);
+    assert_eq!(manifest["tables"][0]["columns"][1]["name"], "name");
+    assert!(manifest["schema_hash"].is_string());
+
+    test_env.cleanup();
+}
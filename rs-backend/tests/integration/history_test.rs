@@ -0,0 +1,85 @@
+use axum::http::StatusCode;
+use serde_json::json;
+
+use crate::common::{get_json, post_json, TestEnv};
+use rs_backend::db::connection::DbConnection;
+use rs_backend::models::query_history::HistoryRetention;
+
+#[tokio::test]
+async fn test_query_history_is_recorded() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT * FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/history", id)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let entries = json["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["query"], "SELECT * FROM test1");
+    assert_eq!(entries[0]["row_count"], 2);
+    assert!(entries[0]["duration_ms"].is_i64());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_history_trims_oldest_entries() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_history_retention(HistoryRetention {
+        max_entries: Some(3),
+        max_age_days: None,
+    });
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    for n in 1..=5 {
+        let (status, _) = post_json(
+            &app,
+            &format!("/databases/{}/query", id),
+            json!({ "sql": format!("SELECT {} AS n", n) }),
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (_, json) = get_json(&app, &format!("/databases/{}/history", id)).await;
+    let queries: Vec<&str> = json["entries"].as_array().unwrap()
+        .iter()
+        .map(|entry| entry["query"].as_str().unwrap())
+        .collect();
+    assert_eq!(queries, vec!["SELECT 5 AS n", "SELECT 4 AS n", "SELECT 3 AS n"]);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_history_drops_expired_entries() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_history_retention(HistoryRetention {
+        max_entries: None,
+        max_age_days: Some(7),
+    });
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let stale = (chrono::Utc::now() - chrono::Duration::days(8)).to_rfc3339();
+    db_connection.get_metadata_pool().get().unwrap().execute(
+        "INSERT INTO query_history (database_id, query, row_count, duration_ms, executed_at)
+         VALUES (?, 'SELECT old', 0, 0, ?)",
+        rusqlite::params![id, stale],
+    ).unwrap();
+
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT 1" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, json) = get_json(&app, &format!("/databases/{}/history", id)).await;
+    let entries = json["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["query"], "SELECT 1");
+
+    test_env.cleanup();
+}
@@ -0,0 +1,231 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+
+use crate::common::{get_json, post_json, send, TestEnv};
+use rs_backend::db::connection::DbConnection;
+use rs_backend::models::database_metadata::DatabaseMetadata;
+
+#[tokio::test]
+async fn test_import_path_outside_allowlist_is_rejected() {
+    let test_env = TestEnv::new();
+    let allowed_dir = test_env.test_dir.join("imports");
+    std::fs::create_dir_all(&allowed_dir).unwrap();
+    let db_connection = DbConnection::new().with_import_allowed_dirs(vec![allowed_dir.clone()]);
+    let app = rs_backend::create_app(db_connection);
+
+    let (status, json) = post_json(&app, "/databases/import/path", json!({ "path": "/etc/passwd" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(json["error"], "Path is not within an allowed import directory");
+
+    // Traversal out of the allowed directory is caught after canonicalization
+    let traversal = format!("{}/../../../../etc/passwd", allowed_dir.display());
+    let (status, _) = post_json(&app, "/databases/import/path", json!({ "path": traversal })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_import_path_inside_allowlist_succeeds() {
+    let test_env = TestEnv::new();
+    let allowed_dir = test_env.test_dir.join("imports");
+    std::fs::create_dir_all(&allowed_dir).unwrap();
+    let db_connection = DbConnection::new().with_import_allowed_dirs(vec![allowed_dir.clone()]);
+    let app = rs_backend::create_app(db_connection);
+
+    let source = allowed_dir.join("source.db");
+    std::fs::copy(test_env.create_test_db(), &source).unwrap();
+
+    let (status, json) = post_json(
+        &app,
+        "/databases/import/path",
+        json!({ "path": source.to_string_lossy() }),
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["database"]["name"], "source.db");
+    assert_eq!(json["database"]["table_count"], 2);
+
+    test_env.cleanup();
+}
+
+// Poll an import job until it completes or fails
+async fn wait_for_import(app: &axum::Router, job_id: i64) -> Value {
+    let mut job = Value::Null;
+    for _ in 0..100 {
+        let (status, json) = get_json(app, &format!("/imports/{}/status", job_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        job = json["job"].clone();
+        if job["status"] == "completed" || job["status"] == "failed" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    job
+}
+
+#[tokio::test]
+async fn test_csv_import_reports_progress_until_complete() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection);
+
+    let mut csv = String::from("id,name,score\n");
+    for i in 1..=2500 {
+        csv.push_str(&format!("{},\"name, {}\",{}.5\n", i, i, i));
+    }
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/databases/import/csv?name=scores.db&table=scores")
+        .header("content-type", "text/csv")
+        .body(Body::from(csv))
+        .unwrap();
+    let (status, json) = send(&app, request).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let job = wait_for_import(&app, json["job"]["id"].as_i64().unwrap()).await;
+    assert_eq!(job["status"], "completed", "job did not complete: {}", job);
+    assert_eq!(job["rows_processed"], 2500);
+    assert_eq!(job["total_rows"], 2500);
+
+    let database_id = job["database_id"].as_i64().unwrap();
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", database_id),
+        json!({ "sql": "SELECT COUNT(*) AS n, typeof(id) AS id_type, typeof(score) AS score_type FROM scores" }),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["n"], 2500);
+    assert_eq!(json["rows"][0]["id_type"], "integer");
+    assert_eq!(json["rows"][0]["score_type"], "real");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_csv_import_is_registered_like_an_upload() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let import = || Request::builder()
+        .method("POST")
+        .uri("/databases/import/csv?name=cities.db&table=cities")
+        .header("content-type", "text/csv")
+        .body(Body::from("id,name\n1,Oslo\n2,Lima\n"))
+        .unwrap();
+
+    let (status, json) = send(&app, import()).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let job = wait_for_import(&app, json["job"]["id"].as_i64().unwrap()).await;
+    assert_eq!(job["status"], "completed", "{}", job);
+    let id = job["database_id"].as_i64().unwrap();
+
+    let metadata = DatabaseMetadata::find_by_id(&db_connection, id).unwrap().unwrap();
+    assert!(metadata.checksum.is_some());
+    assert!(metadata.original_copy.is_some());
+
+    // The untouched copy is there to reset to
+    let uri = format!("/databases/{}/query", id);
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "DELETE FROM cities", "read_only": false })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, json) = post_json(&app, &format!("/databases/{}/reset", id), json!({ "confirm": true })).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    let (_, json) = post_json(&app, &uri, json!({ "sql": "SELECT COUNT(*) AS n FROM cities" })).await;
+    assert_eq!(json["rows"][0]["n"], 2);
+
+    // The same data again is a duplicate of the first import
+    let (_, json) = send(&app, import()).await;
+    let job = wait_for_import(&app, json["job"]["id"].as_i64().unwrap()).await;
+    assert_eq!(job["status"], "failed", "{}", job);
+    assert!(job["error"].as_str().unwrap().contains("identical database"), "{}", job);
+
+    // Databases built from CSV are held to the upload size limit too
+    let config = rs_backend::config::Config {
+        max_file_size: 2048,
+        ..rs_backend::config::Config::from_env().unwrap()
+    };
+    let app = rs_backend::create_app(DbConnection::from_config(config).unwrap());
+    let (_, json) = send(&app, import()).await;
+    let job = wait_for_import(&app, json["job"]["id"].as_i64().unwrap()).await;
+    assert_eq!(job["status"], "failed", "{}", job);
+    assert!(job["error"].as_str().unwrap().contains("File too large"), "{}", job);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_csv_import_dry_run_reports_schema_without_storing() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/databases/import/csv?name=dry-run-scores.db&table=scores&dry_run=true")
+        .header("content-type", "text/csv")
+        .body(Body::from("id,score,,score,note\n1,2.5,a,x,\n2,3,b,y,\n3,4.25,c,z,\n"))
+        .unwrap();
+    let (status, json) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["dry_run"], true);
+    assert!(json.get("job").is_none());
+    assert_eq!(json["tables"], json!([{
+        "name": "scores",
+        "columns": [
+            { "name": "id", "type": "INTEGER" },
+            { "name": "score", "type": "REAL" },
+            { "name": "column_3", "type": "TEXT" },
+            { "name": "score_2", "type": "TEXT" },
+            { "name": "note", "type": "TEXT" },
+        ],
+        "row_count": 3,
+    }]));
+    let warnings = json["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+
+    // Nothing was registered or written to storage
+    assert!(DatabaseMetadata::find_by_name(&db_connection, "dry-run-scores.db").unwrap().is_none());
+    let stored = std::fs::read_dir(db_connection.get_storage_path("databases")).unwrap();
+    assert!(stored.flatten().all(|entry| !entry.file_name().to_string_lossy().contains("dry-run-scores")));
+
+    // Malformed input is reported up front instead of failing a job later
+    let request = Request::builder()
+        .method("POST")
+        .uri("/databases/import/csv?dry_run=true")
+        .header("content-type", "text/csv")
+        .body(Body::from("a,b\n1,2,3\n"))
+        .unwrap();
+    let (status, json) = send(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().starts_with("Invalid CSV"));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_import_path_dry_run_lists_tables() {
+    let test_env = TestEnv::new();
+    let allowed_dir = test_env.test_dir.join("imports");
+    std::fs::create_dir_all(&allowed_dir).unwrap();
+    let db_connection = DbConnection::new().with_import_allowed_dirs(vec![allowed_dir.clone()]);
+    let app = rs_backend::create_app(db_connection.clone());
+
+    let source = allowed_dir.join("dry-run-source.db");
+    std::fs::copy(test_env.create_test_db(), &source).unwrap();
+
+    let (status, json) = post_json(
+        &app,
+        "/databases/import/path?dry_run=true",
+        json!({ "path": source.to_string_lossy() }),
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["tables"][0]["name"], "test1");
+    assert_eq!(json["tables"][0]["row_count"], 2);
+    assert_eq!(json["tables"][0]["columns"][1], json!({ "name": "name", "type": "TEXT" }));
+    assert!(DatabaseMetadata::find_by_name(&db_connection, "dry-run-source.db").unwrap().is_none());
+
+    test_env.cleanup();
+}
@@ -0,0 +1,1816 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use std::time::Duration;
+
+use crate::common::{get_json, post_json, send, send_json_as, TestEnv};
+use rs_backend::db::connection::DbConnection;
+use rs_backend::db::pragmas::{PragmaGuard, PragmaOverrides};
+use rs_backend::utils::json_limits::JsonLimits;
+
+fn setup() -> (Router, DbConnection, TestEnv) {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    (app, db_connection, test_env)
+}
+
+#[tokio::test]
+async fn test_sample_query_is_bounded_and_flagged() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE numbers (n INTEGER);
+         WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 500)
+         INSERT INTO numbers SELECT n FROM seq;"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query/sample", id),
+        json!({ "sql": "SELECT COUNT(*) AS total FROM numbers", "sample_size": 10 }),
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["sample"], true);
+    assert_eq!(json["sample_size"], 10);
+    assert_eq!(json["rows"][0]["total"], 10);
+
+    // Parameters are bound as for a full query
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query/sample", id),
+        json!({ "sql": "SELECT COUNT(*) AS total FROM numbers WHERE n > ?", "params": [4], "sample_size": 10 }),
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["rows"][0]["total"], 6);
+
+    // The full query still sees every row once the sample views are dropped
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT COUNT(*) AS total FROM numbers" }),
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 500);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_missing_table_returns_table_not_found() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT * FROM missing_table" }),
+    ).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+    assert_eq!(json["error"]["table"], "missing_table");
+
+    let (status, json) = get_json(&app, &format!("/databases/{}/tables/missing_table/schema", id)).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+    assert_eq!(json["error"]["table"], "missing_table");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_describe_reports_column_stats() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE readings (label TEXT, value REAL);
+         INSERT INTO readings VALUES ('a', 1.5), ('b', NULL), (NULL, 4.5), ('d', 3.0);"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query?describe=true", id),
+        json!({ "sql": "SELECT label, value FROM readings" }),
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"].as_array().unwrap().len(), 4);
+
+    let value = &json["describe"]["value"];
+    assert_eq!(value["count"], 4);
+    assert_eq!(value["null_count"], 1);
+    assert_eq!(value["min"], 1.5);
+    assert_eq!(value["max"], 4.5);
+    assert_eq!(value["mean"], 3.0);
+
+    let label = &json["describe"]["label"];
+    assert_eq!(label["null_count"], 1);
+    assert!(label.get("min").is_none());
+
+    // Without the flag no describe block is returned
+    let (_, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT label, value FROM readings" }),
+    ).await;
+    assert!(json.get("describe").is_none());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_param_types_coerce_before_binding() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE items (id INTEGER PRIMARY KEY, code);
+         INSERT INTO items (id, code) VALUES (5, 5), (6, '5');"
+    ).unwrap();
+    drop(conn);
+
+    // Without a declared type the string binds as TEXT and only matches the text row
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT id FROM items WHERE code = ?", "params": ["5"] }),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 6 }]));
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT id FROM items WHERE code = ?", "params": ["5"], "param_types": ["integer"] }),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 5 }]));
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT id FROM items WHERE code = ?", "params": ["five"], "param_types": ["integer"] }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().contains("cannot coerce"));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_arrow_query_returns_ipc_stream() {
+    use arrow::array::{Array, BinaryArray, Float64Array, Int64Array, StringArray};
+    use arrow::datatypes::DataType;
+    use arrow::ipc::reader::StreamReader;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE items (id INTEGER, label TEXT, price REAL, payload BLOB);
+         INSERT INTO items VALUES (1, 'one', 1.5, x'0102'), (2, NULL, NULL, NULL);"
+    ).unwrap();
+    drop(conn);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/databases/{}/query/arrow", id))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "sql": "SELECT * FROM items ORDER BY id" }).to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/vnd.apache.arrow.stream");
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let reader = StreamReader::try_new(std::io::Cursor::new(body.to_vec()), None).unwrap();
+    let schema = reader.schema();
+    let types: Vec<_> = schema.fields().iter().map(|f| (f.name().clone(), f.data_type().clone())).collect();
+    assert_eq!(types, vec![
+        ("id".to_string(), DataType::Int64),
+        ("label".to_string(), DataType::Utf8),
+        ("price".to_string(), DataType::Float64),
+        ("payload".to_string(), DataType::Binary),
+    ]);
+
+    let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), 2);
+
+    let ids = batch.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(ids.values(), &[1, 2]);
+    let labels = batch.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(labels.value(0), "one");
+    assert!(labels.is_null(1));
+    let prices = batch.column(2).as_any().downcast_ref::<Float64Array>().unwrap();
+    assert_eq!(prices.value(0), 1.5);
+    assert!(prices.is_null(1));
+    let payloads = batch.column(3).as_any().downcast_ref::<BinaryArray>().unwrap();
+    assert_eq!(payloads.value(0), &[1, 2]);
+    assert!(payloads.is_null(1));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_xlsx_query_returns_workbook() {
+    use axum::{body::Body, http::Request};
+    use calamine::{Data, Reader, Xlsx};
+    use tower::ServiceExt;
+
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE items (id INTEGER, label TEXT, price REAL, payload BLOB);
+         INSERT INTO items VALUES (1, 'one', 1.5, x'0102'), (2, NULL, NULL, NULL);"
+    ).unwrap();
+    drop(conn);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/databases/{}/query/xlsx", id))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "sql": "SELECT * FROM items ORDER BY id" }).to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    );
+    let disposition = response.headers()["content-disposition"].to_str().unwrap().to_string();
+    assert!(disposition.starts_with("attachment; filename=\""));
+    assert!(disposition.ends_with(".xlsx\""));
+    assert_eq!(response.headers()["x-truncated"], "false");
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut workbook: Xlsx<_> = Xlsx::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    let range = workbook.worksheet_range("Results").unwrap();
+
+    assert_eq!(range.get_size(), (3, 4));
+    let header: Vec<_> = (0..4).map(|c| range.get_value((0, c)).cloned()).collect();
+    assert_eq!(header, vec![
+        Some(Data::String("id".to_string())),
+        Some(Data::String("label".to_string())),
+        Some(Data::String("price".to_string())),
+        Some(Data::String("payload".to_string())),
+    ]);
+    assert_eq!(range.get_value((1, 0)), Some(&Data::Float(1.0)));
+    assert_eq!(range.get_value((1, 1)), Some(&Data::String("one".to_string())));
+    assert_eq!(range.get_value((1, 2)), Some(&Data::Float(1.5)));
+    assert_eq!(range.get_value((1, 3)), Some(&Data::String("<BLOB: 2 bytes>".to_string())));
+    assert_eq!(range.get_value((2, 0)), Some(&Data::Float(2.0)));
+    assert_eq!(range.get_value((2, 1)), Some(&Data::Empty));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_arrow_and_xlsx_queries_run_read_only() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let count = format!("/databases/{}/query", id);
+
+    for format in ["arrow", "xlsx"] {
+        let uri = format!("/databases/{}/query/{}", id, format);
+        for sql in ["DELETE FROM test1 RETURNING id", "DROP TABLE test2"] {
+            let (status, json) = post_json(&app, &uri, json!({ "sql": sql })).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{} {}", format, sql);
+            assert_eq!(json["code"], "READ_ONLY");
+        }
+        let (_, json) = post_json(&app, &count, json!({ "sql": "SELECT COUNT(*) AS n FROM test1" })).await;
+        assert_eq!(json["rows"][0]["n"], 2);
+    }
+
+    // Writes still run when asked for
+    let uri = format!("/databases/{}/query/arrow", id);
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "DELETE FROM test1 WHERE id = 1 RETURNING id", "read_only": false })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, json) = post_json(&app, &count, json!({ "sql": "SELECT COUNT(*) AS n FROM test1" })).await;
+    assert_eq!(json["rows"][0]["n"], 1);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_csv_export_quotes_fields() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE notes (id INTEGER, body TEXT, attachment BLOB);
+         INSERT INTO notes VALUES
+             (1, 'plain', NULL),
+             (2, 'comma, \"quoted\"' || char(10) || 'and a newline', x'010203');"
+    ).unwrap();
+    drop(conn);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/databases/{}/query/export", id))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "sql": "SELECT * FROM notes ORDER BY id" }).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    assert_eq!(response.headers()["content-disposition"], "attachment; filename=\"test-results.csv\"");
+    // Streamed, so the length isn't known up front
+    assert!(response.headers().get("content-length").is_none());
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(
+        std::str::from_utf8(&body).unwrap(),
+        "id,body,attachment\n1,plain,\n2,\"comma, \"\"quoted\"\"\nand a newline\",<BLOB: 3 bytes>\n"
+    );
+
+    // Writes are refused before anything is streamed, and change nothing
+    let uri = format!("/databases/{}/query/export", id);
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "DELETE FROM notes RETURNING id" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(json["code"], "READ_ONLY");
+    let (_, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT COUNT(*) AS n FROM notes" })).await;
+    assert_eq!(json["rows"][0]["n"], 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_expect_guards_statement_type() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(
+        &app,
+        &uri,
+        json!({ "sql": "-- count rows\nSELECT COUNT(*) AS total FROM test1", "expect": "select" }),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 2);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "DELETE FROM test1", "expect": "select" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["expected"], "select");
+    assert_eq!(json["actual"], "delete");
+
+    // Nothing was deleted
+    let (_, json) = post_json(&app, &uri, json!({ "sql": "SELECT COUNT(*) AS total FROM test1" })).await;
+    assert_eq!(json["rows"][0]["total"], 2);
+
+    let (status, _) = post_json(
+        &app,
+        &uri,
+        json!({ "sql": "WITH doomed AS (SELECT id FROM test1) DELETE FROM test1 WHERE id IN doomed", "expect": "select" }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "SELECT 1", "expect": "merge" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_size_estimate_for_known_table() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE readings (id INTEGER PRIMARY KEY, sensor TEXT);
+         WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 1000)
+         INSERT INTO readings SELECT n, 'sensor-' || printf('%04d', n) FROM seq;"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query/size-estimate", id),
+        json!({ "sql": "SELECT * FROM readings;" }),
+    ).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["estimate"], true);
+    assert_eq!(json["estimated_rows"], 1000);
+
+    // A trailing comment doesn't swallow the count's closing parenthesis
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query/size-estimate", id),
+        json!({ "sql": "SELECT * FROM readings -- every reading" }),
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["estimated_rows"], 1000);
+
+    // The real response is 1000 rows of roughly {"id":123,"sensor":"sensor-0123"}
+    let (_, full) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT * FROM readings" }),
+    ).await;
+    let actual = full["rows"].to_string().len() as f64;
+    let estimated = json["estimated_bytes"].as_f64().unwrap();
+    assert!((estimated - actual).abs() / actual < 0.1, "estimated {} vs actual {}", estimated, actual);
+
+    let (status, _) = post_json(
+        &app,
+        &format!("/databases/{}/query/size-estimate", id),
+        json!({ "sql": "DELETE FROM readings" }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_named_bindings_fill_placeholders() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE accounts (id INTEGER PRIMARY KEY, status TEXT);
+         INSERT INTO accounts VALUES (4, 'active'), (5, 'active'), (6, 'closed');"
+    ).unwrap();
+    drop(conn);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT id FROM accounts WHERE id = :id AND status = :status",
+        "bindings": { "id": 5, "status": "active" }
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 5 }]));
+
+    // The prefix doesn't have to match between placeholders
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT COUNT(*) AS total FROM accounts WHERE status = @status OR id = $id",
+        "bindings": { "id": 6, "status": "active" }
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 3);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT id FROM accounts WHERE id = :id AND status = :status",
+        "bindings": { "id": 5 }
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["unbound"], json!([":status"]));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_positional_params_bind_scalars_and_blobs() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT ? AS n, ? AS x, ? AS s, ? AS b, ? IS NULL AS null_bound, hex(?) AS blob, typeof(?) AS blob_type",
+        "params": [7, 1.5, "o'brien", true, null, { "blob": "3q2+7w==" }, { "blob": "" }]
+    })).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["rows"], json!([{
+        "n": 7, "x": 1.5, "s": "o'brien", "b": 1, "null_bound": 1, "blob": "DEADBEEF", "blob_type": "blob"
+    }]));
+
+    // A value that can only be interpolated is still data, not SQL
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT COUNT(*) AS total FROM test1 WHERE name = ?",
+        "params": ["x' OR '1'='1"]
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 0);
+
+    for params in [json!([]), json!([1, 2])] {
+        let (status, json) = post_json(&app, &uri, json!({
+            "sql": "SELECT id FROM test1 WHERE id = ?",
+            "params": params
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", json);
+        assert_eq!(json["expected"], 1);
+        assert_eq!(json["supplied"], params.as_array().unwrap().len());
+    }
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT ?", "params": [{ "blob": "not base64!" }]
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().contains("base64"), "{}", json);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_trigger_loop_is_bounded_and_reported() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_max_statement_changes(Some(1000));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    // Each insert into `events` fans out into more inserts into itself; with
+    // recursive triggers enabled this would run until the trigger depth limit,
+    // and a single fan-out already writes far more rows than allowed
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE events (id INTEGER PRIMARY KEY, generation INTEGER);
+         CREATE TRIGGER events_fan_out AFTER INSERT ON events BEGIN
+             INSERT INTO events (generation)
+             WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 5000)
+             SELECT NEW.generation + 1 FROM seq;
+         END;"
+    ).unwrap();
+    drop(conn);
+    let uri = format!("/databases/{}/query", id);
+
+    let request = post_json(&app, &uri, json!({ "sql": "INSERT INTO events (generation) VALUES (0)", "read_only": false }));
+    let (status, json) = tokio::time::timeout(std::time::Duration::from_secs(10), request)
+        .await
+        .expect("trigger loop was not bounded");
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(json["code"], "MAX_CHANGES_EXCEEDED");
+    assert_eq!(json["max_changes"], 1000);
+    assert!(json["error"].as_str().unwrap().contains("trigger loop"));
+
+    // The aborted statement left nothing behind
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT COUNT(*) AS total FROM events" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 0);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_row_conversion_is_serial_for_small_results() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_row_conversion(100, 1);
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT * FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"].as_array().unwrap().len(), 2);
+    let stats = db_connection.row_converter().stats();
+    assert_eq!((stats.serial, stats.parallel), (1, 0));
+
+    let started = std::time::Instant::now();
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000) SELECT i FROM n"
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    let rows = json["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 5000);
+    assert_eq!(rows[4999]["i"], 5000);
+    let stats = db_connection.row_converter().stats();
+    assert_eq!((stats.serial, stats.parallel, stats.degraded), (1, 1, 0));
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    // With the one pool thread busy on the outer batch, each inner one converts in place
+    let converter = db_connection.row_converter();
+    let outer: Vec<usize> = (0..200).collect();
+    let sums = converter.map(&outer, |_| converter.map(&outer, |n| *n).into_iter().sum::<usize>());
+    assert!(sums.iter().all(|sum| *sum == 19900));
+    assert_eq!(converter.stats().degraded, 200);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_slow_query_times_out() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_query_timeout(Some(Duration::from_millis(200)));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    // Counting to a trillion one row at a time would take hours
+    let started = std::time::Instant::now();
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000000000000)
+                SELECT COUNT(*) AS total FROM n"
+    })).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT, "{}", json);
+    assert_eq!(json["code"], "QUERY_TIMEOUT");
+    assert_eq!(json["timeout_ms"], 200);
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    // Rows streamed before the deadline don't come back as a partial result
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000000000000)
+                SELECT i FROM n"
+    })).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    assert!(json.get("partial").is_none());
+
+    // Sample queries and size estimates are held to the same deadline
+    let (status, json) = post_json(&app, &format!("/databases/{}/query/sample", id), json!({
+        "sql": "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000000000000)
+                SELECT COUNT(*) AS total FROM n"
+    })).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT, "{}", json);
+    assert_eq!(json["code"], "QUERY_TIMEOUT");
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query/size-estimate", id), json!({
+        "sql": "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000000000000)
+                SELECT i FROM n"
+    })).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT, "{}", json);
+    assert_eq!(json["code"], "QUERY_TIMEOUT");
+
+    // The next query gets a fresh deadline
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT COUNT(*) AS total FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_self_trigger_does_not_recurse() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE counters (id INTEGER PRIMARY KEY, hits INTEGER);
+         INSERT INTO counters VALUES (1, 0);
+         CREATE TRIGGER counters_bump AFTER UPDATE ON counters BEGIN
+             UPDATE counters SET hits = hits + 1 WHERE id = NEW.id;
+         END;"
+    ).unwrap();
+    drop(conn);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "UPDATE counters SET hits = 1 WHERE id = 1", "read_only": false })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The trigger fired once rather than re-firing itself
+    let (_, json) = post_json(&app, &uri, json!({ "sql": "SELECT hits FROM counters" })).await;
+    assert_eq!(json["rows"][0]["hits"], 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_null_as_controls_null_rendering() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let sql = json!({ "sql": "SELECT 1 AS id, NULL AS missing" });
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), sql.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 1, "missing": null }]));
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query?null_as=null", id), sql.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 1, "missing": null }]));
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query?null_as=string", id), sql.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 1, "missing": "\u{0}NULL" }]));
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query?null_as=omit", id), sql.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 1 }]));
+
+    let (status, _) = post_json(&app, &format!("/databases/{}/query?null_as=empty", id), sql).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_stream_query_returns_ndjson() {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/databases/{}/query/stream", id))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "sql": "SELECT id, name FROM test1 ORDER BY id" }).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let lines: Vec<serde_json::Value> = std::str::from_utf8(&body).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines, vec![
+        json!({ "id": 1, "name": "Test 1" }),
+        json!({ "id": 2, "name": "Test 2" }),
+    ]);
+
+    // Errors before the first row are still ordinary JSON responses
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query/stream", id),
+        json!({ "sql": "SELECT * FROM missing" }),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "TABLE_NOT_FOUND");
+
+    let (status, _) = post_json(
+        &app,
+        &format!("/databases/{}/query/stream", id),
+        json!({ "sql": "DELETE FROM test1" }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_prev_result_hash_skips_unchanged_results() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+    let sql = "SELECT * FROM test1 ORDER BY id";
+
+    let (status, first) = post_json(&app, &uri, json!({ "sql": sql })).await;
+    assert_eq!(status, StatusCode::OK);
+    let hash = first["result_hash"].as_str().unwrap().to_string();
+    assert_eq!(first["rows"].as_array().unwrap().len(), 2);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": sql, "prev_result_hash": hash })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json, json!({ "unchanged": true, "result_hash": hash }));
+
+    // Once the data changes the full result comes back under a new hash
+    post_json(&app, &uri, json!({ "sql": "INSERT INTO test1 (name) VALUES ('Test 3')", "read_only": false })).await;
+    let (status, json) = post_json(&app, &uri, json!({ "sql": sql, "prev_result_hash": hash })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json.get("unchanged").is_none());
+    assert_eq!(json["rows"].as_array().unwrap().len(), 3);
+    assert_ne!(json["result_hash"], hash);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_pivot_cross_tabulates_sales() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE sales (region TEXT, quarter TEXT, amount INTEGER);
+         INSERT INTO sales VALUES
+             ('East', 'Q1', 100), ('East', 'Q1', 50), ('East', 'Q2', 80),
+             ('West', 'Q2', 30), ('West', 'Q3', 70), ('West', 'Q3', NULL);"
+    ).unwrap();
+    drop(conn);
+    let uri = format!("/databases/{}/query/pivot", id);
+    let base = json!({
+        "sql": "SELECT * FROM sales ORDER BY rowid",
+        "row_key": "region",
+        "col_key": "quarter",
+        "value": "amount"
+    });
+
+    let (status, json) = post_json(&app, &uri, base.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["columns"], json!(["Q1", "Q2", "Q3"]));
+    assert_eq!(json["rows"], json!([
+        { "key": "East", "values": { "Q1": 150, "Q2": 80 } },
+        { "key": "West", "values": { "Q2": 30, "Q3": 70 } }
+    ]));
+
+    let mut counted = base.clone();
+    counted["aggregate"] = json!("count");
+    let (status, json) = post_json(&app, &uri, counted).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["values"], json!({ "Q1": 2, "Q2": 1 }));
+    assert_eq!(json["rows"][1]["values"], json!({ "Q2": 1, "Q3": 1 }));
+
+    let mut unknown = base.clone();
+    unknown["value"] = json!("revenue");
+    let (status, _) = post_json(&app, &uri, unknown).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_pivot_rejects_too_many_columns() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query/pivot", id), json!({
+        "sql": "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500) \
+                SELECT 'all' AS bucket, i, i AS amount FROM n",
+        "row_key": "bucket",
+        "col_key": "i",
+        "value": "amount"
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().contains("more than 200 distinct values"));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_saved_query_template_substitutes_identifiers() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let base = format!("/databases/{}/saved-queries", id);
+
+    let (status, json) = post_json(&app, &base, json!({
+        "name": "first rows",
+        "sql": "SELECT {{column}} FROM {{table}} ORDER BY id LIMIT {{limit}}"
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    let query_id = json["query"]["id"].as_i64().unwrap();
+    assert_eq!(json["placeholders"], json!([
+        { "name": "column", "kind": "column" },
+        { "name": "table", "kind": "table" },
+        { "name": "limit", "kind": "integer" }
+    ]));
+
+    let run = format!("{}/{}/run", base, query_id);
+    let (status, json) = post_json(&app, &run, json!({
+        "args": { "table": "test1", "column": "name", "limit": 1 }
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "name": "Test 1" }]));
+    assert_eq!(json["sql"], "SELECT \"name\" FROM \"test1\" ORDER BY id LIMIT 1");
+
+    let (status, json) = post_json(&app, &run, json!({
+        "args": { "table": "test2", "column": "value", "limit": 5 }
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"].as_array().unwrap().len(), 2);
+
+    // Names that aren't real tables or columns never reach the SQL
+    for args in [
+        json!({ "table": "missing", "column": "name", "limit": 1 }),
+        json!({ "table": "test1; DROP TABLE test2", "column": "name", "limit": 1 }),
+        json!({ "table": "test1", "column": "value", "limit": 1 }),
+        json!({ "table": "test1", "column": "name", "limit": "1; DROP TABLE test2" }),
+        json!({ "table": "test1", "column": "name" }),
+    ] {
+        let (status, json) = post_json(&app, &run, json!({ "args": args })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", args);
+        assert!(json["error"].is_string());
+    }
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({
+        "sql": "SELECT COUNT(*) AS total FROM test2"
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["total"], 2);
+
+    // Placeholders without an implied kind must declare one
+    let (status, _) = post_json(&app, &base, json!({ "name": "untyped", "sql": "SELECT * FROM {{source}}" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = post_json(&app, &base, json!({ "name": "typed", "sql": "SELECT * FROM {{source:table}}" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_json(&app, &base, json!({ "name": "typed", "sql": "SELECT 1" })).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, json) = get_json(&app, &base).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["queries"].as_array().unwrap().len(), 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_blocklisted_queries_are_rejected() {
+    use rs_backend::db::blocklist::QueryBlocklist;
+
+    let test_env = TestEnv::new();
+    let blocklist = QueryBlocklist::new([
+        ("attach", "ATTACH"),
+        ("writable_schema", r"pragma\s+writable_schema"),
+    ]).unwrap();
+    let db_connection = DbConnection::new().with_query_blocklist(blocklist);
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "attach database '/tmp/other.db' AS other" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(json["rule"], "attach");
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "PRAGMA   Writable_Schema = ON" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(json["rule"], "writable_schema");
+
+    // Word boundaries keep the rule from catching names that merely contain it
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT name AS attachments FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"].as_array().unwrap().len(), 2);
+
+    let (status, _) = post_json(&app, &format!("/databases/{}/query/stream", id), json!({
+        "sql": "SELECT 1 FROM test1 WHERE 'ATTACH' = name"
+    })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_diff_reports_single_changed_row() {
+    use rs_backend::models::database_metadata::DatabaseMetadata;
+
+    let (app, db_connection, test_env) = setup();
+    let (left_id, left_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&left_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT, price REAL);
+         INSERT INTO products VALUES (1, 'apple', 1.0), (2, 'pear', 2.5), (3, 'plum', 0.75);"
+    ).unwrap();
+    drop(conn);
+
+    // The "after" copy differs from the original by one row
+    let right_path = test_env.test_dir.join("databases").join("after.db");
+    std::fs::copy(&left_path, &right_path).unwrap();
+    Connection::open(&right_path).unwrap()
+        .execute("UPDATE products SET price = 2.75 WHERE id = 2", []).unwrap();
+    let right_id = DatabaseMetadata::new(
+        "after.db".to_string(),
+        right_path.to_string_lossy().into_owned(),
+        1000,
+        1,
+        false,
+        None,
+    ).save(&db_connection).unwrap().id.unwrap();
+
+    let payload = json!({
+        "left_id": left_id,
+        "right_id": right_id,
+        "sql": "SELECT id, name, price FROM products ORDER BY id",
+        "key": "id"
+    });
+    let (status, json) = post_json(&app, "/databases/query-diff", payload).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+
+    let diff = &json["diff"];
+    assert_eq!(diff["added"], json!([]));
+    assert_eq!(diff["removed"], json!([]));
+    assert_eq!(diff["changed"], json!([{ "key": 2, "changes": { "price": { "from": 2.5, "to": 2.75 } } }]));
+    assert_eq!(diff["unchanged"], 2);
+
+    // Columns on only one side are reported, not treated as changes
+    let payload = json!({
+        "left_id": left_id,
+        "right_id": right_id,
+        "sql": "SELECT * FROM products WHERE id <> 2",
+        "key": "id"
+    });
+    Connection::open(&right_path).unwrap()
+        .execute_batch("ALTER TABLE products ADD COLUMN stock INTEGER; INSERT INTO products VALUES (4, 'fig', 3.0, 9);")
+        .unwrap();
+    let (status, json) = post_json(&app, "/databases/query-diff", payload).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    let diff = &json["diff"];
+    assert_eq!(diff["columns"]["only_right"], json!(["stock"]));
+    assert_eq!(diff["added"], json!([{ "id": 4, "name": "fig", "price": 3.0, "stock": 9 }]));
+    assert_eq!(diff["changed"], json!([]));
+    assert_eq!(diff["unchanged"], 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_snapshot_keeps_view_until_closed() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let writer = Connection::open(&db_path).unwrap();
+    writer.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         CREATE TABLE orders (id INTEGER PRIMARY KEY, total REAL);
+         INSERT INTO orders (total) VALUES (10.0), (20.0);"
+    ).unwrap();
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/snapshots", id), json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["snapshot"]["journal_mode"], "wal");
+    let token = json["snapshot"]["token"].as_str().unwrap().to_string();
+
+    writer.execute("INSERT INTO orders (total) VALUES (30.0)", []).unwrap();
+
+    let count = json!({ "sql": "SELECT count(*) AS n FROM orders" });
+    let (status, json) = post_json(&app, &format!("/snapshots/{}/query", token), count.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["rows"], json!([{ "n": 2 }]));
+
+    // Outside the snapshot the new row is visible
+    let (_, json) = post_json(&app, &format!("/databases/{}/query", id), count.clone()).await;
+    assert_eq!(json["rows"], json!([{ "n": 3 }]));
+
+    let (status, _) = post_json(
+        &app,
+        &format!("/snapshots/{}/query", token),
+        json!({ "sql": "DELETE FROM orders" }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_json(&app, &format!("/snapshots/{}/close", token), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, json) = post_json(&app, &format!("/snapshots/{}/query", token), count).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "SNAPSHOT_NOT_FOUND");
+
+    // Idle snapshots are reaped
+    let (_, json) = post_json(&app, &format!("/databases/{}/snapshots", id), json!({})).await;
+    assert!(json["snapshot"]["token"].is_string());
+    assert_eq!(db_connection.snapshots().len(), 1);
+    assert_eq!(db_connection.snapshots().reap_idle(std::time::Duration::ZERO), 1);
+    assert!(db_connection.snapshots().is_empty());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_returns_msgpack_matching_json() {
+    use axum::{body::Body, http::Request};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE readings (id INTEGER, sensor TEXT, value REAL);
+         INSERT INTO readings VALUES (1, 'a', 0.5), (2, 'b', NULL), (3, NULL, -12.25);"
+    ).unwrap();
+    drop(conn);
+
+    let uri = format!("/databases/{}/query", id);
+    let payload = json!({ "sql": "SELECT * FROM readings ORDER BY id" });
+    let (status, expected) = post_json(&app, &uri, payload.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let msgpack_request = |uri: String, accept: Option<&str>| {
+        let mut builder = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(accept) = accept {
+            builder = builder.header("accept", accept);
+        }
+        builder.body(Body::from(payload.to_string())).unwrap()
+    };
+
+    for request in [
+        msgpack_request(format!("{}?format=msgpack", uri), None),
+        msgpack_request(uri.clone(), Some("application/msgpack")),
+    ] {
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/msgpack");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let decoded: Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(decoded["rows"], expected["rows"]);
+        assert_eq!(decoded["result_hash"], expected["result_hash"]);
+    }
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_rounds_reals_to_float_precision() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE prices (id INTEGER, amount REAL, label TEXT);
+         INSERT INTO prices VALUES (1, 12.34567, '12.34567'), (2, 0.1 + 0.2, NULL), (3, 1234.0, 'x');"
+    ).unwrap();
+    drop(conn);
+
+    let payload = json!({ "sql": "SELECT * FROM prices ORDER BY id" });
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &format!("{}?float_precision=2", uri), payload.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([
+        { "id": 1, "amount": 12.35, "label": "12.34567" },
+        { "id": 2, "amount": 0.3, "label": null },
+        { "id": 3, "amount": 1234.0, "label": "x" }
+    ]));
+
+    // Full precision without the flag
+    let (_, json) = post_json(&app, &uri, payload.clone()).await;
+    assert_eq!(json["rows"][0]["amount"], 12.34567);
+    assert_eq!(json["rows"][1]["amount"], 0.1 + 0.2);
+
+    let (status, _) = post_json(&app, &format!("{}?float_precision=40", uri), payload).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_wide_results_are_rejected_above_column_limit() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_max_result_columns(Some(32));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let columns: Vec<String> = (0..40).map(|i| format!("c{} INTEGER", i)).collect();
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(&format!("CREATE TABLE wide ({}); INSERT INTO wide (c0, c39) VALUES (1, 2);", columns.join(", ")))
+        .unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT * FROM wide" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["code"], "TOO_MANY_COLUMNS");
+    assert_eq!(json["max_columns"], 32);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT c0, c39 FROM wide" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "c0": 1, "c39": 2 }]));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_affected_preview_rolls_back_writes() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query/affected-preview", id),
+        json!({ "sql": "DELETE FROM test1 WHERE id IN (?, ?)", "params": [1, 2] }),
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["affected_rows"], 2);
+    assert_eq!(json["statement"], "delete");
+
+    // Nothing was actually deleted
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT COUNT(*) AS n FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["n"], 2);
+
+    // Reads and transaction control aren't previewed
+    for sql in ["SELECT * FROM test1", "COMMIT"] {
+        let (status, _) = post_json(&app, &format!("/databases/{}/query/affected-preview", id), json!({ "sql": sql })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_connection_init_sql_runs_on_new_connections() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new()
+        .with_connection_init_sql(Some("CREATE TEMP VIEW test1_names AS SELECT name FROM test1 ORDER BY id".to_string()));
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT name FROM test1_names" })).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["rows"].as_array().unwrap().len(), 2);
+
+    // The view only ever existed on the pooled connections, not in the file
+    let conn = Connection::open(&db_path).unwrap();
+    let persisted: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'test1_names'", [], |row| row.get(0)).unwrap();
+    assert_eq!(persisted, 0);
+
+    // Init SQL that would write to the file is refused on the connection itself
+    assert!(rs_backend::db::init_sql::apply(&conn, "DELETE FROM test1").is_err());
+    let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM test1", [], |row| row.get(0)).unwrap();
+    assert_eq!(remaining, 2);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_mid_query_error_returns_partial_rows() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    // abs() of the smallest integer overflows, so the statement fails on row 4
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE readings (id INTEGER PRIMARY KEY, value INTEGER);
+         INSERT INTO readings VALUES (1, -1), (2, -2), (3, -3), (4, -9223372036854775808), (5, -5);"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT id, abs(value) AS magnitude FROM readings ORDER BY id" }),
+    ).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!(json["partial"], true);
+    assert_eq!(json["rows"], json!([
+        { "id": 1, "magnitude": 1 },
+        { "id": 2, "magnitude": 2 },
+        { "id": 3, "magnitude": 3 }
+    ]));
+    assert!(json["detail"].as_str().unwrap().contains("integer overflow"), "{}", json);
+    assert!(json.get("result_hash").is_none());
+
+    // Failing on the very first row is still a plain error
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT abs(value) FROM readings WHERE id = 4" }),
+    ).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(json.get("partial").is_none());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_payload_depth_and_size_are_limited() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_json_limits(JsonLimits { max_depth: 16, max_elements: Some(1000) });
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let mut nested = json!(1);
+    for _ in 0..64 {
+        nested = json!([nested]);
+    }
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT ?", "params": [nested] }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().contains("depth of 16"), "{}", json);
+
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT 1", "params": vec![0; 5000] }),
+    ).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().contains("1000 elements"), "{}", json);
+
+    // Ordinary payloads, including brackets inside strings, pass untouched
+    let (status, json) = post_json(
+        &app,
+        &format!("/databases/{}/query", id),
+        json!({ "sql": "SELECT ? AS text", "params": ["[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[{{{{{{{{{{{{{{{{{{{{{{\\\""] }),
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["rows"][0]["text"], "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[{{{{{{{{{{{{{{{{{{{{{{\\\"");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_invalid_utf8_text_is_signalled() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    // "Hel\xFFlo": 0xFF never appears in UTF-8
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT);
+         INSERT INTO notes VALUES (1, CAST(X'48656CFF6C6F' AS TEXT)), (2, 'fine');"
+    ).unwrap();
+    drop(conn);
+
+    let sql = json!({ "sql": "SELECT id, body, typeof(body) AS kind FROM notes ORDER BY id" });
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), sql.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["kind"], "text");
+    assert_eq!(json["rows"][0]["body"], json!({ "_encoding": "base64", "data": "SGVs/2xv" }));
+    assert_eq!(json["rows"][1]["body"], "fine");
+    assert!(json.get("lossy_text_cells").is_none());
+
+    // Lossy conversion is opt-in and reported
+    let (status, json) = post_json(&app, &format!("/databases/{}/query?invalid_text=lossy", id), sql).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["body"], "Hel\u{FFFD}lo");
+    assert_eq!(json["rows"][1]["body"], "fine");
+    assert_eq!(json["lossy_text_cells"], 1);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_column_policy_hides_columns_from_role() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new()
+        .with_admin_token(Some("admin-token".to_string()))
+        .with_role_token("analyst", "analyst-token")
+        .with_role_token("auditor", "auditor-token");
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, _) = test_env.register_test_db(&db_connection);
+
+    let (status, _) = send_json_as(&app, "PUT", &format!("/databases/{}", id), Some(json!({
+        "properties": { "column_policy": r#"{"analyst": {"test1": ["name"]}}"# }
+    })), Some("admin-token")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let request = |uri: &str, body: Value, token: Option<&str>| {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(format!("/databases/{}{}", id, uri))
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    };
+    let query = |sql: &str, token: Option<&str>| request("/query", json!({ "sql": sql }), token);
+
+    let (status, json) = send(&app, query("SELECT * FROM test1 ORDER BY id", Some("analyst-token"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0], json!({ "id": 1 }));
+
+    for sql in ["SELECT name FROM test1", "SELECT id FROM test1 WHERE name = 'x'", "SELECT name FROM main.test1"] {
+        let (status, json) = send(&app, query(sql, Some("analyst-token"))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", sql);
+        assert_eq!(json["code"], "COLUMN_FORBIDDEN");
+    }
+
+    // A role without an entry sees every column; the views don't linger
+    let (status, json) = send(&app, query("SELECT * FROM test1 ORDER BY id", Some("auditor-token"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["name"], "Test 1");
+
+    // Without an authenticated role every restriction applies, whatever the
+    // request claims about itself
+    let mut anonymous = query("SELECT * FROM test1 ORDER BY id", Some("not-a-token"));
+    anonymous.headers_mut().insert("X-Client-Role", "auditor".parse().unwrap());
+    let (status, json) = send(&app, anonymous).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0], json!({ "id": 1 }));
+
+    // Other ways of reading rows are held to the same policy
+    let response = app.clone().oneshot(request("/query/stream", json!({ "sql": "SELECT * FROM test1 ORDER BY id" }), None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let first: Value = serde_json::from_slice(body.split(|b| *b == b'\n').next().unwrap()).unwrap();
+    assert_eq!(first, json!({ "id": 1 }));
+
+    let (status, json) = send(&app, request("/query/sample", json!({ "sql": "SELECT * FROM test1" }), None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["rows"][0].get("name").is_none());
+
+    let (status, json) = send(&app, request("/query/stream", json!({ "sql": "SELECT name FROM main.test1" }), None)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(json["code"], "COLUMN_FORBIDDEN");
+
+    let (status, _) = get_json(&app, &format!("/databases/{}/download", id)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Writes reach the table past its view, but still can't read hidden columns
+    let preview = |sql: &str| request("/query/affected-preview", json!({ "sql": sql }), Some("analyst-token"));
+    let (status, json) = send(&app, preview("UPDATE test1 SET id = id + 10")).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["affected_rows"], 2);
+    let (status, json) = send(&app, preview("DELETE FROM test1 RETURNING *")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", json);
+
+    let (status, _) = send_json_as(&app, "PUT", &format!("/databases/{}", id), Some(json!({
+        "properties": { "column_policy": "[\"not a policy\"]" }
+    })), Some("admin-token")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_column_policy_cannot_be_sidestepped() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new()
+        .with_admin_token(Some("admin-token".to_string()))
+        .with_role_token("analyst", "analyst-token");
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = |path: &str| format!("/databases/{}{}", id, path);
+
+    let policy = json!({ "properties": { "column_policy": r#"{"analyst": {"test1": ["name"]}}"# } });
+    let (status, _) = send_json_as(&app, "PUT", &uri(""), Some(policy), Some("admin-token")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Only an admin can change the policy, the analyst included
+    let lifted = json!({ "properties": { "column_policy": "{}" } });
+    for token in [None, Some("analyst-token")] {
+        let (status, _) = send_json_as(&app, "PUT", &uri(""), Some(lifted.clone()), token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let (status, mut bundle) = get_json(&app, &uri("/bundle/export")).await;
+    assert_eq!(status, StatusCode::OK);
+    bundle["properties"]["column_policy"] = json!("{}");
+    let (status, _) = send_json_as(&app, "POST", &uri("/bundle/import"), Some(bundle), Some("analyst-token")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The rename endpoint doesn't see the hidden column, and won't change the schema
+    let rename = |column: &str| format!("/databases/{}/tables/test1/columns/{}/rename", id, column);
+    let (status, _) = send_json_as(
+        &app, "POST", &rename("name"), Some(json!({ "new_name": "leak" })), Some("analyst-token"),
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, json) = send_json_as(
+        &app, "POST", &rename("id"), Some(json!({ "new_name": "key" })), Some("analyst-token"),
+    ).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", json);
+    assert_eq!(json["code"], "COLUMN_FORBIDDEN");
+
+    let attach = format!("ATTACH DATABASE '{}' AS copy", db_path.display());
+    for sql in ["ALTER TABLE test1 RENAME COLUMN name TO leak", "CREATE VIEW leak AS SELECT id FROM test1", attach.as_str()] {
+        let (status, json) = send_json_as(
+            &app, "POST", &uri("/query"),
+            Some(json!({ "sql": sql, "read_only": false })), Some("analyst-token"),
+        ).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}: {}", sql, json);
+        assert_eq!(json["code"], "COLUMN_FORBIDDEN");
+    }
+
+    let (status, json) = send_json_as(&app, "POST", &uri("/migrate"), Some(json!({
+        "migrations": [{ "version": 1, "up_sql": "ALTER TABLE test1 RENAME COLUMN name TO leak" }]
+    })), Some("analyst-token")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", json);
+
+    // The column is still there under its own name, and still hidden
+    let conn = Connection::open(&db_path).unwrap();
+    let name: String = conn.query_row("SELECT name FROM test1 WHERE id = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(name, "Test 1");
+    drop(conn);
+    let (status, json) = send_json_as(
+        &app, "POST", &uri("/query"), Some(json!({ "sql": "SELECT * FROM test1" })), Some("analyst-token"),
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["rows"][0].get("name").is_none());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_empty_and_rowless_results_are_distinguished() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT id, name FROM test1 ORDER BY id" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["columns"], json!(["id", "name"]));
+    assert_eq!(json["rows"].as_array().unwrap().len(), 2);
+    assert_eq!(json["returns_rows"], true);
+    assert!(json.get("empty_result").is_none());
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT id, name FROM test1 WHERE id < 0" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["columns"], json!(["id", "name"]));
+    assert_eq!(json["rows"], json!([]));
+    assert_eq!(json["returns_rows"], true);
+    assert_eq!(json["empty_result"], true);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "CREATE TABLE created (id INTEGER)", "read_only": false })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["columns"], json!([]));
+    assert_eq!(json["rows"], json!([]));
+    assert_eq!(json["returns_rows"], false);
+    assert_eq!(json["empty_result"], true);
+
+    // With require_rows the DDL is refused before it runs
+    let (status, json) = post_json(&app, &format!("{}?require_rows=true", uri), json!({ "sql": "DROP TABLE created" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(json["code"], "NO_RESULT_COLUMNS");
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "SELECT * FROM created" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_with_rowid_marks_single_table_rows() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query?with_rowid=true", id);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE notes (body TEXT);
+         INSERT INTO notes (rowid, body) VALUES (10, 'a'), (20, 'b');
+         CREATE TABLE pairs (k TEXT PRIMARY KEY, v TEXT) WITHOUT ROWID;
+         CREATE VIEW note_view AS SELECT body FROM notes;"
+    ).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT body FROM notes n WHERE body <> ? ORDER BY body", "params": ["z"] })).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["rows"], json!([{ "_rowid": 10, "body": "a" }, { "_rowid": 20, "body": "b" }]));
+    assert_eq!(json["rowid_included"], true);
+
+    // Rows that don't map to one table row come back unchanged
+    for sql in [
+        "SELECT COUNT(*) AS total FROM notes",
+        "SELECT body FROM notes GROUP BY body",
+        "SELECT DISTINCT body FROM notes",
+        "SELECT n.body FROM notes n JOIN test1 t ON t.id = n.rowid",
+        "SELECT notes.body FROM notes, test1",
+        "SELECT body FROM notes UNION SELECT name FROM test1",
+        "SELECT body FROM note_view",
+        "SELECT body FROM (SELECT body FROM notes)",
+        "SELECT k FROM pairs",
+    ] {
+        let (status, json) = post_json(&app, &uri, json!({ "sql": sql })).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", sql, json);
+        assert_eq!(json["rowid_included"], false, "{}", sql);
+        assert!(json["rows"].as_array().unwrap().iter().all(|row| row.get("_rowid").is_none()), "{}", sql);
+    }
+
+    // Without the flag nothing changes
+    let (_, json) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT body FROM notes" })).await;
+    assert!(json["rows"][0].get("_rowid").is_none());
+    assert!(json.get("rowid_included").is_none());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_query_pagination_windows_rows() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_max_query_page_size(2);
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let app = rs_backend::create_app(db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let conn = Connection::open(&db_path).unwrap();
+    conn.execute("INSERT INTO test1 (name) VALUES ('Test 3'), ('Test 4'), ('Test 5')", []).unwrap();
+    drop(conn);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT id FROM test1 ORDER BY id; ",
+        "page": 2,
+        "page_size": 2
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 3 }, { "id": 4 }]));
+    assert_eq!(json["page"], 2);
+    assert_eq!(json["page_size"], 2);
+    assert_eq!(json["total_count"], 5);
+
+    // Oversized pages are clamped; bindings still reach the caller's placeholders
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT id FROM test1 WHERE id > :min ORDER BY id -- trailing comment",
+        "bindings": { "min": 1 },
+        "page_size": 50
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"], json!([{ "id": 2 }, { "id": 3 }]));
+    assert_eq!(json["page"], 1);
+    assert_eq!(json["page_size"], 2);
+    assert_eq!(json["total_count"], 4);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT id FROM test1 ORDER BY id LIMIT 3",
+        "page": 1
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json["error"].as_str().unwrap().contains("LIMIT"));
+
+    // A LIMIT inside a subquery is fine
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT id FROM (SELECT id FROM test1 ORDER BY id LIMIT 3)",
+        "page": 1
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total_count"], 3);
+
+    for (page, page_size) in [(json!(0), json!(1)), (json!(1), json!(-2)), (json!("x"), json!(1))] {
+        let (status, json) = post_json(&app, &uri, json!({
+            "sql": "SELECT id FROM test1",
+            "page": page,
+            "page_size": page_size
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "INVALID_PAGINATION");
+    }
+
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "DELETE FROM test1", "page": 1 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Without page fields the query is unpaged
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT id FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"].as_array().unwrap().len(), 5);
+    assert!(json.get("total_count").is_none());
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_pragma_overrides_last_one_request() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "PRAGMA cache_size",
+        "pragmas": { "cache_size": 1234, "synchronous": "off" }
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["cache_size"], 1234);
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "PRAGMA cache_size" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["cache_size"], -2000);
+
+    // The guard restores the previous value on the connection it changed
+    let conn = Connection::open(&db_path).unwrap();
+    let overrides = PragmaOverrides::parse(Some(&json!({ "cache_size": 1234 }))).unwrap();
+    let guard = PragmaGuard::apply(&conn, &overrides).unwrap();
+    let during: i64 = conn.query_row("PRAGMA cache_size", [], |row| row.get(0)).unwrap();
+    assert_eq!(during, 1234);
+    drop(guard);
+    let after: i64 = conn.query_row("PRAGMA cache_size", [], |row| row.get(0)).unwrap();
+    assert_eq!(after, -2000);
+
+    let (status, json) = post_json(&app, &uri, json!({
+        "sql": "SELECT 1",
+        "pragmas": { "journal_mode": "off" }
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["code"], "PRAGMA_NOT_ALLOWED");
+    assert_eq!(json["pragma"], "journal_mode");
+
+    let (status, _) = post_json(&app, &uri, json!({
+        "sql": "SELECT 1",
+        "pragmas": { "synchronous": "sometimes" }
+    })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_queries_are_read_only_unless_asked() {
+    let (app, db_connection, test_env) = setup();
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+    let uri = format!("/databases/{}/query", id);
+
+    for sql in ["DELETE FROM test1", "DROP TABLE test2", "CREATE TABLE scratch (id INTEGER)"] {
+        let (status, json) = post_json(&app, &uri, json!({ "sql": sql })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", sql);
+        assert_eq!(json["code"], "READ_ONLY");
+    }
+
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT COUNT(*) AS n FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["n"], 2);
+
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "DELETE FROM test1 WHERE id = 1", "read_only": false })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json(&app, &uri, json!({ "sql": "SELECT 1", "read_only": "no" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Saved queries have no way to ask for writes
+    let base = format!("/databases/{}/saved-queries", id);
+    let (status, json) = post_json(&app, &base, json!({ "name": "purge", "sql": "DELETE FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let run = format!("{}/{}/run", base, json["query"]["id"].as_i64().unwrap());
+    let (status, json) = post_json(&app, &run, json!({ "args": {} })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(json["code"], "READ_ONLY");
+
+    // WAL databases can still be read through a read-only connection
+    let conn = Connection::open(&db_path).unwrap();
+    conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(())).unwrap();
+    let (status, json) = post_json(&app, &uri, json!({ "sql": "SELECT COUNT(*) AS n FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["rows"][0]["n"], 1);
+    drop(conn);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_locked_database_answers_503_busy() {
+    let test_env = TestEnv::new();
+    let config = rs_backend::config::Config {
+        busy_timeout: Duration::from_millis(50),
+        ..rs_backend::config::Config::from_env().unwrap()
+    };
+    let db_connection = DbConnection::from_config(config).unwrap();
+    let app = rs_backend::create_app(db_connection.clone());
+    let (id, db_path) = test_env.register_test_db(&db_connection);
+
+    // Another process holds the write lock past the busy timeout; reads still work
+    let holder = Connection::open(&db_path).unwrap();
+    holder.execute_batch("BEGIN IMMEDIATE; INSERT INTO test1 (name) VALUES ('holder');").unwrap();
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", id), json!({ "sql": "SELECT * FROM test1" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/query", id), json!({
+        "sql": "INSERT INTO test1 (name) VALUES ('blocked')",
+        "read_only": false
+    })).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{}", json);
+    assert_eq!(json["code"], "DATABASE_BUSY");
+    assert_eq!(json["retryable"], true);
+
+    holder.execute_batch("COMMIT").unwrap();
+    let (status, _) = post_json(&app, &format!("/databases/{}/query", id), json!({
+        "sql": "INSERT INTO test1 (name) VALUES ('unblocked')",
+        "read_only": false
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    test_env.cleanup();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_reads_and_writes_on_one_database() {
+    let (app, db_connection, test_env) = setup();
+    let (id, _) = test_env.register_test_db(&db_connection);
+    let query = format!("/databases/{}/query", id);
+
+    let tasks: Vec<_> = (0..8).map(|worker| {
+        let app = app.clone();
+        let query = query.clone();
+        tokio::spawn(async move {
+            let mut statuses = Vec::new();
+            for i in 0..10 {
+                let payload = if i % 2 == 0 {
+                    json!({ "sql": format!("INSERT INTO test1 (name) VALUES ('w{}-{}')", worker, i), "read_only": false })
+                } else {
+                    json!({ "sql": "SELECT COUNT(*) AS n FROM test1" })
+                };
+                let (status, json) = post_json(&app, &query, payload).await;
+                statuses.push((i % 2 == 0, status, json));
+            }
+            statuses
+        })
+    }).collect();
+
+    let mut writes = 0;
+    for task in tasks {
+        for (is_write, status, json) in task.await.unwrap() {
+            // Contention may turn a request away, but only ever as a retryable 503
+            assert!(
+                status == StatusCode::OK || (status == StatusCode::SERVICE_UNAVAILABLE && json["code"] == "DATABASE_BUSY"),
+                "{} {}", status, json
+            );
+            if is_write && status == StatusCode::OK {
+                writes += 1;
+            }
+        }
+    }
+    assert!(writes > 0);
+
+    let (_, json) = post_json(&app, &query, json!({ "sql": "SELECT COUNT(*) AS n FROM test1 WHERE name LIKE 'w%'" })).await;
+    assert_eq!(json["rows"][0]["n"], writes);
+
+    test_env.cleanup();
+}
@@ -2,10 +2,10 @@ use axum::{
     body::{Body, Bytes},
     http::{Request, StatusCode},
 };
-use serde_json::Value;
+use serde_json::{json, Value};
 use tower::ServiceExt;
 use rs_backend::db::connection::DbConnection;
-use crate::common::TestEnv;
+use crate::common::{get_json, post_json, TestEnv};
 
 async fn setup_test_app() -> (axum::Router, TestEnv) {
     let test_env = TestEnv::new();
@@ -21,6 +21,38 @@ async fn read_response_body(response: axum::response::Response) -> Result<Bytes,
         .map_err(|e| e.to_string())
 }
 
+// Helper function to build a multipart upload body for a single file
+fn multipart_body(boundary: &str, filename: &str, content_type: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+    body.extend_from_slice(
+        format!("Content-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n").as_bytes()
+    );
+    body.extend_from_slice(format!("Content-Type: {content_type}\r\n\r\n").as_bytes());
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+// Helper function to build an upload request for a single file
+fn upload_request(filename: &str, content_type: &str, data: &[u8]) -> Request<Body> {
+    let boundary = "test_boundary";
+    Request::builder()
+        .method("POST")
+        .uri("/databases/upload")
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(multipart_body(boundary, filename, content_type, data)))
+        .unwrap()
+}
+
+// A copy of a database file with a different application id in its header, so it
+// uploads as a distinct database rather than a duplicate
+fn distinct_copy(data: &[u8], application_id: u32) -> Vec<u8> {
+    let mut copy = data.to_vec();
+    copy[68..72].copy_from_slice(&application_id.to_be_bytes());
+    copy
+}
+
 #[tokio::test]
 async fn test_upload_invalid_file_type() {
     let (app, test_env) = setup_test_app().await;
@@ -136,4 +168,441 @@ async fn test_upload_corrupted_database() {
     assert!(json["error"].as_str().unwrap().contains("Failed to read database structure"));
     
     test_env.cleanup();
-} 
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_upload_without_sqlite_header_is_rejected() {
+    let (app, test_env) = setup_test_app().await;
+
+    let response = app
+        .oneshot(upload_request("zeros.db", "application/x-sqlite3", &[0u8; 2048]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "Not a valid SQLite database file");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_if_none_name_conflict() {
+    let (app, test_env) = setup_test_app().await;
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+
+    let mut request = upload_request("unique.db", "application/x-sqlite3", &data);
+    request.headers_mut().insert("x-if-none-name", "true".parse().unwrap());
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let first_id = json["database"]["id"].as_i64().unwrap();
+
+    let mut request = upload_request("unique.db", "application/x-sqlite3", &data);
+    request.headers_mut().insert("x-if-none-name", "true".parse().unwrap());
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["existing_id"], first_id);
+
+    // Without the header the duplicate name is still accepted
+    let response = app
+        .oneshot(upload_request("unique.db", "application/x-sqlite3", &distinct_copy(&data, 1)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_rejected_when_database_limit_reached() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_max_databases(Some(1));
+    let app = rs_backend::create_app(db_connection);
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(upload_request("first.db", "application/x-sqlite3", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(upload_request("second.db", "application/x-sqlite3", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let error = json["error"].as_str().unwrap();
+    assert!(error.contains("Database limit reached (1 of 1)"));
+    assert!(error.contains("Delete unused databases"));
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_generic_content_type_uses_extension() {
+    let (app, test_env) = setup_test_app().await;
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(upload_request("inventory.sqlite", "application/octet-stream", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["database"]["name"], "inventory.sqlite");
+
+    // A generic type without a SQLite extension is not a candidate
+    let response = app
+        .clone()
+        .oneshot(upload_request("inventory.bin", "application/octet-stream", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // The right extension is not enough without the SQLite header
+    let response = app
+        .oneshot(upload_request("fake.db", "application/octet-stream", &[0u8; 2048]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_extensions_are_configurable() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_upload_extensions([".DBX"]);
+    let app = rs_backend::create_app(db_connection);
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(upload_request("custom.dbx", "application/octet-stream", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(upload_request("custom.sqlite", "application/octet-stream", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_concurrent_uploads_are_limited() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new()
+        .with_max_concurrent_uploads(Some(1))
+        .with_upload_permit_wait(std::time::Duration::from_millis(50));
+    let app = rs_backend::create_app(db_connection.clone());
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+
+    // While another upload holds the only slot, new ones give up with 503
+    let held = db_connection.acquire_upload_permit().await.unwrap();
+    let response = app
+        .clone()
+        .oneshot(upload_request("blocked.db", "application/x-sqlite3", &data))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    drop(held);
+
+    // Fired together, each upload either waits its turn or is turned away
+    let uploads = (0..4).map(|i| {
+        let data = distinct_copy(&data, i);
+        app.clone().oneshot(upload_request(&format!("concurrent{}.db", i), "application/x-sqlite3", &data))
+    });
+    let statuses: Vec<StatusCode> = futures::future::join_all(uploads)
+        .await
+        .into_iter()
+        .map(|response| response.unwrap().status())
+        .collect();
+    assert!(statuses.iter().all(|s| *s == StatusCode::OK || *s == StatusCode::SERVICE_UNAVAILABLE));
+    let succeeded = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+    assert!(succeeded >= 1);
+
+    let stored = rs_backend::models::database_metadata::DatabaseMetadata::count(&db_connection).unwrap();
+    assert_eq!(stored as usize, succeeded);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_converts_rollback_journal_to_wal() {
+    let (app, test_env) = setup_test_app().await;
+    let db_path = test_env.create_test_db();
+    let mode: String = rusqlite::Connection::open(&db_path).unwrap()
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(mode, "delete");
+    let data = std::fs::read(&db_path).unwrap();
+
+    let mut request = upload_request("journal.db", "application/x-sqlite3", &data);
+    request.headers_mut().insert("x-convert-to-wal", "true".parse().unwrap());
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["database"]["original_journal_mode"], "delete");
+
+    let stored = json["database"]["path"].as_str().unwrap();
+    let mode: String = rusqlite::Connection::open(stored).unwrap()
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(mode, "wal");
+
+    // Without the flag the file is stored as uploaded
+    let response = app.oneshot(upload_request("plain.db", "application/x-sqlite3", &distinct_copy(&data, 1))).await.unwrap();
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["database"]["original_journal_mode"].is_null());
+    let mode: String = rusqlite::Connection::open(json["database"]["path"].as_str().unwrap()).unwrap()
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(mode, "delete");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_quota_is_per_client() {
+    use axum::extract::ConnectInfo;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    let test_env = TestEnv::new();
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+    // Room for one upload, not two
+    let quota = data.len() as u64 + data.len() as u64 / 2;
+    let db_connection = DbConnection::new().with_upload_quota(Some(quota), Duration::from_secs(3600));
+    let app = rs_backend::create_app(db_connection);
+
+    // Each upload is a different database, so only the quota can turn one away
+    let upload_from = |ip: &str, filename: &str, application_id: u32| {
+        let mut request = upload_request(filename, "application/x-sqlite3", &distinct_copy(&data, application_id));
+        let addr: SocketAddr = format!("{}:40000", ip).parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        request
+    };
+
+    let response = app.clone().oneshot(upload_from("10.0.0.1", "first.db", 1)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app.clone().oneshot(upload_from("10.0.0.1", "second.db", 2)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["used_bytes"], data.len() as u64);
+    assert_eq!(json["quota_bytes"], quota);
+
+    // A failed upload is refunded, so another client still has its full quota
+    let mut corrupted = vec![0u8; data.len()];
+    corrupted[..16].copy_from_slice(b"SQLite format 3\0");
+    let mut request = upload_request("corrupted.db", "application/x-sqlite3", &corrupted);
+    request.extensions_mut().insert(ConnectInfo("10.0.0.2:40000".parse::<SocketAddr>().unwrap()));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app.oneshot(upload_from("10.0.0.2", "third.db", 3)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_reset_restores_uploaded_database() {
+    let (app, test_env) = setup_test_app().await;
+    let db_path = test_env.create_test_db();
+    let data = std::fs::read(&db_path).unwrap();
+
+    let response = app.clone().oneshot(upload_request("resettable.db", "application/x-sqlite3", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let id = json["database"]["id"].as_i64().unwrap();
+    let query = format!("/databases/{}/query", id);
+
+    let (_, original) = post_json(&app, &query, json!({ "sql": "SELECT * FROM test1 ORDER BY id" })).await;
+    assert_eq!(original["rows"].as_array().unwrap().len(), 2);
+
+    for sql in [
+        "DELETE FROM test1 WHERE id = 1",
+        "CREATE TABLE scratch (id INTEGER)",
+        "INSERT INTO scratch VALUES (1), (2), (3)",
+    ] {
+        let (status, _) = post_json(&app, &query, json!({ "sql": sql, "read_only": false })).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Destructive, so it has to be confirmed
+    let (status, _) = post_json(&app, &format!("/databases/{}/reset", id), json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, mutated) = post_json(&app, &query, json!({ "sql": "SELECT * FROM test1 ORDER BY id" })).await;
+    assert_eq!(mutated["rows"].as_array().unwrap().len(), 1);
+
+    let (status, json) = post_json(&app, &format!("/databases/{}/reset", id), json!({ "confirm": true })).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["database"]["size"], data.len());
+    assert_eq!(json["database"]["table_count"], 2);
+
+    let (_, restored) = post_json(&app, &query, json!({ "sql": "SELECT * FROM test1 ORDER BY id" })).await;
+    assert_eq!(restored["rows"], original["rows"]);
+    let (status, _) = post_json(&app, &query, json!({ "sql": "SELECT * FROM scratch" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(std::fs::read(json["database"]["path"].as_str().unwrap()).unwrap(), data);
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_upload_accepts_gzip_compressed_database() {
+    use std::io::Write;
+
+    let (app, test_env) = setup_test_app().await;
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&data).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // Detected by the gzip header, named after the file inside
+    let response = app
+        .clone()
+        .oneshot(upload_request("compressed.db.gz", "application/gzip", &compressed))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["database"]["name"], "compressed.db");
+    let stored = std::fs::read(json["database"]["path"].as_str().unwrap()).unwrap();
+    assert_eq!(stored, data);
+
+    // A part declaring gzip encoding that doesn't inflate is a clear 400
+    let boundary = "test_boundary";
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+    body.extend_from_slice(b"Content-Disposition: form-data; name=\"file\"; filename=\"broken.db\"\r\n");
+    body.extend_from_slice(b"Content-Type: application/x-sqlite3\r\nContent-Encoding: gzip\r\n\r\n");
+    body.extend_from_slice(&data[..64]);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    let request = Request::builder()
+        .method("POST")
+        .uri("/databases/upload")
+        .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["error"].as_str().unwrap().contains("decompress"));
+
+    test_env.cleanup();
+}
+
+// Point an index's definition at a different column, leaving its entries as they
+// were: the file still opens and passes quick_check, but integrity_check fails
+fn corrupt_index(path: &std::path::Path) {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS damaged (a INTEGER, b TEXT);
+         CREATE INDEX IF NOT EXISTS damaged_a ON damaged (a);
+         WITH RECURSIVE n(v) AS (SELECT 1 UNION ALL SELECT v + 1 FROM n WHERE v < 200)
+         INSERT INTO damaged SELECT v, 'row ' || v FROM n;
+         PRAGMA writable_schema = ON;
+         UPDATE sqlite_master SET sql = 'CREATE INDEX damaged_a ON damaged (b)' WHERE name = 'damaged_a';
+         PRAGMA writable_schema = OFF;"
+    ).unwrap();
+}
+
+#[tokio::test]
+async fn test_upload_runs_integrity_check() {
+    let (app, test_env) = setup_test_app().await;
+    let db_path = test_env.create_test_db();
+    let data = std::fs::read(&db_path).unwrap();
+
+    let response = app.clone().oneshot(upload_request("healthy.db", "application/x-sqlite3", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["integrity"]["ok"], true);
+    assert_eq!(json["database"]["integrity"], "ok");
+    let id = json["database"]["id"].as_i64().unwrap();
+    let stored = std::path::PathBuf::from(json["database"]["path"].as_str().unwrap());
+
+    // A damaged upload is refused with the report
+    let damaged = test_env.test_dir.join("damaged.db");
+    std::fs::copy(&db_path, &damaged).unwrap();
+    corrupt_index(&damaged);
+    let response = app.clone()
+        .oneshot(upload_request("damaged.db", "application/x-sqlite3", &std::fs::read(&damaged).unwrap()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["integrity"]["ok"], false);
+    assert_eq!(json["integrity"]["quick_check"]["ok"], true);
+    let problems = json["integrity"]["integrity_check"]["problems"].as_array().unwrap();
+    assert_eq!(problems.len(), 100);
+    assert_eq!(json["integrity"]["integrity_check"]["truncated"], true);
+
+    // Re-checking on demand notices damage done after the upload
+    let (status, json) = post_json(&app, &format!("/databases/{}/integrity", id), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["integrity"]["ok"], true);
+
+    corrupt_index(&stored);
+    let (status, json) = post_json(&app, &format!("/databases/{}/integrity", id), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["integrity"]["ok"], false);
+    let (_, json) = get_json(&app, &format!("/databases/{}", id)).await;
+    assert_eq!(json["database"]["integrity"], "corrupt");
+
+    test_env.cleanup();
+}
+
+#[tokio::test]
+async fn test_duplicate_upload_points_at_existing_database() {
+    let (app, test_env) = setup_test_app().await;
+    let data = std::fs::read(test_env.create_test_db()).unwrap();
+
+    let response = app.clone().oneshot(upload_request("original.db", "application/x-sqlite3", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let first_id = json["database"]["id"].as_i64().unwrap();
+    let checksum = json["database"]["checksum"].as_str().unwrap().to_string();
+    assert_eq!(checksum.len(), 64);
+
+    // Same bytes under another name
+    let response = app.clone().oneshot(upload_request("copy.db", "application/x-sqlite3", &data)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = read_response_body(response).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["existing_id"], first_id);
+
+    let (_, json) = get_json(&app, &format!("/databases/{}", first_id)).await;
+    assert_eq!(json["database"]["checksum"], checksum);
+
+    test_env.cleanup();
+}
@@ -1,13 +1,23 @@
 // Unit tests
 pub mod unit {
+    pub mod config_test;
     pub mod connection_test;
     pub mod database_metadata_test;
+    pub mod identifier_test;
+    pub mod storage_test;
+    pub mod stream_test;
 }
 
 // Integration tests
 pub mod integration {
+    pub mod admin_test;
     pub mod api_test;
+    pub mod audit_test;
+    pub mod export_test;
+    pub mod history_test;
+    pub mod import_test;
     pub mod upload_test;
+    pub mod query_test;
 }
 
 // Common test utilities
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use rs_backend::config::{Config, ConfigError};
+
+fn config_from(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    Config::from_lookup(|name| vars.get(name).cloned())
+}
+
+#[test]
+fn test_config_parses_env_with_default_fallback() {
+    let config = config_from(&[
+        ("PORT", "8080"),
+        ("SQLITE_STORAGE_PATH", "/var/lib/aggro"),
+        ("DATABASE_POOL_SIZE", "4"),
+        ("MAX_FILE_SIZE", "2097152"),
+        ("MAX_DATABASES", "0"),
+        ("UPLOAD_PERMIT_WAIT_MS", "250"),
+        ("SQLITE_BUSY_TIMEOUT_MS", "750"),
+        ("SQLITE_FOREIGN_KEYS", "false"),
+        ("SQLITE_WAL_ON_OPEN", "true"),
+        ("ROLE_TOKENS", "analyst:s3cr:et, support:other"),
+    ])
+    .unwrap();
+
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.storage_path, PathBuf::from("/var/lib/aggro"));
+    assert_eq!(config.database_pool_size, 4);
+    assert_eq!(config.max_file_size, 2 * 1024 * 1024);
+    assert_eq!(config.max_databases, None);
+    assert_eq!(config.upload_permit_wait, Duration::from_millis(250));
+    assert_eq!(config.busy_timeout, Duration::from_millis(750));
+    assert!(!config.foreign_keys);
+    assert!(config.wal_on_open);
+    assert_eq!(config.role_tokens, vec![
+        ("analyst".to_string(), "s3cr:et".to_string()),
+        ("support".to_string(), "other".to_string()),
+    ]);
+    assert_eq!(config.redacted()["role_tokens"], serde_json::json!(["analyst", "support"]));
+
+    // Everything unset keeps its default
+    let defaults = Config::default();
+    assert_eq!(defaults.port, 3001);
+    assert_eq!(config.metadata_pool_size, defaults.metadata_pool_size);
+    assert_eq!(config.min_file_size, defaults.min_file_size);
+    assert_eq!(config.max_concurrent_uploads, defaults.max_concurrent_uploads);
+    assert_eq!(config.max_statement_changes, defaults.max_statement_changes);
+    assert_eq!(config.upload_extensions, defaults.upload_extensions);
+    assert_eq!(config.admin_token, None);
+}
+
+#[test]
+fn test_config_rejects_invalid_values() {
+    assert_matches!(
+        config_from(&[("PORT", "http")]),
+        Err(ConfigError::InvalidValue { name, .. }) if name == "PORT"
+    );
+    assert_matches!(
+        config_from(&[("PORT", "70000")]),
+        Err(ConfigError::InvalidValue { name, .. }) if name == "PORT"
+    );
+    assert_matches!(
+        config_from(&[("DATABASE_POOL_SIZE", "0")]),
+        Err(ConfigError::InvalidValue { name, .. }) if name == "DATABASE_POOL_SIZE"
+    );
+    assert_matches!(
+        config_from(&[("MIN_FILE_SIZE", "4096"), ("MAX_FILE_SIZE", "1024")]),
+        Err(ConfigError::FileSizeRange { min: 4096, max: 1024 })
+    );
+    assert_matches!(
+        config_from(&[("ROLE_TOKENS", "analyst")]),
+        Err(ConfigError::InvalidValue { name, .. }) if name == "ROLE_TOKENS"
+    );
+    assert_matches!(config_from(&[("STORAGE_BACKEND", "s3")]), Err(ConfigError::UnsupportedStorageBackend(_)));
+    assert_matches!(
+        config_from(&[("CONNECTION_INIT_SQL", "CREATE TABLE scratch (id INTEGER)")]),
+        Err(ConfigError::ConnectionInitSql(_))
+    );
+    assert!(config_from(&[("CONNECTION_INIT_SQL", "CREATE TEMP VIEW recent AS SELECT * FROM events")]).is_ok());
+}
@@ -1,5 +1,5 @@
 use crate::common::TestEnv;
-use rs_backend::db::connection::DbConnection;
+use rs_backend::db::connection::{ConnectionError, DbConnection};
 
 #[test]
 fn test_new_connection() {
@@ -85,4 +85,152 @@ fn test_concurrent_connections() {
         handle.join().unwrap();
     }
     test_env.cleanup();
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_open_reports_unwritable_storage_path() {
+    let test_env = TestEnv::new();
+
+    // A regular file where a directory is expected makes the storage path uncreatable
+    let blocker = test_env.test_dir.join("not-a-directory");
+    std::fs::write(&blocker, b"").unwrap();
+    let storage_path = blocker.join("storage");
+
+    let error = match DbConnection::open(&storage_path) {
+        Ok(_) => panic!("expected opening {} to fail", storage_path.display()),
+        Err(e) => e,
+    };
+    assert!(matches!(error, ConnectionError::StorageDirectory { ref path, .. } if path == &storage_path));
+
+    let message = error.to_string();
+    assert!(message.contains("Failed to create storage directory"));
+    assert!(message.contains(&storage_path.display().to_string()));
+
+    test_env.cleanup();
+}
+
+#[test]
+fn test_query_blocklist_parses_named_rules() {
+    use rs_backend::db::blocklist::QueryBlocklist;
+
+    let blocklist = QueryBlocklist::parse("attach=ATTACH; extensions = load_extension ;").unwrap();
+    assert_eq!(blocklist.rule_names().collect::<Vec<_>>(), vec!["attach", "extensions"]);
+    assert_eq!(blocklist.matched("SELECT load_extension('x')"), Some("extensions"));
+    assert_eq!(blocklist.matched("SELECT 1"), None);
+
+    assert!(QueryBlocklist::parse("ATTACH").is_err());
+    assert!(QueryBlocklist::parse("broken=(").is_err());
+}
+
+#[test]
+fn test_self_test_reports_unwritable_storage() {
+    use rs_backend::db::self_test::self_test;
+
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+
+    let report = self_test(&db_connection);
+    assert!(report.passed(), "{}", report.summary());
+
+    // A file where the storage directory was leaves nothing to write into
+    std::fs::remove_dir_all(&test_env.test_dir).unwrap();
+    std::fs::write(&test_env.test_dir, b"").unwrap();
+
+    let report = self_test(&db_connection);
+    let storage = report.check("storage_writable").unwrap();
+    assert!(!storage.passed);
+    assert!(storage.critical);
+    assert!(storage.detail.contains(&test_env.test_dir.display().to_string()), "{}", storage.detail);
+    assert_eq!(report.critical_failures().next().map(|c| c.name), Some("storage_writable"));
+    assert!(report.summary().contains("FAIL storage_writable"));
+    assert!(report.check("trivial_query").unwrap().passed);
+
+    std::fs::remove_file(&test_env.test_dir).unwrap();
+}
+
+#[test]
+fn test_database_pools_are_cached_and_bounded() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new().with_database_pool_cache_size(2);
+    let paths: Vec<_> = ["a.db", "b.db", "c.db"].iter().map(|name| test_env.test_dir.join(name)).collect();
+    for path in &paths {
+        rusqlite::Connection::open(path).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+    }
+    let pools = db_connection.database_pools();
+
+    // The same file through a different spelling of its path shares the pool
+    db_connection.get_database_pool(&paths[0]);
+    let respelled = test_env.test_dir.join("databases").join("..").join("a.db");
+    db_connection.get_database_pool(&respelled);
+    assert_eq!(pools.len(), 1);
+    assert!(pools.contains(&respelled));
+
+    // A pooled connection sees schema changes made elsewhere
+    {
+        let conn = db_connection.get_database_pool(&paths[0]).get().unwrap();
+        assert_eq!(conn.prepare("SELECT * FROM t").unwrap().column_count(), 1);
+    }
+    rusqlite::Connection::open(&paths[0]).unwrap().execute_batch("ALTER TABLE t ADD COLUMN y").unwrap();
+    let conn = db_connection.get_database_pool(&paths[0]).get().unwrap();
+    assert_eq!(conn.prepare("SELECT * FROM t").unwrap().column_count(), 2);
+    drop(conn);
+
+    // Past capacity the least recently used pool is dropped
+    db_connection.get_database_pool(&paths[1]);
+    db_connection.get_database_pool(&paths[0]);
+    db_connection.get_database_pool(&paths[2]);
+    assert_eq!(pools.len(), 2);
+    assert!(pools.contains(&paths[0]));
+    assert!(!pools.contains(&paths[1]));
+
+    // Eviction works on a path whose file is already gone
+    std::fs::remove_file(&paths[2]).unwrap();
+    db_connection.evict_database_pool(&paths[2]);
+    assert!(!pools.contains(&paths[2]));
+    assert_eq!(pools.len(), 1);
+
+    test_env.cleanup();
+}
+
+#[test]
+fn test_connections_get_configured_pragmas() {
+    let test_env = TestEnv::new();
+    let config = rs_backend::config::Config {
+        storage_path: test_env.test_dir.join("pragmas"),
+        busy_timeout: std::time::Duration::from_millis(1234),
+        ..Default::default()
+    };
+    let db_connection = DbConnection::from_config(config).unwrap();
+    let pragma = |conn: &rusqlite::Connection, name: &str| -> String {
+        conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, rusqlite::types::Value>(0))
+            .map(|value| match value {
+                rusqlite::types::Value::Integer(n) => n.to_string(),
+                rusqlite::types::Value::Text(s) => s,
+                other => format!("{:?}", other),
+            })
+            .unwrap()
+    };
+
+    let metadata = db_connection.get_metadata_pool().get().unwrap();
+    assert_eq!(pragma(&metadata, "journal_mode"), "wal");
+    assert_eq!(pragma(&metadata, "busy_timeout"), "1234");
+    assert_eq!(pragma(&metadata, "foreign_keys"), "1");
+
+    // Stored databases get the connection settings but keep their journal mode
+    let db_path = test_env.create_test_db();
+    let conn = db_connection.get_database_pool(&db_path).get().unwrap();
+    assert_eq!(pragma(&conn, "journal_mode"), "delete");
+    assert_eq!(pragma(&conn, "busy_timeout"), "1234");
+    assert_eq!(pragma(&conn, "foreign_keys"), "1");
+    let conn = db_connection.get_read_only_database_pool(&db_path).get().unwrap();
+    assert_eq!(pragma(&conn, "busy_timeout"), "1234");
+
+    // Unless they're switched to WAL on open
+    drop(conn);
+    db_connection.evict_database_pool(&db_path);
+    let db_connection = db_connection.with_wal_on_open(true);
+    let conn = db_connection.get_database_pool(&db_path).get().unwrap();
+    assert_eq!(pragma(&conn, "journal_mode"), "wal");
+
+    test_env.cleanup();
+}
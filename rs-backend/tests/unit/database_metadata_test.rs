@@ -1,5 +1,7 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use rs_backend::db::connection::DbConnection;
-use rs_backend::models::database_metadata::DatabaseMetadata;
+use rs_backend::models::database_metadata::{DatabaseMetadata, ListFilter};
 use crate::common::TestEnv;
 
 fn setup() -> (DbConnection, String, TestEnv) {
@@ -125,4 +127,171 @@ fn test_update_database_metadata() {
     assert_eq!(found.notes, Some("Updated notes".to_string()));
     
     test_env.cleanup();
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_properties_round_trip_and_filter() {
+    let (db_connection, db_path, test_env) = setup();
+
+    let mut metadata = DatabaseMetadata::new(
+        "Prod DB".to_string(),
+        db_path.clone(),
+        1000,
+        2,
+        false,
+        None,
+    );
+    metadata.properties.insert("owner".to_string(), "team-a".to_string());
+    metadata.properties.insert("env".to_string(), "prod".to_string());
+    let saved = metadata.save(&db_connection).unwrap();
+
+    let other = DatabaseMetadata::new("Other DB".to_string(), db_path, 1000, 2, false, None);
+    other.save(&db_connection).unwrap();
+
+    let found = DatabaseMetadata::find_by_id(&db_connection, saved.id.unwrap()).unwrap().unwrap();
+    assert_eq!(found.properties.get("owner").map(String::as_str), Some("team-a"));
+    assert_eq!(found.properties.get("env").map(String::as_str), Some("prod"));
+
+    let filter = ListFilter {
+        property: Some(("owner".to_string(), "team-a".to_string())),
+        ..Default::default()
+    };
+    let list = DatabaseMetadata::list_filtered(&db_connection, &filter).unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].name, "Prod DB");
+
+    test_env.cleanup();
+}
+
+#[test]
+fn test_retried_save_updates_the_same_record() {
+    let (db_connection, db_path, test_env) = setup();
+
+    let mut metadata = DatabaseMetadata::new("Test DB".to_string(), db_path.clone(), 1000, 2, false, None);
+    metadata.checksum = Some("a".repeat(64));
+    let first = metadata.save(&db_connection).unwrap();
+
+    // The same save sent again, as after a timeout, with the id still unknown to the caller
+    let mut retry = metadata.clone();
+    retry.size = 2000;
+    let second = retry.save(&db_connection).unwrap();
+
+    assert_eq!(second.id, first.id);
+    assert_eq!(second.size, 2000);
+    assert_eq!(DatabaseMetadata::list(&db_connection).unwrap().len(), 1);
+
+    // Without a checksum there's nothing to recognise a retry by
+    DatabaseMetadata::new("Other DB".to_string(), db_path, 1000, 2, false, None).save(&db_connection).unwrap();
+    assert_eq!(DatabaseMetadata::list(&db_connection).unwrap().len(), 2);
+
+    test_env.cleanup();
+}
+
+#[test]
+fn test_checksum_is_unique_across_files() {
+    let (db_connection, db_path, test_env) = setup();
+
+    let mut metadata = DatabaseMetadata::new("Test DB".to_string(), db_path.clone(), 1000, 2, false, None);
+    metadata.checksum = Some("b".repeat(64));
+    metadata.save(&db_connection).unwrap();
+
+    // Identical bytes stored under another file, as two racing uploads would leave
+    let mut other = metadata.clone();
+    other.path = format!("{}.copy", db_path);
+    let err = other.save(&db_connection).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<rusqlite::Error>(),
+        Some(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation
+    ));
+    assert_eq!(DatabaseMetadata::list(&db_connection).unwrap().len(), 1);
+
+    test_env.cleanup();
+}
+
+#[test]
+fn test_checksum_column_is_migrated_onto_older_catalogs() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE database_metadata (
+            id INTEGER PRIMARY KEY, name TEXT NOT NULL, path TEXT NOT NULL, size INTEGER NOT NULL,
+            table_count INTEGER NOT NULL, is_favorite BOOLEAN NOT NULL DEFAULT 0, notes TEXT,
+            created_at TEXT NOT NULL, updated_at TEXT NOT NULL, properties TEXT,
+            audit_enabled BOOLEAN NOT NULL DEFAULT 0, tags TEXT
+        );
+        INSERT INTO database_metadata (name, path, size, table_count, created_at, updated_at)
+        VALUES ('old.db', 'old.db', 1, 0, '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');"
+    ).unwrap();
+
+    rs_backend::models::database_metadata::migrate_schema(&conn).unwrap();
+    assert!(rs_backend::models::database_metadata::check_schema(&conn).unwrap().is_empty());
+    let checksum: Option<String> = conn
+        .query_row("SELECT checksum FROM database_metadata WHERE name = 'old.db'", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(checksum, None);
+
+    // Records serialized before the field existed still deserialize
+    let legacy = serde_json::json!({
+        "id": 1, "name": "old.db", "path": "old.db", "size": 1, "table_count": 0,
+        "is_favorite": false, "notes": null, "created_at": null, "updated_at": null
+    });
+    let metadata: DatabaseMetadata = serde_json::from_value(legacy).unwrap();
+    assert_eq!(metadata.checksum, None);
+}
+
+#[test]
+fn test_search_matches_notes_and_literal_wildcards() {
+    let (db_connection, db_path, test_env) = setup();
+    let token = format!("nightly-{}", std::process::id());
+
+    let saved = DatabaseMetadata::new(
+        "warehouse.db".to_string(),
+        db_path,
+        1000,
+        2,
+        false,
+        Some(format!("Loaded by the {} JOB at 100%_done", token.to_uppercase())),
+    )
+    .save(&db_connection)
+    .unwrap();
+
+    // Only the notes mention the token, in a different case
+    let found = DatabaseMetadata::search(&db_connection, &token).unwrap();
+    assert!(found.iter().any(|m| m.id == saved.id));
+
+    // Wildcards are literal: "100%done" would match as a pattern, but not as text
+    let found = DatabaseMetadata::search(&db_connection, &format!("{} job at 100%_done", token)).unwrap();
+    assert!(found.iter().any(|m| m.id == saved.id));
+    let found = DatabaseMetadata::search(&db_connection, &format!("{} job at 100%done", token)).unwrap();
+    assert!(found.is_empty());
+
+    assert!(DatabaseMetadata::search(&db_connection, "").unwrap().is_empty());
+
+    test_env.cleanup();
+}
+
+#[test]
+fn test_tags_round_trip() {
+    let (db_connection, db_path, test_env) = setup();
+
+    let mut metadata = DatabaseMetadata::new("Tagged DB".to_string(), db_path, 1000, 2, false, None);
+    metadata.tags = ["project-x", "finance", "q3"].into_iter().map(String::from).collect();
+    let saved = metadata.save(&db_connection).unwrap();
+
+    let found = DatabaseMetadata::find_by_id(&db_connection, saved.id.unwrap()).unwrap().unwrap();
+    assert_eq!(found.tags.iter().map(String::as_str).collect::<Vec<_>>(), ["finance", "project-x", "q3"]);
+
+    let filter = ListFilter { tag: Some("project-x".to_string()), ..Default::default() };
+    let list = DatabaseMetadata::list_filtered(&db_connection, &filter).unwrap();
+    assert!(list.iter().any(|m| m.id == saved.id));
+
+    // Cleared tags come back as an empty set
+    let mut cleared = found;
+    cleared.tags.clear();
+    cleared.save(&db_connection).unwrap();
+    let found = DatabaseMetadata::find_by_id(&db_connection, saved.id.unwrap()).unwrap().unwrap();
+    assert!(found.tags.is_empty());
+    let list = DatabaseMetadata::list_filtered(&db_connection, &filter).unwrap();
+    assert!(!list.iter().any(|m| m.id == saved.id));
+
+    test_env.cleanup();
+}
@@ -0,0 +1,42 @@
+use rusqlite::Connection;
+use rs_backend::utils::{is_valid_identifier, quote_identifier, identifier::MAX_IDENTIFIER_LENGTH};
+
+#[test]
+fn test_quote_plain_and_spaced_names() {
+    assert_eq!(quote_identifier("users"), "\"users\"");
+    assert_eq!(quote_identifier("order items"), "\"order items\"");
+}
+
+#[test]
+fn test_quote_escapes_embedded_quotes() {
+    assert_eq!(quote_identifier("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(quote_identifier("\""), "\"\"\"\"");
+}
+
+#[test]
+fn test_quoted_injection_attempt_stays_one_identifier() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch("CREATE TABLE victims (id INTEGER)").unwrap();
+
+    let hostile = "x\"); DROP TABLE victims; --";
+    conn.execute_batch(&format!("CREATE TABLE {} (id INTEGER)", quote_identifier(hostile))).unwrap();
+
+    let tables: Vec<String> = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name").unwrap()
+        .query_map([], |row| row.get(0)).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    assert_eq!(tables, vec!["victims".to_string(), hostile.to_string()]);
+}
+
+#[test]
+fn test_is_valid_identifier() {
+    assert!(is_valid_identifier("users"));
+    assert!(is_valid_identifier("order items"));
+    assert!(is_valid_identifier("say \"hi\""));
+    assert!(is_valid_identifier("x\"); DROP TABLE users; --"));
+    assert!(is_valid_identifier(&"a".repeat(MAX_IDENTIFIER_LENGTH)));
+
+    assert!(!is_valid_identifier(""));
+    assert!(!is_valid_identifier("nul\0byte"));
+    assert!(!is_valid_identifier("new\nline"));
+    assert!(!is_valid_identifier(&"a".repeat(MAX_IDENTIFIER_LENGTH + 1)));
+}
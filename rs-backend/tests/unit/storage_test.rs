@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use rs_backend::db::connection::DbConnection;
+use rs_backend::storage::{LocalStorage, StorageBackend, StorageError};
+
+use crate::common::TestEnv;
+
+#[test]
+fn test_local_storage_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage: Arc<dyn StorageBackend> = Arc::new(LocalStorage::new(dir.path()));
+
+    assert!(!storage.exists("databases/app.db").unwrap());
+    storage.put("databases/app.db", b"first").unwrap();
+    storage.put("databases/nested/other.db", b"second").unwrap();
+    storage.put("exports/report.csv", b"third").unwrap();
+
+    assert!(storage.exists("databases/app.db").unwrap());
+    assert_eq!(storage.get("databases/app.db").unwrap(), b"first");
+    assert_eq!(
+        storage.list("databases/").unwrap(),
+        vec!["databases/app.db".to_string(), "databases/nested/other.db".to_string()]
+    );
+
+    // SQLite opens the same file the backend wrote
+    let path = storage.local_path("databases/app.db").unwrap();
+    assert_eq!(path, dir.path().join("databases").join("app.db"));
+    assert_eq!(std::fs::read(&path).unwrap(), b"first");
+
+    storage.delete("databases/app.db").unwrap();
+    assert!(!storage.exists("databases/app.db").unwrap());
+    assert_matches!(storage.get("databases/app.db"), Err(StorageError::NotFound(_)));
+    assert_matches!(storage.delete("databases/app.db"), Err(StorageError::NotFound(_)));
+}
+
+#[test]
+fn test_local_storage_rejects_keys_outside_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = LocalStorage::new(dir.path().join("root"));
+
+    for key in ["", "../escape.db", "databases/../../escape.db", "/etc/passwd"] {
+        assert_matches!(storage.put(key, b"x"), Err(StorageError::InvalidKey(_)), "{}", key);
+    }
+    assert!(!dir.path().join("escape.db").exists());
+}
+
+#[test]
+fn test_connection_defaults_to_local_storage() {
+    let test_env = TestEnv::new();
+    let db_connection = DbConnection::new();
+
+    db_connection.storage().put("databases/probe.db", b"probe").unwrap();
+    let expected = db_connection.get_storage_path("databases").join("probe.db");
+    assert_eq!(db_connection.storage().local_path("databases/probe.db").unwrap(), expected);
+    assert!(expected.is_file());
+
+    test_env.cleanup();
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use rusqlite::Connection;
+use rs_backend::db::stream::{self, NdjsonStream};
+
+const TOTAL_ROWS: usize = 100_000;
+
+async fn start_counting(capacity: usize) -> NdjsonStream {
+    let conn = Box::new(Connection::open_in_memory().unwrap());
+    let sql = format!(
+        "WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < {}) SELECT n FROM seq",
+        TOTAL_ROWS
+    );
+    stream::start::<_, rusqlite::Error, _, _>(conn, sql, capacity, |_| Ok(Vec::new()), ())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_stream_does_not_read_ahead_of_slow_consumer() {
+    let capacity = 8;
+    let mut rows = start_counting(capacity).await;
+
+    let first = rows.next_line().await.unwrap();
+    assert_eq!(&first[..], b"{\"n\":1}\n");
+
+    // However long the consumer stalls, the reader stops once the channel is
+    // full: at most `capacity` buffered rows plus the one waiting to be sent
+    for _ in 0..5 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(rows.produced() <= 1 + capacity + 1, "reader ran ahead to {} rows", rows.produced());
+    }
+
+    let mut received = 1;
+    while let Some(line) = rows.next_line().await {
+        received += 1;
+        if received % 10_000 == 0 {
+            assert!(rows.produced() <= received + capacity + 1);
+            assert_eq!(line, format!("{{\"n\":{}}}\n", received));
+        }
+    }
+    assert_eq!(received, TOTAL_ROWS);
+}
+
+#[tokio::test]
+async fn test_stream_reports_prepare_errors_up_front() {
+    let conn = Box::new(Connection::open_in_memory().unwrap());
+    let result = stream::start::<_, rusqlite::Error, _, _>(
+        conn,
+        "SELECT * FROM missing".to_string(),
+        4,
+        |_| Ok(Vec::new()),
+        (),
+    ).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_stream_ends_with_error_line_when_a_row_fails() {
+    let conn = Box::new(Connection::open_in_memory().unwrap());
+    // json() raises at the third row, after two have been sent
+    let sql = "WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 5)
+               SELECT CASE WHEN n = 3 THEN json('not json') ELSE n END AS n FROM seq";
+    let mut rows = stream::start::<_, rusqlite::Error, _, _>(conn, sql.to_string(), 4, |_| Ok(Vec::new()), ())
+        .await
+        .unwrap();
+
+    let mut lines = Vec::new();
+    while let Some(line) = rows.next_line().await {
+        lines.push(serde_json::from_slice::<serde_json::Value>(&line).unwrap());
+    }
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], serde_json::json!({ "n": 1 }));
+    assert_eq!(lines[1], serde_json::json!({ "n": 2 }));
+    assert_eq!(lines[2]["error"]["rows_sent"], 2);
+    assert!(lines[2]["error"]["message"].as_str().unwrap().contains("malformed JSON"));
+}